@@ -0,0 +1,56 @@
+//! Throughput of the sample format conversions run on every buffer fed to a stream.
+//!
+//! Run with `cargo bench --bench conversions`. Criterion isn't used so the benchmark
+//! builds without extra dependencies; each conversion runs over ten seconds of
+//! 16 kHz audio until a second has passed, and the mean time per run is printed.
+
+use coqui_stt::audio::{f32_to_i16, i16_to_f32, PcmFormat};
+use coqui_stt::testing::tone;
+use coqui_stt::util::downmix;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SAMPLES: usize = 16_000 * 10;
+
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) {
+    // warm up caches and the allocator
+    for _ in 0..10 {
+        black_box(f());
+    }
+    let started = Instant::now();
+    let mut runs = 0_u32;
+    while started.elapsed() < Duration::from_secs(1) {
+        black_box(f());
+        runs += 1;
+    }
+    let per_run = started.elapsed() / runs;
+    let samples_per_sec = SAMPLES as f64 / per_run.as_secs_f64();
+    println!(
+        "{:<16} {:>10.2?} per run, {:>8.0} Msamples/s",
+        name,
+        per_run,
+        samples_per_sec / 1e6
+    );
+}
+
+fn main() {
+    let samples = tone(440.0, Duration::from_secs(10), 16_000);
+    assert_eq!(samples.len(), SAMPLES);
+    let floats = i16_to_f32(&samples);
+    let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+
+    bench("f32_to_i16", || f32_to_i16(black_box(&floats)));
+    bench("i16_to_f32", || i16_to_f32(black_box(&samples)));
+    bench("s16le decode", || {
+        let mut output = Vec::new();
+        PcmFormat::S16Le.decode_into(black_box(&bytes), &mut output);
+        output
+    });
+    bench("s16be decode", || {
+        let mut output = Vec::new();
+        PcmFormat::S16Be.decode_into(black_box(&bytes), &mut output);
+        output
+    });
+    bench("stereo downmix", || downmix(black_box(&samples), 2));
+    bench("5.1 downmix", || downmix(black_box(&samples), 6));
+}
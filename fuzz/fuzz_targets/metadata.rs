@@ -0,0 +1,90 @@
+//! Strings and metadata returned from `libstt`.
+//!
+//! The fake library returns arbitrary candidate transcripts, with any number of tokens
+//! (including none), and token texts containing invalid UTF-8.
+//! Invalid UTF-8 must surface as `Error::Utf8Error` or be replaced, never panic,
+//! and every slice built over the metadata must match what the library returned.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use coqui_stt::{Error, Model, Stream};
+use coqui_stt_fake::{live, set_output, Kind, Output};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    transcripts: Vec<Vec<Vec<u8>>>,
+    num_results: u8,
+    audio_len: u16,
+}
+
+fn truncate_at_nul(token: &[u8]) -> &[u8] {
+    match token.iter().position(|&b| b == 0) {
+        Some(nul) => &token[..nul],
+        None => token,
+    }
+}
+
+fuzz_target!(|input: Input| {
+    set_output(Some(Output {
+        transcripts: input.transcripts.clone(),
+    }));
+    let expected_text: Vec<u8> = input
+        .transcripts
+        .first()
+        .into_iter()
+        .flatten()
+        .flat_map(|token| truncate_at_nul(token).iter().copied())
+        .collect();
+
+    let mut model = Model::new("model").unwrap();
+    let audio = vec![0; usize::from(input.audio_len)];
+
+    match model.speech_to_text(&audio) {
+        Ok(text) => assert_eq!(text.as_bytes(), expected_text),
+        Err(Error::Utf8Error(_)) => assert!(std::str::from_utf8(&expected_text).is_err()),
+        Err(e) => panic!("unexpected error: {}", e),
+    }
+
+    let num_results = u32::from(input.num_results);
+    let metadata = model
+        .speech_to_text_with_metadata(&audio, num_results)
+        .unwrap();
+    assert_eq!(
+        metadata.num_transcripts() as usize,
+        input.transcripts.len().min(num_results as usize)
+    );
+    for (transcript, expected) in metadata.transcripts().iter().zip(&input.transcripts) {
+        assert_eq!(transcript.num_tokens() as usize, expected.len());
+        for (token, expected) in transcript.tokens().iter().zip(expected) {
+            let text = token.text();
+            if let Ok(expected) = std::str::from_utf8(truncate_at_nul(expected)) {
+                assert_eq!(text, expected);
+            }
+        }
+        let _ = transcript.words();
+        let _ = transcript.to_owned().words();
+    }
+    let _ = metadata.to_owned();
+    let _ = metadata.rank_by(|transcript| transcript.num_tokens());
+    drop(metadata);
+
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    stream.feed_audio(&audio);
+    match stream.intermediate_decode() {
+        Ok(text) => assert_eq!(text.as_bytes(), expected_text),
+        Err(Error::Utf8Error(_)) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+    }
+    let _ = stream
+        .intermediate_decode_with_metadata(num_results)
+        .unwrap();
+    let _ = stream.finish_stream_transcription().unwrap();
+    drop(model);
+
+    for kind in [Kind::Model, Kind::Stream, Kind::String, Kind::Metadata] {
+        assert_eq!(live(kind), 0, "{:?} leaked", kind);
+    }
+    set_output(None);
+});
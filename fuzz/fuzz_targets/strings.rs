@@ -0,0 +1,66 @@
+//! Strings passed into `libstt`: model and scorer paths, and hot-words.
+//!
+//! Strings with embedded NUL bytes must be rejected with `Error::NulBytesFound`
+//! before they ever reach the C library, and nothing else may fail or panic.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use coqui_stt::{Error, Model};
+use coqui_stt_fake::{live, Kind, FAILING_PATH};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    model_path: String,
+    model_buffer: Vec<u8>,
+    scorer_path: String,
+    scorer_buffer: Vec<u8>,
+    hot_words: Vec<(String, f32)>,
+}
+
+/// `Model::new_from_buffer` takes an `AsRef<&[u8]>`, which no standard type implements.
+struct Buffer<'a>(&'a [u8]);
+
+impl<'a> AsRef<&'a [u8]> for Buffer<'a> {
+    fn as_ref(&self) -> &&'a [u8] {
+        &self.0
+    }
+}
+
+fn check(res: coqui_stt::Result<()>, arg: &str) {
+    match res {
+        Ok(()) => assert!(!arg.contains('\0') && arg != FAILING_PATH),
+        Err(Error::NulBytesFound) => assert!(arg.contains('\0')),
+        Err(Error::CreateModelFailed | Error::InvalidScorer) => assert_eq!(arg, FAILING_PATH),
+        Err(e) => panic!("unexpected error for {:?}: {}", arg, e),
+    }
+}
+
+fuzz_target!(|input: Input| {
+    check(
+        Model::new(input.model_path.as_str()).map(drop),
+        &input.model_path,
+    );
+    let from_buffer = Model::new_from_buffer(Buffer(&input.model_buffer));
+    assert_eq!(
+        from_buffer.is_err(),
+        input.model_buffer == FAILING_PATH.as_bytes()
+    );
+    drop(from_buffer);
+
+    let mut model = Model::new("model").unwrap();
+    check(
+        model.enable_external_scorer(input.scorer_path.as_str()),
+        &input.scorer_path,
+    );
+    let _ = model.enable_external_scorer_from_buffer(&input.scorer_buffer);
+    for (word, boost) in &input.hot_words {
+        check(model.add_hot_word(word.as_str(), *boost), word);
+        check(model.erase_hot_word(word.as_str()), word);
+    }
+    model.clear_hot_words().unwrap();
+    drop(model);
+
+    assert_eq!(live(Kind::Model), 0);
+});
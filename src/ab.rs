@@ -0,0 +1,379 @@
+//! Comparing two models, or one model set up two ways, on the same audio,
+//! for tuning scorers and hot-words, or deciding on an upgrade, from data.
+//!
+//! [`compare`] transcribes every [`Utterance`] of a dataset with both models at once.
+//! The [`ComparisonReport`] has the word error rate and latency of each,
+//! and a word-by-word diff of every utterance they transcribed differently.
+//! With the `serde` feature it can be serialized, such as to JSON, and
+//! [`to_markdown`](ComparisonReport::to_markdown) renders it for a pull request or a wiki.
+//!
+//! ```no_run
+//! use coqui_stt::ab::{compare, Utterance};
+//! use coqui_stt::audio::AudioBuffer;
+//! use coqui_stt::Model;
+//!
+//! # fn main() -> coqui_stt::Result<()> {
+//! let mut current = Model::new("model.tflite")?;
+//! current.enable_external_scorer("current.scorer")?;
+//! let mut candidate = Model::new("model.tflite")?;
+//! candidate.enable_external_scorer("candidate.scorer")?;
+//!
+//! let audio = AudioBuffer::new(vec![0; 16000], 1, 16000);
+//! let dataset = [Utterance::new("greeting", audio).with_reference("hello world")];
+//! let report = compare(&mut current, &mut candidate, &dataset);
+//! println!("{}", report.to_markdown());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::audio::AudioBuffer;
+use crate::wer::{align, rate, word_errors};
+use crate::Model;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// One piece of audio in a dataset for [`compare`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Utterance {
+    /// What to call this utterance in the report, such as the file it came from.
+    pub name: String,
+    /// The audio, converted to what each model expects.
+    pub audio: AudioBuffer,
+    /// What was actually said, if known. Only utterances with one count towards word error rates.
+    pub reference: Option<String>,
+}
+
+impl Utterance {
+    /// Create a new `Utterance` called `name`, without a reference transcript.
+    #[inline]
+    #[must_use]
+    pub fn new(name: impl Into<String>, audio: AudioBuffer) -> Self {
+        Self {
+            name: name.into(),
+            audio,
+            reference: None,
+        }
+    }
+
+    /// Set [`reference`](Utterance::reference).
+    #[inline]
+    #[must_use]
+    pub fn with_reference(mut self, reference: impl Into<String>) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+}
+
+/// How one model did on one utterance.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Outcome {
+    /// The transcript, or `None` if transcribing failed.
+    pub transcript: Option<String>,
+    /// Why transcribing failed, if it did.
+    pub error: Option<String>,
+    /// How long converting and transcribing the audio took.
+    pub latency: Duration,
+    /// The word error rate of the transcript, if the utterance has a reference.
+    pub wer: Option<f64>,
+}
+
+/// One step of the word-by-word diff between two transcripts.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WordDiff {
+    /// Both transcripts have this word.
+    Same(String),
+    /// Model A heard one word where model B heard another.
+    Changed {
+        /// Model A's word.
+        a: String,
+        /// Model B's word.
+        b: String,
+    },
+    /// Only model A heard this word.
+    OnlyA(String),
+    /// Only model B heard this word.
+    OnlyB(String),
+}
+
+/// How both models did on one utterance.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UtteranceComparison {
+    /// The [name](Utterance::name) of the utterance.
+    pub name: String,
+    /// The [reference](Utterance::reference) transcript, if any.
+    pub reference: Option<String>,
+    /// How model A did.
+    pub a: Outcome,
+    /// How model B did.
+    pub b: Outcome,
+    /// The word-by-word diff from model A's transcript to model B's,
+    /// empty if either failed.
+    pub diff: Vec<WordDiff>,
+}
+
+impl UtteranceComparison {
+    /// Whether the models transcribed this utterance differently, or only one of them failed.
+    #[inline]
+    #[must_use]
+    pub fn differs(&self) -> bool {
+        self.a.transcript != self.b.transcript
+    }
+}
+
+/// How one model did over the whole dataset.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Summary {
+    /// The word error rate over the utterances with a reference that both models transcribed,
+    /// so the two are measured on the same words. `None` if there are none.
+    pub wer: Option<f64>,
+    /// The mean latency over the utterances this model transcribed.
+    pub mean_latency: Duration,
+    /// How many utterances this model failed to transcribe.
+    pub failures: usize,
+}
+
+/// The outcome of [`compare`].
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ComparisonReport {
+    /// How model A did overall.
+    pub a: Summary,
+    /// How model B did overall.
+    pub b: Summary,
+    /// Each utterance, in the order of the dataset.
+    pub utterances: Vec<UtteranceComparison>,
+}
+
+impl ComparisonReport {
+    /// How much the word error rate changed from model A to model B:
+    /// negative if model B is better. `None` without any references to measure against.
+    #[inline]
+    #[must_use]
+    pub fn wer_delta(&self) -> Option<f64> {
+        Some(self.b.wer? - self.a.wer?)
+    }
+
+    /// How much the mean latency changed from model A to model B, in seconds:
+    /// negative if model B is faster.
+    #[inline]
+    #[must_use]
+    pub fn latency_delta(&self) -> f64 {
+        self.b.mean_latency.as_secs_f64() - self.a.mean_latency.as_secs_f64()
+    }
+
+    /// The utterances the models transcribed differently.
+    #[inline]
+    pub fn differences(&self) -> impl Iterator<Item = &UtteranceComparison> {
+        self.utterances
+            .iter()
+            .filter(|utterance| utterance.differs())
+    }
+
+    /// Render this report as Markdown: a table of the summaries,
+    /// then each utterance the models transcribed differently, with its diff.
+    /// Words only model A heard are struck through, and words only model B heard are bold.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn to_markdown(&self) -> String {
+        let percent = |wer: Option<f64>| {
+            wer.map_or_else(|| "-".to_string(), |wer| format!("{:.1}%", wer * 100.0))
+        };
+        let millis = |latency: Duration| format!("{:.0} ms", latency.as_secs_f64() * 1000.0);
+
+        let mut out = String::new();
+        let _ = writeln!(out, "| | A | B | Change |");
+        let _ = writeln!(out, "|---|---|---|---|");
+        let _ = writeln!(
+            out,
+            "| WER | {} | {} | {} |",
+            percent(self.a.wer),
+            percent(self.b.wer),
+            self.wer_delta().map_or_else(
+                || "-".to_string(),
+                |delta| format!("{:+.1}%", delta * 100.0)
+            )
+        );
+        let _ = writeln!(
+            out,
+            "| Mean latency | {} | {} | {:+.0} ms |",
+            millis(self.a.mean_latency),
+            millis(self.b.mean_latency),
+            self.latency_delta() * 1000.0
+        );
+        let _ = writeln!(
+            out,
+            "| Failures | {} | {} | |",
+            self.a.failures, self.b.failures
+        );
+
+        let differences: Vec<_> = self.differences().collect();
+        let _ = writeln!(
+            out,
+            "\n{} of {} utterances were transcribed differently.",
+            differences.len(),
+            self.utterances.len()
+        );
+        for utterance in differences {
+            let _ = writeln!(out, "\n**{}**\n", utterance.name);
+            if let Some(reference) = &utterance.reference {
+                let _ = writeln!(out, "- Reference: {}", reference);
+            }
+            for (label, outcome) in [("A", &utterance.a), ("B", &utterance.b)] {
+                match (&outcome.transcript, &outcome.error) {
+                    (Some(transcript), _) => {
+                        let _ = writeln!(out, "- {}: {}", label, transcript);
+                    }
+                    (None, error) => {
+                        let error = error.as_deref().unwrap_or_default();
+                        let _ = writeln!(out, "- {}: failed: {}", label, error);
+                    }
+                }
+            }
+            if !utterance.diff.is_empty() {
+                let words: Vec<String> = utterance
+                    .diff
+                    .iter()
+                    .map(|step| match step {
+                        WordDiff::Same(word) => word.clone(),
+                        WordDiff::Changed { a, b } => format!("~~{}~~ **{}**", a, b),
+                        WordDiff::OnlyA(word) => format!("~~{}~~", word),
+                        WordDiff::OnlyB(word) => format!("**{}**", word),
+                    })
+                    .collect();
+                let _ = writeln!(out, "- Diff: {}", words.join(" "));
+            }
+        }
+        out
+    }
+}
+
+/// Transcribe every utterance in `dataset` with `model_a` and `model_b`, and compare them.
+///
+/// The two models transcribe at the same time, on two threads, so this takes about as long
+/// as the slower of them; each model's latencies are measured while the other is busy too.
+/// An utterance failing to transcribe, with either model, is reported rather than stopping
+/// the comparison.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn compare(
+    model_a: &mut Model,
+    model_b: &mut Model,
+    dataset: &[Utterance],
+) -> ComparisonReport {
+    let mut a = Vec::new();
+    let mut b = Vec::new();
+    std::thread::scope(|scope| {
+        scope.spawn(|| a = transcribe_all(model_a, dataset));
+        b = transcribe_all(model_b, dataset);
+    });
+
+    let utterances: Vec<UtteranceComparison> = dataset
+        .iter()
+        .zip(a.into_iter().zip(b))
+        .map(|(utterance, (a, b))| {
+            let diff = match (&a.transcript, &b.transcript) {
+                (Some(a), Some(b)) => align(a, b)
+                    .into_iter()
+                    .filter_map(|pair| match pair {
+                        (Some(a), Some(b)) if a == b => Some(WordDiff::Same(a.to_string())),
+                        (Some(a), Some(b)) => Some(WordDiff::Changed {
+                            a: a.to_string(),
+                            b: b.to_string(),
+                        }),
+                        (Some(a), None) => Some(WordDiff::OnlyA(a.to_string())),
+                        (None, Some(b)) => Some(WordDiff::OnlyB(b.to_string())),
+                        (None, None) => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            UtteranceComparison {
+                name: utterance.name.clone(),
+                reference: utterance.reference.clone(),
+                a,
+                b,
+                diff,
+            }
+        })
+        .collect();
+
+    ComparisonReport {
+        a: summarize(&utterances, |utterance| &utterance.a),
+        b: summarize(&utterances, |utterance| &utterance.b),
+        utterances,
+    }
+}
+
+fn transcribe_all(model: &mut Model, dataset: &[Utterance]) -> Vec<Outcome> {
+    dataset
+        .iter()
+        .map(|utterance| {
+            let start = Instant::now();
+            let res = model.speech_to_text_best(&utterance.audio);
+            let latency = start.elapsed();
+            match res {
+                Ok(transcription) => Outcome {
+                    wer: utterance.reference.as_deref().map(|reference| {
+                        let (errors, words) = word_errors(reference, &transcription.text);
+                        rate(errors, words)
+                    }),
+                    transcript: Some(transcription.text),
+                    error: None,
+                    latency,
+                },
+                Err(e) => Outcome {
+                    transcript: None,
+                    // the report has no room for the source chain, so spell out the root cause
+                    error: Some(match e.op() {
+                        Some(_) => format!("{}: {}", e, e.root()),
+                        None => e.to_string(),
+                    }),
+                    latency,
+                    wer: None,
+                },
+            }
+        })
+        .collect()
+}
+
+fn summarize(
+    utterances: &[UtteranceComparison],
+    side: impl Fn(&UtteranceComparison) -> &Outcome,
+) -> Summary {
+    let (mut errors, mut words, mut scored) = (0, 0, false);
+    let (mut latency, mut transcribed, mut failures) = (Duration::ZERO, 0, 0);
+    for utterance in utterances {
+        let outcome = side(utterance);
+        let Some(transcript) = &outcome.transcript else {
+            failures += 1;
+            continue;
+        };
+        latency += outcome.latency;
+        transcribed += 1;
+        // only count what both models transcribed, so they're measured on the same words
+        if let (Some(reference), Some(_), Some(_)) = (
+            &utterance.reference,
+            &utterance.a.transcript,
+            &utterance.b.transcript,
+        ) {
+            let (e, n) = word_errors(reference, transcript);
+            errors += e;
+            words += n;
+            scored = true;
+        }
+    }
+    Summary {
+        wer: scored.then(|| rate(errors, words)),
+        mean_latency: latency / transcribed.max(1),
+        failures,
+    }
+}
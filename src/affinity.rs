@@ -0,0 +1,51 @@
+/// Pin the current thread to the CPU core with the given ID,
+/// so the operating system doesn't migrate it between cores.
+///
+/// Decoding streams audio through the same model weights over and over,
+/// so a thread that stays on one core keeps them in that core's caches.
+/// [`StreamOptions::with_core`](crate::StreamOptions::with_core) and
+/// `WatchConfig::with_cores` pin the worker threads this crate starts;
+/// threads of your own running inference, such as ones taking models from a
+/// [`ModelPool`](crate::ModelPool), can call this themselves.
+///
+/// Returns `false` if there's no such core, or the operating system refused.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn pin_current_thread(core: usize) -> bool {
+    let pinned = core_affinity::get_core_ids()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|id| id.id == core)
+        .is_some_and(core_affinity::set_for_current);
+    #[cfg(feature = "tracing")]
+    if !pinned {
+        tracing::warn!(core, "could not pin a thread to a core");
+    }
+    pinned
+}
+
+/// The IDs of the CPU cores threads can be pinned to with [`pin_current_thread`].
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn available_cores() -> Vec<usize> {
+    core_affinity::get_core_ids()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|id| id.id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{available_cores, pin_current_thread};
+
+    #[test]
+    fn threads_can_be_pinned() {
+        let cores = available_cores();
+        assert!(!cores.is_empty());
+        let core = cores[cores.len() - 1];
+        assert!(std::thread::spawn(move || pin_current_thread(core))
+            .join()
+            .unwrap());
+        assert!(!pin_current_thread(usize::MAX));
+    }
+}
@@ -0,0 +1,62 @@
+use crate::audio::PcmFormat;
+use crate::{Model, OwnedStream, Transcription};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// How many bytes are read from the reader at once.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// Transcribe raw PCM audio in the given [`PcmFormat`] from `reader`, as it arrives,
+/// until it reaches the end.
+///
+/// Audio is fed to a stream chunk by chunk, so output from something like
+/// `ffmpeg -f s16le -ac 1 -ar 16000 -` or a network socket can be piped in
+/// without buffering it all first. The audio must already be mono and
+/// at the sample rate of `model`.
+///
+/// Feeding and decoding run on Tokio's blocking thread pool, so they never hold up
+/// the runtime. The model is returned along with the result, as with
+/// [`OwnedStream::finish_stream`](OwnedStream::finish_stream).
+///
+/// # Errors
+/// Returns [`Error::Io`](crate::Error::Io) if `reader` returns an error.
+/// Otherwise, passes through any errors from the C library. See enum [`Error`](crate::Error).
+#[allow(clippy::missing_inline_in_public_items)]
+pub async fn transcribe_async_read(
+    model: Model,
+    mut reader: impl AsyncRead + Unpin,
+    format: PcmFormat,
+) -> (crate::Result<Transcription>, Model) {
+    let mut stream = match OwnedStream::try_new(model) {
+        Ok(stream) => stream,
+        Err((e, model)) => return (Err(e), model),
+    };
+
+    let mut buffer = vec![0; CHUNK_BYTES];
+    loop {
+        let read = match reader.read(&mut buffer).await {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) => return (Err(e.into()), stream.into_model()),
+        };
+        let chunk = buffer;
+        (stream, buffer) = blocking(move || {
+            stream.feed_audio_bytes(&chunk[..read], format);
+            (stream, chunk)
+        })
+        .await;
+    }
+
+    blocking(move || stream.finish_stream_transcription()).await
+}
+
+/// Run `f` on the blocking thread pool, resuming its panic if it panics.
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(e) => match e.try_into_panic() {
+            Ok(panic) => std::panic::resume_unwind(panic),
+            // only cancelled when the runtime shuts down, which drops this future too
+            Err(_) => std::future::pending().await,
+        },
+    }
+}
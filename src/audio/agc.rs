@@ -0,0 +1,193 @@
+use super::Preprocessor;
+use std::time::Duration;
+
+/// Automatic gain control: amplifies quiet audio (and attenuates loud audio)
+/// towards a consistent level.
+///
+/// The level of the input is tracked with an envelope follower.
+/// When the input gets louder, the envelope follows it within the attack time,
+/// so the gain drops quickly and loud sounds don't clip.
+/// When the input gets quieter, the envelope follows it within the (longer) release time,
+/// so the gain rises gradually instead of pumping up background noise between words.
+///
+/// This is most useful for far-field microphones, where speech is often far quieter
+/// than the audio the model was trained on.
+#[derive(Clone, Debug)]
+pub struct Agc {
+    target_level: f32,
+    max_gain: f32,
+    attack: Duration,
+    release: Duration,
+    attack_coeff: f32,
+    release_coeff: f32,
+    envelope: f32,
+}
+
+impl Agc {
+    /// Create a new `Agc` with a target level of -20 dBFS, a maximum gain of 30 dB,
+    /// an attack time of 10ms and a release time of 500ms.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            target_level: db_to_linear(-20.0),
+            max_gain: db_to_linear(30.0),
+            attack: Duration::from_millis(10),
+            release: Duration::from_millis(500),
+            attack_coeff: 0.0,
+            release_coeff: 0.0,
+            envelope: 0.0,
+        }
+    }
+
+    /// Set the level, in dBFS, that audio is amplified or attenuated towards.
+    #[inline]
+    #[must_use]
+    pub fn with_target_level(mut self, dbfs: f32) -> Self {
+        self.target_level = db_to_linear(dbfs);
+        self
+    }
+
+    /// Set the maximum gain, in dB, that will ever be applied.
+    ///
+    /// This stops silence being amplified into loud noise.
+    #[inline]
+    #[must_use]
+    pub fn with_max_gain(mut self, db: f32) -> Self {
+        self.max_gain = db_to_linear(db);
+        self
+    }
+
+    /// Set how quickly the gain drops when the input gets louder.
+    #[inline]
+    #[must_use]
+    pub const fn with_attack(mut self, attack: Duration) -> Self {
+        self.attack = attack;
+        self
+    }
+
+    /// Set how quickly the gain rises when the input gets quieter.
+    #[inline]
+    #[must_use]
+    pub const fn with_release(mut self, release: Duration) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// The gain, in dB, currently being applied.
+    #[inline]
+    #[must_use]
+    pub fn gain(&self) -> f32 {
+        20.0 * self.current_gain().log10()
+    }
+
+    fn current_gain(&self) -> f32 {
+        if self.envelope <= f32::EPSILON {
+            self.max_gain
+        } else {
+            (self.target_level / self.envelope).min(self.max_gain)
+        }
+    }
+}
+
+impl Default for Agc {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for Agc {
+    #[inline]
+    fn prepare(&mut self, sample_rate: u32) -> crate::Result<()> {
+        let coeff = |time: Duration| {
+            let samples = time.as_secs_f32() * sample_rate as f32;
+            if samples <= 0.0 {
+                0.0
+            } else {
+                (-1.0 / samples).exp()
+            }
+        };
+        self.attack_coeff = coeff(self.attack);
+        self.release_coeff = coeff(self.release);
+
+        Ok(())
+    }
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        output.reserve(input.len());
+        for &sample in input {
+            let sample = f32::from(sample) / 32768.0;
+            let level = sample.abs();
+            let coeff = if level > self.envelope {
+                self.attack_coeff
+            } else {
+                self.release_coeff
+            };
+            self.envelope = coeff * self.envelope + (1.0 - coeff) * level;
+
+            let out = (sample * self.current_gain() * 32768.0)
+                .clamp(f32::from(i16::MIN), f32::from(i16::MAX));
+            output.push(out as i16);
+        }
+    }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(amplitude: i16, len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+            .collect()
+    }
+
+    fn prepared() -> Agc {
+        let mut agc = Agc::new();
+        agc.prepare(16000).unwrap();
+        agc
+    }
+
+    #[test]
+    fn silence_gets_the_maximum_gain() {
+        let mut agc = prepared();
+        let mut output = Vec::new();
+        agc.process(&[0; 1600], &mut output);
+        assert!(output.iter().all(|&s| s == 0));
+        assert!((agc.gain() - 30.0).abs() < 1e-3, "{}", agc.gain());
+    }
+
+    #[test]
+    fn gain_converges_on_the_target_level() {
+        // -40 dBFS in, so 20 dB of gain brings it up to the -20 dBFS target
+        let mut agc = prepared();
+        let mut output = Vec::new();
+        agc.process(&square(328, 32000), &mut output);
+        assert!((agc.gain() - 20.0).abs() < 0.5, "{}", agc.gain());
+        let last = output.last().unwrap().unsigned_abs();
+        assert!((3200..3360).contains(&last), "{}", last);
+
+        // quieter audio is amplified more, but never by more than the maximum gain
+        agc.process(&square(33, 32000), &mut output);
+        assert!((agc.gain() - 30.0).abs() < 1e-3, "{}", agc.gain());
+    }
+
+    #[test]
+    fn loud_audio_is_clipped_and_then_attenuated() {
+        let mut agc = prepared();
+        let mut output = Vec::new();
+        agc.process(&square(i16::MAX, 1600), &mut output);
+        assert_eq!(output[..2], [i16::MAX, i16::MIN]);
+
+        // the 10ms attack brings it down to the target within 100ms
+        assert!(agc.gain() < -19.0, "{}", agc.gain());
+        let last = output.last().unwrap().unsigned_abs();
+        assert!((3200..3360).contains(&last), "{}", last);
+    }
+}
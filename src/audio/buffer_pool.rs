@@ -0,0 +1,212 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// A pool of buffers to reuse instead of allocating new ones.
+///
+/// Every stage that converts audio needs somewhere to put its output.
+/// Allocating that for every packet adds up with many concurrent streams,
+/// as does each stream keeping its own buffers while it is idle.
+/// A pool hands out buffers with [`get`](BufferPool::get), and takes them back
+/// when the [`PooledBuffer`] is dropped, so once it has warmed up,
+/// converting audio doesn't allocate at all.
+///
+/// Cloning a `BufferPool` returns a handle to the same pool, so one pool can be shared
+/// between the [`Pipeline`](super::Pipeline)s of every stream, and the caller's own code.
+pub struct BufferPool<T> {
+    buffers: Arc<Mutex<Vec<Vec<T>>>>,
+    max_buffers: usize,
+}
+
+impl<T> Clone for BufferPool<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            buffers: Arc::clone(&self.buffers),
+            max_buffers: self.max_buffers,
+        }
+    }
+}
+
+impl<T> Default for BufferPool<T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            buffers: Arc::default(),
+            max_buffers: 64,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for BufferPool<T> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BufferPool")
+            .field("idle", &self.idle())
+            .field("max_buffers", &self.max_buffers)
+            .finish()
+    }
+}
+
+impl<T> BufferPool<T> {
+    /// Create a new, empty `BufferPool`, which keeps up to 64 idle buffers.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep up to `max_buffers` idle buffers. Buffers returned to a full pool are freed.
+    ///
+    /// This only applies to this handle, and buffers taken from it.
+    #[inline]
+    #[must_use]
+    pub fn with_max_buffers(mut self, max_buffers: usize) -> Self {
+        self.max_buffers = max_buffers;
+        self
+    }
+
+    /// The number of buffers waiting in the pool to be reused.
+    #[inline]
+    #[must_use]
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Take an empty buffer from the pool, with room for at least `capacity` elements.
+    #[inline]
+    #[must_use]
+    pub fn get_empty(&self, capacity: usize) -> PooledBuffer<T> {
+        let mut buffer = self.lock().pop().unwrap_or_default();
+        buffer.clear();
+        buffer.reserve(capacity);
+        PooledBuffer {
+            buffer,
+            pool: self.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Vec<T>>> {
+        self.buffers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<T: Clone + Default> BufferPool<T> {
+    /// Take a buffer of `len` default elements, such as silence, from the pool.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, len: usize) -> PooledBuffer<T> {
+        let mut buffer = self.get_empty(len);
+        buffer.resize(len, T::default());
+        buffer
+    }
+}
+
+/// A buffer taken from a [`BufferPool`], which goes back to the pool when dropped.
+///
+/// It dereferences to a `Vec`, so it can be resized as needed.
+pub struct PooledBuffer<T> {
+    buffer: Vec<T>,
+    pool: BufferPool<T>,
+}
+
+impl<T> PooledBuffer<T> {
+    /// Take the buffer out of the pool for good.
+    #[inline]
+    #[must_use]
+    pub fn into_inner(mut self) -> Vec<T> {
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+impl<T> Deref for PooledBuffer<T> {
+    type Target = Vec<T>;
+
+    #[inline]
+    fn deref(&self) -> &Vec<T> {
+        &self.buffer
+    }
+}
+
+impl<T> DerefMut for PooledBuffer<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.buffer
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for PooledBuffer<T> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.buffer.fmt(f)
+    }
+}
+
+impl<T> Drop for PooledBuffer<T> {
+    #[inline]
+    fn drop(&mut self) {
+        // nothing to reuse in a buffer that was never allocated, or taken with `into_inner`
+        if self.buffer.capacity() == 0 {
+            return;
+        }
+        let mut buffers = self.pool.lock();
+        if buffers.len() < self.pool.max_buffers {
+            buffers.push(std::mem::take(&mut self.buffer));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BufferPool;
+    use crate::audio::{Pipeline, Preprocessor};
+
+    #[test]
+    fn buffers_are_reused() {
+        let pool = BufferPool::<i16>::new().with_max_buffers(1);
+        let mut first = pool.get(4);
+        assert_eq!(*first, [0; 4]);
+        first.extend_from_slice(&[1; 100]);
+        let capacity = first.capacity();
+        let second = pool.get_empty(8);
+        assert!(second.is_empty());
+        drop(first);
+        drop(second);
+        assert_eq!(pool.idle(), 1);
+
+        let reused = pool.clone().get(2);
+        assert_eq!(*reused, [0; 2]);
+        assert_eq!(reused.capacity(), capacity);
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(reused.into_inner().len(), 2);
+        assert_eq!(pool.idle(), 0);
+    }
+
+    struct Double;
+
+    impl Preprocessor for Double {
+        fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+            output.extend(input.iter().map(|s| s * 2));
+        }
+    }
+
+    #[test]
+    fn pipelines_share_a_pool() {
+        let pool = BufferPool::new();
+        let mut pipelines: Vec<_> = (0..2)
+            .map(|_| {
+                Pipeline::new()
+                    .with(Double)
+                    .with(Double)
+                    .with(Double)
+                    .with_buffer_pool(pool.clone())
+            })
+            .collect();
+        let mut output = Vec::new();
+        for pipeline in &mut pipelines {
+            pipeline.process(&[1, 2], &mut output);
+        }
+        assert_eq!(output, [8, 16, 8, 16]);
+        // the buffers between stages are back in the pool, not held by either pipeline
+        assert_eq!(pool.idle(), 2);
+    }
+}
@@ -0,0 +1,146 @@
+// These run on every buffer of audio fed to a stream, so they are written as plain loops
+// over slices of equal length, which compilers turn into SIMD instructions.
+// `benches/conversions.rs` measures them.
+
+/// Convert floating point samples, nominally from -1 to 1, to 16-bit samples.
+///
+/// Samples outside that range are clamped, and NaN becomes 0.
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn f32_to_i16(samples: &[f32]) -> Vec<i16> {
+    let mut output = vec![0; samples.len()];
+    for (out, &sample) in output.iter_mut().zip(samples) {
+        // `as` saturates, so this only needs clamping to be explicit for readers
+        *out = (sample * 32767.0).clamp(-32768.0, 32767.0) as i16;
+    }
+    output
+}
+
+/// Convert 16-bit samples to floating point samples from -1 to 1.
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn i16_to_f32(samples: &[i16]) -> Vec<f32> {
+    let mut output = vec![0.0; samples.len()];
+    for (out, &sample) in output.iter_mut().zip(samples) {
+        *out = f32::from(sample) / 32768.0;
+    }
+    output
+}
+
+/// Decode 16-bit samples from `bytes`, little-endian if `little_endian`,
+/// appending them to `output`. A trailing odd byte is ignored.
+pub(crate) fn decode_s16(bytes: &[u8], little_endian: bool, output: &mut Vec<i16>) {
+    let start = output.len();
+    output.resize(start + bytes.len() / 2, 0);
+    let samples = output[start..].iter_mut().zip(bytes.chunks_exact(2));
+    if little_endian {
+        for (out, b) in samples {
+            *out = i16::from_le_bytes([b[0], b[1]]);
+        }
+    } else {
+        for (out, b) in samples {
+            *out = i16::from_be_bytes([b[0], b[1]]);
+        }
+    }
+}
+
+/// Average the channels of interleaved stereo audio, appending them to `output`.
+/// A trailing partial frame is dropped.
+pub(crate) fn downmix_stereo(samples: &[i16], output: &mut Vec<i16>) {
+    let start = output.len();
+    output.resize(start + samples.len() / 2, 0);
+    for (out, frame) in output[start..].iter_mut().zip(samples.chunks_exact(2)) {
+        *out = ((i32::from(frame[0]) + i32::from(frame[1])) / 2) as i16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_s16, downmix_stereo, f32_to_i16, i16_to_f32};
+    use proptest::prelude::*;
+
+    #[test]
+    fn floats_round_trip_and_clamp() {
+        assert_eq!(
+            f32_to_i16(&[0.0, 1.0, -1.0, 2.0, -2.0, f32::NAN]),
+            [0, 32767, -32767, 32767, -32768, 0]
+        );
+        let floats = i16_to_f32(&[0, 16384, -32768]);
+        assert_eq!(floats, [0.0, 0.5, -1.0]);
+    }
+
+    #[test]
+    fn bytes_decode_in_both_orders() {
+        let mut samples = vec![7];
+        decode_s16(&[1, 0, 0xff, 0xff, 9], true, &mut samples);
+        decode_s16(&[0, 1], false, &mut samples);
+        assert_eq!(samples, [7, 1, -1, 1]);
+    }
+
+    #[test]
+    fn stereo_downmix_averages() {
+        let stereo = [i16::MAX, i16::MAX, -4, 3, i16::MIN, i16::MIN, 5];
+        let mut mono = vec![1];
+        downmix_stereo(&stereo, &mut mono);
+        assert_eq!(mono, [1, i16::MAX, 0, i16::MIN]);
+    }
+
+    proptest! {
+        #[test]
+        fn floats_survive_a_round_trip(sample in -1.0f32..=1.0) {
+            let back = i16_to_f32(&f32_to_i16(&[sample]))[0];
+            prop_assert!((back - sample).abs() <= 2.0 / 32768.0);
+        }
+
+        #[test]
+        fn samples_survive_a_round_trip(sample: i16) {
+            let back = f32_to_i16(&i16_to_f32(&[sample]))[0];
+            prop_assert!((i32::from(back) - i32::from(sample)).abs() <= 1);
+        }
+
+        #[test]
+        fn float_conversion_clamps_and_keeps_order(a: f32, b: f32) {
+            prop_assume!(!a.is_nan() && !b.is_nan());
+            let (low, high) = if a <= b { (a, b) } else { (b, a) };
+            let converted = f32_to_i16(&[low, high]);
+            prop_assert!(converted[0] <= converted[1]);
+            if high >= 1.0 {
+                prop_assert_eq!(converted[1], i16::MAX);
+            }
+            if low <= -32768.0 / 32767.0 {
+                prop_assert_eq!(converted[0], i16::MIN);
+            }
+        }
+
+        #[test]
+        fn bytes_decode_in_the_order_they_were_written(samples: Vec<i16>, trailing: Option<u8>) {
+            for little_endian in [true, false] {
+                let mut bytes: Vec<u8> = samples
+                    .iter()
+                    .flat_map(|s| if little_endian { s.to_le_bytes() } else { s.to_be_bytes() })
+                    .collect();
+                bytes.extend(trailing);
+                let mut decoded = Vec::new();
+                decode_s16(&bytes, little_endian, &mut decoded);
+                prop_assert_eq!(&decoded, &samples);
+
+                // the other order swaps the bytes of every sample
+                let mut swapped = Vec::new();
+                decode_s16(&bytes, !little_endian, &mut swapped);
+                let expected: Vec<_> = samples.iter().map(|s| s.swap_bytes()).collect();
+                prop_assert_eq!(swapped, expected);
+            }
+        }
+
+        #[test]
+        fn stereo_downmix_stays_between_the_channels(stereo: Vec<i16>) {
+            let mut mono = Vec::new();
+            downmix_stereo(&stereo, &mut mono);
+            prop_assert_eq!(mono.len(), stereo.len() / 2);
+            for (&sample, frame) in mono.iter().zip(stereo.chunks_exact(2)) {
+                prop_assert!(sample >= frame[0].min(frame[1]));
+                prop_assert!(sample <= frame[0].max(frame[1]));
+            }
+        }
+    }
+}
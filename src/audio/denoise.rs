@@ -0,0 +1,153 @@
+use super::Preprocessor;
+use nnnoiseless::DenoiseState;
+
+const MODEL_RATE: u32 = 48000;
+const FRAME_SIZE: usize = DenoiseState::FRAME_SIZE;
+
+/// Suppresses background noise using the RNNoise recurrent neural network,
+/// through a pure Rust port of it.
+///
+/// RNNoise works on 48 kHz audio. Audio at other sample rates that evenly divide 48 kHz
+/// (such as 16 kHz, the rate most models use) is upsampled before denoising,
+/// and downsampled again afterwards.
+///
+/// This adds 10ms of latency, as audio is processed in 10ms frames.
+pub struct Denoiser {
+    state: Box<DenoiseState<'static>>,
+    factor: usize,
+    last_sample: f32,
+    input: Vec<f32>,
+    output: Vec<f32>,
+}
+
+impl Denoiser {
+    /// Create a new `Denoiser`.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            state: DenoiseState::new(),
+            factor: 1,
+            last_sample: 0.0,
+            input: Vec::with_capacity(FRAME_SIZE * 2),
+            output: vec![0.0; FRAME_SIZE],
+        }
+    }
+
+    fn denoise_frames(&mut self, output: &mut Vec<i16>) {
+        while self.input.len() >= FRAME_SIZE {
+            self.state
+                .process_frame(&mut self.output, &self.input[..FRAME_SIZE]);
+            self.input.drain(..FRAME_SIZE);
+
+            // downsample by averaging each group of `factor` samples
+            output.extend(self.output.chunks_exact(self.factor).map(|c| {
+                let avg = c.iter().sum::<f32>() / self.factor as f32;
+                avg.clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16
+            }));
+        }
+    }
+}
+
+impl Default for Denoiser {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preprocessor for Denoiser {
+    #[inline]
+    fn prepare(&mut self, sample_rate: u32) -> crate::Result<()> {
+        if sample_rate == 0
+            || !MODEL_RATE.is_multiple_of(sample_rate)
+            || !FRAME_SIZE.is_multiple_of((MODEL_RATE / sample_rate) as usize)
+        {
+            return Err(crate::Error::UnsupportedSampleRate(sample_rate));
+        }
+        self.factor = (MODEL_RATE / sample_rate) as usize;
+
+        Ok(())
+    }
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        // upsample by linear interpolation between consecutive samples
+        let factor = self.factor as f32;
+        for &sample in input {
+            let sample = f32::from(sample);
+            let step = (sample - self.last_sample) / factor;
+            self.input
+                .extend((1..=self.factor).map(|i| self.last_sample + step * i as f32));
+            self.last_sample = sample;
+        }
+
+        self.denoise_frames(output);
+    }
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn flush(&mut self, output: &mut Vec<i16>) {
+        if self.input.is_empty() {
+            return;
+        }
+
+        // pad out the last frame with silence, and drop the padding from the output
+        let remaining = self.input.len() / self.factor;
+        self.input.resize(FRAME_SIZE, 0.0);
+        let mut frame = Vec::with_capacity(FRAME_SIZE / self.factor);
+        self.denoise_frames(&mut frame);
+        output.extend_from_slice(&frame[..remaining]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepared(sample_rate: u32) -> Denoiser {
+        let mut denoiser = Denoiser::new();
+        denoiser.prepare(sample_rate).unwrap();
+        denoiser
+    }
+
+    #[test]
+    fn only_rates_dividing_48khz_into_whole_frames_are_supported() {
+        for rate in [8000, 16000, 48000] {
+            assert!(Denoiser::new().prepare(rate).is_ok(), "{}", rate);
+        }
+        for rate in [0, 44100, 22050] {
+            assert!(matches!(
+                Denoiser::new().prepare(rate),
+                Err(crate::Error::UnsupportedSampleRate(r)) if r == rate
+            ));
+        }
+    }
+
+    #[test]
+    fn audio_comes_out_in_whole_frames() {
+        // a 10ms frame at 48 kHz is 160 samples at 16 kHz
+        let mut denoiser = prepared(16000);
+        let mut output = Vec::new();
+        denoiser.process(&[0; 100], &mut output);
+        assert!(output.is_empty());
+        denoiser.process(&[0; 250], &mut output);
+        assert_eq!(output.len(), 320);
+
+        denoiser.flush(&mut output);
+        assert_eq!(output.len(), 350);
+        denoiser.flush(&mut output);
+        assert_eq!(output.len(), 350);
+    }
+
+    #[test]
+    fn silence_passes_through() {
+        for rate in [16000, 48000] {
+            let mut denoiser = prepared(rate);
+            let mut output = Vec::new();
+            denoiser.process(&vec![0; rate as usize / 10], &mut output);
+            denoiser.flush(&mut output);
+            assert_eq!(output.len(), rate as usize / 10);
+            assert!(output.iter().all(|s| s.abs() <= 1), "{:?}", output);
+        }
+    }
+}
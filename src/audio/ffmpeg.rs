@@ -0,0 +1,181 @@
+use std::ffi::OsString;
+use std::io::{BufReader, Read};
+use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
+
+/// Decodes audio in any format the `ffmpeg` command line tool understands,
+/// by running it and reading its output.
+///
+/// This supports every container and codec `ffmpeg` does, including URLs,
+/// without linking against any codec libraries. `ffmpeg` has to be installed
+/// separately, and is looked up on the `PATH` unless
+/// [`with_program`](Ffmpeg::with_program) says otherwise.
+#[derive(Clone, Debug)]
+pub struct Ffmpeg {
+    program: OsString,
+}
+
+impl Default for Ffmpeg {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            program: "ffmpeg".into(),
+        }
+    }
+}
+
+impl Ffmpeg {
+    /// Run `program` instead of the `ffmpeg` on the `PATH`.
+    #[inline]
+    #[must_use]
+    pub fn with_program(mut self, program: impl Into<OsString>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Start decoding `input`, a path or URL, into mono audio at `sample_rate` Hz.
+    ///
+    /// Pass [`Model::get_sample_rate`](crate::Model::get_sample_rate) as `sample_rate`
+    /// to get audio ready to be fed to that model.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if `ffmpeg` could not be started.
+    /// Errors decoding `input` are only known once `ffmpeg` exits,
+    /// and are returned by [`FfmpegSamples::finish`].
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn decode(
+        &self,
+        input: impl Into<OsString>,
+        sample_rate: u32,
+    ) -> crate::Result<FfmpegSamples> {
+        let mut child = Command::new(&self.program)
+            .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-i"])
+            .arg(input.into())
+            .args(["-f", "s16le", "-acodec", "pcm_s16le", "-ac", "1", "-ar"])
+            .arg(sample_rate.to_string())
+            .arg("-")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| crate::Error::Io(format!("could not run ffmpeg: {}", e)))?;
+
+        let (stdout, stderr) = match (child.stdout.take(), child.stderr.take()) {
+            (Some(stdout), Some(stderr)) => (stdout, stderr),
+            _ => unreachable!("both outputs were piped"),
+        };
+        Ok(FfmpegSamples {
+            child: Some(child),
+            stdout: BufReader::new(stdout),
+            stderr,
+        })
+    }
+}
+
+/// Decode `input`, a path or URL, into mono audio at `sample_rate` Hz with `ffmpeg`.
+///
+/// Shorthand for [`Ffmpeg::decode`] with the `ffmpeg` on the `PATH`.
+///
+/// # Errors
+/// Returns [`Error::Io`](crate::Error::Io) if `ffmpeg` could not be started.
+#[inline]
+pub fn ffmpeg_decode(input: impl Into<OsString>, sample_rate: u32) -> crate::Result<FfmpegSamples> {
+    Ffmpeg::default().decode(input, sample_rate)
+}
+
+/// The samples decoded by `ffmpeg`, read as it produces them.
+///
+/// The iterator ends when `ffmpeg` does, whether it succeeded or not,
+/// so call [`finish`](FfmpegSamples::finish) afterwards to find out which.
+/// Dropping this before then stops `ffmpeg`.
+#[derive(Debug)]
+pub struct FfmpegSamples {
+    child: Option<Child>,
+    stdout: BufReader<ChildStdout>,
+    stderr: ChildStderr,
+}
+
+impl FfmpegSamples {
+    /// Wait for `ffmpeg` to exit, and check that it decoded everything.
+    ///
+    /// Samples not yet read are discarded.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidAudio`](crate::Error::InvalidAudio) with what `ffmpeg` printed
+    /// if it failed, and [`Error::Io`](crate::Error::Io) if it could not be waited for.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn finish(mut self) -> crate::Result<()> {
+        std::io::copy(&mut self.stdout, &mut std::io::sink())?;
+        let mut message = String::new();
+        self.stderr.read_to_string(&mut message)?;
+
+        let status = match self.child.take() {
+            Some(mut child) => child.wait()?,
+            None => unreachable!("the child is only taken out once"),
+        };
+        if status.success() {
+            Ok(())
+        } else {
+            Err(crate::Error::InvalidAudio(format!(
+                "ffmpeg exited with {}: {}",
+                status,
+                message.trim()
+            )))
+        }
+    }
+}
+
+impl Iterator for FfmpegSamples {
+    type Item = i16;
+
+    #[inline]
+    fn next(&mut self) -> Option<i16> {
+        let mut sample = [0; 2];
+        self.stdout.read_exact(&mut sample).ok()?;
+        Some(i16::from_le_bytes(sample))
+    }
+}
+
+impl Drop for FfmpegSamples {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::Ffmpeg;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn reads_samples_and_exit_status() {
+        let dir = std::env::temp_dir().join(format!("coqui-stt-ffmpeg-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let program = dir.join("ffmpeg");
+        // stands in for ffmpeg: two samples, then fails if the input is called "bad"
+        std::fs::write(
+            &program,
+            "#!/bin/sh\nprintf '\\001\\000\\377\\377'\nfor a; do [ \"$a\" = bad ] && echo oops >&2 && exit 1; done\nexit 0\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let ffmpeg = Ffmpeg::default().with_program(&program);
+
+        let mut samples = ffmpeg.decode("good.mp3", 16000).unwrap();
+        assert_eq!(samples.by_ref().collect::<Vec<_>>(), [1, -1]);
+        samples.finish().unwrap();
+
+        let samples = ffmpeg.decode("bad", 16000).unwrap();
+        let err = samples.finish().unwrap_err().to_string();
+        assert!(err.contains("oops"), "{}", err);
+
+        assert!(Ffmpeg::default()
+            .with_program(dir.join("missing"))
+            .decode("good.mp3", 16000)
+            .is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Decoding for G.711 telephony audio.
+//!
+//! G.711 is the codec used by most telephone networks and SIP/RTP deployments.
+//! It carries 8 kHz audio, with each sample companded to a single byte using either
+//! μ-law (North America and Japan) or A-law (everywhere else).
+//!
+//! [`G711Decoder`] turns raw payloads (such as RTP payload types 0 and 8)
+//! into audio at the model's sample rate, ready for [`Stream::feed_audio`](crate::Stream::feed_audio).
+
+/// The sample rate of G.711 audio, in Hz.
+pub const SAMPLE_RATE: u32 = 8000;
+
+/// The companding law used to encode G.711 audio.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Law {
+    /// μ-law, used in North America and Japan. This is RTP payload type 0 (PCMU).
+    MuLaw,
+    /// A-law, used in Europe and most other places. This is RTP payload type 8 (PCMA).
+    ALaw,
+}
+
+impl Law {
+    /// Decode a single companded sample.
+    #[inline]
+    #[must_use]
+    pub fn decode(self, byte: u8) -> i16 {
+        match self {
+            Self::MuLaw => decode_mulaw(byte),
+            Self::ALaw => decode_alaw(byte),
+        }
+    }
+}
+
+/// Decode a single μ-law sample to linear 16-bit PCM.
+#[inline]
+#[must_use]
+pub fn decode_mulaw(byte: u8) -> i16 {
+    let byte = !byte;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = i32::from(byte & 0x0F);
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+
+    (if byte & 0x80 == 0 {
+        magnitude
+    } else {
+        -magnitude
+    }) as i16
+}
+
+/// Decode a single A-law sample to linear 16-bit PCM.
+#[inline]
+#[must_use]
+pub fn decode_alaw(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = i32::from(byte & 0x0F);
+    let magnitude = match exponent {
+        0 => (mantissa << 4) + 8,
+        _ => ((mantissa << 4) + 0x108) << (exponent - 1),
+    };
+
+    // unlike μ-law, a set sign bit means a positive sample
+    (if byte & 0x80 == 0 {
+        -magnitude
+    } else {
+        magnitude
+    }) as i16
+}
+
+/// Decodes G.711 payloads and resamples them to the model's sample rate.
+///
+/// Resampling is done by linear interpolation, and carries over between calls to
+/// [`decode`](G711Decoder::decode), so payloads can be decoded one packet at a time.
+#[derive(Clone, Debug)]
+pub struct G711Decoder {
+    law: Law,
+    sample_rate: u32,
+    step: f64,
+    position: f64,
+    last_sample: f64,
+}
+
+impl G711Decoder {
+    /// Create a new `G711Decoder` that produces audio at `sample_rate` Hz.
+    ///
+    /// Pass in [`Model::get_sample_rate`](crate::Model::get_sample_rate).
+    ///
+    /// # Errors
+    /// Returns [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate)
+    /// if `sample_rate` is 0.
+    #[inline]
+    pub fn new(law: Law, sample_rate: u32) -> crate::Result<Self> {
+        if sample_rate == 0 {
+            return Err(crate::Error::UnsupportedSampleRate(sample_rate));
+        }
+
+        Ok(Self {
+            law,
+            sample_rate,
+            step: f64::from(SAMPLE_RATE) / f64::from(sample_rate),
+            position: 1.0,
+            last_sample: 0.0,
+        })
+    }
+
+    /// The companding law this decoder expects.
+    #[inline]
+    #[must_use]
+    pub const fn law(&self) -> Law {
+        self.law
+    }
+
+    /// The sample rate this decoder produces audio at, in Hz.
+    #[inline]
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Decode `payload`, appending audio at the output sample rate to `output`.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn decode(&mut self, payload: &[u8], output: &mut Vec<i16>) {
+        if self.sample_rate == SAMPLE_RATE {
+            output.extend(payload.iter().map(|&b| self.law.decode(b)));
+            return;
+        }
+
+        output.reserve((payload.len() as f64 / self.step).ceil() as usize);
+        for &byte in payload {
+            let sample = f64::from(self.law.decode(byte));
+            // emit every output sample that falls between the last input sample and this one
+            while self.position <= 1.0 {
+                let out = self.last_sample + (sample - self.last_sample) * self.position;
+                output.push(out.round() as i16);
+                self.position += self.step;
+            }
+            self.position -= 1.0;
+            self.last_sample = sample;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference μ-law encoder, as in the ITU-T G.711 reference code.
+    fn encode_mulaw(sample: i16) -> u8 {
+        let (sign, magnitude) = match sample {
+            s if s < 0 => (0x80, -i32::from(s)),
+            s => (0, i32::from(s)),
+        };
+        let biased = magnitude.min(32635) + 0x84;
+        let exponent = (31 - biased.leading_zeros()).saturating_sub(7).min(7);
+        let mantissa = (biased >> (exponent + 3)) & 0x0F;
+        !(sign | (exponent << 4) as u8 | mantissa as u8)
+    }
+
+    /// Reference A-law encoder, as in the ITU-T G.711 reference code.
+    fn encode_alaw(sample: i16) -> u8 {
+        let (mask, value) = match i32::from(sample) >> 3 {
+            v if v >= 0 => (0xD5, v),
+            v => (0x55, -v - 1),
+        };
+        let segment = (0..8).find(|&s| value < (0x20 << s));
+        let encoded = match segment {
+            None => 0x7F,
+            Some(s @ (0 | 1)) => (s << 4) as u8 | ((value >> 1) & 0x0F) as u8,
+            Some(s) => (s << 4) as u8 | ((value >> s) & 0x0F) as u8,
+        };
+        encoded ^ mask
+    }
+
+    #[test]
+    fn known_vectors_decode() {
+        assert_eq!(decode_mulaw(0xFF), 0);
+        assert_eq!(decode_mulaw(0x7F), 0);
+        assert_eq!(decode_mulaw(0x00), -32124);
+        assert_eq!(decode_mulaw(0x80), 32124);
+        assert_eq!(decode_alaw(0xD5), 8);
+        assert_eq!(decode_alaw(0x55), -8);
+        assert_eq!(decode_alaw(0xAA), 32256);
+        assert_eq!(decode_alaw(0x2A), -32256);
+        assert_eq!(Law::MuLaw.decode(0x00), decode_mulaw(0x00));
+        assert_eq!(Law::ALaw.decode(0xD5), decode_alaw(0xD5));
+    }
+
+    #[test]
+    fn every_code_round_trips() {
+        for byte in 0..=u8::MAX {
+            // 0x7F is μ-law's negative zero, which encodes as positive zero
+            let expected = if byte == 0x7F { 0xFF } else { byte };
+            assert_eq!(encode_mulaw(decode_mulaw(byte)), expected, "{:#04x}", byte);
+            assert_eq!(encode_alaw(decode_alaw(byte)), byte, "{:#04x}", byte);
+        }
+    }
+
+    #[test]
+    fn quantization_error_is_bounded() {
+        // each segment doubles the step size, so the error grows with the magnitude
+        for sample in i16::MIN..=i16::MAX {
+            let bound = (i32::from(sample).abs() + 256) / 32;
+            for (law, encoded) in [
+                (Law::MuLaw, encode_mulaw(sample)),
+                (Law::ALaw, encode_alaw(sample)),
+            ] {
+                let error = (i32::from(law.decode(encoded)) - i32::from(sample)).abs();
+                assert!(error <= bound, "{:?} {}: {}", law, sample, error);
+            }
+        }
+    }
+
+    #[test]
+    fn resampling_keeps_the_output_rate() {
+        let mut decoder = G711Decoder::new(Law::MuLaw, 16000).unwrap();
+        let mut output = Vec::new();
+        for _ in 0..10 {
+            decoder.decode(&[0xFF; 80], &mut output);
+        }
+        // the last output sample falls after the last input sample, so waits for the next one
+        assert_eq!(output.len(), 1599);
+        assert!(output.iter().all(|&s| s == 0));
+        assert!(matches!(
+            G711Decoder::new(Law::ALaw, 0),
+            Err(crate::Error::UnsupportedSampleRate(0))
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+//! Audio processing applied before audio is fed to the model.
+//!
+//! A [`Preprocessor`] transforms audio on its way into a [`Stream`](crate::Stream).
+//! Several can be chained together with a [`Pipeline`],
+//! which is then attached to a stream with
+//! [`Stream::set_preprocessor`](crate::Stream::set_preprocessor).
+//!
+//! Decoders for audio that needs converting before it can be fed to a model
+//! at all, such as [`g711`] telephony audio, or anything `ffmpeg` can read
+//! with the `ffmpeg-cli` feature, also live here,
+//! along with [`SamplesExt`] and friends for converting between sample counts and durations,
+//! and [`f32_to_i16`] and [`i16_to_f32`] for converting between sample formats.
+//!
+//! [`IntoModelAudio`] converts audio into what a model transcribes,
+//! for the one-shot entry points such as [`Model::speech_to_text`](crate::Model::speech_to_text).
+//! It's implemented for buffers of samples, [`AudioBuffer`]s that carry their format,
+//! and with the `audrey` feature, `audrey` readers.
+
+mod agc;
+mod buffer_pool;
+mod convert;
+#[cfg(feature = "rnnoise")]
+mod denoise;
+#[cfg(feature = "ffmpeg-cli")]
+mod ffmpeg;
+pub mod g711;
+mod model_audio;
+mod pcm;
+mod samples;
+#[cfg(any(feature = "watch", feature = "batch"))]
+mod wav;
+
+pub use agc::Agc;
+pub use buffer_pool::{BufferPool, PooledBuffer};
+pub(crate) use convert::{decode_s16, downmix_stereo};
+pub use convert::{f32_to_i16, i16_to_f32};
+#[cfg(feature = "rnnoise")]
+pub use denoise::Denoiser;
+#[cfg(feature = "ffmpeg-cli")]
+pub use ffmpeg::{ffmpeg_decode, Ffmpeg, FfmpegSamples};
+pub use model_audio::{AudioBuffer, IntoModelAudio};
+pub use pcm::PcmFormat;
+pub use samples::{duration_of, samples_in, SamplesExt};
+#[cfg(any(feature = "watch", feature = "batch"))]
+pub(crate) use wav::read_wav;
+
+/// A stage that transforms audio before it is fed to the model.
+///
+/// Stages may buffer audio internally, so the output of a call to
+/// [`process`](Preprocessor::process) is not necessarily the same length as its input.
+pub trait Preprocessor: Send {
+    /// Prepare to process audio at `sample_rate` Hz.
+    ///
+    /// This is called once, before any audio is processed.
+    /// The default implementation accepts any sample rate.
+    ///
+    /// # Errors
+    /// Returns an error if this stage cannot process audio at `sample_rate` Hz.
+    #[inline]
+    fn prepare(&mut self, sample_rate: u32) -> crate::Result<()> {
+        let _ = sample_rate;
+        Ok(())
+    }
+
+    /// Process `input`, appending the result to `output`.
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>);
+
+    /// Append any audio still buffered internally to `output`.
+    ///
+    /// This is called when the stream is finished. The default implementation does nothing.
+    #[inline]
+    fn flush(&mut self, output: &mut Vec<i16>) {
+        let _ = output;
+    }
+}
+
+impl<T: Preprocessor + ?Sized> Preprocessor for Box<T> {
+    #[inline]
+    fn prepare(&mut self, sample_rate: u32) -> crate::Result<()> {
+        (**self).prepare(sample_rate)
+    }
+
+    #[inline]
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        (**self).process(input, output);
+    }
+
+    #[inline]
+    fn flush(&mut self, output: &mut Vec<i16>) {
+        (**self).flush(output);
+    }
+}
+
+/// A chain of [`Preprocessor`]s, run one after another.
+///
+/// The audio passed between stages is kept in buffers from a [`BufferPool`].
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Box<dyn Preprocessor>>,
+    pool: BufferPool<i16>,
+}
+
+impl Pipeline {
+    /// Create a new, empty `Pipeline`. Audio passes through unchanged until stages are added.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a stage to the end of this pipeline, returning the pipeline.
+    #[inline]
+    #[must_use]
+    pub fn with(mut self, stage: impl Preprocessor + 'static) -> Self {
+        self.push(stage);
+        self
+    }
+
+    /// Take the buffers passed between stages from `pool`, rather than one of its own,
+    /// so they can be shared with other pipelines.
+    #[inline]
+    #[must_use]
+    pub fn with_buffer_pool(mut self, pool: BufferPool<i16>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Add a stage to the end of this pipeline.
+    #[inline]
+    pub fn push(&mut self, stage: impl Preprocessor + 'static) {
+        self.stages.push(Box::new(stage));
+    }
+
+    /// The number of stages in this pipeline.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    /// Whether this pipeline has no stages.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl Preprocessor for Pipeline {
+    #[inline]
+    fn prepare(&mut self, sample_rate: u32) -> crate::Result<()> {
+        self.stages
+            .iter_mut()
+            .try_for_each(|stage| stage.prepare(sample_rate))
+    }
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        let (last, rest) = match self.stages.split_last_mut() {
+            Some(stages) => stages,
+            None => return output.extend_from_slice(input),
+        };
+
+        let mut current = self.pool.get_empty(input.len());
+        current.extend_from_slice(input);
+        let mut next = self.pool.get_empty(input.len());
+        for stage in rest {
+            next.clear();
+            stage.process(&current, &mut next);
+            std::mem::swap(&mut current, &mut next);
+        }
+        last.process(&current, output);
+    }
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn flush(&mut self, output: &mut Vec<i16>) {
+        // audio flushed out of one stage still has to pass through the ones after it
+        let mut carry = self.pool.get_empty(0);
+        for stage in &mut self.stages {
+            let mut next = self.pool.get_empty(carry.len());
+            stage.process(&carry, &mut next);
+            stage.flush(&mut next);
+            carry = next;
+        }
+        output.extend_from_slice(&carry);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::audio::{duration_of, f32_to_i16};
+use crate::util::{downmix, resample};
+use std::borrow::Cow;
+use std::time::Duration;
+
+/// Audio that can be converted into what a model transcribes:
+/// 16-bit, mono audio at the model's sample rate.
+///
+/// [`Model::speech_to_text`](crate::Model::speech_to_text) and the other one-shot
+/// entry points take anything implementing this, so applications with their own audio types
+/// can implement it once, rather than converting at every call site.
+///
+/// Plain buffers of samples carry no format, so they are taken to be mono audio
+/// at the model's sample rate already. Use an [`AudioBuffer`] for audio that isn't.
+/// Buffers behind other pointers don't deref-coerce to `&[i16]` here: pass `&buf[..]`.
+///
+/// [`Stream`](crate::Stream)s don't take this: feeding them can't fail,
+/// so audio in other formats should be converted once, before it is fed.
+pub trait IntoModelAudio<'a> {
+    /// Convert this into 16-bit, mono audio at `sample_rate` Hz,
+    /// borrowing it if it's already in that format.
+    ///
+    /// # Errors
+    /// Returns an error if this audio couldn't be read or converted.
+    fn into_model_audio(self, sample_rate: u32) -> crate::Result<Cow<'a, [i16]>>;
+}
+
+impl<'a> IntoModelAudio<'a> for &'a [i16] {
+    #[inline]
+    fn into_model_audio(self, _sample_rate: u32) -> crate::Result<Cow<'a, [i16]>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl<'a, const N: usize> IntoModelAudio<'a> for &'a [i16; N] {
+    #[inline]
+    fn into_model_audio(self, _sample_rate: u32) -> crate::Result<Cow<'a, [i16]>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl<'a> IntoModelAudio<'a> for &'a Vec<i16> {
+    #[inline]
+    fn into_model_audio(self, _sample_rate: u32) -> crate::Result<Cow<'a, [i16]>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl IntoModelAudio<'_> for Vec<i16> {
+    #[inline]
+    fn into_model_audio(self, _sample_rate: u32) -> crate::Result<Cow<'static, [i16]>> {
+        Ok(Cow::Owned(self))
+    }
+}
+
+/// Samples from -1.0 to 1.0, as most audio libraries produce, converted with [`f32_to_i16`].
+impl IntoModelAudio<'_> for &[f32] {
+    #[inline]
+    fn into_model_audio(self, _sample_rate: u32) -> crate::Result<Cow<'static, [i16]>> {
+        Ok(Cow::Owned(f32_to_i16(self)))
+    }
+}
+
+impl IntoModelAudio<'_> for &Vec<f32> {
+    #[inline]
+    fn into_model_audio(self, sample_rate: u32) -> crate::Result<Cow<'static, [i16]>> {
+        self.as_slice().into_model_audio(sample_rate)
+    }
+}
+
+/// Interleaved 16-bit audio along with its format,
+/// mixed down and resampled to what the model expects when transcribed.
+///
+/// Converting it fails with [`Error::InvalidAudio`](crate::Error::InvalidAudio)
+/// if there are no channels, and with
+/// [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate) if the sample rate is 0.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioBuffer {
+    /// The samples, with the channels of each frame interleaved.
+    pub samples: Vec<i16>,
+    /// The number of channels.
+    pub channels: u16,
+    /// The sample rate, in Hz.
+    pub sample_rate: u32,
+}
+
+impl AudioBuffer {
+    /// Create a new `AudioBuffer` from interleaved `samples`
+    /// with `channels` channels at `sample_rate` Hz.
+    #[inline]
+    #[must_use]
+    pub const fn new(samples: Vec<i16>, channels: u16, sample_rate: u32) -> Self {
+        Self {
+            samples,
+            channels,
+            sample_rate,
+        }
+    }
+
+    /// How long this audio lasts.
+    #[inline]
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() / usize::from(self.channels.max(1));
+        duration_of(frames as u64, self.sample_rate)
+    }
+}
+
+impl<'a> IntoModelAudio<'a> for &'a AudioBuffer {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn into_model_audio(self, sample_rate: u32) -> crate::Result<Cow<'a, [i16]>> {
+        if self.channels == 1 && self.sample_rate == sample_rate {
+            return Ok(Cow::Borrowed(&self.samples));
+        }
+        let mono = downmix(&self.samples, self.channels)?;
+        Ok(Cow::Owned(resample(&mono, self.sample_rate, sample_rate)?))
+    }
+}
+
+impl IntoModelAudio<'_> for AudioBuffer {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn into_model_audio(self, sample_rate: u32) -> crate::Result<Cow<'static, [i16]>> {
+        if self.channels == 1 && self.sample_rate == sample_rate {
+            return Ok(Cow::Owned(self.samples));
+        }
+        (&self)
+            .into_model_audio(sample_rate)
+            .map(|audio| Cow::Owned(audio.into_owned()))
+    }
+}
+
+/// Reads the whole file, in any format `audrey` supports, then converts it like an [`AudioBuffer`].
+#[cfg(feature = "audrey")]
+impl<R: std::io::Read + std::io::Seek> IntoModelAudio<'_> for audrey::read::Reader<R> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn into_model_audio(mut self, sample_rate: u32) -> crate::Result<Cow<'static, [i16]>> {
+        let description = self.description();
+        let channels = u16::try_from(description.channel_count()).map_err(|_| {
+            crate::Error::InvalidAudio(format!(
+                "{} channels is too many",
+                description.channel_count()
+            ))
+        })?;
+        let samples = self
+            .samples()
+            .collect::<Result<Vec<i16>, _>>()
+            .map_err(audrey::read::ReadError::from)?;
+        AudioBuffer::new(samples, channels, description.sample_rate()).into_model_audio(sample_rate)
+    }
+}
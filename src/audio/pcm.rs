@@ -0,0 +1,42 @@
+/// The layout of raw PCM audio passed in as bytes.
+///
+/// Audio from files and most sound cards is little-endian, but audio arriving
+/// from some telephony systems and network protocols (such as RTP's L16 payload) is big-endian.
+/// Feeding big-endian audio as little-endian produces garbage output, without an error.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PcmFormat {
+    /// Signed 16-bit little-endian samples.
+    S16Le,
+    /// Signed 16-bit big-endian samples.
+    S16Be,
+}
+
+impl PcmFormat {
+    /// Signed 16-bit samples in the native byte order of the target platform.
+    #[cfg(target_endian = "little")]
+    pub const S16_NATIVE: Self = Self::S16Le;
+    /// Signed 16-bit samples in the native byte order of the target platform.
+    #[cfg(target_endian = "big")]
+    pub const S16_NATIVE: Self = Self::S16Be;
+
+    /// The number of bytes taken up by each sample.
+    #[inline]
+    #[must_use]
+    pub const fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::S16Le | Self::S16Be => 2,
+        }
+    }
+
+    /// Decode `bytes` into samples, appending them to `output`.
+    ///
+    /// Any trailing bytes that don't make up a whole sample are ignored.
+    #[inline]
+    pub fn decode_into(self, bytes: &[u8], output: &mut Vec<i16>) {
+        match self {
+            Self::S16Le => super::decode_s16(bytes, true, output),
+            Self::S16Be => super::decode_s16(bytes, false, output),
+        }
+    }
+}
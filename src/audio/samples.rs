@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// The number of samples in `duration` of audio at `sample_rate` Hz, rounded down.
+#[inline]
+#[must_use]
+pub fn samples_in(duration: Duration, sample_rate: u32) -> usize {
+    let samples = duration.as_nanos() * u128::from(sample_rate) / NANOS_PER_SEC;
+    usize::try_from(samples).unwrap_or(usize::MAX)
+}
+
+/// How long `samples` samples of audio at `sample_rate` Hz last, rounded up to the nanosecond,
+/// so [`samples_in`] gives back `samples`.
+///
+/// Returns [`Duration::ZERO`] if `sample_rate` is zero.
+#[inline]
+#[must_use]
+pub fn duration_of(samples: u64, sample_rate: u32) -> Duration {
+    if sample_rate == 0 {
+        return Duration::ZERO;
+    }
+    let nanos = (u128::from(samples) * NANOS_PER_SEC).div_ceil(u128::from(sample_rate));
+    Duration::new(
+        (nanos / NANOS_PER_SEC) as u64,
+        (nanos % NANOS_PER_SEC) as u32,
+    )
+}
+
+/// Helpers for working with buffers of audio in terms of time, rather than sample counts.
+pub trait SamplesExt {
+    /// How long this audio lasts at `sample_rate` Hz.
+    fn duration(&self, sample_rate: u32) -> Duration;
+
+    /// The first `duration` of this audio at `sample_rate` Hz,
+    /// or all of it if it is shorter than that.
+    fn trim_to(&self, duration: Duration, sample_rate: u32) -> &Self;
+}
+
+impl SamplesExt for [i16] {
+    #[inline]
+    fn duration(&self, sample_rate: u32) -> Duration {
+        duration_of(self.len() as u64, sample_rate)
+    }
+
+    #[inline]
+    fn trim_to(&self, duration: Duration, sample_rate: u32) -> &Self {
+        &self[..samples_in(duration, sample_rate).min(self.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{duration_of, samples_in, SamplesExt};
+    use std::time::Duration;
+
+    #[test]
+    fn durations_round_up_to_the_nanosecond() {
+        assert_eq!(duration_of(16000, 16000), Duration::from_secs(1));
+        assert_eq!(duration_of(441, 44100), Duration::from_millis(10));
+        // 22675.73ns
+        assert_eq!(duration_of(1, 44100), Duration::from_nanos(22676));
+        assert_eq!(duration_of(100, 0), Duration::ZERO);
+    }
+
+    #[test]
+    fn sample_counts_round_down() {
+        assert_eq!(samples_in(Duration::from_millis(10), 44100), 441);
+        // 44.1 samples
+        assert_eq!(samples_in(Duration::from_millis(1), 44100), 44);
+        assert_eq!(samples_in(Duration::from_nanos(22675), 44100), 0);
+        assert_eq!(samples_in(Duration::from_secs(1), 0), 0);
+        assert_eq!(samples_in(Duration::MAX, 48000), usize::MAX);
+    }
+
+    #[test]
+    fn sample_counts_survive_a_round_trip_through_durations() {
+        for sample_rate in [8000, 11025, 16000, 22050, 44100, 48000, 96000] {
+            for samples in (0..2000).chain([44099, 44100, 44101, 1 << 32]) {
+                let duration = duration_of(samples, sample_rate);
+                assert_eq!(
+                    samples_in(duration, sample_rate) as u64,
+                    samples,
+                    "{} samples at {} Hz",
+                    samples,
+                    sample_rate
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn buffers_are_measured_and_trimmed_in_time() {
+        let audio = [0_i16; 441];
+        assert_eq!(audio.duration(44100), Duration::from_millis(10));
+        assert_eq!(audio[..1].duration(44100), Duration::from_nanos(22676));
+        assert_eq!(audio.duration(0), Duration::ZERO);
+
+        assert_eq!(audio.trim_to(Duration::from_millis(1), 44100).len(), 44);
+        assert_eq!(audio.trim_to(Duration::from_millis(5), 44100).len(), 220);
+        assert!(audio.trim_to(Duration::ZERO, 44100).is_empty());
+        // trimming past the end keeps all of it
+        assert_eq!(audio.trim_to(Duration::from_secs(1), 44100).len(), 441);
+        assert_eq!(audio.trim_to(Duration::MAX, 44100).len(), 441);
+    }
+}
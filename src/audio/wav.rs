@@ -0,0 +1,39 @@
+use std::path::Path;
+
+/// Read the WAV file at `path` as 16-bit mono audio, which must be at `sample_rate` Hz.
+/// Samples of other sizes are scaled to 16 bits, and multichannel audio is downmixed.
+pub(crate) fn read_wav(path: &Path, sample_rate: u32) -> crate::Result<Vec<i16>> {
+    let reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    if spec.sample_rate != sample_rate {
+        return Err(crate::Error::UnsupportedSampleRate(spec.sample_rate));
+    }
+
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Int if spec.bits_per_sample == 16 => reader
+            .into_samples::<i16>()
+            .collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let shift = i32::from(spec.bits_per_sample) - 16;
+            reader
+                .into_samples::<i32>()
+                .map(|s| {
+                    s.map(|s| {
+                        if shift > 0 {
+                            (s >> shift) as i16
+                        } else {
+                            (s << -shift) as i16
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        hound::SampleFormat::Float => crate::audio::f32_to_i16(
+            &reader
+                .into_samples::<f32>()
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+
+    crate::util::downmix(&samples, spec.channels.max(1))
+}
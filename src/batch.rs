@@ -0,0 +1,340 @@
+use crate::audio::{duration_of, read_wav};
+use crate::window::{join_words, stitch, Window};
+use crate::{Model, Shutdown, Transcription, WindowOptions, Word};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// One window of one file, done, as a line of the manifest.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    file: String,
+    /// How long the file was, so progress on a file that has since changed is thrown away.
+    samples: usize,
+    window: usize,
+    text: String,
+    words: Vec<Word>,
+    confidence: f64,
+}
+
+/// Transcribes a list of WAV files, checkpointing its progress to a manifest file,
+/// so a run that crashed or was stopped picks up where it left off when run again.
+///
+/// Long files are split into overlapping [windows](WindowOptions), and every window
+/// is recorded in the manifest as soon as it has been transcribed, so at most one window's
+/// work is lost however long the files are. Once all of a file's windows are done,
+/// they are stitched together like
+/// [`Model::speech_to_text_windowed`](crate::Model::speech_to_text_windowed) does.
+///
+/// The manifest is appended to a line of JSON at a time. A line cut short by a crash is ignored,
+/// and so is the progress on a file whose length has changed since.
+/// Files and windows that failed are tried again on the next run.
+#[derive(Clone, Debug)]
+pub struct BatchJob {
+    manifest: PathBuf,
+    windows: WindowOptions,
+    on_error: ErrorPolicy,
+    shutdown: Option<Shutdown>,
+}
+
+/// What a [`BatchJob`] does when a file can't be read, or a window of one can't be transcribed.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Stop the run, returning the error from [`BatchJob::run`].
+    /// Everything done until then is still checkpointed.
+    FailFast,
+    /// Carry on with the rest, recording the error in [`BatchReport::errors`].
+    /// A file with some windows that failed is still transcribed from the others.
+    #[default]
+    SkipAndReport,
+}
+
+/// Something a [`BatchJob`] couldn't do, and carried on without.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct BatchError {
+    /// The file that couldn't be transcribed.
+    pub file: PathBuf,
+    /// The part of the file that couldn't be transcribed,
+    /// or `None` if the file couldn't be read at all.
+    pub window: Option<Range<Duration>>,
+    /// What went wrong.
+    pub error: crate::Error,
+}
+
+/// What a [`BatchJob::run`] did.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub struct BatchReport {
+    /// How many files were finished by this run.
+    pub completed: usize,
+    /// How many of the files finished by this run are missing windows that failed.
+    /// The next run tries those windows again.
+    pub incomplete: usize,
+    /// How many files had already been finished by an earlier run, and were skipped.
+    pub skipped: usize,
+    /// How many files couldn't be transcribed.
+    pub failed: usize,
+    /// How many files weren't started or finished, because a shutdown was triggered.
+    pub remaining: usize,
+    /// Every error skipped over, in the order they happened.
+    pub errors: Vec<BatchError>,
+}
+
+impl BatchJob {
+    /// Create a new `BatchJob` that checkpoints to the manifest file at `manifest`,
+    /// creating it if needed.
+    #[inline]
+    #[must_use]
+    pub fn new(manifest: impl Into<PathBuf>) -> Self {
+        Self {
+            manifest: manifest.into(),
+            windows: WindowOptions::default(),
+            on_error: ErrorPolicy::default(),
+            shutdown: None,
+        }
+    }
+
+    /// Set how files are split into windows, each of which is checkpointed.
+    ///
+    /// Changing this between runs of the same manifest mixes windows of different sizes,
+    /// so start a new manifest instead.
+    #[inline]
+    #[must_use]
+    pub const fn with_windows(mut self, windows: WindowOptions) -> Self {
+        self.windows = windows;
+        self
+    }
+
+    /// Set what to do when a file can't be read, or a window of one can't be transcribed.
+    /// By default, the run skips it and carries on.
+    #[inline]
+    #[must_use]
+    pub const fn with_error_policy(mut self, on_error: ErrorPolicy) -> Self {
+        self.on_error = on_error;
+        self
+    }
+
+    /// Stop between windows when `shutdown` is triggered, with everything done so far
+    /// checkpointed, so the next run carries on from there.
+    #[inline]
+    #[must_use]
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// The manifest file this checkpoints to.
+    #[inline]
+    #[must_use]
+    pub fn manifest(&self) -> &Path {
+        &self.manifest
+    }
+
+    /// Transcribe each of `files` with `model`, skipping any finished by an earlier run
+    /// and picking up part way through the one that was interrupted.
+    ///
+    /// `callback` is called with the path and [`Transcription`] of every file this run finishes,
+    /// or the error that stopped it from being transcribed.
+    /// Files must be WAV files at the model's sample rate. Multichannel audio is downmixed.
+    ///
+    /// # Errors
+    /// Returns an error if the manifest couldn't be read or written.
+    /// With [`ErrorPolicy::FailFast`], also returns the first error reading or transcribing
+    /// a file; otherwise, those are reported and skipped.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn run<P, F>(
+        &self,
+        model: &mut Model,
+        files: impl IntoIterator<Item = P>,
+        mut callback: F,
+    ) -> crate::Result<BatchReport>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path, crate::Result<Transcription>),
+    {
+        let (mut done, cut_short) = self.load()?;
+        let mut manifest = BufWriter::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.manifest)?,
+        );
+        if cut_short {
+            writeln!(manifest)?;
+        }
+        let model_info = model.info();
+        let sample_rate = model_info.sample_rate;
+
+        let mut report = BatchReport::default();
+        for file in files {
+            let file = file.as_ref();
+            if self.is_shutting_down() {
+                report.remaining += 1;
+                continue;
+            }
+            let start = Instant::now();
+            let audio = match read_wav(file, sample_rate) {
+                Ok(audio) => audio,
+                Err(e) => {
+                    self.skip(&mut report, file, None, e.clone())?;
+                    report.failed += 1;
+                    callback(file, Err(e));
+                    continue;
+                }
+            };
+            let name = file.to_string_lossy().into_owned();
+            let ranges = self.windows.ranges(audio.len(), sample_rate);
+            let mut checkpoints: Vec<Option<Checkpoint>> =
+                (0..ranges.len()).map(|_| None).collect();
+            for checkpoint in done.remove(&name).unwrap_or_default() {
+                if checkpoint.samples == audio.len() && checkpoint.window < ranges.len() {
+                    let window = checkpoint.window;
+                    checkpoints[window] = Some(checkpoint);
+                }
+            }
+            if checkpoints.iter().all(Option::is_some) {
+                report.skipped += 1;
+                continue;
+            }
+
+            let mut last_error = None;
+            let mut interrupted = false;
+            for (window, range) in ranges.iter().enumerate() {
+                if checkpoints[window].is_some() {
+                    continue;
+                }
+                if self.is_shutting_down() {
+                    interrupted = true;
+                    break;
+                }
+                let transcription = match model.speech_to_text_best(&audio[range.clone()]) {
+                    Ok(transcription) => transcription,
+                    Err(e) => {
+                        let span = duration_of(range.start as u64, sample_rate)
+                            ..duration_of(range.end as u64, sample_rate);
+                        self.skip(&mut report, file, Some(span), e.clone())?;
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                let checkpoint = Checkpoint {
+                    file: name.clone(),
+                    samples: audio.len(),
+                    window,
+                    text: transcription.text,
+                    words: transcription.words,
+                    confidence: transcription.confidence,
+                };
+                serde_json::to_writer(&mut manifest, &checkpoint)?;
+                writeln!(manifest)?;
+                manifest.flush()?;
+                checkpoints[window] = Some(checkpoint);
+            }
+
+            if interrupted {
+                report.remaining += 1;
+                continue;
+            }
+            let windows: Vec<(Range<usize>, Checkpoint)> = ranges
+                .into_iter()
+                .zip(checkpoints)
+                .filter_map(|(range, checkpoint)| Some((range, checkpoint?)))
+                .collect();
+            // a file is only failed if none of it could be transcribed
+            if let Some(e) = last_error.clone().filter(|_| windows.is_empty()) {
+                report.failed += 1;
+                callback(file, Err(e));
+                continue;
+            }
+
+            let confidence = windows.iter().map(|(_, c)| c.confidence).sum::<f64>()
+                / windows.len().max(1) as f64;
+            let (text, words) = match &windows[..] {
+                [(range, checkpoint)] if range.len() == audio.len() => {
+                    (checkpoint.text.clone(), checkpoint.words.clone())
+                }
+                _ => {
+                    let windows = windows
+                        .into_iter()
+                        .map(|(range, checkpoint)| {
+                            Window::new(range, sample_rate, checkpoint.words)
+                        })
+                        .collect();
+                    let words = stitch(windows, self.windows.tolerance);
+                    (join_words(&words), words)
+                }
+            };
+            report.completed += 1;
+            if last_error.is_some() {
+                report.incomplete += 1;
+            }
+            callback(
+                file,
+                Ok(Transcription {
+                    text,
+                    words,
+                    confidence,
+                    audio_duration: duration_of(audio.len() as u64, sample_rate),
+                    processing_time: start.elapsed(),
+                    model_info: model_info.clone(),
+                }),
+            );
+        }
+        Ok(report)
+    }
+
+    /// Skip over `error`, or with [`ErrorPolicy::FailFast`], return it to stop the run.
+    fn skip(
+        &self,
+        report: &mut BatchReport,
+        file: &Path,
+        window: Option<Range<Duration>>,
+        error: crate::Error,
+    ) -> crate::Result<()> {
+        match self.on_error {
+            ErrorPolicy::FailFast => Err(error),
+            ErrorPolicy::SkipAndReport => {
+                report.errors.push(BatchError {
+                    file: file.to_path_buf(),
+                    window,
+                    error,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn is_shutting_down(&self) -> bool {
+        self.shutdown
+            .as_ref()
+            .is_some_and(|shutdown| shutdown.is_triggered())
+    }
+
+    /// Read the windows already done from the manifest, by file.
+    ///
+    /// Also returns whether the manifest ends part way through a line, after a crash,
+    /// so the next line written has to start on a new one.
+    fn load(&self) -> crate::Result<(HashMap<String, Vec<Checkpoint>>, bool)> {
+        let mut done: HashMap<String, Vec<Checkpoint>> = HashMap::new();
+        let manifest = match std::fs::read_to_string(&self.manifest) {
+            Ok(manifest) => manifest,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((done, false)),
+            Err(e) => return Err(e.into()),
+        };
+        for line in manifest.lines() {
+            // a crash while writing can only cut the last line short, which is skipped
+            if let Ok(checkpoint) = serde_json::from_str::<Checkpoint>(line) {
+                done.entry(checkpoint.file.clone())
+                    .or_default()
+                    .push(checkpoint);
+            }
+        }
+        Ok((done, !manifest.is_empty() && !manifest.ends_with('\n')))
+    }
+}
@@ -0,0 +1,74 @@
+// Debug builds with the `tokio` and `tracing` features warn when inference blocks
+// a Tokio worker thread, which stalls every other task scheduled on it.
+
+/// Warn if `method` was called from a task on a Tokio worker thread,
+/// rather than from `spawn_blocking` or a thread of its own.
+#[inline(always)]
+pub(crate) fn check(method: &'static str) {
+    #[cfg(all(debug_assertions, feature = "tokio", feature = "tracing"))]
+    if on_async_worker() {
+        tracing::warn!(
+            method,
+            "blocking inference called from an async task, which stalls the Tokio worker thread; \
+             use the `_async` methods of `ThreadSafeStream`, or `tokio::task::spawn_blocking`"
+        );
+    }
+    #[cfg(not(all(debug_assertions, feature = "tokio", feature = "tracing")))]
+    let _ = method;
+}
+
+/// Whether the current thread is polling a future on a Tokio runtime.
+///
+/// Tokio doesn't say so directly, but only futures polled by the runtime have a limited
+/// [coop budget](tokio::task::coop); `spawn_blocking` and threads outside the runtime don't.
+/// Spending the budget down shows which one this is, and it is put back afterwards.
+#[cfg(all(debug_assertions, feature = "tokio", feature = "tracing"))]
+fn on_async_worker() -> bool {
+    use std::task::{Context, Poll, Waker};
+    use tokio::task::coop::poll_proceed;
+
+    // no budget is ever more than this
+    const MAX_BUDGET: usize = 256;
+
+    let mut cx = Context::from_waker(Waker::noop());
+    // puts the budget back as it was when dropped
+    let _restore = match poll_proceed(&mut cx) {
+        Poll::Ready(restore) => restore,
+        Poll::Pending => return true,
+    };
+    for _ in 0..MAX_BUDGET {
+        match poll_proceed(&mut cx) {
+            // dropping it would put back the unit it spent
+            Poll::Ready(spent) => std::mem::forget(spent),
+            Poll::Pending => return true,
+        }
+    }
+    false
+}
+
+#[cfg(all(
+    test,
+    not(loom),
+    debug_assertions,
+    feature = "tokio",
+    feature = "tracing"
+))]
+mod tests {
+    use super::on_async_worker;
+
+    #[test]
+    fn only_tasks_on_the_runtime_are_flagged() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert!(!on_async_worker());
+        runtime.block_on(async {
+            assert!(on_async_worker());
+            // checking leaves the budget as it was
+            assert!(tokio::task::coop::has_budget_remaining());
+            assert!(tokio::spawn(async { on_async_worker() }).await.unwrap());
+            let blocking = tokio::task::spawn_blocking(on_async_worker).await;
+            assert!(!blocking.unwrap());
+        });
+    }
+}
@@ -1,5 +1,8 @@
-use crate::{OwnedTokenMetadata, TokenMetadata};
+use crate::segment::{Whitespace, WordSegmenter};
+use crate::transcription::{group_words, group_words_with_end_times, token_offsets};
+use crate::{OwnedTokenMetadata, TokenMetadata, TokenOffset, Word};
 use std::fmt::{Display, Formatter, Write};
+use std::time::Duration;
 
 /// A single transcript computed by the model,
 /// including a confidence value and the metadata for its constituent tokens.
@@ -38,6 +41,72 @@ impl CandidateTranscript {
         self.0.num_tokens
     }
 
+    /// Group the tokens in this transcript into [`Word`](Word)s.
+    ///
+    /// Words are separated by whitespace. See [`words_with`](CandidateTranscript::words_with)
+    /// for languages that don't put spaces between words.
+    #[inline]
+    #[must_use]
+    pub fn words(&self) -> Vec<Word> {
+        self.words_with(&Whitespace)
+    }
+
+    /// Group the tokens in this transcript into [`Word`](Word)s found by `segmenter`.
+    ///
+    /// Each word takes its timing from the token it starts in.
+    #[inline]
+    #[must_use]
+    pub fn words_with(&self, segmenter: &(impl WordSegmenter + ?Sized)) -> Vec<Word> {
+        let tokens = self.tokens();
+        let texts: Vec<_> = tokens.iter().map(TokenMetadata::text).collect();
+        group_words(
+            texts
+                .iter()
+                .zip(tokens)
+                .map(|(text, t)| (text.as_ref(), t.timestep(), t.start_time())),
+            segmenter,
+        )
+    }
+
+    /// Group the tokens in this transcript into [`Word`](Word)s, each paired with when it ends,
+    /// for subtitles and karaoke-style highlighting.
+    ///
+    /// `libstt` only reports when tokens start, so a word is taken to end when the token after it,
+    /// usually a space, starts. The last word ends at `audio_duration`,
+    /// the length of the audio that was transcribed.
+    #[inline]
+    #[must_use]
+    pub fn words_with_end_times(&self, audio_duration: Duration) -> Vec<(Word, Duration)> {
+        let tokens = self.tokens();
+        let texts: Vec<_> = tokens.iter().map(TokenMetadata::text).collect();
+        group_words_with_end_times(
+            texts
+                .iter()
+                .zip(tokens)
+                .map(|(text, t)| (text.as_ref(), t.timestep(), t.start_time())),
+            &Whitespace,
+            audio_duration,
+        )
+    }
+
+    /// Return the text of this transcript: its tokens joined together.
+    #[inline]
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.tokens().iter().map(TokenMetadata::text).collect()
+    }
+
+    /// Return where each token sits in [`text`](CandidateTranscript::text),
+    /// in the same order as [`tokens`](CandidateTranscript::tokens).
+    ///
+    /// Useful for highlighting the word being spoken in a UI.
+    #[inline]
+    #[must_use]
+    pub fn char_offsets(&self) -> Vec<TokenOffset> {
+        let texts: Vec<_> = self.tokens().iter().map(TokenMetadata::text).collect();
+        token_offsets(texts.iter().map(AsRef::as_ref))
+    }
+
     /// Convert this into an [`OwnedCandidateTranscript`](OwnedCandidateTranscript) struct.
     ///
     /// **Warning**: this can be very expensive depending on the total number of tokens in this object.
@@ -60,6 +129,10 @@ pub struct OwnedCandidateTranscript {
 }
 
 impl OwnedCandidateTranscript {
+    pub(crate) const fn new(tokens: Vec<OwnedTokenMetadata>, confidence: f64) -> Self {
+        Self { tokens, confidence }
+    }
+
     /// Return an array of tokens in this transcript.
     #[inline]
     #[must_use]
@@ -82,6 +155,62 @@ impl OwnedCandidateTranscript {
     pub fn num_tokens(&self) -> usize {
         self.tokens.len()
     }
+
+    /// Return the text of this transcript: its tokens joined together.
+    #[inline]
+    #[must_use]
+    pub fn text(&self) -> String {
+        self.tokens.iter().map(|t| t.text.as_str()).collect()
+    }
+
+    /// Return where each token sits in [`text`](OwnedCandidateTranscript::text),
+    /// in the same order as [`tokens`](OwnedCandidateTranscript::tokens).
+    ///
+    /// Useful for highlighting the word being spoken in a UI.
+    #[inline]
+    #[must_use]
+    pub fn char_offsets(&self) -> Vec<TokenOffset> {
+        token_offsets(self.tokens.iter().map(|t| t.text.as_str()))
+    }
+
+    /// Group the tokens in this transcript into [`Word`](Word)s.
+    ///
+    /// Words are separated by whitespace. See [`words_with`](OwnedCandidateTranscript::words_with)
+    /// for languages that don't put spaces between words.
+    #[inline]
+    #[must_use]
+    pub fn words(&self) -> Vec<Word> {
+        self.words_with(&Whitespace)
+    }
+
+    /// Group the tokens in this transcript into [`Word`](Word)s found by `segmenter`.
+    ///
+    /// Each word takes its timing from the token it starts in.
+    #[inline]
+    #[must_use]
+    pub fn words_with(&self, segmenter: &(impl WordSegmenter + ?Sized)) -> Vec<Word> {
+        group_words(
+            self.tokens
+                .iter()
+                .map(|t| (t.text.as_str(), t.timestep, t.start_time)),
+            segmenter,
+        )
+    }
+
+    /// Group the tokens in this transcript into [`Word`](Word)s, each paired with when it ends.
+    ///
+    /// See [`CandidateTranscript::words_with_end_times`].
+    #[inline]
+    #[must_use]
+    pub fn words_with_end_times(&self, audio_duration: Duration) -> Vec<(Word, Duration)> {
+        group_words_with_end_times(
+            self.tokens
+                .iter()
+                .map(|t| (t.text.as_str(), t.timestep, t.start_time)),
+            &Whitespace,
+            audio_duration,
+        )
+    }
 }
 
 impl Display for OwnedCandidateTranscript {
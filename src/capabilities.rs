@@ -0,0 +1,71 @@
+use std::ffi::CStr;
+
+/// The runtime a `libstt` build runs models with.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Runtime {
+    /// TensorFlow Lite, which loads `.tflite` models.
+    TfLite,
+    /// The runtime couldn't be determined from the version of `libstt`.
+    Unknown,
+}
+
+/// What the linked `libstt` was built with, as returned by [`capabilities`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The version of `libstt`, as reported by the library itself.
+    pub version: String,
+    /// The runtime models are run with.
+    pub runtime: Runtime,
+    /// Whether models can run on a CUDA GPU, or `None` if it couldn't be determined.
+    pub cuda: Option<bool>,
+}
+
+/// Return the version of the linked `libstt`.
+///
+/// # Errors
+/// Returns an error if the version string is not valid UTF-8.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn version() -> crate::Result<String> {
+    // SAFETY: STT_Version always returns a valid string, which must be freed by STT_FreeString
+    unsafe {
+        let ptr = coqui_stt_sys::STT_Version();
+        if ptr.is_null() {
+            return Err(crate::Error::Unknown.context("STT_Version"));
+        }
+        let version = CStr::from_ptr(ptr).to_bytes().to_vec();
+        coqui_stt_sys::STT_FreeString(ptr);
+        Ok(String::from_utf8(version)?)
+    }
+}
+
+/// Report what the linked `libstt` was built with,
+/// so deployments can check at startup that they got the build they expected.
+///
+/// The C API has no way to ask for this directly, so it is worked out from the version:
+/// every release since 1.0.0 runs models with TensorFlow Lite, on the CPU only.
+/// For other versions, the runtime and CUDA support are reported as unknown.
+///
+/// # Errors
+/// Returns an error if the version string is not valid UTF-8.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn capabilities() -> crate::Result<Capabilities> {
+    let version = version()?;
+    let major = version
+        .trim_start_matches('v')
+        .split('.')
+        .next()
+        .and_then(|major| major.parse::<u32>().ok());
+
+    let (runtime, cuda) = match major {
+        Some(major) if major >= 1 => (Runtime::TfLite, Some(false)),
+        _ => (Runtime::Unknown, None),
+    };
+
+    Ok(Capabilities {
+        version,
+        runtime,
+        cuda,
+    })
+}
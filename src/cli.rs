@@ -0,0 +1,172 @@
+//! Helpers for showing transcripts in a terminal.
+
+use crate::{Metadata, Word};
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// How far apart, in timesteps of 20ms, the same word may start in two candidate transcripts
+/// and still count as agreeing.
+const TIMESTEP_TOLERANCE: u32 = 10;
+
+/// Thresholds for sorting words into confidence buckets.
+///
+/// Words with a confidence of at least `high` are shown in green,
+/// at least `low` in yellow, and anything below in red.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ConfidenceBuckets {
+    /// The lowest confidence shown in green.
+    pub high: f64,
+    /// The lowest confidence shown in yellow.
+    pub low: f64,
+}
+
+impl Default for ConfidenceBuckets {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            high: 0.9,
+            low: 0.5,
+        }
+    }
+}
+
+impl ConfidenceBuckets {
+    /// Create new buckets with the given thresholds.
+    #[inline]
+    #[must_use]
+    pub const fn new(high: f64, low: f64) -> Self {
+        Self { high, low }
+    }
+}
+
+/// Estimate the confidence of every word in the best candidate transcript in `metadata`.
+///
+/// `libstt` only scores whole transcripts, so this estimates each word's confidence
+/// as the fraction of candidate transcripts that contain the same word at about the same time.
+/// The estimate is only useful with several candidates:
+/// with a single one, every word gets a confidence of 1.
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn word_confidences(metadata: &Metadata) -> Vec<(Word, f64)> {
+    let candidates: Vec<_> = metadata.transcripts().iter().map(|t| t.words()).collect();
+    let best = match candidates.first() {
+        Some(best) => best,
+        None => return Vec::new(),
+    };
+
+    best.iter()
+        .map(|word| {
+            let agreeing = candidates
+                .iter()
+                .filter(|candidate| {
+                    candidate.iter().any(|other| {
+                        other.text == word.text
+                            && other.timestep.abs_diff(word.timestep) <= TIMESTEP_TOLERANCE
+                    })
+                })
+                .count();
+            (word.clone(), agreeing as f64 / candidates.len() as f64)
+        })
+        .collect()
+}
+
+/// Render the best candidate transcript in `metadata` as text with ANSI colours,
+/// colouring each word by its estimated confidence (see [`word_confidences`]).
+///
+/// Useful for a quick look at where a model or scorer is unsure of itself.
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn render_confidence(metadata: &Metadata, buckets: ConfidenceBuckets) -> String {
+    word_confidences(metadata)
+        .iter()
+        .map(|(word, confidence)| {
+            let colour = if *confidence >= buckets.high {
+                GREEN
+            } else if *confidence >= buckets.low {
+                YELLOW
+            } else {
+                RED
+            };
+            format!("{}{}{}", colour, word.text, RESET)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Model;
+    use coqui_stt_fake::{set_output, Output};
+
+    /// Transcribe with candidate transcripts `candidates`, best first, a token per character.
+    fn metadata(candidates: &[&str]) -> Metadata {
+        set_output(Some(Output {
+            transcripts: candidates
+                .iter()
+                .map(|text| text.bytes().map(|c| vec![c]).collect())
+                .collect(),
+        }));
+        let mut model = Model::new("model").unwrap();
+        let metadata = model
+            .speech_to_text_with_metadata(&[0; 16000], candidates.len() as u32)
+            .unwrap();
+        set_output(None);
+        metadata
+    }
+
+    fn candidates() -> Metadata {
+        metadata(&[
+            "the cat sat down",
+            "the cat sat town",
+            "the hat sat town",
+            "a hat sat town",
+        ])
+    }
+
+    #[test]
+    fn confidence_is_the_share_of_candidates_agreeing() {
+        let confidences: Vec<_> = word_confidences(&candidates())
+            .into_iter()
+            .map(|(word, confidence)| (word.text, confidence))
+            .collect();
+        assert_eq!(
+            confidences,
+            [
+                ("the".to_string(), 0.75),
+                ("cat".to_string(), 0.5),
+                ("sat".to_string(), 1.0),
+                ("down".to_string(), 0.25),
+            ]
+        );
+
+        let single = word_confidences(&metadata(&["the cat"]));
+        assert!(single.iter().all(|(_, confidence)| *confidence == 1.0));
+        assert!(word_confidences(&metadata(&[])).is_empty());
+    }
+
+    #[test]
+    fn words_are_coloured_by_bucket() {
+        // thresholds are inclusive, so "cat" at exactly 0.5 is yellow
+        assert_eq!(
+            render_confidence(&candidates(), ConfidenceBuckets::default()),
+            "\x1b[33mthe\x1b[0m \x1b[33mcat\x1b[0m \x1b[32msat\x1b[0m \x1b[31mdown\x1b[0m"
+        );
+        assert_eq!(
+            render_confidence(&candidates(), ConfidenceBuckets::new(0.75, 0.25)),
+            "\x1b[32mthe\x1b[0m \x1b[33mcat\x1b[0m \x1b[32msat\x1b[0m \x1b[33mdown\x1b[0m"
+        );
+        assert_eq!(
+            render_confidence(&candidates(), ConfidenceBuckets::new(1.5, 1.5)),
+            "\x1b[31mthe\x1b[0m \x1b[31mcat\x1b[0m \x1b[31msat\x1b[0m \x1b[31mdown\x1b[0m"
+        );
+        assert_eq!(
+            render_confidence(&metadata(&[]), ConfidenceBuckets::default()),
+            ""
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::Model;
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, Tag};
+use zeroize::Zeroize;
+
+/// Length of the nonce at the start of an encrypted model, in bytes.
+const NONCE_LEN: usize = 12;
+/// Length of the authentication tag at the end of an encrypted model, in bytes.
+const TAG_LEN: usize = 16;
+
+/// An authenticated cipher used to encrypt a model,
+/// for [`Model::new_from_encrypted_buffer`].
+///
+/// Encrypted models are laid out as the nonce, followed by the ciphertext,
+/// followed by the authentication tag, with no associated data.
+/// [`Aead::encrypt`] produces this layout.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aead {
+    /// ChaCha20-Poly1305, as defined in RFC 8439, with a 12 byte nonce.
+    ChaCha20Poly1305,
+}
+
+impl Aead {
+    /// Encrypt `plaintext` with `key` and `nonce`,
+    /// in the layout [`Model::new_from_encrypted_buffer`] expects.
+    ///
+    /// Never reuse a nonce with the same key.
+    ///
+    /// # Panics
+    /// Panics if `plaintext` is longer than the cipher allows, which is 256 GiB.
+    #[allow(clippy::missing_inline_in_public_items, clippy::expect_used)]
+    #[must_use]
+    pub fn encrypt(self, plaintext: &[u8], key: &[u8; 32], nonce: &[u8; NONCE_LEN]) -> Vec<u8> {
+        match self {
+            Self::ChaCha20Poly1305 => {
+                let mut out = Vec::with_capacity(NONCE_LEN + plaintext.len() + TAG_LEN);
+                out.extend_from_slice(nonce);
+                out.extend_from_slice(plaintext);
+                let tag = ChaCha20Poly1305::new(Key::from_slice(key))
+                    .encrypt_in_place_detached(Nonce::from_slice(nonce), b"", &mut out[NONCE_LEN..])
+                    .expect("plaintext is too long to encrypt");
+                out.extend_from_slice(&tag);
+                out
+            }
+        }
+    }
+}
+
+/// A buffer that is locked into memory if possible, and wiped before it is freed.
+struct LockedBuffer {
+    // declared first, so the memory is unlocked only after it is wiped
+    _lock: Option<region::LockGuard>,
+    buf: Vec<u8>,
+}
+
+impl LockedBuffer {
+    /// Copy `contents` into a new buffer, locking it into memory first.
+    ///
+    /// Locking fails if the buffer is bigger than `RLIMIT_MEMLOCK` allows,
+    /// in which case the buffer is only wiped.
+    fn new(contents: &[u8]) -> Self {
+        let mut buf = vec![0; contents.len()];
+        let lock = match region::lock(buf.as_ptr(), buf.len()) {
+            Ok(lock) => Some(lock),
+            Err(error) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    %error,
+                    len = buf.len(),
+                    "could not lock the decrypted model into memory, so it may be swapped to disk"
+                );
+                #[cfg(not(feature = "tracing"))]
+                let _ = error;
+                None
+            }
+        };
+        buf.copy_from_slice(contents);
+        Self { _lock: lock, buf }
+    }
+}
+
+impl Drop for LockedBuffer {
+    fn drop(&mut self) {
+        self.buf.zeroize();
+    }
+}
+
+impl Model {
+    /// Create a new model from an encrypted memory buffer,
+    /// for applications that must not ship a plaintext model.
+    ///
+    /// The model is decrypted into memory that is locked, so it is never swapped to disk,
+    /// and wiped as soon as the model has been loaded from it.
+    /// Note that `libstt` keeps its own copy of the model while it is loaded,
+    /// which this crate has no control over.
+    ///
+    /// Locking is best effort. Most systems limit how much memory a process can lock
+    /// with `RLIMIT_MEMLOCK`, often to 8 MiB or less, which is smaller than most models.
+    /// Raise the limit (with `ulimit -l`, `LimitMEMLOCK=` in a systemd unit, or the
+    /// `CAP_IPC_LOCK` capability) for the model to be locked.
+    /// Otherwise, it is loaded from unlocked memory and still wiped afterwards,
+    /// with a warning logged when the `tracing` feature is enabled.
+    ///
+    /// See [`Aead`] for the layout `ciphertext` must be in.
+    ///
+    /// # Errors
+    /// Returns [`Error::DecryptionFailed`](crate::Error::DecryptionFailed)
+    /// if `ciphertext` could not be decrypted with `key`.
+    /// Returns [`Error::NotSupportedOnPlatform`](crate::Error::NotSupportedOnPlatform) on Windows,
+    /// where `libstt` can't load models from memory.
+    /// Otherwise, passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new_from_encrypted_buffer(
+        ciphertext: &[u8],
+        key: &[u8; 32],
+        aead: Aead,
+    ) -> crate::Result<Self> {
+        if ciphertext.len() <= NONCE_LEN + TAG_LEN {
+            return Err(crate::Error::DecryptionFailed);
+        }
+        let (nonce, rest) = ciphertext.split_at(NONCE_LEN);
+        let (body, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+        let mut plaintext = LockedBuffer::new(body);
+        match aead {
+            Aead::ChaCha20Poly1305 => ChaCha20Poly1305::new(Key::from_slice(key))
+                .decrypt_in_place_detached(
+                    Nonce::from_slice(nonce),
+                    b"",
+                    &mut plaintext.buf,
+                    Tag::from_slice(tag),
+                )
+                .map_err(|_| crate::Error::DecryptionFailed)?,
+        }
+
+        Self::_new_from_buffer(&plaintext.buf)
+    }
+}
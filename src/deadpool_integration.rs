@@ -1,7 +1,8 @@
 pub use deadpool::managed::reexports::*;
 pub use deadpool_sync::reexports::*;
 
-use deadpool::managed::{Manager, RecycleResult};
+use crate::HealthCheck;
+use deadpool::managed::{Manager, RecycleError, RecycleResult};
 use deadpool_sync::SyncWrapper;
 
 /// A `deadpool` wrapper for Models.
@@ -9,6 +10,7 @@ pub struct DeadpoolModelWrapper {
     model_path: String,
     scorer_path: Option<String>,
     runtime: Runtime,
+    health_check: Option<HealthCheck>,
 }
 
 impl DeadpoolModelWrapper {
@@ -26,12 +28,26 @@ impl DeadpoolModelWrapper {
             model_path: model_path.into(),
             scorer_path: scorer_path.map(Into::into),
             runtime,
+            health_check: None,
         }
     }
+
+    /// Check that models still work when they are recycled, and throw away any that don't.
+    ///
+    /// Without a health check, models are only thrown away if they panicked.
+    #[must_use]
+    pub fn with_health_check(mut self, health_check: HealthCheck) -> Self {
+        self.health_check = Some(health_check);
+        self
+    }
 }
 
+/// Errors from creating or recycling a model in a [`DeadpoolModelWrapper`] pool.
+#[derive(Debug)]
 pub enum DeadpoolModelWrapperError {
+    /// An error from the C library.
     Stt(crate::Error),
+    /// The model could not be reached, for example because it panicked.
     Deadpool(InteractError),
 }
 
@@ -54,7 +70,7 @@ impl Manager for DeadpoolModelWrapper {
 
     async fn create(&self) -> Result<Self::Type, Self::Error> {
         let model_path = self.model_path.clone();
-        let mut m = SyncWrapper::new(self.runtime, move || crate::Model::new(model_path)).await?;
+        let m = SyncWrapper::new(self.runtime, move || crate::Model::new(model_path)).await?;
         if let Some(scorer_path) = &self.scorer_path {
             let scorer_path = scorer_path.clone();
             m.interact(move |m| m.enable_external_scorer(scorer_path))
@@ -64,7 +80,24 @@ impl Manager for DeadpoolModelWrapper {
         Ok(m)
     }
 
-    async fn recycle(&self, _: &mut Self::Type) -> RecycleResult<Self::Error> {
-        Ok(())
+    async fn recycle(&self, m: &mut Self::Type) -> RecycleResult<Self::Error> {
+        if m.is_mutex_poisoned() {
+            return Err(RecycleError::StaticMessage("model panicked"));
+        }
+        let check = match self.health_check {
+            Some(check) => check,
+            None => return Ok(()),
+        };
+
+        match self
+            .runtime
+            .timeout(check.deadline, m.interact(move |m| check.run(m)))
+            .await
+        {
+            Some(Ok(Ok(()))) => Ok(()),
+            Some(Ok(Err(e))) => Err(RecycleError::Backend(e.into())),
+            Some(Err(e)) => Err(RecycleError::Backend(e.into())),
+            None => Err(RecycleError::StaticMessage("health check timed out")),
+        }
     }
 }
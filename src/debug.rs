@@ -0,0 +1,137 @@
+//! Tools for finding out why audio was transcribed the way it was.
+//!
+//! A [`TapWriter`] records exactly what a stream fed to the model,
+//! after any resampling, downmixing or other processing, so it can be listened to.
+
+use crate::audio::Preprocessor;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Distinguishes files created in the same millisecond.
+static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// A [`Preprocessor`] that passes audio through unchanged,
+/// writing a copy of it to a WAV file at the model's sample rate.
+///
+/// Attach it to a stream with [`Stream::set_preprocessor`](crate::Stream::set_preprocessor),
+/// as the last stage of a [`Pipeline`](crate::audio::Pipeline) if there are others,
+/// and every buffer fed to the model afterwards is recorded.
+/// Each stream it is attached to gets a new file in the directory,
+/// named `tap-<milliseconds since the Unix epoch>-<counter>.wav`.
+///
+/// Audio is fed to the model even if writing it fails;
+/// the tap stops, and with the `tracing` feature, a warning is logged.
+pub struct TapWriter {
+    dir: PathBuf,
+    enabled: bool,
+    path: Option<PathBuf>,
+    writer: Option<WavWriter<BufWriter<File>>>,
+}
+
+impl std::fmt::Debug for TapWriter {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TapWriter")
+            .field("dir", &self.dir)
+            .field("enabled", &self.enabled)
+            .field("path", &self.path())
+            .finish()
+    }
+}
+
+impl TapWriter {
+    /// Create a new `TapWriter`, which writes files to `dir`, creating it if needed.
+    #[inline]
+    #[must_use]
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            enabled: true,
+            path: None,
+            writer: None,
+        }
+    }
+
+    /// Set whether audio is written out. Enabled by default.
+    ///
+    /// A disabled `TapWriter` only passes audio through, so it can be left in a pipeline
+    /// and switched on from an application's configuration when something needs debugging.
+    /// This takes effect from the next stream it is attached to.
+    #[inline]
+    #[must_use]
+    pub const fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// The file audio is currently being written to, if any.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.writer.as_ref().and(self.path.as_deref())
+    }
+
+    fn stop(&mut self, error: &hound::Error) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(%error, path = ?self.path, "stopped writing tapped audio");
+        #[cfg(not(feature = "tracing"))]
+        let _ = error;
+        self.writer = None;
+    }
+}
+
+impl Preprocessor for TapWriter {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn prepare(&mut self, sample_rate: u32) -> crate::Result<()> {
+        // finishes the file of the last stream, if any
+        self.writer = None;
+        if !self.enabled {
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&self.dir)?;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let n = NEXT_FILE.fetch_add(1, Ordering::Relaxed);
+        let path = self.dir.join(format!("tap-{millis}-{n}.wav"));
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        self.writer = Some(WavWriter::create(&path, spec)?);
+        self.path = Some(path);
+        Ok(())
+    }
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        output.extend_from_slice(input);
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+        if let Err(e) = input
+            .iter()
+            .try_for_each(|&sample| writer.write_sample(sample))
+        {
+            self.stop(&e);
+        }
+    }
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn flush(&mut self, _output: &mut Vec<i16>) {
+        // the stream is finishing, so make the file playable
+        if let Some(writer) = &mut self.writer {
+            if let Err(e) = writer.flush() {
+                self.stop(&e);
+            }
+        }
+    }
+}
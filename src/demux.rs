@@ -0,0 +1,198 @@
+use crate::vad::VoiceActivityDetector;
+use crate::{Segment, Transcriber, TranscriptEvent};
+use std::collections::hash_map::{Entry, HashMap};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Routes audio from many sources to a [`Transcriber`] each, keyed by an arbitrary ID,
+/// such as the participants of a conference bridge or the channels of a radio scanner.
+///
+/// The transcriber for a key is created by a factory function the first time audio arrives
+/// for it, and events from every key come back as one stream of `(key, event)` pairs.
+/// With an [`idle_timeout`](Demux::with_idle_timeout), keys that haven't had any audio for
+/// that long are finished and freed by [`evict_idle`](Demux::evict_idle).
+///
+/// If the factory fails, the error is returned and it is tried again on the next audio for the key.
+pub struct Demux<K, V, F> {
+    new_session: F,
+    sessions: HashMap<K, Session<V>>,
+    idle_timeout: Option<Duration>,
+}
+
+struct Session<V> {
+    transcriber: Transcriber<V>,
+    last_audio: Instant,
+}
+
+impl<K, V, F> Demux<K, V, F>
+where
+    K: Eq + Hash + Clone,
+    V: VoiceActivityDetector,
+    F: FnMut(&K) -> crate::Result<Transcriber<V>>,
+{
+    /// Create a new `Demux`, which calls `new_session` to create the [`Transcriber`]
+    /// for each key the first time audio arrives for it.
+    #[inline]
+    pub fn new(new_session: F) -> Self {
+        Self {
+            new_session,
+            sessions: HashMap::new(),
+            idle_timeout: None,
+        }
+    }
+
+    /// Evict keys that haven't had any audio for `idle_timeout`
+    /// when [`evict_idle`](Demux::evict_idle) is called.
+    ///
+    /// Without one, the default, sessions are only freed by [`remove`](Demux::remove)
+    /// and [`finish_all`](Demux::finish_all).
+    #[inline]
+    #[must_use]
+    pub const fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Feed audio from `key` into its transcriber, creating it if needed,
+    /// and return any events this caused.
+    ///
+    /// `audio` should be 16-bit, mono audio at the sample rate of the key's model.
+    ///
+    /// # Errors
+    /// Passes through any errors from creating the transcriber, or from
+    /// [`Transcriber::push_audio`].
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn push_audio(
+        &mut self,
+        key: K,
+        audio: &[i16],
+    ) -> crate::Result<Vec<(K, TranscriptEvent)>> {
+        let events = self.session(key.clone())?.push_audio(audio)?;
+        if let Some(session) = self.sessions.get_mut(&key) {
+            session.last_audio = Instant::now();
+        }
+        Ok(events
+            .into_iter()
+            .map(|event| (key.clone(), event))
+            .collect())
+    }
+
+    /// Return the transcriber for `key`, creating it if it doesn't exist yet.
+    ///
+    /// This is useful for checking the model's sample rate before pushing audio.
+    ///
+    /// # Errors
+    /// Passes through any errors from creating the transcriber.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn session(&mut self, key: K) -> crate::Result<&mut Transcriber<V>> {
+        let session = match self.sessions.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let transcriber = (self.new_session)(entry.key())?;
+                entry.insert(Session {
+                    transcriber,
+                    last_audio: Instant::now(),
+                })
+            }
+        };
+        Ok(&mut session.transcriber)
+    }
+
+    /// Return the transcriber for `key`, if it has one.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, key: &K) -> Option<&Transcriber<V>> {
+        self.sessions.get(key).map(|session| &session.transcriber)
+    }
+
+    /// Return the transcriber for `key`, if it has one.
+    #[inline]
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut Transcriber<V>> {
+        self.sessions
+            .get_mut(key)
+            .map(|session| &mut session.transcriber)
+    }
+
+    /// Iterate over the keys that currently have a transcriber.
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.sessions.keys()
+    }
+
+    /// The number of keys that currently have a transcriber.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Whether no key currently has a transcriber.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Finish the utterance in progress for `key`, if any, and free its transcriber.
+    ///
+    /// Does nothing if `key` has no transcriber.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn remove(&mut self, key: &K) -> crate::Result<Vec<(K, TranscriptEvent)>> {
+        let Some((key, mut session)) = self.sessions.remove_entry(key) else {
+            return Ok(Vec::new());
+        };
+        Ok(finals(key, session.transcriber.finish()?))
+    }
+
+    /// Finish and free the transcribers of every key that hasn't had audio
+    /// for the [`idle_timeout`](Demux::with_idle_timeout), returning their last events.
+    ///
+    /// Does nothing without an idle timeout.
+    /// A key whose utterance fails to finish is still freed, and the error is returned for it.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn evict_idle(&mut self) -> Vec<(K, crate::Result<TranscriptEvent>)> {
+        let Some(timeout) = self.idle_timeout else {
+            return Vec::new();
+        };
+        let now = Instant::now();
+        let idle: Vec<_> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| now.saturating_duration_since(session.last_audio) >= timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        self.finish_keys(idle)
+    }
+
+    /// Finish and free the transcribers of every key, returning their last events.
+    ///
+    /// A key whose utterance fails to finish is still freed, and the error is returned for it.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn finish_all(&mut self) -> Vec<(K, crate::Result<TranscriptEvent>)> {
+        let keys: Vec<_> = self.sessions.keys().cloned().collect();
+        self.finish_keys(keys)
+    }
+
+    fn finish_keys(&mut self, keys: Vec<K>) -> Vec<(K, crate::Result<TranscriptEvent>)> {
+        let mut events = Vec::new();
+        for key in keys {
+            match self.remove(&key) {
+                Ok(finals) => {
+                    events.extend(finals.into_iter().map(|(key, event)| (key, Ok(event))))
+                }
+                Err(e) => events.push((key, Err(e))),
+            }
+        }
+        events
+    }
+}
+
+fn finals<K>(key: K, segment: Option<Segment>) -> Vec<(K, TranscriptEvent)> {
+    segment
+        .map(|segment| (key, TranscriptEvent::Final(segment)))
+        .into_iter()
+        .collect()
+}
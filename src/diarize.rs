@@ -0,0 +1,191 @@
+use crate::audio::samples_in;
+use crate::Word;
+
+/// A backend that labels who is speaking, for tagging a [`Transcriber`](crate::Transcriber)'s
+/// segments and words with speakers.
+///
+/// This crate doesn't ship a diarization model:
+/// implement this to plug one in from another crate.
+///
+/// Speaker labels are arbitrary numbers, chosen by the implementation.
+/// They should stay the same for the same speaker across calls.
+pub trait Diarizer {
+    /// Return the speaker of `audio`, or `None` if it can't be told.
+    ///
+    /// `audio` is 16-bit, mono audio at `sample_rate` Hz,
+    /// either a whole utterance or a single word of one.
+    ///
+    /// # Errors
+    /// Returns an error if the backend fails to process the audio.
+    fn speaker(&mut self, audio: &[i16], sample_rate: u32) -> crate::Result<Option<u32>>;
+
+    /// Set the [`speaker`](Word::speaker) of every word in an utterance.
+    ///
+    /// `audio` is the whole utterance, and each word's [`start`](Word::start)
+    /// is relative to the start of it.
+    ///
+    /// The default implementation calls [`speaker`](Diarizer::speaker) with the audio
+    /// from the start of each word to the start of the next one.
+    /// Implementations that need more context than a single word should override this.
+    ///
+    /// # Errors
+    /// Returns an error if the backend fails to process the audio.
+    #[inline]
+    fn label_words(
+        &mut self,
+        audio: &[i16],
+        sample_rate: u32,
+        words: &mut [Word],
+    ) -> crate::Result<()> {
+        let starts: Vec<_> = words
+            .iter()
+            .map(|word| samples_in(word.start, sample_rate).min(audio.len()))
+            .collect();
+        for (i, word) in words.iter_mut().enumerate() {
+            let end = starts
+                .get(i + 1)
+                .map_or(audio.len(), |&end| end.max(starts[i]));
+            word.speaker = self.speaker(&audio[starts[i]..end], sample_rate)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Diarizer + ?Sized> Diarizer for Box<T> {
+    #[inline]
+    fn speaker(&mut self, audio: &[i16], sample_rate: u32) -> crate::Result<Option<u32>> {
+        (**self).speaker(audio, sample_rate)
+    }
+
+    #[inline]
+    fn label_words(
+        &mut self,
+        audio: &[i16],
+        sample_rate: u32,
+        words: &mut [Word],
+    ) -> crate::Result<()> {
+        (**self).label_words(audio, sample_rate, words)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{concat, silence, tone};
+    use std::time::Duration;
+
+    const RATE: u32 = 16000;
+
+    /// Tells speakers apart by pitch, with the zero-crossing rate as a one-number embedding.
+    /// Speakers are enrolled the first time they're heard, and matched to the closest one after.
+    #[derive(Default)]
+    struct PitchDiarizer {
+        speakers: Vec<f64>,
+        calls: Vec<usize>,
+    }
+
+    impl Diarizer for PitchDiarizer {
+        fn speaker(&mut self, audio: &[i16], _sample_rate: u32) -> crate::Result<Option<u32>> {
+            self.calls.push(audio.len());
+            let crossings = audio
+                .windows(2)
+                .filter(|pair| (pair[0] < 0) != (pair[1] < 0))
+                .count();
+            if crossings == 0 {
+                return Ok(None);
+            }
+            let embedding = crossings as f64 / audio.len() as f64;
+            let closest = self
+                .speakers
+                .iter()
+                .map(|speaker| (speaker - embedding).abs())
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            let speaker = match closest {
+                Some((speaker, distance)) if distance < embedding / 4.0 => speaker,
+                _ => {
+                    self.speakers.push(embedding);
+                    self.speakers.len() - 1
+                }
+            };
+            Ok(Some(speaker as u32))
+        }
+    }
+
+    fn word(start_ms: u64) -> Word {
+        Word {
+            text: String::from("word"),
+            start: Duration::from_millis(start_ms),
+            timestep: (start_ms / 20) as u32,
+            speaker: None,
+        }
+    }
+
+    fn voice(freq: f32) -> Vec<i16> {
+        tone(freq, Duration::from_millis(500), RATE)
+    }
+
+    #[test]
+    fn words_are_labelled_with_the_speaker_of_their_audio() {
+        let audio = concat([
+            voice(200.0),
+            voice(200.0),
+            voice(800.0),
+            voice(200.0),
+            voice(800.0),
+            voice(1600.0),
+        ]);
+        let mut words: Vec<_> = (0..6).map(|i| word(i * 500)).collect();
+        let mut diarizer = PitchDiarizer::default();
+        diarizer.label_words(&audio, RATE, &mut words).unwrap();
+
+        let speakers: Vec<_> = words.iter().map(|word| word.speaker).collect();
+        assert_eq!(
+            speakers,
+            [Some(0), Some(0), Some(1), Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(diarizer.calls, [8000; 6]);
+    }
+
+    #[test]
+    fn each_word_gets_the_audio_up_to_the_next_one() {
+        let audio = concat([silence(Duration::from_millis(300), RATE), voice(800.0)]);
+        let mut words = vec![word(0), word(300), word(700)];
+        let mut diarizer = PitchDiarizer::default();
+        diarizer.label_words(&audio, RATE, &mut words).unwrap();
+
+        assert_eq!(diarizer.calls, [4800, 6400, 1600]);
+        let speakers: Vec<_> = words.iter().map(|word| word.speaker).collect();
+        assert_eq!(speakers, [None, Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn words_outside_the_audio_get_none_of_it() {
+        let audio = voice(200.0);
+        // out of order, and past the end of the audio
+        let mut words = vec![word(250), word(100), word(900)];
+        let mut diarizer = PitchDiarizer::default();
+        diarizer.label_words(&audio, RATE, &mut words).unwrap();
+
+        assert_eq!(diarizer.calls, [0, 6400, 0]);
+        let speakers: Vec<_> = words.iter().map(|word| word.speaker).collect();
+        assert_eq!(speakers, [None, Some(0), None]);
+    }
+
+    #[test]
+    fn boxed_diarizers_keep_their_speakers() {
+        let mut diarizer: Box<dyn Diarizer> = Box::new(PitchDiarizer::default());
+        let low = voice(200.0);
+        let high = voice(800.0);
+        assert_eq!(diarizer.speaker(&low, RATE).unwrap(), Some(0));
+        assert_eq!(diarizer.speaker(&high, RATE).unwrap(), Some(1));
+        assert_eq!(diarizer.speaker(&low, RATE).unwrap(), Some(0));
+
+        let mut words = vec![word(0), word(500)];
+        diarizer
+            .label_words(&concat([high, low]), RATE, &mut words)
+            .unwrap();
+        assert_eq!(words[0].speaker, Some(1));
+        assert_eq!(words[1].speaker, Some(0));
+    }
+}
@@ -0,0 +1,282 @@
+use crate::audio::samples_in;
+use crate::util::{downmix_into, resample_into};
+use crate::vad::VoiceActivityDetector;
+use crate::{Demux, Transcriber, TranscriptEvent};
+use songbird::events::{CoreEvent, Event, EventContext, EventHandler};
+use songbird::Driver;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+/// The sample rate songbird decodes voice to, unless configured otherwise.
+const DISCORD_SAMPLE_RATE: u32 = 48_000;
+/// The number of channels songbird decodes voice to, unless configured otherwise.
+const DISCORD_CHANNELS: u16 = 2;
+/// How often songbird fires [`CoreEvent::VoiceTick`].
+const TICK: Duration = Duration::from_millis(20);
+
+/// Something that happened to the speech of one user in a voice channel,
+/// from a [`VoiceReceiver`].
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct VoiceEvent {
+    /// The RTP synchronisation source the audio came from, which identifies the user in the call.
+    pub ssrc: u32,
+    /// The Discord user ID of the speaker, once Discord has said who the SSRC belongs to.
+    pub user_id: Option<u64>,
+    /// What happened, or why transcribing this user's audio failed.
+    pub event: crate::Result<TranscriptEvent>,
+}
+
+enum Message {
+    Voice(u32, Vec<i16>),
+    Silent(Vec<u32>),
+    User(u32, u64),
+    Disconnect(u64),
+}
+
+/// Transcribes everyone speaking in a Discord voice call, received with songbird.
+///
+/// Each user gets a [`Transcriber`] of their own, created by a factory function
+/// the first time they speak, so their utterances are split and transcribed separately.
+/// Songbird's 48 kHz stereo audio is downmixed and resampled to the rate of each model.
+/// Every [`TranscriptEvent`] is passed to a callback as a [`VoiceEvent`],
+/// labelled with the SSRC and, once known, the Discord user ID of the speaker.
+///
+/// Transcribing runs on a worker thread, so songbird's event handlers never wait for inference.
+/// The callback is called on that thread, so it shouldn't block for long;
+/// sending the events down a channel works well.
+/// When a user leaves the call, their last utterance is finished and their model is freed.
+/// Once every clone of the receiver is dropped, including those registered with songbird,
+/// the utterances still in progress are finished and the worker thread exits.
+///
+/// Songbird only decodes audio with [`DecodeMode::Decode`](songbird::driver::DecodeMode::Decode),
+/// which has to be set in its `Config`, at the default 48 kHz stereo.
+///
+/// This enables songbird's default `rustls` and `tungstenite` features,
+/// so it can't be used along with songbird's `tws` feature.
+///
+/// Audio received some other way can be pushed in with [`push_voice`](VoiceReceiver::push_voice).
+#[derive(Clone, Debug)]
+pub struct VoiceReceiver {
+    tx: Sender<Message>,
+}
+
+impl VoiceReceiver {
+    /// Create a new `VoiceReceiver`, which calls `new_session` to create the [`Transcriber`]
+    /// for each user as they start speaking, and `on_event` with every event.
+    ///
+    /// Register it with a call with [`register`](VoiceReceiver::register).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new<V, F, E>(new_session: F, on_event: E) -> Self
+    where
+        V: VoiceActivityDetector + Send + 'static,
+        F: FnMut() -> crate::Result<Transcriber<V>> + Send + 'static,
+        E: FnMut(VoiceEvent) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let mut new_session = new_session;
+        let mut worker = Worker {
+            demux: Demux::new(move |_: &u32| new_session()),
+            on_event,
+            failed: HashSet::new(),
+            users: HashMap::new(),
+            mono: Vec::new(),
+            resampled: Vec::new(),
+        };
+        std::thread::spawn(move || {
+            for message in rx {
+                worker.handle(message);
+            }
+            worker.finish_all();
+        });
+        Self { tx }
+    }
+
+    /// Register this receiver with a songbird call for the events it needs:
+    /// [`CoreEvent::VoiceTick`] for audio, [`CoreEvent::SpeakingStateUpdate`] for who is speaking,
+    /// and [`CoreEvent::ClientDisconnect`] for users leaving.
+    ///
+    /// A [`Call`](songbird::Call) dereferences to a [`Driver`].
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn register(&self, driver: &mut Driver) {
+        for event in [
+            CoreEvent::VoiceTick,
+            CoreEvent::SpeakingStateUpdate,
+            CoreEvent::ClientDisconnect,
+        ] {
+            driver.add_global_event(event.into(), self.clone());
+        }
+    }
+
+    /// Queue up interleaved 48 kHz stereo audio spoken by `ssrc`.
+    #[inline]
+    pub fn push_voice(&self, ssrc: u32, audio: Vec<i16>) {
+        self.send(Message::Voice(ssrc, audio));
+    }
+
+    /// Record that `ssrc` belongs to the Discord user `user_id`.
+    #[inline]
+    pub fn set_user(&self, ssrc: u32, user_id: u64) {
+        self.send(Message::User(ssrc, user_id));
+    }
+
+    /// Finish the utterances of the Discord user `user_id`, and free their sessions.
+    #[inline]
+    pub fn remove_user(&self, user_id: u64) {
+        self.send(Message::Disconnect(user_id));
+    }
+
+    /// Send `message` to the worker. It only exits once every sender is gone,
+    /// so this can't fail unless it panicked, and then there is nobody to tell.
+    fn send(&self, message: Message) {
+        let _ = self.tx.send(message);
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler for VoiceReceiver {
+    #[allow(clippy::missing_inline_in_public_items)]
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        match ctx {
+            EventContext::VoiceTick(tick) => {
+                for (&ssrc, data) in &tick.speaking {
+                    if let Some(audio) = &data.decoded_voice {
+                        self.push_voice(ssrc, audio.clone());
+                    }
+                }
+                self.send(Message::Silent(tick.silent.iter().copied().collect()));
+            }
+            EventContext::SpeakingStateUpdate(speaking) => {
+                if let Some(user_id) = speaking.user_id {
+                    self.set_user(speaking.ssrc, user_id.0);
+                }
+            }
+            EventContext::ClientDisconnect(disconnect) => self.remove_user(disconnect.user_id.0),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// The worker thread's side of a [`VoiceReceiver`].
+struct Worker<V, F, E> {
+    demux: Demux<u32, V, F>,
+    on_event: E,
+    /// SSRCs whose transcriber couldn't be created, whose audio is ignored until they rejoin.
+    failed: HashSet<u32>,
+    users: HashMap<u32, u64>,
+    mono: Vec<i16>,
+    resampled: Vec<i16>,
+}
+
+impl<V, F, E> Worker<V, F, E>
+where
+    V: VoiceActivityDetector,
+    F: FnMut(&u32) -> crate::Result<Transcriber<V>>,
+    E: FnMut(VoiceEvent),
+{
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Voice(ssrc, audio) => self.voice(ssrc, &audio),
+            Message::Silent(ssrcs) => {
+                for ssrc in ssrcs {
+                    self.silence(ssrc);
+                }
+            }
+            Message::User(ssrc, user_id) => {
+                self.users.insert(ssrc, user_id);
+            }
+            Message::Disconnect(user_id) => {
+                let ssrcs: Vec<_> = self
+                    .users
+                    .iter()
+                    .filter(|(_, &user)| user == user_id)
+                    .map(|(&ssrc, _)| ssrc)
+                    .collect();
+                for ssrc in ssrcs {
+                    self.failed.remove(&ssrc);
+                    let res = self.demux.remove(&ssrc);
+                    self.emit(ssrc, res);
+                    self.users.remove(&ssrc);
+                }
+            }
+        }
+    }
+
+    fn voice(&mut self, ssrc: u32, audio: &[i16]) {
+        if self.failed.contains(&ssrc) {
+            return;
+        }
+        let sample_rate = match self.demux.session(ssrc) {
+            Ok(transcriber) => transcriber.model().get_sample_rate() as u32,
+            Err(e) => {
+                // only reported once
+                self.failed.insert(ssrc);
+                self.emit(ssrc, Err(e));
+                return;
+            }
+        };
+
+        self.mono.clear();
+        self.resampled.clear();
+        let res = downmix_into(audio, DISCORD_CHANNELS, &mut self.mono)
+            .and_then(|()| {
+                resample_into(
+                    &self.mono,
+                    DISCORD_SAMPLE_RATE,
+                    sample_rate,
+                    &mut self.resampled,
+                )
+            })
+            .and_then(|()| self.demux.push_audio(ssrc, &self.resampled));
+        self.emit(ssrc, res);
+    }
+
+    /// Feed a tick of silence to `ssrc`'s transcriber if it is in an utterance,
+    /// since Discord sends nothing while a user is quiet, and the utterance can't end without it.
+    fn silence(&mut self, ssrc: u32) {
+        let Some(transcriber) = self.demux.get(&ssrc) else {
+            return;
+        };
+        if !transcriber.in_speech() {
+            return;
+        }
+        let sample_rate = transcriber.model().get_sample_rate() as u32;
+        self.resampled.clear();
+        self.resampled.resize(samples_in(TICK, sample_rate), 0);
+        let res = self.demux.push_audio(ssrc, &self.resampled);
+        self.emit(ssrc, res);
+    }
+
+    fn finish_all(&mut self) {
+        for (ssrc, event) in self.demux.finish_all() {
+            let user_id = self.users.get(&ssrc).copied();
+            (self.on_event)(VoiceEvent {
+                ssrc,
+                user_id,
+                event,
+            });
+        }
+    }
+
+    fn emit(&mut self, ssrc: u32, events: crate::Result<Vec<(u32, TranscriptEvent)>>) {
+        let user_id = self.users.get(&ssrc).copied();
+        match events {
+            Ok(events) => {
+                for (_, event) in events {
+                    (self.on_event)(VoiceEvent {
+                        ssrc,
+                        user_id,
+                        event: Ok(event),
+                    });
+                }
+            }
+            Err(e) => (self.on_event)(VoiceEvent {
+                ssrc,
+                user_id,
+                event: Err(e),
+            }),
+        }
+    }
+}
@@ -66,6 +66,74 @@ pub enum Error {
     NulBytesFound,
     /// A string returned by `libstt` contained invalid UTF-8.
     Utf8Error(FromUtf8Error),
+    /// The voice activity detector returned an error.
+    Vad(String),
+    /// Audio at this sample rate (in Hz) is not supported.
+    UnsupportedSampleRate(u32),
+    /// An I/O error occurred.
+    Io(String),
+    /// Audio could not be decoded.
+    InvalidAudio(String),
+    /// JSON could not be written, or what was read isn't valid.
+    Json(String),
+    /// A setting, such as an environment variable read by
+    /// [`ModelConfig::from_env`](crate::util::ModelConfig::from_env), is missing or invalid.
+    InvalidConfig(String),
+    /// An encrypted model could not be decrypted, because the key is wrong or it was tampered with.
+    DecryptionFailed,
+    /// A downloaded or cached file, named here, does not match its checksum.
+    ChecksumMismatch(String),
+    /// A file, named here, was to be downloaded without a checksum to check it against.
+    MissingChecksum(String),
+    /// There is nothing to download at the URL given here.
+    ModelNotFound(String),
+    /// A model identifier or file name, given here, would reach outside the cache directory.
+    InvalidPath(String),
+    /// Growing a [`ModelPool`](crate::ModelPool) would take more memory than its limit allows.
+    MemoryLimitExceeded {
+        /// The memory the models would take, in bytes.
+        needed: u64,
+        /// The limit, in bytes.
+        limit: u64,
+    },
+    /// The worker thread of a [`ThreadSafeStream`](crate::ThreadSafeStream) panicked,
+    /// taking the stream with it.
+    WorkerPanicked,
+    /// The operation was refused because a [`Shutdown`](crate::Shutdown) was triggered.
+    ShuttingDown,
+    /// An [`SttService`](crate::SttService) refused a request, because its queue was full.
+    QueueFull,
+    /// An [`SttService`](crate::SttService) refused a request, because going by how fast
+    /// it has been transcribing, the request would take longer than it allows.
+    Overloaded {
+        /// How long the request was projected to take.
+        projected: std::time::Duration,
+        /// The most it was allowed to take.
+        limit: std::time::Duration,
+    },
+    /// A request to an [`SttService`](crate::SttService) waited past its deadline,
+    /// so it wasn't transcribed.
+    DeadlineExceeded,
+    /// The model is in a format this build of `libstt` can't load.
+    /// Current releases only load TensorFlow Lite models.
+    UnsupportedModelFormat(crate::util::ModelFormat),
+    /// `libstt` doesn't support the operation on this platform,
+    /// such as loading a model from a buffer on Windows.
+    NotSupportedOnPlatform,
+    /// A call into `libstt` failed.
+    ///
+    /// Every error returned by `libstt` comes wrapped in this, so it says which call it came from,
+    /// such as whether an [`InvalidScorer`](Error::InvalidScorer) came from loading a scorer
+    /// from a path or from a buffer. Use [`root`](Error::root) to match on the error itself.
+    ///
+    /// Its message only names the function, so error reporters that walk the
+    /// [`source`](StdError::source) chain don't repeat the error underneath.
+    Context {
+        /// The `libstt` function that failed, such as `STT_EnableExternalScorer`.
+        op: &'static str,
+        /// The error it returned.
+        source: Box<Error>,
+    },
 }
 
 impl Error {
@@ -98,6 +166,57 @@ impl Error {
             _ => Some(Other(err)),
         }
     }
+
+    /// Like [`from_c_int`](Error::from_c_int), but with the name of the `libstt` function
+    /// that returned `err`.
+    pub(crate) fn from_c_call(op: &'static str, err: std::os::raw::c_int) -> Option<Self> {
+        Self::from_c_int(err).map(|e| e.context(op))
+    }
+
+    /// Wrap this error in [`Context`](Error::Context), saying it came from the `libstt`
+    /// function `op`.
+    pub(crate) fn context(self, op: &'static str) -> Self {
+        Self::Context {
+            op,
+            source: Box::new(self),
+        }
+    }
+
+    /// The error underneath any [`Context`](Error::Context), for matching on what went wrong
+    /// regardless of where.
+    #[inline]
+    #[must_use]
+    pub const fn root(&self) -> &Self {
+        let mut error = self;
+        while let Self::Context { source, .. } = error {
+            error = &**source;
+        }
+        error
+    }
+
+    /// The `libstt` function this error came from, if it came from one.
+    #[inline]
+    #[must_use]
+    pub const fn op(&self) -> Option<&'static str> {
+        match self {
+            Self::Context { op, .. } => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Whether this error may go away if the same call is tried again,
+    /// such as a TensorFlow Lite session failing to run.
+    ///
+    /// `libstt` reports failed decodes without a reason, as [`Unknown`](Error::Unknown),
+    /// so that counts as transient too.
+    #[inline]
+    #[must_use]
+    pub const fn is_transient(&self) -> bool {
+        matches!(
+            self.root(),
+            Self::RunSessionFailed | Self::InterpreterFailed | Self::Unknown
+        )
+    }
 }
 
 impl Display for Error {
@@ -139,13 +258,59 @@ impl Display for Error {
                 e
             )
             .into(),
+            Self::Vad(e) => format!("The voice activity detector returned an error: {}", e).into(),
+            Self::UnsupportedSampleRate(rate) => {
+                format!("Audio at a sample rate of {} Hz is not supported.", rate).into()
+            }
+            Self::Io(e) => format!("An I/O error occurred: {}", e).into(),
+            Self::InvalidAudio(e) => format!("The audio could not be decoded: {}", e).into(),
+            Self::Json(e) => format!("A JSON error occurred: {}", e).into(),
+            Self::InvalidConfig(e) => format!("The configuration is invalid: {}", e).into(),
+            Self::DecryptionFailed => "The encrypted model could not be decrypted.".into(),
+            Self::ChecksumMismatch(file) => format!("{} does not match its checksum.", file).into(),
+            Self::MissingChecksum(file) => format!("No checksum is pinned for {}.", file).into(),
+            Self::ModelNotFound(url) => format!("Nothing was found at {}.", url).into(),
+            Self::InvalidPath(path) => format!("{} is not a valid path in the cache.", path).into(),
+            Self::MemoryLimitExceeded { needed, limit } => format!(
+                "The models would take {} bytes, more than the limit of {} bytes.",
+                needed, limit
+            )
+            .into(),
+            Self::WorkerPanicked => "The stream's worker thread panicked.".into(),
+            Self::ShuttingDown => "Shutdown has started, so the operation was refused.".into(),
+            Self::QueueFull => "The queue was full, so the request was refused.".into(),
+            Self::Overloaded { projected, limit } => format!(
+                "The request would take {:?}, more than the limit of {:?}, so it was refused.",
+                projected, limit
+            )
+            .into(),
+            Self::DeadlineExceeded => {
+                "The request waited past its deadline, so it wasn't transcribed.".into()
+            }
+            Self::UnsupportedModelFormat(format) => format!(
+                "This build of `libstt` only supports TensorFlow Lite models, not .{} models.",
+                format.extension()
+            )
+            .into(),
+            Self::NotSupportedOnPlatform => {
+                "The operation is not supported on this platform.".into()
+            }
+            Self::Context { op, .. } => format!("{} failed", op).into(),
             _ => "An unknown error was returned.".into(),
         };
         f.write_str(fancy_err.as_ref())
     }
 }
 
-impl StdError for Error {}
+impl StdError for Error {
+    #[inline]
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl From<FromBytesWithNulError> for Error {
     #[inline]
@@ -160,3 +325,105 @@ impl From<FromUtf8Error> for Error {
         Self::Utf8Error(e)
     }
 }
+
+impl From<std::io::Error> for Error {
+    #[inline]
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+#[cfg(any(
+    feature = "watch",
+    feature = "http",
+    feature = "debug-tap",
+    feature = "record",
+    feature = "batch"
+))]
+impl From<hound::Error> for Error {
+    #[inline]
+    fn from(e: hound::Error) -> Self {
+        match e {
+            hound::Error::IoError(e) => e.into(),
+            e => Self::InvalidAudio(e.to_string()),
+        }
+    }
+}
+
+#[cfg(any(
+    feature = "watch",
+    feature = "jsonl",
+    feature = "record",
+    feature = "batch"
+))]
+impl From<serde_json::Error> for Error {
+    #[inline]
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e.to_string())
+    }
+}
+
+#[cfg(feature = "audrey")]
+impl From<audrey::read::ReadError> for Error {
+    #[inline]
+    fn from(e: audrey::read::ReadError) -> Self {
+        match e {
+            audrey::read::ReadError::Io(e) => e.into(),
+            e => Self::InvalidAudio(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested() -> Error {
+        Error::InvalidScorer
+            .context("STT_EnableExternalScorer")
+            .context("STT_CreateModel")
+    }
+
+    #[test]
+    fn op_names_the_outermost_call() {
+        assert_eq!(nested().op(), Some("STT_CreateModel"));
+        assert_eq!(Error::InvalidScorer.op(), None);
+    }
+
+    #[test]
+    fn root_sees_through_every_context() {
+        assert!(matches!(nested().root(), Error::InvalidScorer));
+        assert!(matches!(Error::NoModel.root(), Error::NoModel));
+
+        // usable in const contexts
+        const { assert!(Error::RunSessionFailed.is_transient()) };
+        assert!(Error::RunSessionFailed
+            .context("STT_SpeechToText")
+            .is_transient());
+        assert!(!nested().is_transient());
+    }
+
+    #[test]
+    fn sources_chain_through_contexts() {
+        let error = nested();
+        assert_eq!(error.to_string(), "STT_CreateModel failed");
+
+        let inner = error.source().unwrap();
+        assert_eq!(inner.to_string(), "STT_EnableExternalScorer failed");
+        let root = inner.source().unwrap();
+        assert_eq!(root.to_string(), "Invalid scorer file.");
+        assert!(root.source().is_none());
+
+        // every message appears once in the chain
+        let mut chain = Vec::new();
+        let mut next: Option<&dyn StdError> = Some(&error);
+        while let Some(error) = next {
+            chain.push(error.to_string());
+            next = error.source();
+        }
+        assert_eq!(
+            chain.join(": "),
+            "STT_CreateModel failed: STT_EnableExternalScorer failed: Invalid scorer file."
+        );
+    }
+}
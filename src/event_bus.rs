@@ -0,0 +1,198 @@
+use crate::TranscriptEvent;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Which events an [`EventBus`] subscriber receives.
+///
+/// The default passes every event from every key. Each condition set narrows it further,
+/// and an event has to pass all of them.
+#[derive(Clone, Debug)]
+pub struct EventFilter<K> {
+    finals_only: bool,
+    /// Lowercase, and split into words.
+    keywords: Vec<Vec<String>>,
+    keys: Option<Vec<K>>,
+}
+
+impl<K> Default for EventFilter<K> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            finals_only: false,
+            keywords: Vec::new(),
+            keys: None,
+        }
+    }
+}
+
+impl<K: PartialEq> EventFilter<K> {
+    /// Create a filter that passes every event.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only pass [`TranscriptEvent::Final`] events.
+    #[inline]
+    #[must_use]
+    pub const fn finals_only(mut self) -> Self {
+        self.finals_only = true;
+        self
+    }
+
+    /// Only pass finished segments that contain at least one of `keywords`.
+    ///
+    /// Keywords are matched case-insensitively against whole words of the text,
+    /// so `"stop"` doesn't match `"stopped"`. A keyword of several words matches them in a row.
+    /// This implies [`finals_only`](EventFilter::finals_only).
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn with_keywords<S: AsRef<str>>(mut self, keywords: impl IntoIterator<Item = S>) -> Self {
+        self.finals_only = true;
+        self.keywords.extend(keywords.into_iter().map(|keyword| {
+            keyword
+                .as_ref()
+                .split_whitespace()
+                .map(str::to_lowercase)
+                .collect()
+        }));
+        self
+    }
+
+    /// Only pass events published with one of `keys`.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn with_keys(mut self, keys: impl IntoIterator<Item = K>) -> Self {
+        self.keys.get_or_insert_with(Vec::new).extend(keys);
+        self
+    }
+
+    /// Whether `event`, published with `key`, passes this filter.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn matches(&self, key: &K, event: &TranscriptEvent) -> bool {
+        if let Some(keys) = &self.keys {
+            if !keys.contains(key) {
+                return false;
+            }
+        }
+        match event {
+            TranscriptEvent::Final(segment) => {
+                if self.keywords.is_empty() {
+                    return true;
+                }
+                let words: Vec<_> = segment
+                    .text
+                    .split_whitespace()
+                    .map(str::to_lowercase)
+                    .collect();
+                self.keywords.iter().any(|keyword| {
+                    !keyword.is_empty() && words.windows(keyword.len()).any(|run| run == keyword)
+                })
+            }
+            TranscriptEvent::SpeechStarted { .. } => !self.finals_only,
+        }
+    }
+}
+
+struct Subscriber<K> {
+    filter: EventFilter<K>,
+    tx: Sender<(K, TranscriptEvent)>,
+}
+
+/// Passes [`TranscriptEvent`]s from the parts of an application that transcribe audio
+/// to the parts that consume them, without either knowing about the other.
+///
+/// Producers [`publish`](EventBus::publish) events with a key saying which session they came
+/// from, such as a user or channel ID, or `()` if there is only one.
+/// Consumers [`subscribe`](EventBus::subscribe) with an [`EventFilter`],
+/// and receive the `(key, event)` pairs that pass it through a channel.
+/// A subscriber is removed once its receiver is dropped.
+///
+/// Every subscriber's channel is unbounded, so one that stops receiving
+/// keeps every event published after that in memory.
+///
+/// Cloning an `EventBus` returns another handle to the same one.
+pub struct EventBus<K = ()> {
+    subscribers: Arc<Mutex<Vec<Subscriber<K>>>>,
+}
+
+impl<K> Clone for EventBus<K> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            subscribers: Arc::clone(&self.subscribers),
+        }
+    }
+}
+
+impl<K> Default for EventBus<K> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl<K> std::fmt::Debug for EventBus<K> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscribers().len())
+            .finish()
+    }
+}
+
+impl<K: PartialEq + Clone> EventBus<K> {
+    /// Create a new `EventBus` with no subscribers.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to the events that pass `filter`, from now on.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn subscribe(&self, filter: EventFilter<K>) -> Receiver<(K, TranscriptEvent)> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers().push(Subscriber { filter, tx });
+        rx
+    }
+
+    /// Send `event`, from the session `key`, to every subscriber whose filter it passes.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn publish(&self, key: &K, event: &TranscriptEvent) {
+        self.subscribers().retain(|subscriber| {
+            !subscriber.filter.matches(key, event)
+                || subscriber.tx.send((key.clone(), event.clone())).is_ok()
+        });
+    }
+
+    /// [`publish`](EventBus::publish) each of `events`,
+    /// such as those returned by [`Demux::push_audio`](crate::Demux::push_audio).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn publish_all(&self, events: impl IntoIterator<Item = (K, TranscriptEvent)>) {
+        for (key, event) in events {
+            self.publish(&key, &event);
+        }
+    }
+
+    /// The number of subscribers.
+    ///
+    /// Subscribers whose receivers were dropped are only noticed
+    /// the next time an event passes their filter, so they may still be counted.
+    #[inline]
+    #[must_use]
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers().len()
+    }
+}
+
+impl<K> EventBus<K> {
+    fn subscribers(&self) -> MutexGuard<'_, Vec<Subscriber<K>>> {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
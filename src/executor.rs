@@ -0,0 +1,272 @@
+use crate::job_queue::JobQueue;
+use crate::{Metadata, Model, OwnedStream};
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+/// How many jobs a thread runs for one stream before giving other streams a turn.
+const BUDGET: usize = 16;
+
+enum Job {
+    Feed(Vec<i16>),
+    IntermediateDecode(Sender<crate::Result<String>>),
+    IntermediateDecodeWithMetadata(u32, Sender<crate::Result<Metadata>>),
+    Finish(Sender<(crate::Result<String>, Model)>),
+    FinishWithMetadata(u32, Sender<(crate::Result<Metadata>, Model)>),
+}
+
+struct Jobs {
+    queue: VecDeque<Job>,
+    /// Whether the stream is waiting in the run queue, or running on a thread.
+    scheduled: bool,
+}
+
+/// A stream, along with the jobs waiting to run on it.
+struct Task {
+    jobs: Mutex<Jobs>,
+    /// Only ever locked by the one thread running the stream,
+    /// or by the caller once the executor is gone.
+    stream: Mutex<Option<OwnedStream>>,
+    /// Why the stream was lost, if a job on it panicked.
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl Task {
+    fn jobs(&self) -> MutexGuard<'_, Jobs> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run the jobs queued up, returning whether there are more left for another turn.
+    fn run(&self) -> bool {
+        for _ in 0..BUDGET {
+            let job = {
+                let mut jobs = self.jobs();
+                match jobs.queue.pop_front() {
+                    Some(job) => job,
+                    None => {
+                        jobs.scheduled = false;
+                        return false;
+                    }
+                }
+            };
+            self.run_job(job);
+        }
+        true
+    }
+
+    /// Wait for the result of finishing the stream,
+    /// passing on the panic that took the model with it, if there was one.
+    fn finished<T>(&self, rx: &Receiver<(crate::Result<T>, Model)>) -> (crate::Result<T>, Model) {
+        match rx.recv() {
+            Ok(res) => res,
+            Err(_) => {
+                let panic = self.panic.lock().unwrap_or_else(|e| e.into_inner()).take();
+                std::panic::resume_unwind(
+                    panic.unwrap_or_else(|| Box::new(crate::Error::WorkerPanicked)),
+                )
+            }
+        }
+    }
+
+    fn run_job(&self, job: Job) {
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        let res = std::panic::catch_unwind(AssertUnwindSafe(|| match job {
+            Job::Feed(audio) => {
+                if let Some(stream) = stream.as_mut() {
+                    stream.feed_audio(&audio);
+                }
+            }
+            Job::IntermediateDecode(tx) => {
+                let res = stream.as_mut().ok_or(crate::Error::WorkerPanicked);
+                let _ = tx.send(res.and_then(OwnedStream::intermediate_decode));
+            }
+            Job::IntermediateDecodeWithMetadata(num_results, tx) => {
+                let res = stream.as_mut().ok_or(crate::Error::WorkerPanicked);
+                let _ = tx.send(
+                    res.and_then(|stream| stream.intermediate_decode_with_metadata(num_results)),
+                );
+            }
+            Job::Finish(tx) => {
+                if let Some(stream) = stream.take() {
+                    let _ = tx.send(stream.finish_stream());
+                }
+            }
+            Job::FinishWithMetadata(num_results, tx) => {
+                if let Some(stream) = stream.take() {
+                    let _ = tx.send(stream.finish_stream_with_metadata(num_results));
+                }
+            }
+        }));
+        // a panic only takes this stream down, not the thread shared with every other stream
+        if let Err(panic) = res {
+            *stream = None;
+            *self.panic.lock().unwrap_or_else(|e| e.into_inner()) = Some(panic);
+        }
+    }
+}
+
+/// A small pool of threads that runs many streams, for services with far more streams
+/// than are busy at any one time, such as push-to-talk voice chat.
+///
+/// A [`ThreadSafeStream`](crate::ThreadSafeStream) keeps a thread for its whole life,
+/// even while nobody is talking. Streams from [`stream`](MiniExecutor::stream) instead only
+/// take up a thread while they have audio to feed or a decode to run,
+/// so hundreds of them can share a couple of threads.
+/// Jobs for one stream still run in the order they were queued, and never at the same time.
+///
+/// Dropping the executor waits for the jobs already queued to finish.
+/// Streams left over after that run their jobs on the calling thread instead.
+pub struct MiniExecutor {
+    run_queue: JobQueue<Arc<Task>>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl MiniExecutor {
+    /// Create a new `MiniExecutor`, running streams on `threads` threads.
+    ///
+    /// At least one thread is always started.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn new(threads: usize) -> Self {
+        let run_queue: JobQueue<Arc<Task>> = JobQueue::new();
+        let threads = (0..threads.max(1))
+            .map(|_| {
+                let run_queue = run_queue.clone();
+                std::thread::spawn(move || {
+                    while let Ok(task) = run_queue.pop(None) {
+                        // give other streams a turn before running the rest of this one's jobs
+                        if task.run() && run_queue.push(Arc::clone(&task)).is_err() {
+                            while task.run() {}
+                        }
+                    }
+                })
+            })
+            .collect();
+        Self { run_queue, threads }
+    }
+
+    /// The number of threads streams run on.
+    #[inline]
+    #[must_use]
+    pub fn threads(&self) -> usize {
+        self.threads.len()
+    }
+
+    /// Start a stream on `model`, which runs on this executor's threads.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn stream(&self, model: Model) -> crate::Result<ExecutorStream> {
+        Ok(ExecutorStream {
+            task: Arc::new(Task {
+                jobs: Mutex::new(Jobs {
+                    queue: VecDeque::new(),
+                    scheduled: false,
+                }),
+                stream: Mutex::new(Some(OwnedStream::new(model)?)),
+                panic: Mutex::new(None),
+            }),
+            run_queue: self.run_queue.clone(),
+        })
+    }
+}
+
+impl Drop for MiniExecutor {
+    #[inline]
+    fn drop(&mut self) {
+        self.run_queue.close();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A stream running on a [`MiniExecutor`], from [`MiniExecutor::stream`].
+///
+/// Calls are queued up and run in the order they were made.
+/// Feeding audio returns immediately, while decoding waits for the result.
+pub struct ExecutorStream {
+    task: Arc<Task>,
+    run_queue: JobQueue<Arc<Task>>,
+}
+
+impl ExecutorStream {
+    /// Queue up audio to be fed to the model.
+    ///
+    /// This returns as soon as the audio is queued.
+    #[inline]
+    pub fn feed_audio(&self, buffer: Vec<i16>) {
+        self.send(Job::Feed(buffer));
+    }
+
+    /// Compute the intermediate decoding of the audio fed so far.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode(&self) -> crate::Result<String> {
+        let (tx, rx) = mpsc::channel();
+        self.send(Job::IntermediateDecode(tx));
+        rx.recv().unwrap_or(Err(crate::Error::WorkerPanicked))
+    }
+
+    /// Compute the intermediate decoding of the audio fed so far,
+    /// with up to `num_results` candidate transcripts.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode_with_metadata(&self, num_results: u32) -> crate::Result<Metadata> {
+        let (tx, rx) = mpsc::channel();
+        self.send(Job::IntermediateDecodeWithMetadata(num_results, tx));
+        rx.recv().unwrap_or(Err(crate::Error::WorkerPanicked))
+    }
+
+    /// Compute the final decoding of the audio fed, and return the model along with it.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    ///
+    /// # Panics
+    /// Panics if an earlier job on this stream panicked, taking the model with it.
+    #[inline]
+    pub fn finish_stream(self) -> (crate::Result<String>, Model) {
+        let (tx, rx) = mpsc::channel();
+        self.send(Job::Finish(tx));
+        self.task.finished(&rx)
+    }
+
+    /// Compute the final decoding of the audio fed, with up to `num_results` candidate transcripts,
+    /// and return the model along with it.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    ///
+    /// # Panics
+    /// Panics if an earlier job on this stream panicked, taking the model with it.
+    #[inline]
+    pub fn finish_stream_with_metadata(self, num_results: u32) -> (crate::Result<Metadata>, Model) {
+        let (tx, rx) = mpsc::channel();
+        self.send(Job::FinishWithMetadata(num_results, tx));
+        self.task.finished(&rx)
+    }
+
+    /// Queue up `job`, and schedule the stream to run if it isn't already.
+    fn send(&self, job: Job) {
+        let mut jobs = self.task.jobs();
+        jobs.queue.push_back(job);
+        if jobs.scheduled {
+            return;
+        }
+        jobs.scheduled = true;
+        drop(jobs);
+        if self.run_queue.push(Arc::clone(&self.task)).is_err() {
+            // the executor is gone, so run the jobs here instead
+            while self.task.run() {}
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// When an [`SttService`](crate::SttService) switches to its standby model, and back.
+///
+/// Errors from the primary model, and transcriptions that take longer than
+/// [`latency_budget`](FailoverPolicy::latency_budget), count as strikes against it.
+/// After [`after_strikes`](FailoverPolicy::after_strikes) in a row, requests go to the standby.
+/// Every [`retry_primary_after`](FailoverPolicy::retry_primary_after),
+/// one request is tried on the primary again, and if it succeeds in time,
+/// requests go back to the primary.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FailoverPolicy {
+    /// How many strikes in a row it takes to fail over.
+    pub after_strikes: u32,
+    /// The longest the primary may take to transcribe a request before it counts as a strike.
+    /// `None` only counts errors.
+    pub latency_budget: Option<Duration>,
+    /// How long to wait after failing over before trying the primary again.
+    pub retry_primary_after: Duration,
+}
+
+impl Default for FailoverPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            after_strikes: 3,
+            latency_budget: None,
+            retry_primary_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl FailoverPolicy {
+    /// Set [`after_strikes`](FailoverPolicy::after_strikes).
+    #[inline]
+    #[must_use]
+    pub const fn with_after_strikes(mut self, after_strikes: u32) -> Self {
+        self.after_strikes = after_strikes;
+        self
+    }
+
+    /// Set [`latency_budget`](FailoverPolicy::latency_budget).
+    #[inline]
+    #[must_use]
+    pub const fn with_latency_budget(mut self, latency_budget: Option<Duration>) -> Self {
+        self.latency_budget = latency_budget;
+        self
+    }
+
+    /// Set [`retry_primary_after`](FailoverPolicy::retry_primary_after).
+    #[inline]
+    #[must_use]
+    pub const fn with_retry_primary_after(mut self, retry_primary_after: Duration) -> Self {
+        self.retry_primary_after = retry_primary_after;
+        self
+    }
+}
+
+/// Why an [`SttService`](crate::SttService) failed over to its standby model.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum FailoverCause {
+    /// The primary model kept failing. This is the last error it returned.
+    Errors(crate::Error),
+    /// The primary model kept taking longer than the
+    /// [latency budget](FailoverPolicy::latency_budget). This is how long it last took.
+    TooSlow(Duration),
+}
+
+/// A change in which model an [`SttService`](crate::SttService) transcribes with,
+/// from [`SttService::failover_events`](crate::SttService::failover_events).
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum FailoverEvent {
+    /// Requests are now transcribed with the standby model.
+    FailedOver(FailoverCause),
+    /// The primary model succeeded again, so requests are transcribed with it again.
+    Recovered,
+}
+
+/// Which model a request should be transcribed with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Route {
+    Primary,
+    /// The primary, to find out whether it has recovered.
+    Probe,
+    Standby,
+}
+
+/// Tracks strikes against the primary model, and whether the service failed over.
+#[derive(Default)]
+pub(crate) struct Failover {
+    strikes: u32,
+    /// When the service failed over, or last tried the primary again while failed over.
+    failed_over: Option<Instant>,
+    probing: bool,
+    subscribers: Vec<mpsc::Sender<FailoverEvent>>,
+}
+
+impl Failover {
+    pub(crate) fn subscribe(&mut self) -> mpsc::Receiver<FailoverEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    pub(crate) const fn is_failed_over(&self) -> bool {
+        self.failed_over.is_some()
+    }
+
+    pub(crate) fn route(&mut self, policy: &FailoverPolicy) -> Route {
+        match self.failed_over {
+            None => Route::Primary,
+            Some(since) if !self.probing && since.elapsed() >= policy.retry_primary_after => {
+                self.probing = true;
+                Route::Probe
+            }
+            Some(_) => Route::Standby,
+        }
+    }
+
+    /// Record a strike against the primary, failing over if it was one too many
+    /// and `can_fail_over`, because there is a standby.
+    pub(crate) fn strike(
+        &mut self,
+        route: Route,
+        cause: FailoverCause,
+        policy: &FailoverPolicy,
+        can_fail_over: bool,
+    ) {
+        match route {
+            Route::Probe => {
+                // not recovered yet, so wait a while longer
+                self.probing = false;
+                self.failed_over = Some(Instant::now());
+            }
+            Route::Primary if self.failed_over.is_none() => {
+                self.strikes += 1;
+                if self.strikes >= policy.after_strikes.max(1) && can_fail_over {
+                    self.strikes = 0;
+                    self.failed_over = Some(Instant::now());
+                    self.publish(&FailoverEvent::FailedOver(cause));
+                }
+            }
+            // requests that were already on their way to the primary when it failed over
+            Route::Primary | Route::Standby => {}
+        }
+    }
+
+    /// Record the primary transcribing a request successfully and in time.
+    pub(crate) fn success(&mut self, route: Route) {
+        match route {
+            Route::Probe => {
+                self.probing = false;
+                self.failed_over = None;
+                self.strikes = 0;
+                self.publish(&FailoverEvent::Recovered);
+            }
+            Route::Primary if self.failed_over.is_none() => self.strikes = 0,
+            Route::Primary | Route::Standby => {}
+        }
+    }
+
+    /// Record a request that didn't say anything about the primary's health,
+    /// such as one that waited past its deadline.
+    pub(crate) fn inconclusive(&mut self, route: Route) {
+        if route == Route::Probe {
+            self.probing = false;
+        }
+    }
+
+    fn publish(&mut self, event: &FailoverEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Failover, FailoverCause, FailoverEvent, FailoverPolicy, Route};
+    use std::time::Duration;
+
+    #[test]
+    fn fails_over_after_strikes_in_a_row_and_recovers() {
+        let policy = FailoverPolicy::default()
+            .with_after_strikes(2)
+            .with_retry_primary_after(Duration::ZERO);
+        let mut failover = Failover::default();
+        let events = failover.subscribe();
+        let slow = || FailoverCause::TooSlow(Duration::from_secs(1));
+
+        failover.strike(Route::Primary, slow(), &policy, true);
+        failover.success(Route::Primary);
+        failover.strike(Route::Primary, slow(), &policy, true);
+        assert!(!failover.is_failed_over());
+        failover.strike(Route::Primary, slow(), &policy, true);
+        assert!(failover.is_failed_over());
+        assert!(matches!(
+            events.try_recv(),
+            Ok(FailoverEvent::FailedOver(FailoverCause::TooSlow(_)))
+        ));
+
+        // one request at a time tries the primary again
+        assert_eq!(failover.route(&policy), Route::Probe);
+        assert_eq!(failover.route(&policy), Route::Standby);
+        failover.strike(Route::Probe, slow(), &policy, true);
+        assert!(failover.is_failed_over());
+        assert_eq!(failover.route(&policy), Route::Probe);
+        failover.success(Route::Probe);
+        assert!(!failover.is_failed_over());
+        assert!(matches!(events.try_recv(), Ok(FailoverEvent::Recovered)));
+        assert_eq!(failover.route(&policy), Route::Primary);
+    }
+
+    #[test]
+    fn needs_a_standby_to_fail_over() {
+        let policy = FailoverPolicy::default().with_after_strikes(1);
+        let mut failover = Failover::default();
+        failover.strike(
+            Route::Primary,
+            FailoverCause::Errors(crate::Error::Unknown),
+            &policy,
+            false,
+        );
+        assert!(!failover.is_failed_over());
+    }
+}
@@ -0,0 +1,180 @@
+//! How this crate was built, for applications to check at runtime,
+//! and to include in bug reports.
+//!
+//! There is a `HAS_` constant for each Cargo feature, and a few for groups of them,
+//! such as [`HAS_ASYNC`]. [`enabled_features`] lists the features by name,
+//! as they would be passed to Cargo.
+//!
+//! ```
+//! if !coqui_stt::features::HAS_ASYNC {
+//!     eprintln!("coqui-stt was built without async support");
+//! }
+//! println!("features: {}", coqui_stt::features::enabled_features().join(","));
+//! ```
+
+/// Whether the `audrey` feature is enabled. `audrey` readers can be transcribed directly.
+pub const HAS_AUDREY: bool = cfg!(feature = "audrey");
+
+/// Whether the `batch` feature is enabled. `BatchJob` is available.
+pub const HAS_BATCH: bool = cfg!(feature = "batch");
+
+/// Whether the `cli` feature is enabled. The `cli` module is available.
+pub const HAS_CLI: bool = cfg!(feature = "cli");
+
+/// Whether the `core-affinity` feature is enabled. Worker threads can be pinned to cores.
+pub const HAS_CORE_AFFINITY: bool = cfg!(feature = "core-affinity");
+
+/// Whether the `crypto` feature is enabled. Models can be loaded from encrypted buffers.
+pub const HAS_CRYPTO: bool = cfg!(feature = "crypto");
+
+/// Whether the `deadpool_integration` feature is enabled. Models can be pooled with `deadpool`.
+pub const HAS_DEADPOOL_INTEGRATION: bool = cfg!(feature = "deadpool_integration");
+
+/// Whether the `debug-tap` feature is enabled. The `debug` module is available.
+pub const HAS_DEBUG_TAP: bool = cfg!(feature = "debug-tap");
+
+/// Whether the `fetch` feature is enabled. `ModelFetcher` is available.
+pub const HAS_FETCH: bool = cfg!(feature = "fetch");
+
+/// Whether the `ffmpeg-cli` feature is enabled. `ffmpeg_decode` is available.
+pub const HAS_FFMPEG_CLI: bool = cfg!(feature = "ffmpeg-cli");
+
+/// Whether the `http` feature is enabled. `HttpSource` is available.
+pub const HAS_HTTP: bool = cfg!(feature = "http");
+
+/// Whether the `jsonl` feature is enabled. `JsonlSink` is available.
+pub const HAS_JSONL: bool = cfg!(feature = "jsonl");
+
+/// Whether the `model-tests` feature is enabled. The golden model tests are enabled.
+pub const HAS_MODEL_TESTS: bool = cfg!(feature = "model-tests");
+
+/// Whether the `native-logs` feature is enabled. `NativeLogCapture` is available, on Unix.
+pub const HAS_NATIVE_LOGS: bool = cfg!(feature = "native-logs");
+
+/// Whether the `parking_lot` feature is enabled. `MutexModel` is available.
+pub const HAS_PARKING_LOT: bool = cfg!(feature = "parking_lot");
+
+/// Whether the `raw-bindings` feature is enabled. The raw bindings are exposed at the root as `coqui_stt_sys`.
+pub const HAS_RAW_BINDINGS: bool = cfg!(feature = "raw-bindings");
+
+/// Whether the `rayon` feature is enabled. Candidate transcripts are scored in parallel.
+pub const HAS_RAYON: bool = cfg!(feature = "rayon");
+
+/// Whether the `record` feature is enabled. The `recording` module is available.
+pub const HAS_RECORD: bool = cfg!(feature = "record");
+
+/// Whether the `rnnoise` feature is enabled. The `Denoiser` stage is available.
+pub const HAS_RNNOISE: bool = cfg!(feature = "rnnoise");
+
+/// Whether the `serde` feature is enabled. Transcripts implement `Serialize` and `Deserialize`.
+pub const HAS_SERDE: bool = cfg!(feature = "serde");
+
+/// Whether the `silero` feature is enabled. `SileroVad` is available.
+pub const HAS_SILERO: bool = cfg!(feature = "silero");
+
+/// Whether the `songbird` feature is enabled. `VoiceReceiver` is available.
+pub const HAS_SONGBIRD: bool = cfg!(feature = "songbird");
+
+/// Whether the `strict` feature is enabled. Streams check they still reference their model before every call into `libstt`.
+pub const HAS_STRICT: bool = cfg!(feature = "strict");
+
+/// Whether the `tokio` feature is enabled. `transcribe_async_read` is available.
+pub const HAS_TOKIO: bool = cfg!(feature = "tokio");
+
+/// Whether the `tracing` feature is enabled. Retries, reloads and other recoveries are logged with `tracing`.
+pub const HAS_TRACING: bool = cfg!(feature = "tracing");
+
+/// Whether the `unicode-segmentation` feature is enabled. The `UnicodeWords` segmenter is available.
+pub const HAS_UNICODE_SEGMENTATION: bool = cfg!(feature = "unicode-segmentation");
+
+/// Whether the `unsync` feature is enabled. [`Stream`](crate::Stream) doesn't implement `Sync`.
+pub const HAS_UNSYNC: bool = cfg!(feature = "unsync");
+
+/// Whether the `watch` feature is enabled. `watch_dir` is available.
+pub const HAS_WATCH: bool = cfg!(feature = "watch");
+
+/// Whether the `webrtc-vad` feature is enabled. `WebRtcVad` is available.
+pub const HAS_WEBRTC_VAD: bool = cfg!(feature = "webrtc-vad");
+
+/// Whether the `zstd` feature is enabled. Models and scorers ending in `.zst` are decompressed when loaded.
+pub const HAS_ZSTD: bool = cfg!(feature = "zstd");
+
+/// Whether any feature integrating with an async runtime is enabled:
+/// `tokio`, `deadpool_integration` or `songbird`.
+pub const HAS_ASYNC: bool = HAS_TOKIO || HAS_DEADPOOL_INTEGRATION || HAS_SONGBIRD;
+
+/// Whether any voice activity detector backed by a model is enabled: `webrtc-vad` or `silero`.
+/// The energy based detector in the [`vad`](crate::vad) module is always available.
+pub const HAS_MODEL_VAD: bool = HAS_WEBRTC_VAD || HAS_SILERO;
+
+/// Every feature and whether it is enabled, by name.
+const FEATURES: &[(&str, bool)] = &[
+    ("audrey", HAS_AUDREY),
+    ("batch", HAS_BATCH),
+    ("cli", HAS_CLI),
+    ("core-affinity", HAS_CORE_AFFINITY),
+    ("crypto", HAS_CRYPTO),
+    ("deadpool_integration", HAS_DEADPOOL_INTEGRATION),
+    ("debug-tap", HAS_DEBUG_TAP),
+    ("fetch", HAS_FETCH),
+    ("ffmpeg-cli", HAS_FFMPEG_CLI),
+    ("http", HAS_HTTP),
+    ("jsonl", HAS_JSONL),
+    ("model-tests", HAS_MODEL_TESTS),
+    ("native-logs", HAS_NATIVE_LOGS),
+    ("parking_lot", HAS_PARKING_LOT),
+    ("raw-bindings", HAS_RAW_BINDINGS),
+    ("rayon", HAS_RAYON),
+    ("record", HAS_RECORD),
+    ("rnnoise", HAS_RNNOISE),
+    ("serde", HAS_SERDE),
+    ("silero", HAS_SILERO),
+    ("songbird", HAS_SONGBIRD),
+    ("strict", HAS_STRICT),
+    ("tokio", HAS_TOKIO),
+    ("tracing", HAS_TRACING),
+    ("unicode-segmentation", HAS_UNICODE_SEGMENTATION),
+    ("unsync", HAS_UNSYNC),
+    ("watch", HAS_WATCH),
+    ("webrtc-vad", HAS_WEBRTC_VAD),
+    ("zstd", HAS_ZSTD),
+];
+
+/// The names of the features this crate was built with, in alphabetical order.
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn enabled_features() -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .filter(|&&(_, enabled)| enabled)
+        .map(|&(name, _)| name)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{enabled_features, FEATURES, HAS_SERDE};
+
+    #[test]
+    fn every_feature_in_the_manifest_is_listed() {
+        let manifest = include_str!("../Cargo.toml");
+        let features = manifest
+            .split("[features]")
+            .nth(1)
+            .and_then(|rest| rest.split("\n[").next())
+            .unwrap();
+        for line in features.lines() {
+            let Some((name, _)) = line.split_once(" = ") else {
+                continue;
+            };
+            if name != "default" {
+                assert!(
+                    FEATURES.iter().any(|&(listed, _)| listed == name),
+                    "{name} is missing"
+                );
+            }
+        }
+        assert!(FEATURES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(enabled_features().contains(&"serde"), HAS_SERDE);
+    }
+}
@@ -0,0 +1,367 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Where the Coqui model zoo serves files from.
+const ZOO_URL: &str = "https://coqui.gateway.scarf.sh";
+
+/// Paths to the files downloaded by [`ModelFetcher::fetch`], ready to load.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct FetchedModel {
+    /// Path to the model, for [`Model::new`](crate::Model::new).
+    pub model: PathBuf,
+    /// Path to the scorer, for [`Model::enable_external_scorer`](crate::Model::enable_external_scorer),
+    /// if one was requested with [`ModelFetcher::with_scorer`].
+    pub scorer: Option<PathBuf>,
+}
+
+/// Downloads models and scorers from the Coqui model zoo, and caches them locally.
+///
+/// Every file must have its SHA-256 checksum pinned with
+/// [`with_checksum`](ModelFetcher::with_checksum), as listed on the model's page in the zoo.
+/// Files are checked against it when they are downloaded, and every time they are
+/// fetched from the cache afterwards, so files corrupted in the cache are caught.
+#[derive(Clone, Debug)]
+pub struct ModelFetcher {
+    base_url: String,
+    model_file: String,
+    scorer_file: Option<String>,
+    checksums: HashMap<String, String>,
+}
+
+impl Default for ModelFetcher {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            base_url: ZOO_URL.to_string(),
+            model_file: "model.tflite".to_string(),
+            scorer_file: None,
+            checksums: HashMap::new(),
+        }
+    }
+}
+
+impl ModelFetcher {
+    /// Create a new `ModelFetcher`, which fetches `model.tflite` from the Coqui model zoo.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fetch files from `base_url` instead of the Coqui model zoo,
+    /// for example from a mirror.
+    #[inline]
+    #[must_use]
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Set the file name of the model to fetch. Defaults to `model.tflite`.
+    #[inline]
+    #[must_use]
+    pub fn with_model_file(mut self, file_name: impl Into<String>) -> Self {
+        self.model_file = file_name.into();
+        self
+    }
+
+    /// Also fetch a scorer with the given file name, such as `large_vocabulary.scorer`.
+    ///
+    /// The name of the scorer varies between models; see the model's page in the zoo.
+    #[inline]
+    #[must_use]
+    pub fn with_scorer(mut self, file_name: impl Into<String>) -> Self {
+        self.scorer_file = Some(file_name.into());
+        self
+    }
+
+    /// Pin the SHA-256 checksum of `file_name`, as a hex string.
+    #[inline]
+    #[must_use]
+    pub fn with_checksum(mut self, file_name: impl Into<String>, sha256: &str) -> Self {
+        self.checksums
+            .insert(file_name.into(), sha256.to_ascii_lowercase());
+        self
+    }
+
+    /// Fetch the model with the zoo identifier `id`, such as `english/coqui/v1.0.0-large-vocab`,
+    /// into `cache_dir`, and return the paths to its files.
+    ///
+    /// Files are stored under `cache_dir/id`, and only downloaded if they aren't there already.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidPath`](crate::Error::InvalidPath) if `id` or a file name
+    /// is empty, absolute, or contains `.` or `..` components,
+    /// and [`Error::MissingChecksum`](crate::Error::MissingChecksum) if a file
+    /// has no checksum pinned, before downloading anything.
+    /// Returns [`Error::ModelNotFound`](crate::Error::ModelNotFound) if a file isn't in the zoo,
+    /// [`Error::Io`](crate::Error::Io) if a file could not be downloaded or written,
+    /// and [`Error::ChecksumMismatch`](crate::Error::ChecksumMismatch) if a file
+    /// does not match its checksum.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn fetch(&self, id: &str, cache_dir: impl AsRef<Path>) -> crate::Result<FetchedModel> {
+        check_path(id, true)?;
+        for file_name in std::iter::once(&self.model_file).chain(&self.scorer_file) {
+            check_path(file_name, false)?;
+            if !self.checksums.contains_key(file_name) {
+                return Err(crate::Error::MissingChecksum(file_name.clone()));
+            }
+        }
+
+        let dir = cache_dir.as_ref().join(id);
+        fs::create_dir_all(&dir)?;
+
+        let model = self.fetch_file(id, &dir, &self.model_file)?;
+        let scorer = self
+            .scorer_file
+            .as_ref()
+            .map(|file_name| self.fetch_file(id, &dir, file_name))
+            .transpose()?;
+
+        Ok(FetchedModel { model, scorer })
+    }
+
+    fn fetch_file(&self, id: &str, dir: &Path, file_name: &str) -> crate::Result<PathBuf> {
+        let path = dir.join(file_name);
+        let expected = self
+            .checksums
+            .get(file_name)
+            .ok_or_else(|| crate::Error::MissingChecksum(file_name.to_string()))?;
+
+        if !path.exists() {
+            let url = format!(
+                "{}/{}/{}",
+                self.base_url.trim_end_matches('/'),
+                id,
+                file_name
+            );
+            let response = ureq::get(&url).call().map_err(|e| match e {
+                ureq::Error::Status(404, _) => crate::Error::ModelNotFound(url.clone()),
+                e => crate::Error::Io(format!("failed to download {}", e)),
+            })?;
+
+            let partial = dir.join(format!("{}.part", file_name));
+            let mut reader = response.into_reader();
+            let mut writer = io::BufWriter::new(File::create(&partial)?);
+            io::copy(&mut reader, &mut writer)?;
+            writer.flush()?;
+            drop(writer);
+
+            if sha256(&partial)? != *expected {
+                fs::remove_file(&partial)?;
+                return Err(crate::Error::ChecksumMismatch(file_name.to_string()));
+            }
+            fs::rename(partial, &path)?;
+            return Ok(path);
+        }
+
+        if sha256(&path)? != *expected {
+            return Err(crate::Error::ChecksumMismatch(file_name.to_string()));
+        }
+        Ok(path)
+    }
+}
+
+/// Check that `path` stays inside the directory it is joined onto,
+/// made up only of plain names separated by `/` if `allow_dirs`.
+fn check_path(path: &str, allow_dirs: bool) -> crate::Result<()> {
+    let valid = |name: &str| {
+        !name.is_empty() && name != "." && name != ".." && !name.contains(['\\', ':', '\0'])
+    };
+    let valid = if allow_dirs {
+        path.split('/').all(valid)
+    } else {
+        !path.contains('/') && valid(path)
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(crate::Error::InvalidPath(path.to_string()))
+    }
+}
+
+fn sha256(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const MODEL: &[u8] = b"a tiny model";
+    const MODEL_SHA256: &str = "48b6616504734f77529eec0bb11abf816a432b133df7efdb9f0d4ebd7736ce2f";
+
+    /// Serve `files` over HTTP on a local port, counting the requests made.
+    fn serve(files: HashMap<String, &'static [u8]>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        std::thread::spawn(move || {
+            for mut conn in listener.incoming().flatten() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut lines = BufReader::new(&conn).lines().map_while(Result::ok);
+                let request = lines.next().unwrap_or_default();
+                lines.take_while(|line| !line.is_empty()).for_each(drop);
+                let path = request.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = match files.get(path) {
+                    Some(body) => ("200 OK", *body),
+                    None => ("404 Not Found", &b""[..]),
+                };
+                let _ = write!(
+                    conn,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = conn.write_all(body);
+            }
+        });
+        (url, requests)
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("coqui-stt-fetch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn zoo() -> (ModelFetcher, Arc<AtomicUsize>) {
+        let files = HashMap::from([
+            ("/english/test/v1/model.tflite".to_string(), MODEL),
+            ("/english/test/v1/bad.scorer".to_string(), &b"corrupted"[..]),
+        ]);
+        let (url, requests) = serve(files);
+        let fetcher = ModelFetcher::new()
+            .with_base_url(url)
+            .with_checksum("model.tflite", &MODEL_SHA256.to_ascii_uppercase());
+        (fetcher, requests)
+    }
+
+    #[test]
+    fn downloads_once_then_fetches_from_the_cache() {
+        let (fetcher, requests) = zoo();
+        let dir = cache_dir("cache");
+        let fetched = fetcher.fetch("english/test/v1", &dir).unwrap();
+        assert_eq!(fetched.model, dir.join("english/test/v1/model.tflite"));
+        assert_eq!(fs::read(&fetched.model).unwrap(), MODEL);
+        assert!(fetched.scorer.is_none());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let cached = fetcher.fetch("english/test/v1", &dir).unwrap();
+        assert_eq!(cached.model, fetched.model);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // a corrupted cache is caught, not loaded
+        fs::write(&fetched.model, b"a tiny modem").unwrap();
+        assert!(matches!(
+            fetcher.fetch("english/test/v1", &dir),
+            Err(crate::Error::ChecksumMismatch(file)) if file == "model.tflite"
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn mismatched_downloads_are_discarded() {
+        let (fetcher, requests) = zoo();
+        let fetcher = fetcher
+            .with_scorer("bad.scorer")
+            .with_checksum("bad.scorer", MODEL_SHA256);
+        let dir = cache_dir("mismatch");
+        assert!(matches!(
+            fetcher.fetch("english/test/v1", &dir),
+            Err(crate::Error::ChecksumMismatch(file)) if file == "bad.scorer"
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        let model_dir = dir.join("english/test/v1");
+        assert!(model_dir.join("model.tflite").exists());
+        assert!(!model_dir.join("bad.scorer").exists());
+        assert!(!model_dir.join("bad.scorer.part").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_are_not_found() {
+        let (fetcher, _) = zoo();
+        let dir = cache_dir("missing");
+        assert!(matches!(
+            fetcher.fetch("english/test/v2", &dir),
+            Err(crate::Error::ModelNotFound(url)) if url.ends_with("/english/test/v2/model.tflite")
+        ));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn files_need_a_checksum() {
+        let (fetcher, requests) = zoo();
+        let dir = cache_dir("unpinned");
+        let fetcher = fetcher.with_scorer("large_vocabulary.scorer");
+        assert!(matches!(
+            fetcher.fetch("english/test/v1", &dir),
+            Err(crate::Error::MissingChecksum(file)) if file == "large_vocabulary.scorer"
+        ));
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn paths_stay_in_the_cache() {
+        let (fetcher, requests) = zoo();
+        let dir = cache_dir("escape");
+        for id in [
+            "",
+            "/etc",
+            "english/../..",
+            "english//v1",
+            "./v1",
+            "c:\\models",
+            "v1/",
+        ] {
+            assert!(
+                matches!(fetcher.fetch(id, &dir), Err(crate::Error::InvalidPath(path)) if path == id),
+                "{}",
+                id
+            );
+        }
+        for file_name in [
+            "..",
+            "../model.tflite",
+            "models/model.tflite",
+            "..\\model.tflite",
+        ] {
+            let fetcher = fetcher
+                .clone()
+                .with_model_file(file_name)
+                .with_checksum(file_name, MODEL_SHA256);
+            assert!(
+                matches!(
+                    fetcher.fetch("english/test/v1", &dir),
+                    Err(crate::Error::InvalidPath(path)) if path == file_name
+                ),
+                "{}",
+                file_name
+            );
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+        assert!(!dir.exists());
+    }
+}
@@ -1,6 +1,9 @@
 macro_rules! handle_error {
-    ($fn: expr) => {
-        match crate::Error::from_c_int(unsafe { $fn }) {
+    ($op: ident($($arg: expr),* $(,)?)) => {
+        match crate::Error::from_c_call(
+            stringify!($op),
+            unsafe { coqui_stt_sys::$op($($arg),*) },
+        ) {
             Some(e) => Err(e),
             None => Ok(()),
         }
@@ -0,0 +1,235 @@
+use crate::{Model, Stream, Transcription};
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+/// How many frames are fed to the model at once by [`HttpSource::transcribe`].
+const CHUNK_FRAMES: usize = 16 * 1024;
+
+/// Audio to be transcribed straight from an `http://` or `https://` URL,
+/// such as a presigned object storage URL.
+///
+/// The audio is decoded as it downloads, so it never has to fit in memory or on disk.
+/// WAV files with 16-bit samples are supported, as is raw big-endian PCM
+/// served as `audio/L16`, with its sample rate and channels in the content type.
+/// Servers that don't know better may send WAV files as `application/octet-stream`,
+/// so that is accepted too.
+#[derive(Clone, Debug)]
+pub struct HttpSource {
+    url: String,
+    max_bytes: u64,
+    timeout: Option<Duration>,
+}
+
+impl HttpSource {
+    /// Create a new `HttpSource` for the audio at `url`,
+    /// which allows up to 1 GiB to be downloaded.
+    #[inline]
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            max_bytes: 1 << 30,
+            timeout: None,
+        }
+    }
+
+    /// Refuse to download more than `max_bytes`.
+    ///
+    /// Responses that say they are bigger are refused before anything is read;
+    /// anything else fails once it goes over.
+    #[inline]
+    #[must_use]
+    pub const fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Give up on the whole download after `timeout`, or never if `None`, the default.
+    #[inline]
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Start downloading the audio, and read its format.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if the request fails,
+    /// or the response is bigger than [`with_max_bytes`](HttpSource::with_max_bytes) allows,
+    /// and [`Error::InvalidAudio`](crate::Error::InvalidAudio) if its content type
+    /// isn't supported, or the audio can't be decoded.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn open(&self) -> crate::Result<HttpAudio> {
+        let mut request = ureq::get(&self.url);
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request
+            .call()
+            .map_err(|e| crate::Error::Io(format!("failed to download {}", e)))?;
+
+        let length = response
+            .header("Content-Length")
+            .and_then(|length| length.parse::<u64>().ok());
+        if let Some(length) = length.filter(|&length| length > self.max_bytes) {
+            return Err(crate::Error::Io(format!(
+                "{} is {} bytes, more than the limit of {}",
+                self.url, length, self.max_bytes
+            )));
+        }
+
+        let content_type = response.content_type().to_ascii_lowercase();
+        let params = response
+            .header("Content-Type")
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        let reader = Limited {
+            inner: response.into_reader(),
+            remaining: self.max_bytes,
+        };
+
+        let format = match content_type.as_str() {
+            "audio/wav"
+            | "audio/x-wav"
+            | "audio/wave"
+            | "audio/vnd.wave"
+            | "application/octet-stream" => {
+                let wav = hound::WavReader::new(reader)?;
+                let spec = wav.spec();
+                if spec.sample_format != hound::SampleFormat::Int || spec.bits_per_sample != 16 {
+                    return Err(crate::Error::InvalidAudio(format!(
+                        "only 16-bit WAV files can be streamed, not {}-bit {:?}",
+                        spec.bits_per_sample, spec.sample_format
+                    )));
+                }
+                Format::Wav(wav.into_samples(), spec.sample_rate, spec.channels)
+            }
+            "audio/l16" => {
+                // RFC 2586: `audio/L16; rate=16000; channels=1`, with one channel by default
+                let param = |name: &str| {
+                    params
+                        .split(';')
+                        .filter_map(|param| param.split_once('='))
+                        .find(|(key, _)| key.trim() == name)
+                        .and_then(|(_, value)| value.trim().parse().ok())
+                };
+                let rate = param("rate").ok_or_else(|| {
+                    crate::Error::InvalidAudio("audio/L16 without a rate".to_string())
+                })?;
+                let channels = u16::try_from(param("channels").unwrap_or(1)).map_err(|_| {
+                    crate::Error::InvalidAudio("audio/L16 with too many channels".to_string())
+                })?;
+                Format::L16(reader, rate, channels)
+            }
+            other => {
+                return Err(crate::Error::InvalidAudio(format!(
+                    "unsupported content type {:?}",
+                    other
+                )))
+            }
+        };
+        Ok(HttpAudio { format })
+    }
+
+    /// Download the audio and transcribe it with `model` as it arrives.
+    ///
+    /// The audio is mixed down to mono, but must already be at the sample rate of `model`.
+    ///
+    /// # Errors
+    /// Returns [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate)
+    /// if the audio is at a different sample rate to `model`.
+    /// Otherwise, see [`open`](HttpSource::open), and passes through any errors from the C library.
+    /// See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn transcribe(&self, model: &mut Model) -> crate::Result<Transcription> {
+        let mut audio = self.open()?;
+        if audio.sample_rate() != model.get_sample_rate() as u32 {
+            return Err(crate::Error::UnsupportedSampleRate(audio.sample_rate()));
+        }
+        let channels = audio.channels();
+
+        let started = Instant::now();
+        let mut stream = Stream::from_model(model)?;
+        let mut chunk = Vec::with_capacity(CHUNK_FRAMES * usize::from(channels));
+        loop {
+            chunk.clear();
+            for sample in audio.by_ref().take(chunk.capacity()) {
+                chunk.push(sample?);
+            }
+            if chunk.is_empty() {
+                break;
+            }
+            stream.feed_audio(&crate::util::downmix(&chunk, channels)?);
+        }
+        let mut transcription = stream.finish_stream_transcription()?;
+        transcription.processing_time = started.elapsed();
+        Ok(transcription)
+    }
+}
+
+/// Audio being downloaded by [`HttpSource::open`], as interleaved samples.
+pub struct HttpAudio {
+    format: Format,
+}
+
+enum Format {
+    Wav(hound::WavIntoSamples<Limited, i16>, u32, u16),
+    L16(Limited, u32, u16),
+}
+
+impl HttpAudio {
+    /// The sample rate of the audio, in Hz.
+    #[inline]
+    #[must_use]
+    pub const fn sample_rate(&self) -> u32 {
+        match self.format {
+            Format::Wav(_, rate, _) | Format::L16(_, rate, _) => rate,
+        }
+    }
+
+    /// The number of channels in the audio.
+    #[inline]
+    #[must_use]
+    pub const fn channels(&self) -> u16 {
+        match self.format {
+            Format::Wav(_, _, channels) | Format::L16(_, _, channels) => channels,
+        }
+    }
+}
+
+impl Iterator for HttpAudio {
+    type Item = crate::Result<i16>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.format {
+            Format::Wav(samples, _, _) => samples.next().map(|s| s.map_err(Into::into)),
+            Format::L16(reader, _, _) => {
+                let mut sample = [0; 2];
+                match reader.read_exact(&mut sample) {
+                    Ok(()) => Some(Ok(i16::from_be_bytes(sample))),
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+                    Err(e) => Some(Err(e.into())),
+                }
+            }
+        }
+    }
+}
+
+/// A reader that fails once more than `remaining` bytes are read from it.
+struct Limited {
+    inner: Box<dyn Read + Send + Sync + 'static>,
+    remaining: u64,
+}
+
+impl Read for Limited {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.remaining = self
+            .remaining
+            .checked_sub(read as u64)
+            .ok_or_else(|| io::Error::other("the download is too big"))?;
+        Ok(read)
+    }
+}
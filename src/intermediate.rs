@@ -0,0 +1,412 @@
+use crate::{Metadata, Word};
+use std::time::{Duration, Instant};
+
+/// A change to the words in a live transcription, reported by [`IntermediateTracker`].
+///
+/// Indices count words from the start of the stream.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum WordEvent {
+    /// A new word was recognized at the end of the transcription.
+    Added {
+        /// The position of the word.
+        index: usize,
+        /// The word that was added.
+        word: Word,
+    },
+    /// A word that was already reported changed its text.
+    ///
+    /// Words whose timing shifts but whose text stays the same aren't reported again,
+    /// but [`words`](IntermediateTracker::words) has their latest timing.
+    Amended {
+        /// The position of the word.
+        index: usize,
+        /// The word as it was reported before.
+        previous: Word,
+        /// The word as it is now.
+        word: Word,
+    },
+    /// A word that was already reported disappeared from the end of the transcription,
+    /// usually because the decoder merged it into the word before it.
+    Removed {
+        /// The position the word was at.
+        index: usize,
+        /// The word that was removed.
+        word: Word,
+    },
+    /// A word is not expected to change anymore, and won't be reported again.
+    Finalized {
+        /// The position of the word.
+        index: usize,
+        /// The final version of the word.
+        word: Word,
+    },
+}
+
+/// How long words took to be finalized by an [`IntermediateTracker`],
+/// from when they first appeared in an intermediate decode.
+///
+/// This is the delay before a live caption stops changing under the reader,
+/// the main latency to tune with [`with_stability`](IntermediateTracker::with_stability).
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FinalizationLag {
+    /// How many words were finalized.
+    pub count: u64,
+    /// The sum of every word's lag.
+    pub total: Duration,
+    /// The longest lag of any word.
+    pub max: Duration,
+    /// The lag of the last word finalized.
+    pub last: Option<Duration>,
+}
+
+impl FinalizationLag {
+    /// The average lag per word, or `None` if no words were finalized.
+    #[inline]
+    #[must_use]
+    pub fn mean(&self) -> Option<Duration> {
+        let count = u32::try_from(self.count).ok().filter(|&count| count > 0)?;
+        Some(self.total / count)
+    }
+
+    fn record(&mut self, lag: Duration) {
+        self.count += 1;
+        self.total += lag;
+        self.max = self.max.max(lag);
+        self.last = Some(lag);
+    }
+}
+
+/// Compares consecutive intermediate decodes of a [`Stream`](crate::Stream) word by word,
+/// for live captions that update individual words as the decoder revises them.
+///
+/// Feed it the result of every
+/// [`intermediate_decode_with_metadata`](crate::Stream::intermediate_decode_with_metadata)
+/// with [`diff_words`](IntermediateTracker::diff_words),
+/// and the final result with [`finish`](IntermediateTracker::finish).
+#[derive(Clone, Debug)]
+pub struct IntermediateTracker {
+    words: Vec<Word>,
+    unchanged: Vec<u32>,
+    first_seen: Vec<Instant>,
+    finalized: usize,
+    stability: u32,
+    lag: FinalizationLag,
+}
+
+impl Default for IntermediateTracker {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            words: Vec::new(),
+            unchanged: Vec::new(),
+            first_seen: Vec::new(),
+            finalized: 0,
+            stability: 2,
+            lag: FinalizationLag::default(),
+        }
+    }
+}
+
+impl IntermediateTracker {
+    /// Create a new `IntermediateTracker`.
+    ///
+    /// By default, a word is finalized once it has stayed the same for two more decodes,
+    /// and another word has been recognized after it.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many more decodes a word has to stay the same for before it is finalized.
+    ///
+    /// Higher values make finalized words more accurate, at the cost of finalizing them later.
+    #[inline]
+    #[must_use]
+    pub const fn with_stability(mut self, decodes: u32) -> Self {
+        self.stability = decodes;
+        self
+    }
+
+    /// The words recognized so far, finalized or not.
+    #[inline]
+    #[must_use]
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
+
+    /// How many of the [`words`](IntermediateTracker::words) have been finalized.
+    #[inline]
+    #[must_use]
+    pub const fn num_finalized(&self) -> usize {
+        self.finalized
+    }
+
+    /// How long words have taken to be finalized, from the first time they were reported.
+    ///
+    /// This covers every stream tracked, and is not cleared by [`reset`](IntermediateTracker::reset).
+    #[inline]
+    #[must_use]
+    pub const fn finalization_lag(&self) -> FinalizationLag {
+        self.lag
+    }
+
+    /// Compare the best transcript in `metadata` with the previous one,
+    /// and return what changed.
+    ///
+    /// Finalized words are never changed, even if the decoder revises them later.
+    #[inline]
+    pub fn diff_words(&mut self, metadata: &Metadata) -> Vec<WordEvent> {
+        self.update(metadata.best_words(), false)
+    }
+
+    /// Compare the final transcript in `metadata` with the previous one,
+    /// and finalize every word left.
+    ///
+    /// Call [`reset`](IntermediateTracker::reset) before reusing this for another stream.
+    #[inline]
+    pub fn finish(&mut self, metadata: &Metadata) -> Vec<WordEvent> {
+        self.update(metadata.best_words(), true)
+    }
+
+    /// Forget every word, to start tracking a new stream.
+    #[inline]
+    pub fn reset(&mut self) {
+        self.words.clear();
+        self.unchanged.clear();
+        self.first_seen.clear();
+        self.finalized = 0;
+    }
+
+    fn update(&mut self, mut words: Vec<Word>, finish: bool) -> Vec<WordEvent> {
+        let mut events = Vec::new();
+        let now = Instant::now();
+
+        // finalized words are locked in, only compare the ones after them
+        let start = self.finalized;
+        let tail = if words.len() > start {
+            words.split_off(start)
+        } else {
+            Vec::new()
+        };
+        let len = start + tail.len();
+
+        for (index, word) in (start..).zip(tail) {
+            match self.words.get_mut(index) {
+                // timings jitter between decodes, only a change of text counts
+                Some(old) if old.text == word.text => {
+                    *old = word;
+                    self.unchanged[index] += 1;
+                }
+                Some(_) => {
+                    let previous = std::mem::replace(&mut self.words[index], word.clone());
+                    self.unchanged[index] = 0;
+                    events.push(WordEvent::Amended {
+                        index,
+                        previous,
+                        word,
+                    });
+                }
+                None => {
+                    self.words.push(word.clone());
+                    self.unchanged.push(0);
+                    self.first_seen.push(now);
+                    events.push(WordEvent::Added { index, word });
+                }
+            }
+        }
+        while self.words.len() > len {
+            self.unchanged.pop();
+            self.first_seen.pop();
+            if let Some(word) = self.words.pop() {
+                events.push(WordEvent::Removed {
+                    index: self.words.len(),
+                    word,
+                });
+            }
+        }
+
+        // the last word may still be growing, so it is only finalized at the end of the stream
+        let finalizable = if finish {
+            self.words.len()
+        } else {
+            self.words.len().saturating_sub(1)
+        };
+        while self.finalized < finalizable
+            && (finish || self.unchanged[self.finalized] >= self.stability)
+        {
+            events.push(WordEvent::Finalized {
+                index: self.finalized,
+                word: self.words[self.finalized].clone(),
+            });
+            self.lag
+                .record(now.saturating_duration_since(self.first_seen[self.finalized]));
+            self.finalized += 1;
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(texts: &[&str]) -> Vec<Word> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Word {
+                text: text.to_string(),
+                start: Duration::from_millis(i as u64 * 500),
+                timestep: i as u32 * 25,
+                speaker: None,
+            })
+            .collect()
+    }
+
+    fn texts(events: &[WordEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                WordEvent::Added { index, word } => format!("+{} {}", index, word.text),
+                WordEvent::Amended {
+                    index,
+                    previous,
+                    word,
+                } => format!("~{} {}>{}", index, previous.text, word.text),
+                WordEvent::Removed { index, word } => format!("-{} {}", index, word.text),
+                WordEvent::Finalized { index, word } => format!("={} {}", index, word.text),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn words_are_added_amended_and_removed() {
+        let mut tracker = IntermediateTracker::new();
+        let events = tracker.update(words(&["the", "quick"]), false);
+        assert_eq!(texts(&events), ["+0 the", "+1 quick"]);
+
+        let events = tracker.update(words(&["the", "quack", "brown"]), false);
+        assert_eq!(texts(&events), ["~1 quick>quack", "+2 brown"]);
+
+        let events = tracker.update(words(&["the"]), false);
+        assert_eq!(texts(&events), ["-2 brown", "-1 quack"]);
+        assert_eq!(tracker.words().len(), 1);
+    }
+
+    #[test]
+    fn timing_changes_update_words_without_events() {
+        let mut tracker = IntermediateTracker::new();
+        tracker.update(words(&["the", "quick"]), false);
+
+        let mut jittered = words(&["the", "quick"]);
+        jittered[1].start += Duration::from_millis(20);
+        jittered[1].timestep += 1;
+        assert!(tracker.update(jittered.clone(), false).is_empty());
+        assert_eq!(tracker.words(), jittered);
+    }
+
+    #[test]
+    fn words_are_finalized_once_stable_and_followed() {
+        let mut tracker = IntermediateTracker::new().with_stability(2);
+        tracker.update(words(&["the"]), false);
+        // the last word is never finalized mid-stream, however stable
+        for _ in 0..3 {
+            assert!(tracker.update(words(&["the"]), false).is_empty());
+        }
+
+        let events = tracker.update(words(&["the", "quick"]), false);
+        assert_eq!(texts(&events), ["+1 quick", "=0 the"]);
+        assert_eq!(tracker.num_finalized(), 1);
+
+        let events = tracker.update(words(&["the", "quick", "brown"]), false);
+        assert_eq!(texts(&events), ["+2 brown"]);
+        let events = tracker.update(words(&["the", "quick", "brown"]), false);
+        assert_eq!(texts(&events), ["=1 quick"]);
+    }
+
+    #[test]
+    fn amendments_restart_the_stability_count() {
+        let mut tracker = IntermediateTracker::new().with_stability(1);
+        tracker.update(words(&["the", "quick"]), false);
+        let events = tracker.update(words(&["thee", "quick"]), false);
+        assert_eq!(texts(&events), ["~0 the>thee"]);
+        let events = tracker.update(words(&["thee", "quick"]), false);
+        assert_eq!(texts(&events), ["=0 thee"]);
+    }
+
+    #[test]
+    fn finalized_words_are_never_changed() {
+        let mut tracker = IntermediateTracker::new().with_stability(0);
+        let events = tracker.update(words(&["the", "quick"]), false);
+        assert_eq!(texts(&events), ["+0 the", "+1 quick", "=0 the"]);
+
+        let events = tracker.update(words(&["a", "quick", "brown"]), false);
+        assert_eq!(texts(&events), ["+2 brown", "=1 quick"]);
+        // finalized words stay, even if the decoder drops them
+        let events = tracker.update(words(&[]), false);
+        assert_eq!(texts(&events), ["-2 brown"]);
+        assert_eq!(tracker.words()[0].text, "the");
+        assert_eq!(tracker.words().len(), 2);
+    }
+
+    #[test]
+    fn finish_finalizes_every_word_left() {
+        let mut tracker = IntermediateTracker::new();
+        tracker.update(words(&["the", "quick"]), false);
+        let events = tracker.update(words(&["the", "quick", "fox"]), true);
+        assert_eq!(texts(&events), ["+2 fox", "=0 the", "=1 quick", "=2 fox"]);
+        assert_eq!(tracker.num_finalized(), 3);
+
+        tracker.reset();
+        assert!(tracker.words().is_empty());
+        assert_eq!(tracker.num_finalized(), 0);
+        let events = tracker.update(words(&["jumps"]), false);
+        assert_eq!(texts(&events), ["+0 jumps"]);
+    }
+
+    #[test]
+    fn finalization_lag_is_measured_from_when_words_appear() {
+        let mut tracker = IntermediateTracker::new().with_stability(0);
+        assert_eq!(tracker.finalization_lag().mean(), None);
+
+        tracker.update(words(&["the"]), false);
+        std::thread::sleep(Duration::from_millis(20));
+        tracker.update(words(&["the", "quick"]), false);
+        let lag = tracker.finalization_lag();
+        assert_eq!(lag.count, 1);
+        assert!(lag.max >= Duration::from_millis(20));
+        assert_eq!((lag.last, lag.mean()), (Some(lag.max), Some(lag.max)));
+
+        tracker.update(words(&["the", "quick"]), true);
+        let lag = tracker.finalization_lag();
+        assert_eq!(lag.count, 2);
+        assert!(lag.last < Some(lag.max));
+        assert_eq!(lag.mean(), Some(lag.total / 2));
+    }
+
+    #[test]
+    fn finalization_lag_covers_every_stream() {
+        let mut tracker = IntermediateTracker::new();
+        tracker.update(words(&["the", "quick"]), true);
+        tracker.reset();
+        tracker.update(words(&["fox"]), true);
+        assert_eq!(tracker.finalization_lag().count, 3);
+    }
+
+    #[test]
+    fn removed_words_start_their_lag_again() {
+        let mut tracker = IntermediateTracker::new().with_stability(0);
+        tracker.update(words(&["the", "quick"]), false);
+        std::thread::sleep(Duration::from_millis(20));
+        tracker.update(words(&["the"]), false);
+        tracker.update(words(&["the", "quack"]), true);
+        let lag = tracker.finalization_lag();
+        assert_eq!(lag.count, 2);
+        assert!(lag.last < Some(Duration::from_millis(20)));
+    }
+}
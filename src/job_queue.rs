@@ -0,0 +1,345 @@
+use crate::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::collections::VecDeque;
+use std::ops::ControlFlow;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+struct State<T> {
+    jobs: VecDeque<T>,
+    closed: bool,
+}
+
+struct Inner<T> {
+    state: Mutex<State<T>>,
+    ready: Condvar,
+}
+
+/// A queue of jobs for a worker thread, which any number of handles can push to.
+///
+/// Unlike a channel, it is closed explicitly rather than by dropping every sender,
+/// so handles kept elsewhere, such as in a [`Shutdown`](crate::Shutdown) hook,
+/// can't keep the worker alive.
+pub(crate) struct JobQueue<T>(Arc<Inner<T>>);
+
+impl<T> Clone for JobQueue<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> JobQueue<T> {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(Inner {
+            state: Mutex::new(State {
+                jobs: VecDeque::new(),
+                closed: false,
+            }),
+            ready: Condvar::new(),
+        }))
+    }
+
+    /// Queue up `job`, or hand it back if the queue is closed.
+    pub(crate) fn push(&self, job: T) -> Result<(), T> {
+        let mut state = self.state();
+        if state.closed {
+            return Err(job);
+        }
+        state.jobs.push_back(job);
+        drop(state);
+        self.0.ready.notify_one();
+        Ok(())
+    }
+
+    /// Take the next job, waiting up to `timeout` for one, or forever if `None`.
+    ///
+    /// Jobs queued before the queue was closed are still returned, in order,
+    /// and then [`RecvTimeoutError::Disconnected`].
+    pub(crate) fn pop(&self, timeout: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.state();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                return Ok(job);
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    self.0
+                        .ready
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self.0.ready.wait(state).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    /// Refuse any more jobs. The jobs already queued can still be taken.
+    pub(crate) fn close(&self) {
+        self.state().closed = true;
+        self.0.ready.notify_all();
+    }
+
+    /// Close the queue, and drop the jobs still in it,
+    /// which closes any channels they would have replied on.
+    pub(crate) fn abandon(&self) {
+        let mut state = self.state();
+        state.closed = true;
+        let jobs = std::mem::take(&mut state.jobs);
+        drop(state);
+        drop(jobs);
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        self.0.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// What a worker thread does with the jobs it takes off a [`JobQueue`], for [`work`].
+pub(crate) trait JobExecutor: Sized {
+    type Job;
+    type Output;
+
+    /// How long to wait for the next job before calling [`idle`](JobExecutor::idle),
+    /// or `None` to wait forever.
+    fn timeout(&self) -> Option<Duration>;
+
+    /// Called when no job arrived within the timeout.
+    fn idle(&mut self);
+
+    /// Run `job`, then either carry on, or stop the worker with its output.
+    fn run(self, job: Self::Job) -> ControlFlow<Self::Output, Self>;
+
+    /// Stop the worker once the queue is closed and empty, returning its output.
+    fn closed(self) -> Self::Output;
+}
+
+/// The loop of a worker thread: run the jobs in `jobs` with `executor` until it stops,
+/// or the queue is closed and empty.
+pub(crate) fn work<E: JobExecutor>(jobs: &JobQueue<E::Job>, mut executor: E) -> E::Output {
+    loop {
+        match jobs.pop(executor.timeout()) {
+            Ok(job) => match executor.run(job) {
+                ControlFlow::Continue(next) => executor = next,
+                ControlFlow::Break(output) => return output,
+            },
+            Err(RecvTimeoutError::Timeout) => executor.idle(),
+            Err(RecvTimeoutError::Disconnected) => return executor.closed(),
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::{work, JobExecutor, JobQueue};
+    use std::ops::ControlFlow;
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Duration;
+
+    /// Sums jobs until one is zero, going idle once if none arrive for a millisecond.
+    #[derive(Default)]
+    struct Sum {
+        total: u32,
+        idled: bool,
+    }
+
+    impl JobExecutor for Sum {
+        type Job = u32;
+        type Output = (u32, bool);
+
+        fn timeout(&self) -> Option<Duration> {
+            (!self.idled).then_some(Duration::from_millis(1))
+        }
+
+        fn idle(&mut self) {
+            self.idled = true;
+        }
+
+        fn run(mut self, job: u32) -> ControlFlow<(u32, bool), Self> {
+            if job == 0 {
+                return ControlFlow::Break(self.closed());
+            }
+            self.total += job;
+            ControlFlow::Continue(self)
+        }
+
+        fn closed(self) -> (u32, bool) {
+            (self.total, self.idled)
+        }
+    }
+
+    #[test]
+    fn workers_stop_when_told_or_closed() {
+        let queue = JobQueue::new();
+        for job in [1, 2, 0, 5] {
+            queue.push(job).unwrap();
+        }
+        assert_eq!(work(&queue, Sum::default()), (3, false));
+        // jobs after the one that stopped the worker are left in the queue
+        queue.close();
+        assert_eq!(work(&queue, Sum::default()), (5, false));
+    }
+
+    #[test]
+    fn workers_go_idle_without_jobs() {
+        let queue = JobQueue::new();
+        let pusher = {
+            let queue = queue.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                queue.push(7).unwrap();
+                queue.close();
+            })
+        };
+        assert_eq!(work(&queue, Sum::default()), (7, true));
+        pusher.join().unwrap();
+    }
+
+    #[test]
+    fn queued_jobs_outlive_closing() {
+        let queue = JobQueue::new();
+        queue.push(1).unwrap();
+        assert_eq!(queue.pop(Some(Duration::ZERO)), Ok(1));
+        assert_eq!(
+            queue.pop(Some(Duration::from_millis(1))),
+            Err(RecvTimeoutError::Timeout)
+        );
+        queue.push(2).unwrap();
+        queue.close();
+        assert_eq!(queue.push(3), Err(3));
+        assert_eq!(queue.pop(None), Ok(2));
+        assert_eq!(queue.pop(None), Err(RecvTimeoutError::Disconnected));
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::{work, JobExecutor, JobQueue};
+    use loom::sync::mpsc;
+    use loom::thread;
+    use std::ops::ControlFlow;
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    enum Job {
+        Feed,
+        Finish(mpsc::Sender<usize>),
+        Shutdown,
+    }
+
+    /// Runs jobs the way a `ThreadSafeStream` worker does, without the model:
+    /// it counts feeds, refuses everything after a shutdown, and stops at a finish.
+    #[derive(Default)]
+    struct Counter {
+        fed: usize,
+        shut_down: bool,
+    }
+
+    impl JobExecutor for Counter {
+        type Job = Job;
+        type Output = (usize, bool);
+
+        fn timeout(&self) -> Option<Duration> {
+            None
+        }
+
+        fn idle(&mut self) {
+            unreachable!("no timeout was set");
+        }
+
+        fn run(mut self, job: Job) -> ControlFlow<(usize, bool), Self> {
+            match job {
+                Job::Feed if !self.shut_down => self.fed += 1,
+                Job::Feed => {}
+                Job::Finish(tx) => {
+                    let _ = tx.send(if self.shut_down { usize::MAX } else { self.fed });
+                    return ControlFlow::Break(self.closed());
+                }
+                Job::Shutdown => self.shut_down = true,
+            }
+            ControlFlow::Continue(self)
+        }
+
+        fn closed(self) -> (usize, bool) {
+            (self.fed, self.shut_down)
+        }
+    }
+
+    #[test]
+    fn finish_racing_shutdown_is_always_answered() {
+        loom::model(|| {
+            let queue = JobQueue::new();
+            let worker = {
+                let queue = queue.clone();
+                thread::spawn(move || work(&queue, Counter::default()))
+            };
+            let hook = {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    let _ = queue.push(Job::Shutdown);
+                })
+            };
+
+            queue.push(Job::Feed).unwrap();
+            let (tx, rx) = mpsc::channel();
+            queue.push(Job::Finish(tx)).unwrap();
+            let answer = rx.recv().unwrap();
+            queue.close();
+            let (fed, shut_down) = worker.join().unwrap();
+            hook.join().unwrap();
+
+            if shut_down {
+                assert_eq!(answer, usize::MAX);
+            } else {
+                assert_eq!(answer, 1);
+                assert_eq!(fed, 1);
+            }
+        });
+    }
+
+    #[test]
+    fn closing_racing_a_push_loses_nothing_accepted() {
+        loom::model(|| {
+            let queue = JobQueue::new();
+            let worker = {
+                let queue = queue.clone();
+                thread::spawn(move || work(&queue, Counter::default()))
+            };
+            let pusher = {
+                let queue = queue.clone();
+                thread::spawn(move || queue.push(Job::Feed).is_ok())
+            };
+
+            queue.close();
+            let accepted = pusher.join().unwrap();
+            let (fed, _) = worker.join().unwrap();
+            assert_eq!(fed, usize::from(accepted));
+        });
+    }
+
+    #[test]
+    fn abandoning_drops_queued_jobs() {
+        loom::model(|| {
+            let queue = JobQueue::new();
+            let job = loom::sync::Arc::new(());
+            let pusher = {
+                let queue = queue.clone();
+                let job = loom::sync::Arc::clone(&job);
+                thread::spawn(move || drop(queue.push(job)))
+            };
+            queue.abandon();
+            pusher.join().unwrap();
+            // whether or not the job got in, nothing holds on to it,
+            // so a reply channel in it would be closed
+            assert_eq!(loom::sync::Arc::strong_count(&job), 1);
+            assert!(queue.pop(None).is_err());
+        });
+    }
+}
@@ -1,36 +1,238 @@
 #![deny(missing_docs)]
+#![cfg_attr(
+    not(test),
+    deny(clippy::unwrap_used, clippy::expect_used, clippy::panic)
+)]
 //! A safe wrapper around the [Coqui STT](https://stt.readthedocs.io/en/latest) API
 //!
 //! Typically, to use this,
 //! * start by creating a [`Model`](Model),
 //! * if you have a scorer to load, call [`enable_external_scorer`](Model::enable_external_scorer),
-//! * then call [`speech_to_text`](Model::speech_to_text) to run the algorithm.
+//! * then call [`speech_to_text_best`](Model::speech_to_text_best) to run the algorithm,
+//!   or [`speech_to_text`](Model::speech_to_text) if only the text is needed.
+//!
+//! For live audio, a [`Transcriber`](Transcriber) uses voice activity detection
+//! to split the audio into utterances, and transcribes each one as it ends.
+//! To transcribe many sources at once, such as the participants of a call,
+//! a [`Demux`](Demux) keeps a transcriber for each one.
+//! An [`EventBus`](EventBus) passes the events of transcribers to the parts of
+//! an application that want them.
+//! For push-to-talk style voice input, [`PushToTalk`](PushToTalk) transcribes
+//! the audio between a key being pressed and released.
+//! To share a stream between threads, a [`ThreadSafeStream`](ThreadSafeStream)
+//! runs it on a worker thread of its own.
+//! For audio in an unknown language, a [`MultiModel`](MultiModel)
+//! picks the model for the language it detects.
+//! For live captions, an [`IntermediateTracker`](IntermediateTracker) reports
+//! which words changed between intermediate decodes of a [`Stream`](Stream).
+//! Servers transcribing requests from many clients can use an [`SttService`](SttService),
+//! which queues requests for a [`ModelPool`](ModelPool), serves clients in turn,
+//! and gives up on requests that wait past their deadline.
+//! The [`raw`](raw) module gathers the `unsafe` ways to reach the `libstt` state
+//! behind a [`Model`](Model) or [`Stream`](Stream), for anything the safe API doesn't cover.
+//!
+//! # Panics
+//! This crate runs inside audio callbacks and servers, so failures are returned as an
+//! [`Error`](Error) rather than panicking, which is enforced with Clippy's `unwrap_used`,
+//! `expect_used` and `panic` lints. The few remaining panics, such as for
+//! a model used by two streams at once in debug builds, are documented where they occur.
 //!
 //! # Features
 //! No features are enabled by default.
+//! The [`features`](features) module reports which ones a build was compiled with.
 //!
 //! * `raw-bindings`: exposes the [`coqui-stt-sys`](coqui_stt_sys) crate at the root under the same name.
+//! * `webrtc-vad`: enables the `WebRtcVad` voice activity detector in the [`vad`](vad) module.
+//! * `silero`: enables the `SileroVad` voice activity detector in the [`vad`](vad) module,
+//!   which runs the Silero VAD model through ONNX Runtime.
+//! * `rayon`: scores candidate transcripts in parallel in
+//!   [`Metadata::rank_by`](Metadata::rank_by).
+//! * `jsonl`: enables `JsonlSink` in the [`sink`](sink) module, which writes segments as JSON lines.
+//! * `model-tests`: enables the golden tests in `tests/golden.rs`, which download the official
+//!   English model and check transcriptions against the real `libstt`.
+//!   Adds no dependencies, and is only useful for developing this crate.
+//! * `strict`: checks that a [`Stream`](Stream) still references the model it was created from
+//!   before every call into `libstt`, and aborts the process if it doesn't.
+//! * `unsync`: removes the `Sync` implementation from [`Stream`](Stream) and `OwnedStream`,
+//!   for applications that want the compiler to rule out sharing them between threads entirely.
+//!   Regardless of this feature, debug builds panic if two streams on one model
+//!   call into `libstt` at once, such as two streams recreated with
+//!   [`Stream::from_ptr`](Stream::from_ptr).
+//! * `crypto`: enables `Model::new_from_encrypted_buffer`, which loads a model encrypted
+//!   with ChaCha20-Poly1305 without it ever being written to disk in plaintext.
+//! * `zstd`: lets [`Model::new`](Model::new) and [`Model::enable_external_scorer`](Model::enable_external_scorer)
+//!   load files compressed with zstd, such as `model.tflite.zst`, by decompressing them into memory.
+//!   `libstt` can't load from memory on Windows, so there they return
+//!   [`Error::NotSupportedOnPlatform`](Error::NotSupportedOnPlatform).
+//! * `fetch`: enables `ModelFetcher`, which downloads models and scorers from the Coqui model zoo
+//!   and caches them locally.
+//! * `cli`: enables the `cli` module, which renders transcripts for terminals,
+//!   with words coloured by confidence.
+//! * `unicode-segmentation`: enables the `UnicodeWords` word segmenter in the [`segment`](segment) module,
+//!   for grouping words in languages that don't put spaces between them.
+//! * `parking_lot`: enables `MutexModel`, which shares a model between threads through `&self`
+//!   without a worker thread.
+//! * `native-logs`: enables `NativeLogCapture` on Unix, which routes what `libstt` prints to stderr
+//!   into `tracing` with the `coqui_native` target.
+//! * `tokio`: enables `transcribe_async_read`, which transcribes PCM audio from an `AsyncRead`
+//!   as it arrives, such as the output of `ffmpeg` or a network socket.
+//!   In debug builds with `tracing` too, blocking inference such as
+//!   [`Model::speech_to_text`](Model::speech_to_text) or
+//!   [`Stream::finish_stream`](Stream::finish_stream) warns when it is called
+//!   from an async task, where it stalls the runtime's worker thread.
+//! * `serde`: implements `Serialize` and `Deserialize` for [`Transcription`](Transcription),
+//!   [`Segment`](Segment) and the types they contain.
+//! * `ffmpeg-cli`: enables `ffmpeg_decode` in the [`audio`](audio) module, which decodes audio
+//!   in any format by running the `ffmpeg` command line tool.
+//! * `http`: enables `HttpSource`, which transcribes WAV or raw PCM audio from an HTTP(S) URL
+//!   as it downloads.
+//! * `tracing`: logs the retries and reloads made by [`RetryingModel`](RetryingModel),
+//!   and decrypted models that couldn't be locked into memory with the `crypto` feature.
+//! * `core-affinity`: enables `pin_current_thread`, and options to pin the worker threads
+//!   of [`ThreadSafeStream`](ThreadSafeStream) and `watch_dir` to CPU cores.
+//! * `songbird`: enables `VoiceReceiver`, which transcribes everyone speaking in a Discord voice call
+//!   received with [songbird](https://docs.rs/songbird), each user separately.
+//! * `debug-tap`: enables the `debug` module, whose `TapWriter` records the audio fed to a stream
+//!   to WAV files, for listening to exactly what the model heard.
+//! * `audrey`: lets `audrey` readers be transcribed directly, decoding, mixing down
+//!   and resampling whatever they read. See `audio::IntoModelAudio`.
+//! * `record`: enables the `recording` module, which records the requests an
+//!   [`SttService`](SttService) transcribes and replays them against another model,
+//!   reporting how the transcripts changed.
+//! * `batch`: enables `BatchJob`, which transcribes a list of WAV files, checkpointing
+//!   each window of audio to a manifest so an interrupted run carries on where it left off.
+//! * `watch`: enables `watch_dir`, which transcribes audio files as they appear in a directory.
+//! * `rnnoise`: enables the `Denoiser` noise suppression stage in the [`audio`](audio) module.
+
+#[cfg(test)]
+extern crate coqui_stt_fake as _;
 
 #[macro_use]
 mod helpers;
 
+pub mod ab;
+#[cfg(feature = "core-affinity")]
+mod affinity;
+#[cfg(feature = "tokio")]
+mod async_read;
+pub mod audio;
+#[cfg(feature = "batch")]
+mod batch;
+mod blocking_check;
 mod candidate_transcript;
+mod capabilities;
+#[cfg(feature = "cli")]
+pub mod cli;
+#[cfg(feature = "crypto")]
+mod crypto;
 #[cfg(feature = "deadpool_integration")]
 mod deadpool_integration;
+#[cfg(feature = "debug-tap")]
+pub mod debug;
+mod demux;
+mod diarize;
+#[cfg(feature = "songbird")]
+mod discord;
 mod errors;
+mod event_bus;
+mod executor;
+mod failover;
+pub mod features;
+#[cfg(feature = "fetch")]
+mod fetch;
+#[cfg(feature = "http")]
+mod http;
+mod intermediate;
+mod job_queue;
 mod metadata;
 mod model;
+mod multi_model;
+#[cfg(feature = "parking_lot")]
+mod mutex_model;
+#[cfg(all(feature = "native-logs", unix))]
+mod native_log;
+mod oneshot;
+mod owned_stream;
+mod packed_metadata;
+mod pool;
+mod progress;
+mod push_to_talk;
+pub mod raw;
+#[cfg(feature = "record")]
+pub mod recording;
+mod retry;
+mod scorer;
+pub mod segment;
+mod service;
+mod shutdown;
+pub mod sink;
+mod stats;
 mod stream;
+mod sync;
+pub mod testing;
+mod threadsafe_stream;
 mod token_metadata;
+mod transcriber;
+mod transcription;
+pub mod util;
+pub mod vad;
+#[cfg(feature = "watch")]
+mod watch;
+mod wer;
+mod window;
 
+#[cfg(feature = "core-affinity")]
+pub use affinity::{available_cores, pin_current_thread};
+#[cfg(feature = "tokio")]
+pub use async_read::transcribe_async_read;
+#[cfg(feature = "batch")]
+pub use batch::{BatchError, BatchJob, BatchReport, ErrorPolicy};
 pub use candidate_transcript::{CandidateTranscript, OwnedCandidateTranscript};
+pub use capabilities::{capabilities, version, Capabilities, Runtime};
+#[cfg(feature = "crypto")]
+pub use crypto::Aead;
 #[cfg(feature = "deadpool_integration")]
 pub use deadpool_integration::*;
+pub use demux::Demux;
+pub use diarize::Diarizer;
+#[cfg(feature = "songbird")]
+pub use discord::{VoiceEvent, VoiceReceiver};
 pub use errors::{Error, Result};
+pub use event_bus::{EventBus, EventFilter};
+pub use executor::{ExecutorStream, MiniExecutor};
+pub use failover::{FailoverCause, FailoverEvent, FailoverPolicy};
+#[cfg(feature = "fetch")]
+pub use fetch::{FetchedModel, ModelFetcher};
+#[cfg(feature = "http")]
+pub use http::{HttpAudio, HttpSource};
+pub use intermediate::{FinalizationLag, IntermediateTracker, WordEvent};
 pub use metadata::{Metadata, OwnedMetadata};
 pub use model::Model;
-pub use stream::Stream;
-pub use token_metadata::{OwnedTokenMetadata, TokenMetadata};
+pub use multi_model::{Detection, MultiModel};
+#[cfg(feature = "parking_lot")]
+pub use mutex_model::{MutexModel, ScorerEvent};
+#[cfg(all(feature = "native-logs", unix))]
+pub use native_log::NativeLogCapture;
+pub use owned_stream::OwnedStream;
+pub use packed_metadata::{PackedMetadata, PackedToken, PackedTranscript};
+pub use pool::{
+    estimate_footprint, HealthCheck, ModelPool, PoolOptions, PoolStats, PooledModel, StreamLease,
+};
+pub use progress::{Progress, ProgressObserver};
+pub use push_to_talk::{PushToTalk, PushToTalkConfig};
+pub use retry::{RetryPolicy, RetryingModel};
+pub use scorer::{CoverageReport, Scorer, ScorerInfo};
+pub use service::{Priority, ServiceOptions, SttService, Submission, SubmitOptions};
+pub use shutdown::Shutdown;
+pub use stats::{InferenceStats, StatsHandle};
+pub use stream::{Stream, StreamOperation, StreamTiming};
+pub use threadsafe_stream::{FeedAudio, JobOutput, StreamJob, StreamOptions, ThreadSafeStream};
+pub use token_metadata::{OwnedTokenMetadata, TokenMetadata, TIMESTEP};
+pub use transcriber::{EndpointPolicy, Segment, Transcriber, TranscriberConfig, TranscriptEvent};
+pub use transcription::{ModelInfo, TokenOffset, Transcription, Word};
+#[cfg(feature = "watch")]
+pub use watch::{watch_dir, WatchConfig, WatchHandle};
+pub use window::WindowOptions;
 
-#[cfg(feature = "raw_bindings")]
+#[cfg(feature = "raw-bindings")]
 pub use coqui_stt_sys;
@@ -1,4 +1,7 @@
-use crate::{CandidateTranscript, OwnedCandidateTranscript};
+use crate::{CandidateTranscript, OwnedCandidateTranscript, PackedMetadata, Word};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::cmp::Ordering;
 
 /// An array of [`CandidateTranscript`](CandidateTranscript) objects computed by the model.
 #[repr(transparent)]
@@ -17,11 +20,12 @@ impl Drop for Metadata {
 }
 
 impl Metadata {
-    pub(crate) fn new(ptr: *mut coqui_stt_sys::Metadata) -> Self {
+    /// Take ownership of `ptr`, which the `libstt` function `op` returns null on failure.
+    pub(crate) fn new(op: &'static str, ptr: *mut coqui_stt_sys::Metadata) -> crate::Result<Self> {
         if ptr.is_null() {
-            unreachable!("attempted to construct Metadata with a null pointer");
+            return Err(crate::Error::Unknown.context(op));
         }
-        Self(ptr)
+        Ok(Self(ptr))
     }
 
     /// Return an array of possible transcriptions.
@@ -37,6 +41,14 @@ impl Metadata {
         unsafe { std::slice::from_raw_parts(data, len) }
     }
 
+    /// The words of the most likely transcript, or none if there are no transcripts.
+    pub(crate) fn best_words(&self) -> Vec<Word> {
+        self.transcripts()
+            .first()
+            .map(CandidateTranscript::words)
+            .unwrap_or_default()
+    }
+
     /// Size of the transcripts array
     #[inline]
     #[must_use]
@@ -46,6 +58,42 @@ impl Metadata {
         unsafe { (*self.0).num_transcripts }
     }
 
+    /// Score every candidate transcript with `score`, and return them
+    /// sorted from highest to lowest score, along with their scores.
+    ///
+    /// This is useful for rescoring candidates with an external language model,
+    /// or anything else that knows more about the domain than the model does.
+    /// Candidates with a score that can't be compared even with itself, such as `NaN`,
+    /// are put last, in their original order. Other scores should be totally ordered,
+    /// as floats other than `NaN` are: if they aren't, the order is unspecified, and this may panic.
+    ///
+    /// With the `rayon` feature enabled, candidates are scored in parallel.
+    ///
+    /// **Warning**: like [`to_owned`](Metadata::to_owned), this copies every candidate.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn rank_by<F, S>(&self, score: F) -> Vec<(S, OwnedCandidateTranscript)>
+    where
+        F: Fn(&CandidateTranscript) -> S + Sync,
+        S: PartialOrd + Send,
+    {
+        let score_one =
+            |transcript: &CandidateTranscript| (score(transcript), transcript.to_owned());
+
+        #[cfg(feature = "rayon")]
+        let mut ranked: Vec<_> = self.transcripts().par_iter().map(score_one).collect();
+        #[cfg(not(feature = "rayon"))]
+        let mut ranked: Vec<_> = self.transcripts().iter().map(score_one).collect();
+
+        // sorted on (comparable, score), so incomparable scores don't break the total order
+        let comparable = |s: &S| s.partial_cmp(s).is_some();
+        ranked.sort_by(|(a, _), (b, _)| match (comparable(a), comparable(b)) {
+            (true, true) => b.partial_cmp(a).unwrap_or(Ordering::Equal),
+            (a, b) => b.cmp(&a),
+        });
+        ranked
+    }
+
     /// Convert this into an [`OwnedMetadata`](OwnedMetadata) struct.
     ///
     /// **Warning**: this can be an extremely expensive operation depending on
@@ -60,6 +108,18 @@ impl Metadata {
                 .collect(),
         )
     }
+
+    /// Convert this into a [`PackedMetadata`](PackedMetadata), which stores the text
+    /// of every token in one string.
+    ///
+    /// This takes three allocations however many tokens there are,
+    /// where [`to_owned`](Metadata::to_owned) takes one per token,
+    /// so prefer it for long transcripts.
+    #[inline]
+    #[must_use]
+    pub fn to_packed(&self) -> PackedMetadata {
+        PackedMetadata::new(self)
+    }
 }
 
 /// An owned variant of [`Metadata`](Metadata).
@@ -90,3 +150,50 @@ impl OwnedMetadata {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Model;
+    use coqui_stt_fake::{set_output, Output};
+
+    #[test]
+    fn rank_by_puts_incomparable_scores_last() {
+        let texts = ["a", "nan", "ccc", "nan too", "ee"];
+        set_output(Some(Output {
+            transcripts: texts.iter().map(|t| vec![t.as_bytes().to_vec()]).collect(),
+        }));
+        let mut model = Model::new("model").unwrap();
+        let metadata = model.speech_to_text_with_metadata(&[0; 16000], 5).unwrap();
+        set_output(None);
+
+        let ranked = metadata.rank_by(|transcript| {
+            let text = transcript.text();
+            if text.starts_with("nan") {
+                f64::NAN
+            } else {
+                text.len() as f64
+            }
+        });
+        let texts: Vec<_> = ranked.iter().map(|(_, t)| t.text()).collect();
+        assert_eq!(texts, ["ccc", "ee", "a", "nan", "nan too"]);
+        assert_eq!(ranked[0].0, 3.0);
+        assert!(ranked[4].0.is_nan());
+    }
+
+    #[test]
+    fn rank_by_keeps_ties_in_their_original_order() {
+        set_output(Some(Output {
+            transcripts: (0..20)
+                .map(|i| vec![format!("{}", i).into_bytes()])
+                .collect(),
+        }));
+        let mut model = Model::new("model").unwrap();
+        let metadata = model.speech_to_text_with_metadata(&[0; 16000], 20).unwrap();
+        set_output(None);
+
+        let ranked = metadata.rank_by(|transcript| transcript.text().len());
+        let texts: Vec<_> = ranked.iter().map(|(_, t)| t.text()).collect();
+        let expected: Vec<_> = (10..20).chain(0..10).map(|i| i.to_string()).collect();
+        assert_eq!(texts, expected);
+    }
+}
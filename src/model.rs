@@ -1,10 +1,22 @@
 #![allow(clippy::missing_safety_doc)]
-use crate::{Metadata, Stream};
+use crate::audio::{duration_of, samples_in, IntoModelAudio, SamplesExt};
+use crate::util::ModelFormat;
+use crate::window::{join_words, stitch, Window};
+use crate::{
+    Metadata, ModelInfo, Progress, ProgressObserver, Stream, Transcription, WindowOptions,
+};
 use std::ffi::CStr;
 use std::os::raw::c_uint;
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
 
 /// A trained Coqui STT model.
-pub struct Model(pub(crate) *mut coqui_stt_sys::ModelState);
+pub struct Model(
+    pub(crate) *mut coqui_stt_sys::ModelState,
+    /// Set while a stream on this model is in a call into `libstt`, checked in debug builds.
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    pub(crate) AtomicBool,
+);
 
 // these implementations are safe, as ModelState can be passed between threads safely
 unsafe impl Send for Model {}
@@ -19,16 +31,32 @@ impl Drop for Model {
 }
 
 impl Model {
+    /// Wrap a model state, which the `Model` frees when dropped.
+    pub(crate) const fn from_state(state: *mut coqui_stt_sys::ModelState) -> Self {
+        Self(state, AtomicBool::new(false))
+    }
+
     /// Create a new model.
     ///
+    /// With the `zstd` feature, paths ending in `.zst` are decompressed into memory
+    /// and loaded from there.
+    ///
     /// # Errors
-    /// Returns an error if the model path is invalid, or for other reasons.
+    /// Returns [`Error::UnsupportedModelFormat`](crate::Error::UnsupportedModelFormat)
+    /// if the model is in a format `libstt` can't load, such as a `.pbmm` model
+    /// on a release that only loads TensorFlow Lite models.
+    /// Otherwise, returns an error if the model path is invalid, or for other reasons.
     #[inline]
     pub fn new(model_path: impl Into<String>) -> crate::Result<Self> {
         Self::_new(model_path.into())
     }
 
     fn _new(model_path: String) -> crate::Result<Self> {
+        #[cfg(feature = "zstd")]
+        if let Some(buffer) = decompress(&model_path)? {
+            return Self::_new_from_buffer(&buffer);
+        }
+
         let mut model_path = model_path.into_bytes();
         model_path.reserve_exact(1);
         model_path.push(b'\0');
@@ -42,30 +70,43 @@ impl Model {
             coqui_stt_sys::STT_CreateModel(model_path.as_ptr(), std::ptr::addr_of_mut!(state))
         };
 
-        if let Some(e) = crate::Error::from_c_int(retval) {
-            return Err(e);
+        if let Some(e) = crate::Error::from_c_call("STT_CreateModel", retval) {
+            let format = model_path
+                .to_str()
+                .ok()
+                .and_then(|path| ModelFormat::detect_file(path).ok().flatten());
+            return Err(unsupported_format(format).unwrap_or(e));
         }
 
         if state.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_CreateModel"));
         }
 
-        Ok(Self(state))
+        Ok(Self::from_state(state))
     }
 
     /// Create a new model from a memory buffer.
     ///
     /// # Errors
-    /// Returns an error if the model is invalid, or for other reasons.
+    /// Returns [`Error::NotSupportedOnPlatform`](crate::Error::NotSupportedOnPlatform) on Windows,
+    /// where `libstt` can't load models from memory, and
+    /// [`Error::UnsupportedModelFormat`](crate::Error::UnsupportedModelFormat)
+    /// if the model is in a format `libstt` can't load.
+    /// Otherwise, returns an error if the model is invalid, or for other reasons.
     #[inline]
-    #[cfg(not(target_os = "windows"))]
     pub fn new_from_buffer<'a>(buffer: impl AsRef<&'a [u8]>) -> crate::Result<Self> {
         Self::_new_from_buffer(buffer.as_ref())
     }
 
+    #[inline]
+    #[cfg(target_os = "windows")]
+    pub(crate) fn _new_from_buffer(_buffer: &[u8]) -> crate::Result<Self> {
+        Err(crate::Error::NotSupportedOnPlatform)
+    }
+
     #[inline]
     #[cfg(not(target_os = "windows"))]
-    fn _new_from_buffer(buffer: &[u8]) -> crate::Result<Self> {
+    pub(crate) fn _new_from_buffer(buffer: &[u8]) -> crate::Result<Self> {
         let mut state = std::ptr::null_mut::<coqui_stt_sys::ModelState>();
 
         // SAFETY: creating a model is only done with a null pointer and a model buffer
@@ -78,20 +119,21 @@ impl Model {
             )
         };
 
-        if let Some(e) = crate::Error::from_c_int(retval) {
-            return Err(e);
+        if let Some(e) = crate::Error::from_c_call("STT_CreateModelFromBuffer", retval) {
+            return Err(unsupported_format(ModelFormat::detect(buffer)).unwrap_or(e));
         }
 
         if state.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_CreateModelFromBuffer"));
         }
 
-        Ok(Self(state))
+        Ok(Self::from_state(state))
     }
 
     /// Take this model, and return the inner model state.
     ///
     /// This is useful if the safe API does not provide something you need.
+    /// See [`raw::model_into_state`](crate::raw::model_into_state), which this calls.
     ///
     /// # Safety
     /// Once this is called, the memory management of the model is no longer handled for you.
@@ -101,22 +143,24 @@ impl Model {
     #[inline]
     #[must_use]
     pub unsafe fn into_inner(self) -> *mut coqui_stt_sys::ModelState {
-        let manual_drop = std::mem::ManuallyDrop::new(self);
-
-        manual_drop.0
+        crate::raw::model_into_state(self)
     }
 
     /// Create a new model from an existing model state.
+    /// See [`raw::model_from_state`](crate::raw::model_from_state), which this calls.
     ///
     /// # Safety
     /// You must ensure `state` is a valid model state.
     #[inline]
     pub const unsafe fn from_model_state(state: *mut coqui_stt_sys::ModelState) -> Self {
-        Self(state)
+        crate::raw::model_from_state(state)
     }
 
     /// Enable an external scorer for this model.
     ///
+    /// With the `zstd` feature, paths ending in `.zst` are decompressed into memory
+    /// and loaded from there.
+    ///
     /// # Errors
     /// Returns an error if the `scorer_path`/file pointed to is invalid in some way.
     #[inline]
@@ -126,22 +170,25 @@ impl Model {
 
     #[inline]
     fn _enable_external_scorer(&mut self, scorer_path: String) -> crate::Result<()> {
+        #[cfg(feature = "zstd")]
+        if let Some(buffer) = decompress(&scorer_path)? {
+            return self._enable_external_scorer_from_buffer(&buffer);
+        }
+
         let mut scorer_path = scorer_path.into_bytes();
         scorer_path.reserve_exact(1);
         scorer_path.push(b'\0');
         let scorer_path = CStr::from_bytes_with_nul(scorer_path.as_ref())?;
-        handle_error!(coqui_stt_sys::STT_EnableExternalScorer(
-            self.0,
-            scorer_path.as_ptr()
-        ))
+        handle_error!(STT_EnableExternalScorer(self.0, scorer_path.as_ptr()))
     }
 
     /// Enable an external scorer for this model, loaded from a buffer in memory.
     ///
     /// # Errors
-    /// Returns an error if the scorer in memory is invalid in some way.
+    /// Returns [`Error::NotSupportedOnPlatform`](crate::Error::NotSupportedOnPlatform) on Windows,
+    /// where `libstt` can't load scorers from memory.
+    /// Otherwise, returns an error if the scorer in memory is invalid in some way.
     #[inline]
-    #[cfg(not(target_os = "windows"))]
     pub fn enable_external_scorer_from_buffer(
         &mut self,
         buffer: impl AsRef<[u8]>,
@@ -149,10 +196,16 @@ impl Model {
         self._enable_external_scorer_from_buffer(buffer.as_ref())
     }
 
+    #[inline]
+    #[cfg(target_os = "windows")]
+    fn _enable_external_scorer_from_buffer(&mut self, _buffer: &[u8]) -> crate::Result<()> {
+        Err(crate::Error::NotSupportedOnPlatform)
+    }
+
     #[inline]
     #[cfg(not(target_os = "windows"))]
     fn _enable_external_scorer_from_buffer(&mut self, buffer: &[u8]) -> crate::Result<()> {
-        handle_error!(coqui_stt_sys::STT_EnableExternalScorerFromBuffer(
+        handle_error!(STT_EnableExternalScorerFromBuffer(
             self.0,
             buffer.as_ptr().cast::<std::os::raw::c_char>(),
             buffer.len() as c_uint
@@ -166,7 +219,7 @@ impl Model {
     /// Returns an error if an error happened while disabling the scorer.
     #[inline]
     pub fn disable_external_scorer(&mut self) -> crate::Result<()> {
-        handle_error!(coqui_stt_sys::STT_DisableExternalScorer(self.0))
+        handle_error!(STT_DisableExternalScorer(self.0))
     }
 
     /// Add a hot-word and its boost.
@@ -187,7 +240,7 @@ impl Model {
         word.reserve_exact(1);
         word.push(b'\0');
         let word = CStr::from_bytes_with_nul(word.as_ref())?;
-        handle_error!(coqui_stt_sys::STT_AddHotWord(self.0, word.as_ptr(), boost))
+        handle_error!(STT_AddHotWord(self.0, word.as_ptr(), boost))
     }
 
     /// Remove entry for a hot-word from the hot-words map.
@@ -207,7 +260,7 @@ impl Model {
         word.reserve_exact(1);
         word.push(b'\0');
         let word = CStr::from_bytes_with_nul(word.as_ref())?;
-        handle_error!(coqui_stt_sys::STT_EraseHotWord(self.0, word.as_ptr()))
+        handle_error!(STT_EraseHotWord(self.0, word.as_ptr()))
     }
 
     /// Removes all elements from the hot-words map.
@@ -216,7 +269,7 @@ impl Model {
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[inline]
     pub fn clear_hot_words(&mut self) -> crate::Result<()> {
-        handle_error!(coqui_stt_sys::STT_ClearHotWords(self.0))
+        handle_error!(STT_ClearHotWords(self.0))
     }
 
     /// Set hyperparameters alpha and beta of the external scorer.
@@ -229,7 +282,7 @@ impl Model {
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[inline]
     pub fn set_scorer_alpha_beta(&mut self, alpha: f32, beta: f32) -> crate::Result<()> {
-        handle_error!(coqui_stt_sys::STT_SetScorerAlphaBeta(self.0, alpha, beta))
+        handle_error!(STT_SetScorerAlphaBeta(self.0, alpha, beta))
     }
 
     /// Return the sample rate expected by a model in Hz.
@@ -239,24 +292,55 @@ impl Model {
         unsafe { coqui_stt_sys::STT_GetModelSampleRate(self.0 as *const _) }
     }
 
+    /// Set the beam width of the decoder. A wider beam finds better transcripts,
+    /// but takes longer to decode.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn set_model_beam_width(&mut self, beam_width: u32) -> crate::Result<()> {
+        handle_error!(STT_SetModelBeamWidth(self.0, beam_width))
+    }
+
+    /// Return the beam width value used by the model.
+    #[inline]
+    #[must_use]
+    pub fn get_beam_width(&self) -> u32 {
+        unsafe { coqui_stt_sys::STT_GetModelBeamWidth(self.0 as *const _) }
+    }
+
+    /// Return information about this model, as included in a [`Transcription`](crate::Transcription).
+    #[inline]
+    #[must_use]
+    pub fn info(&self) -> ModelInfo {
+        ModelInfo {
+            sample_rate: self.get_sample_rate() as u32,
+            beam_width: self.get_beam_width(),
+        }
+    }
+
     /// Use the Coqui STT model to convert speech to text.
     ///
     /// `buffer` should be a 16-bit, mono, raw audio signal
     /// at the appropriate sample rate, matching what the model was trained on.
     /// The required sample rate can be obtained from [`get_sample_rate`](crate::Model::get_sample_rate).
+    /// Audio in other formats is converted first; see [`IntoModelAudio`].
     ///
     /// # Errors
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     ///
-    /// Additionally, if the returned string is not valid UTF-8, this function returns an error.
+    /// Additionally, if the returned string is not valid UTF-8,
+    /// or `buffer` couldn't be converted, this function returns an error.
     #[allow(clippy::missing_inline_in_public_items)]
-    pub fn speech_to_text(&mut self, buffer: &[i16]) -> crate::Result<String> {
+    pub fn speech_to_text<'a>(&mut self, buffer: impl IntoModelAudio<'a>) -> crate::Result<String> {
+        crate::blocking_check::check("Model::speech_to_text");
+        let buffer = buffer.into_model_audio(self.get_sample_rate() as u32)?;
         let ptr = unsafe {
             coqui_stt_sys::STT_SpeechToText(self.0, buffer.as_ptr(), buffer.len() as c_uint)
         };
 
         if ptr.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_SpeechToText"));
         }
 
         // SAFETY: STT_SpeechToText will always return a valid CStr
@@ -276,6 +360,8 @@ impl Model {
     /// at the appropriate sample rate, matching what the model was trained on.
     /// The required sample rate can be obtained from [`get_sample_rate`](crate::Model::get_sample_rate).
     ///
+    /// Audio in other formats is converted first; see [`IntoModelAudio`].
+    ///
     /// `num_results` is the maximum number of possible transcriptions to return.
     /// Note that it is not guaranteed this many will be returned at minimum,
     /// but there will never be more than this number at maximum.
@@ -283,11 +369,13 @@ impl Model {
     /// # Errors
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[inline]
-    pub fn speech_to_text_with_metadata(
+    pub fn speech_to_text_with_metadata<'a>(
         &mut self,
-        buffer: &[i16],
+        buffer: impl IntoModelAudio<'a>,
         num_results: u32,
     ) -> crate::Result<Metadata> {
+        crate::blocking_check::check("Model::speech_to_text_with_metadata");
+        let buffer = buffer.into_model_audio(self.get_sample_rate() as u32)?;
         let ptr = unsafe {
             coqui_stt_sys::STT_SpeechToTextWithMetadata(
                 self.0,
@@ -297,11 +385,144 @@ impl Model {
             )
         };
 
-        if ptr.is_null() {
-            return Err(crate::Error::Unknown);
+        crate::Metadata::new("STT_SpeechToTextWithMetadata", ptr)
+    }
+
+    /// Use the Coqui STT model to convert speech to a [`Transcription`]:
+    /// the text of the best candidate transcript, with its words, their timings,
+    /// and its confidence.
+    ///
+    /// This is the recommended way to transcribe audio in one go.
+    /// Use [`speech_to_text`](Model::speech_to_text) if only the text is needed,
+    /// or [`speech_to_text_with_metadata`](Model::speech_to_text_with_metadata)
+    /// for more than one candidate transcript.
+    ///
+    /// `buffer` should be a 16-bit, mono, raw audio signal
+    /// at the appropriate sample rate, matching what the model was trained on.
+    /// Audio in other formats is converted first; see [`IntoModelAudio`].
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn speech_to_text_best<'a>(
+        &mut self,
+        buffer: impl IntoModelAudio<'a>,
+    ) -> crate::Result<Transcription> {
+        let model_info = self.info();
+        let start = Instant::now();
+        let buffer = buffer.into_model_audio(model_info.sample_rate)?;
+        let metadata = self.speech_to_text_with_metadata(&*buffer, 1)?;
+        Ok(Transcription::from_metadata(
+            &metadata,
+            buffer.duration(model_info.sample_rate),
+            start.elapsed(),
+            model_info,
+        ))
+    }
+
+    /// Use the Coqui STT model to convert a very long recording, such as hours of audio,
+    /// to a [`Transcription`], decoding it in overlapping windows.
+    ///
+    /// `buffer` should be a 16-bit, mono, raw audio signal
+    /// at the appropriate sample rate, matching what the model was trained on.
+    /// Audio in other formats is converted first; see [`IntoModelAudio`].
+    ///
+    /// Each window is decoded with a fresh stream, so memory use doesn't grow with the audio,
+    /// and the transcripts of neighbouring windows are stitched together in their overlap
+    /// using the timings of their words; see [`WindowOptions`].
+    /// The words of the result are joined with spaces, and its confidence
+    /// is the mean of the windows'. Audio no longer than one window is decoded in one go.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn speech_to_text_windowed<'a>(
+        &mut self,
+        buffer: impl IntoModelAudio<'a>,
+        options: &WindowOptions,
+    ) -> crate::Result<Transcription> {
+        let model_info = self.info();
+        let sample_rate = model_info.sample_rate;
+        let start = Instant::now();
+        let buffer = buffer.into_model_audio(sample_rate)?;
+        let ranges = options.ranges(buffer.len(), sample_rate);
+        if ranges.len() <= 1 {
+            return self
+                .speech_to_text_best(&*buffer)
+                .map(|transcription| Transcription {
+                    processing_time: start.elapsed(),
+                    ..transcription
+                });
+        }
+
+        let mut windows = Vec::with_capacity(ranges.len());
+        let mut confidence = 0.0;
+        for range in ranges {
+            let transcription = self.speech_to_text_best(&buffer[range.clone()])?;
+            confidence += transcription.confidence;
+            windows.push(Window::new(range, sample_rate, transcription.words));
         }
 
-        Ok(crate::Metadata::new(ptr))
+        confidence /= windows.len() as f64;
+        let words = stitch(windows, options.tolerance);
+        Ok(Transcription {
+            text: join_words(&words),
+            words,
+            confidence,
+            audio_duration: buffer.duration(sample_rate),
+            processing_time: start.elapsed(),
+            model_info,
+        })
+    }
+
+    /// Use the Coqui STT model to convert a long recording to text,
+    /// reporting progress as it goes.
+    ///
+    /// `buffer` should be a 16-bit, mono, raw audio signal
+    /// at the appropriate sample rate, matching what the model was trained on.
+    /// Audio in other formats is converted first; see [`IntoModelAudio`].
+    ///
+    /// The audio is fed to the model one `chunk` at a time, and `observer` is called
+    /// after each chunk with the text so far and an estimate of the time remaining.
+    /// Smaller chunks report progress more often, at the cost of an intermediate decode each time.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn speech_to_text_with_progress<'a>(
+        &mut self,
+        buffer: impl IntoModelAudio<'a>,
+        chunk: Duration,
+        mut observer: impl ProgressObserver,
+    ) -> crate::Result<Transcription> {
+        let sample_rate = self.get_sample_rate().max(1) as u32;
+        let buffer = buffer.into_model_audio(sample_rate)?;
+        let chunk_len = samples_in(chunk, sample_rate).max(1);
+        let total = buffer.duration(sample_rate);
+
+        let start = Instant::now();
+        let mut stream = Stream::from_model(self)?;
+        let mut processed = 0;
+        for audio in buffer.chunks(chunk_len) {
+            stream.feed_audio(audio);
+            processed += audio.len();
+
+            let partial_text = stream.intermediate_decode()?;
+            let processed = duration_of(processed as u64, sample_rate);
+            let real_time_factor = start.elapsed().as_secs_f64() / processed.as_secs_f64();
+            observer.on_progress(&Progress {
+                processed,
+                total,
+                partial_text,
+                real_time_factor,
+                eta: Duration::try_from_secs_f64(
+                    total.saturating_sub(processed).as_secs_f64() * real_time_factor,
+                )
+                .unwrap_or(Duration::MAX),
+            });
+        }
+
+        stream.finish_stream_transcription()
     }
 
     /// Convert this model into one used for streaming inference states.
@@ -312,23 +533,38 @@ impl Model {
     /// # Errors
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[allow(clippy::missing_inline_in_public_items)]
-    pub fn as_streaming(&mut self) -> crate::Result<Stream> {
+    pub fn as_streaming(&mut self) -> crate::Result<Stream<'_>> {
         let mut state = std::ptr::null_mut();
 
         let retval = unsafe { coqui_stt_sys::STT_CreateStream(self.0, &mut state) };
 
-        if let Some(e) = crate::Error::from_c_int(retval) {
+        if let Some(e) = crate::Error::from_c_call("STT_CreateStream", retval) {
             return Err(e);
         }
 
         if state.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_CreateStream"));
         }
 
-        Ok(Stream {
-            model: self,
-            state,
-            already_freed: false,
-        })
+        Ok(Stream::from_parts(self, state, false))
+    }
+}
+
+/// The error to return when `libstt` failed to load a model in `format`,
+/// if it failed because it can't load models in that format.
+fn unsupported_format(format: Option<ModelFormat>) -> Option<crate::Error> {
+    match format? {
+        ModelFormat::TfLite => None,
+        format => Some(crate::Error::UnsupportedModelFormat(format)),
+    }
+}
+
+/// Read and decompress the file at `path` if it is compressed with zstd,
+/// judging by its extension.
+#[cfg(feature = "zstd")]
+fn decompress(path: &str) -> crate::Result<Option<Vec<u8>>> {
+    if !path.ends_with(".zst") {
+        return Ok(None);
     }
+    Ok(Some(zstd::decode_all(std::fs::File::open(path)?)?))
 }
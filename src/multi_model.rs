@@ -0,0 +1,188 @@
+use crate::audio::samples_in;
+use crate::{Model, Transcription};
+use std::time::Duration;
+
+/// The result of [`MultiModel::auto_detect`].
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct Detection {
+    /// The language that was detected, as registered with [`MultiModel::with_model`].
+    pub language: String,
+    /// The score of every language, in the order they were registered.
+    ///
+    /// Scores are the confidence of the prefix's transcript, divided by its number of tokens,
+    /// so models that produce more tokens aren't penalised for it.
+    /// A prefix transcribed as nothing, or with a confidence that isn't a number,
+    /// scores negative infinity.
+    pub scores: Vec<(String, f64)>,
+    /// The transcription of the whole buffer, by the detected language's model.
+    pub transcription: Transcription,
+}
+
+/// A set of models for different languages,
+/// that can detect which language audio is in before transcribing it.
+pub struct MultiModel {
+    models: Vec<(String, Model)>,
+    detection_prefix: Duration,
+}
+
+impl Default for MultiModel {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            models: Vec::new(),
+            detection_prefix: Duration::from_secs(3),
+        }
+    }
+}
+
+impl MultiModel {
+    /// Create a new, empty `MultiModel`.
+    ///
+    /// By default, the first 3 seconds of audio are used to detect the language.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `model` as the model for `language`, returning the `MultiModel`.
+    ///
+    /// If a model was already registered for `language`, it is replaced.
+    #[inline]
+    #[must_use]
+    pub fn with_model(mut self, language: impl Into<String>, model: Model) -> Self {
+        self.insert(language, model);
+        self
+    }
+
+    /// Register `model` as the model for `language`,
+    /// returning the model previously registered for it, if any.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn insert(&mut self, language: impl Into<String>, model: Model) -> Option<Model> {
+        let language = language.into();
+        match self.models.iter_mut().find(|(l, _)| *l == language) {
+            Some((_, old)) => Some(std::mem::replace(old, model)),
+            None => {
+                self.models.push((language, model));
+                None
+            }
+        }
+    }
+
+    /// Remove the model registered for `language`, returning it.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn remove(&mut self, language: &str) -> Option<Model> {
+        let index = self.models.iter().position(|(l, _)| l == language)?;
+        Some(self.models.remove(index).1)
+    }
+
+    /// Set how much audio from the start of the buffer is transcribed
+    /// with every model to detect the language.
+    ///
+    /// Longer prefixes detect the language more reliably, but take longer.
+    #[inline]
+    #[must_use]
+    pub const fn with_detection_prefix(mut self, prefix: Duration) -> Self {
+        self.detection_prefix = prefix;
+        self
+    }
+
+    /// The languages registered, in the order they were registered.
+    #[inline]
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.models.iter().map(|(language, _)| language.as_str())
+    }
+
+    /// Return a mutable reference to the model registered for `language`.
+    #[inline]
+    #[must_use]
+    pub fn model_mut(&mut self, language: &str) -> Option<&mut Model> {
+        self.models
+            .iter_mut()
+            .find(|(l, _)| l == language)
+            .map(|(_, model)| model)
+    }
+
+    /// Detect which language `buffer` is in, then transcribe it with that language's model.
+    ///
+    /// The start of the buffer is transcribed with every model in parallel,
+    /// and the model with the highest score (see [`Detection::scores`]) wins.
+    /// Of models with the same score, the one registered first wins.
+    ///
+    /// # Errors
+    /// Returns [`Error::NoModel`](crate::Error::NoModel) if no models are registered.
+    /// Otherwise, passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn auto_detect(&mut self, buffer: &[i16]) -> crate::Result<Detection> {
+        if self.models.is_empty() {
+            return Err(crate::Error::NoModel);
+        }
+
+        let detection_prefix = self.detection_prefix;
+        let scores = std::thread::scope(|s| {
+            let handles: Vec<_> = self
+                .models
+                .iter_mut()
+                .map(|(language, model)| {
+                    s.spawn(move || {
+                        let prefix_len =
+                            samples_in(detection_prefix, model.get_sample_rate() as u32);
+                        let prefix = &buffer[..prefix_len.min(buffer.len())];
+                        let score = score(model, prefix)?;
+                        Ok((language.clone(), score))
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect::<crate::Result<Vec<_>>>()
+        })?;
+
+        let (winner, _) = scores.iter().enumerate().fold(
+            (0, f64::NEG_INFINITY),
+            |(best, best_score), (i, (_, score))| {
+                if *score > best_score {
+                    (i, *score)
+                } else {
+                    (best, best_score)
+                }
+            },
+        );
+        let (language, model) = &mut self.models[winner];
+
+        Ok(Detection {
+            language: language.clone(),
+            transcription: model.speech_to_text_best(buffer)?,
+            scores,
+        })
+    }
+
+    /// Take this `MultiModel`, and return the models it contained along with their languages.
+    #[inline]
+    #[must_use]
+    pub fn into_models(self) -> Vec<(String, Model)> {
+        self.models
+    }
+}
+
+fn score(model: &mut Model, prefix: &[i16]) -> crate::Result<f64> {
+    let metadata = model.speech_to_text_with_metadata(prefix, 1)?;
+    let score = match metadata.transcripts().first() {
+        // with no tokens, this would divide by zero
+        Some(transcript) if transcript.num_tokens() > 0 => {
+            transcript.confidence() / f64::from(transcript.num_tokens())
+        }
+        _ => f64::NEG_INFINITY,
+    };
+    Ok(if score.is_nan() {
+        f64::NEG_INFINITY
+    } else {
+        score
+    })
+}
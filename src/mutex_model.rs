@@ -0,0 +1,210 @@
+use crate::audio::IntoModelAudio;
+use crate::{Metadata, Model, ModelInfo};
+use parking_lot::{Mutex, RwLock};
+use std::sync::mpsc;
+
+/// A [`Model`] that can be shared between threads and used through `&self`,
+/// without a worker thread.
+///
+/// Inference runs on the calling thread, so there is no channel hop as with
+/// [`ThreadSafeStream`](crate::ThreadSafeStream), which makes this a better fit
+/// for applications that only occasionally transcribe from several threads.
+///
+/// # Blocking
+/// `libstt` can't run two inferences on one model at once, so every method that runs
+/// inference or changes the model takes the lock exclusively, and blocks until any
+/// other call finishes. The getters share the lock with each other, but still wait
+/// for inference in progress. For many concurrent requests, use a
+/// [`ModelPool`](crate::ModelPool) instead.
+pub struct MutexModel {
+    model: RwLock<Model>,
+    subscribers: Mutex<Vec<mpsc::Sender<ScorerEvent>>>,
+}
+
+/// A change to the scorer of a [`MutexModel`], from [`MutexModel::scorer_events`].
+///
+/// Anything worked out from the scorer, such as a [`CoverageReport`](crate::CoverageReport),
+/// or from transcripts made with it, may be out of date once one of these arrives.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScorerEvent {
+    /// The scorer at `path` was enabled, replacing any scorer enabled before.
+    Enabled {
+        /// The path the scorer was loaded from.
+        path: String,
+    },
+    /// The scorer was disabled.
+    Disabled,
+    /// The scorer's hyperparameters were set.
+    HyperparametersChanged {
+        /// The language model weight.
+        alpha: f32,
+        /// The word insertion weight.
+        beta: f32,
+    },
+}
+
+impl MutexModel {
+    /// Wrap `model` for sharing.
+    #[inline]
+    #[must_use]
+    pub fn new(model: Model) -> Self {
+        Self {
+            model: RwLock::new(model),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Take the model back out.
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> Model {
+        self.model.into_inner()
+    }
+
+    /// Run `f` with exclusive access to the model, for anything not wrapped here,
+    /// such as streaming. Blocks other callers until `f` returns.
+    ///
+    /// Changes to the scorer made through `f` aren't sent to
+    /// [`scorer_events`](MutexModel::scorer_events) subscribers.
+    #[inline]
+    pub fn with_model<R>(&self, f: impl FnOnce(&mut Model) -> R) -> R {
+        f(&mut self.model.write())
+    }
+
+    /// See [`Model::speech_to_text`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn speech_to_text<'a>(&self, buffer: impl IntoModelAudio<'a>) -> crate::Result<String> {
+        self.model.write().speech_to_text(buffer)
+    }
+
+    /// See [`Model::speech_to_text_with_metadata`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn speech_to_text_with_metadata<'a>(
+        &self,
+        buffer: impl IntoModelAudio<'a>,
+        num_results: u32,
+    ) -> crate::Result<Metadata> {
+        self.model
+            .write()
+            .speech_to_text_with_metadata(buffer, num_results)
+    }
+
+    /// See [`Model::enable_external_scorer`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn enable_external_scorer(&self, scorer_path: impl Into<String>) -> crate::Result<()> {
+        let path = scorer_path.into();
+        let mut model = self.model.write();
+        model.enable_external_scorer(path.clone())?;
+        self.publish(&ScorerEvent::Enabled { path });
+        Ok(())
+    }
+
+    /// See [`Model::disable_external_scorer`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn disable_external_scorer(&self) -> crate::Result<()> {
+        let mut model = self.model.write();
+        model.disable_external_scorer()?;
+        self.publish(&ScorerEvent::Disabled);
+        Ok(())
+    }
+
+    /// See [`Model::add_hot_word`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn add_hot_word(&self, word: impl Into<String>, boost: f32) -> crate::Result<()> {
+        self.model.write().add_hot_word(word, boost)
+    }
+
+    /// See [`Model::erase_hot_word`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn erase_hot_word(&self, word: impl Into<String>) -> crate::Result<()> {
+        self.model.write().erase_hot_word(word)
+    }
+
+    /// See [`Model::clear_hot_words`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn clear_hot_words(&self) -> crate::Result<()> {
+        self.model.write().clear_hot_words()
+    }
+
+    /// See [`Model::set_scorer_alpha_beta`]. Takes the lock exclusively.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn set_scorer_alpha_beta(&self, alpha: f32, beta: f32) -> crate::Result<()> {
+        let mut model = self.model.write();
+        model.set_scorer_alpha_beta(alpha, beta)?;
+        self.publish(&ScorerEvent::HyperparametersChanged { alpha, beta });
+        Ok(())
+    }
+
+    /// Receive a [`ScorerEvent`] for every change made to the scorer through this `MutexModel`
+    /// from now on, in the order they were made.
+    ///
+    /// Events are only sent for changes that succeeded. Dropping the receiver unsubscribes.
+    #[inline]
+    #[must_use]
+    pub fn scorer_events(&self) -> mpsc::Receiver<ScorerEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    /// Send `event` to every subscriber. Called with the model locked,
+    /// so events arrive in the order the changes were made.
+    fn publish(&self, event: &ScorerEvent) {
+        self.subscribers
+            .lock()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// See [`Model::get_sample_rate`]. Shares the lock.
+    #[inline]
+    #[must_use]
+    pub fn get_sample_rate(&self) -> i32 {
+        self.model.read().get_sample_rate()
+    }
+
+    /// See [`Model::get_beam_width`]. Shares the lock.
+    #[inline]
+    #[must_use]
+    pub fn get_beam_width(&self) -> u32 {
+        self.model.read().get_beam_width()
+    }
+
+    /// See [`Model::info`]. Shares the lock.
+    #[inline]
+    #[must_use]
+    pub fn info(&self) -> ModelInfo {
+        self.model.read().info()
+    }
+}
+
+impl From<Model> for MutexModel {
+    #[inline]
+    fn from(model: Model) -> Self {
+        Self::new(model)
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether stderr is currently redirected by a [`NativeLogCapture`].
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// Routes what `libstt` prints to stderr, such as TensorFlow Lite warnings,
+/// into [`tracing`](https://docs.rs/tracing) instead.
+///
+/// `libstt` has no logging hook, so this redirects the process's stderr into a pipe
+/// and reads it line by line on a thread of its own.
+/// That captures everything written to stderr, not only `libstt`'s output,
+/// so only one capture can be active at a time.
+///
+/// Stderr is restored when this is dropped.
+/// Lines still in the pipe at that point are read and logged afterwards.
+#[derive(Debug)]
+pub struct NativeLogCapture {
+    original: Option<OwnedFd>,
+}
+
+impl NativeLogCapture {
+    /// Start capturing stderr, logging each line as an event with the target `coqui_native`.
+    ///
+    /// Lines starting with `ERROR:`, `WARNING:` or `INFO:`, as TensorFlow Lite prints them,
+    /// are logged at those levels, and anything else as a warning.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if stderr is already being captured,
+    /// or could not be redirected.
+    #[inline]
+    pub fn start() -> crate::Result<Self> {
+        Self::start_with(log_line)
+    }
+
+    /// Start capturing stderr, passing each line to `handler` instead of `tracing`.
+    ///
+    /// `handler` runs on the capture's thread, so it must not write to stderr itself.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if stderr is already being captured,
+    /// or could not be redirected.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn start_with(mut handler: impl FnMut(&str) + Send + 'static) -> crate::Result<Self> {
+        if CAPTURING.swap(true, Ordering::AcqRel) {
+            return Err(crate::Error::Io(
+                "stderr is already being captured".to_string(),
+            ));
+        }
+
+        let redirected = redirect_stderr();
+        let (original, reader) = match redirected {
+            Ok(fds) => fds,
+            Err(e) => {
+                CAPTURING.store(false, Ordering::Release);
+                return Err(e.into());
+            }
+        };
+
+        std::thread::spawn(move || {
+            let mut lines = BufReader::new(File::from(reader));
+            let mut line = Vec::new();
+            // the pipe closes once stderr is restored, ending the loop
+            while matches!(lines.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                let text = String::from_utf8_lossy(&line);
+                handler(text.trim_end_matches(['\n', '\r']));
+                line.clear();
+            }
+        });
+
+        Ok(Self {
+            original: Some(original),
+        })
+    }
+}
+
+/// Point stderr at a new pipe, returning the original stderr and the read end of the pipe.
+fn redirect_stderr() -> std::io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two descriptors `pipe` writes
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: `pipe` succeeded, so both descriptors are open and owned by nothing else
+    let (reader, writer) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+    // SAFETY: `dup` has no preconditions, and a returned descriptor is new
+    let original = unsafe { libc::dup(libc::STDERR_FILENO) };
+    if original < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: `original` was just returned by `dup`
+    let original = unsafe { OwnedFd::from_raw_fd(original) };
+
+    // SAFETY: `writer` is open; `dup2` replaces stderr with a copy of it
+    if unsafe { libc::dup2(writer.as_raw_fd(), libc::STDERR_FILENO) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok((original, reader))
+}
+
+impl Drop for NativeLogCapture {
+    #[inline]
+    fn drop(&mut self) {
+        let original = self
+            .original
+            .take()
+            .unwrap_or_else(|| unreachable!("original stderr is only taken out once"));
+        // SAFETY: `original` is open; restoring it closes the last write end of the pipe
+        unsafe { libc::dup2(original.as_raw_fd(), libc::STDERR_FILENO) };
+        CAPTURING.store(false, Ordering::Release);
+    }
+}
+
+fn log_line(line: &str) {
+    if let Some(message) = line.strip_prefix("ERROR: ") {
+        tracing::error!(target: "coqui_native", "{}", message);
+    } else if let Some(message) = line.strip_prefix("WARNING: ") {
+        tracing::warn!(target: "coqui_native", "{}", message);
+    } else if let Some(message) = line.strip_prefix("INFO: ") {
+        tracing::info!(target: "coqui_native", "{}", message);
+    } else if !line.is_empty() {
+        tracing::warn!(target: "coqui_native", "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NativeLogCapture;
+    use std::time::Duration;
+
+    #[test]
+    fn captures_native_stderr() {
+        let (sender, lines) = std::sync::mpsc::channel();
+        let capture = NativeLogCapture::start_with(move |line| {
+            let _ = sender.send(line.to_string());
+        })
+        .unwrap();
+        assert!(NativeLogCapture::start().is_err());
+
+        let text = b"WARNING: from C\npartial";
+        // SAFETY: writing a valid buffer to stderr, as native code would
+        unsafe { libc::write(libc::STDERR_FILENO, text.as_ptr().cast(), text.len()) };
+        drop(capture);
+
+        let timeout = Duration::from_secs(5);
+        assert_eq!(lines.recv_timeout(timeout).unwrap(), "WARNING: from C");
+        assert_eq!(lines.recv_timeout(timeout).unwrap(), "partial");
+        drop(NativeLogCapture::start().unwrap());
+    }
+}
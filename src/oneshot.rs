@@ -0,0 +1,119 @@
+// A channel for a worker thread's single reply, which can be waited for
+// by blocking or by awaiting it, without depending on any async runtime.
+
+use crate::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+struct Slot<T> {
+    value: Option<T>,
+    /// Whether the sender is gone, so no value is coming if there isn't one already.
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+struct Inner<T> {
+    slot: Mutex<Slot<T>>,
+    ready: Condvar,
+}
+
+impl<T> Inner<T> {
+    fn slot(&self) -> MutexGuard<'_, Slot<T>> {
+        self.slot.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub(crate) fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Inner {
+        slot: Mutex::new(Slot {
+            value: None,
+            closed: false,
+            waker: None,
+        }),
+        ready: Condvar::new(),
+    });
+    (Sender(Arc::clone(&inner)), Receiver(inner))
+}
+
+/// Sends the reply. Dropping it without sending closes the channel.
+pub(crate) struct Sender<T>(Arc<Inner<T>>);
+
+impl<T> Sender<T> {
+    /// Send `value`. It is dropped if nobody is waiting for it anymore.
+    pub(crate) fn send(self, value: T) {
+        self.0.slot().value = Some(value);
+        // waking the receiver happens when `self` is dropped
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut slot = self.0.slot();
+            slot.closed = true;
+            slot.waker.take()
+        };
+        self.0.ready.notify_all();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Waits for the reply, returning `None` if the sender was dropped without sending one.
+pub(crate) struct Receiver<T>(Arc<Inner<T>>);
+
+impl<T> Receiver<T> {
+    /// Block until the reply arrives.
+    pub(crate) fn recv(self) -> Option<T> {
+        let mut slot = self.0.slot();
+        while !slot.closed {
+            slot = self.0.ready.wait(slot).unwrap_or_else(|e| e.into_inner());
+        }
+        slot.value.take()
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut slot = self.0.slot();
+        if slot.closed {
+            return Poll::Ready(slot.value.take());
+        }
+        match &slot.waker {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            _ => slot.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::channel;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    #[test]
+    fn replies_arrive_by_blocking_or_polling() {
+        let (tx, rx) = channel();
+        let sender = std::thread::spawn(move || tx.send(1));
+        assert_eq!(rx.recv(), Some(1));
+        sender.join().unwrap();
+
+        let (tx, rx) = channel();
+        let mut rx = pin!(rx);
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(rx.as_mut().poll(&mut cx), Poll::Pending);
+        tx.send(2);
+        assert_eq!(rx.poll(&mut cx), Poll::Ready(Some(2)));
+
+        let (tx, rx) = channel::<()>();
+        drop(tx);
+        assert_eq!(rx.recv(), None);
+    }
+}
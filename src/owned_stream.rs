@@ -0,0 +1,341 @@
+use crate::audio::{PcmFormat, Preprocessor};
+use crate::{Metadata, Model, Stream, StreamTiming, Transcription};
+use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
+use std::time::Duration;
+
+/// Two values that are always dropped in order: `first`, then `second`.
+///
+/// Struct fields are dropped in declaration order, but that is easy to break by
+/// reordering fields. This makes the order part of the type instead.
+struct FreeInOrder<A, B> {
+    first: ManuallyDrop<A>,
+    second: ManuallyDrop<B>,
+}
+
+impl<A, B> FreeInOrder<A, B> {
+    const fn new(first: A, second: B) -> Self {
+        Self {
+            first: ManuallyDrop::new(first),
+            second: ManuallyDrop::new(second),
+        }
+    }
+
+    /// Take both values out, leaving the caller responsible for the order they are dropped in.
+    fn into_parts(self) -> (A, B) {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped again, so each value is only taken once
+        unsafe {
+            (
+                ManuallyDrop::take(&mut this.first),
+                ManuallyDrop::take(&mut this.second),
+            )
+        }
+    }
+}
+
+impl<A, B> Drop for FreeInOrder<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: both values are only dropped here, once
+        unsafe {
+            ManuallyDrop::drop(&mut self.first);
+            ManuallyDrop::drop(&mut self.second);
+        }
+    }
+}
+
+/// A heap-allocated [`Model`] owned through a raw pointer,
+/// so a `Stream<'static>` can borrow it for as long as it is alive.
+struct ModelBox(NonNull<Model>);
+
+impl ModelBox {
+    fn new(model: Model) -> Self {
+        Self(NonNull::from(Box::leak(Box::new(model))))
+    }
+
+    fn into_inner(self) -> Model {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: the pointer came from `Box::leak`, and is only reclaimed once
+        *unsafe { Box::from_raw(this.0.as_ptr()) }
+    }
+}
+
+impl Drop for ModelBox {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the pointer came from `Box::leak`, and is only reclaimed once
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+    }
+}
+
+/// A [`Stream`] that owns the [`Model`] it was created from.
+///
+/// Unlike a [`Stream`], this has no lifetime, so it can be stored in structs,
+/// sent to other threads and kept around for as long as needed.
+///
+/// The streaming state is always freed before the model is,
+/// however this is dropped, finished or taken apart.
+pub struct OwnedStream(FreeInOrder<Stream<'static>, ModelBox>);
+
+// SAFETY: the model is only ever accessed through the stream, which is `Send` and `Sync`
+unsafe impl Send for OwnedStream {}
+#[cfg(not(feature = "unsync"))]
+unsafe impl Sync for OwnedStream {}
+
+impl OwnedStream {
+    /// Create a new `OwnedStream`, taking ownership of `model`.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    /// The model is dropped if this fails.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new(model: Model) -> crate::Result<Self> {
+        Self::try_new(model).map_err(|(e, _)| e)
+    }
+
+    /// Like [`new`](OwnedStream::new), but hands the model back if this fails.
+    pub(crate) fn try_new(model: Model) -> Result<Self, (crate::Error, Model)> {
+        let model = ModelBox::new(model);
+        // SAFETY: the model is heap allocated, so it doesn't move,
+        // and `FreeInOrder` makes sure it is only freed after the stream is
+        match Stream::from_model(unsafe { &mut *model.0.as_ptr() }) {
+            Ok(stream) => Ok(Self(FreeInOrder::new(stream, model))),
+            Err(e) => Err((e, model.into_inner())),
+        }
+    }
+
+    /// Return a reference to the [`Model`] this `OwnedStream` owns.
+    #[inline]
+    #[must_use]
+    pub fn model(&self) -> &Model {
+        self.0.first.model()
+    }
+
+    /// See [`Stream::set_preprocessor`].
+    ///
+    /// # Errors
+    /// Returns an error if the preprocessor does not support the model's sample rate.
+    #[inline]
+    pub fn set_preprocessor(
+        &mut self,
+        preprocessor: impl Preprocessor + 'static,
+    ) -> crate::Result<()> {
+        self.0.first.set_preprocessor(preprocessor)
+    }
+
+    /// See [`Stream::take_preprocessor`].
+    #[inline]
+    pub fn take_preprocessor(&mut self) -> Option<Box<dyn Preprocessor>> {
+        self.0.first.take_preprocessor()
+    }
+
+    /// See [`Stream::set_timing_observer`].
+    #[inline]
+    pub fn set_timing_observer(&mut self, observer: impl Fn(StreamTiming) + Send + 'static) {
+        self.0.first.set_timing_observer(observer);
+    }
+
+    /// See [`Stream::clear_timing_observer`].
+    #[inline]
+    pub fn clear_timing_observer(&mut self) {
+        self.0.first.clear_timing_observer();
+    }
+
+    /// Report `wait` as the [`queue_wait`](StreamTiming::queue_wait) of the next operation.
+    pub(crate) fn set_queue_wait(&mut self, wait: Duration) {
+        self.0.first.queue_wait = Some(wait);
+    }
+
+    /// See [`Stream::feed_audio`].
+    #[inline]
+    pub fn feed_audio(&mut self, buffer: &[i16]) {
+        self.0.first.feed_audio(buffer);
+    }
+
+    /// See [`Stream::reserve_audio_hint`].
+    #[inline]
+    pub fn reserve_audio_hint(&mut self, samples: usize) {
+        self.0.first.reserve_audio_hint(samples);
+    }
+
+    /// See [`Stream::feed_silence`].
+    #[inline]
+    pub fn feed_silence(&mut self, duration: Duration) {
+        self.0.first.feed_silence(duration);
+    }
+
+    /// See [`Stream::feed_audio_bytes`].
+    #[inline]
+    pub fn feed_audio_bytes(&mut self, bytes: &[u8], format: PcmFormat) {
+        self.0.first.feed_audio_bytes(bytes, format);
+    }
+
+    /// See [`Stream::intermediate_decode`].
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode(&mut self) -> crate::Result<String> {
+        self.0.first.intermediate_decode()
+    }
+
+    /// See [`Stream::intermediate_decode_with_metadata`].
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode_with_metadata(
+        &mut self,
+        num_results: u32,
+    ) -> crate::Result<Metadata> {
+        self.0.first.intermediate_decode_with_metadata(num_results)
+    }
+
+    /// See [`Stream::flush_buffers`].
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn flush_buffers(&mut self) -> crate::Result<()> {
+        self.0.first.flush_buffers()
+    }
+
+    /// See [`Stream::intermediate_decode_with_buffer_flush`].
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode_with_buffer_flush(&mut self) -> crate::Result<String> {
+        self.0.first.intermediate_decode_with_buffer_flush()
+    }
+
+    /// See [`Stream::intermediate_decode_with_metadata_and_buffer_flush`].
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode_with_metadata_and_buffer_flush(
+        &mut self,
+        num_results: u32,
+    ) -> crate::Result<Metadata> {
+        self.0
+            .first
+            .intermediate_decode_with_metadata_and_buffer_flush(num_results)
+    }
+
+    /// See [`Stream::pending_utf8`].
+    #[inline]
+    #[must_use]
+    pub fn pending_utf8(&self) -> &[u8] {
+        self.0.first.pending_utf8()
+    }
+
+    /// See [`Stream::finish_stream`]. The model is returned along with the result,
+    /// so it can be used for another stream.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream(self) -> (crate::Result<String>, Model) {
+        self.finish_with(Stream::finish_stream)
+    }
+
+    /// See [`Stream::finish_stream_with_metadata`]. The model is returned along with the result,
+    /// so it can be used for another stream.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream_with_metadata(self, num_results: u32) -> (crate::Result<Metadata>, Model) {
+        self.finish_with(|stream| stream.finish_stream_with_metadata(num_results))
+    }
+
+    /// See [`Stream::finish_stream_transcription`]. The model is returned along with the result,
+    /// so it can be used for another stream.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream_transcription(self) -> (crate::Result<Transcription>, Model) {
+        self.finish_with(Stream::finish_stream_transcription)
+    }
+
+    /// Discard this stream without decoding it, and return the [`Model`] it owned.
+    #[inline]
+    #[must_use]
+    pub fn into_model(self) -> Model {
+        let (stream, model) = self.0.into_parts();
+        drop(stream);
+        model.into_inner()
+    }
+
+    /// See [`Stream::debug_assert_liveness`].
+    #[cfg(feature = "strict")]
+    #[inline]
+    pub fn debug_assert_liveness(&self) {
+        self.0.first.debug_assert_liveness();
+    }
+
+    fn finish_with<T>(self, finish: impl FnOnce(Stream<'static>) -> T) -> (T, Model) {
+        let (stream, model) = self.0.into_parts();
+        // the stream is consumed, and its state freed, before the model is handed back
+        let res = finish(stream);
+        (res, model.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FreeInOrder;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Recorder(&'static str, Rc<RefCell<Vec<&'static str>>>);
+
+    impl Drop for Recorder {
+        fn drop(&mut self) {
+            self.1.borrow_mut().push(self.0);
+        }
+    }
+
+    fn pair() -> (
+        FreeInOrder<Recorder, Recorder>,
+        Rc<RefCell<Vec<&'static str>>>,
+    ) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let pair = FreeInOrder::new(
+            Recorder("stream", Rc::clone(&log)),
+            Recorder("model", Rc::clone(&log)),
+        );
+        (pair, log)
+    }
+
+    #[test]
+    fn drops_first_before_second() {
+        let (pair, log) = pair();
+        drop(pair);
+        assert_eq!(*log.borrow(), ["stream", "model"]);
+    }
+
+    #[test]
+    fn drops_first_before_second_while_unwinding() {
+        let (pair, log) = pair();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let _pair = pair;
+            panic!("unwinding");
+        }));
+        assert!(res.is_err());
+        assert_eq!(*log.borrow(), ["stream", "model"]);
+    }
+
+    #[test]
+    fn into_parts_drops_nothing() {
+        let (pair, log) = pair();
+        let (first, second) = pair.into_parts();
+        assert!(log.borrow().is_empty());
+        drop(first);
+        drop(second);
+        assert_eq!(*log.borrow(), ["stream", "model"]);
+    }
+}
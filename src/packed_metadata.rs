@@ -0,0 +1,186 @@
+use crate::segment::{Whitespace, WordSegmenter};
+use crate::transcription::group_words;
+use crate::{Metadata, OwnedCandidateTranscript, OwnedTokenMetadata, TokenMetadata, Word};
+use std::ops::Range;
+
+/// An owned copy of [`Metadata`] packed into a handful of allocations,
+/// from [`Metadata::to_packed`].
+///
+/// [`OwnedMetadata`](crate::OwnedMetadata) allocates a `String` for every token,
+/// which adds up for long recordings. This stores the text of every token in one string
+/// instead, with each token holding its range in it, so converting takes three
+/// allocations however long the transcripts are, and the text of a transcript
+/// can be borrowed rather than built.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackedMetadata {
+    text: String,
+    tokens: Vec<PackedToken>,
+    transcripts: Vec<PackedRanges>,
+}
+
+/// Where a transcript's tokens and text sit in a [`PackedMetadata`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct PackedRanges {
+    tokens: Range<usize>,
+    text: Range<usize>,
+    confidence: f64,
+}
+
+/// A token in a [`PackedMetadata`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackedToken {
+    /// Where the text of this token sits in the text of its transcript, in bytes.
+    pub text: Range<usize>,
+    /// Position of the token in [`TIMESTEP`](crate::TIMESTEP)s of 20ms
+    pub timestep: u32,
+    /// Position of the token in seconds
+    pub start_time: f32,
+}
+
+impl PackedMetadata {
+    pub(crate) fn new(metadata: &Metadata) -> Self {
+        let transcripts = metadata.transcripts();
+        let (num_tokens, len) = transcripts
+            .iter()
+            .flat_map(|transcript| transcript.tokens())
+            .fold((0, 0), |(tokens, len), token| {
+                (tokens + 1, len + token.text().len())
+            });
+
+        let mut packed = Self {
+            text: String::with_capacity(len),
+            tokens: Vec::with_capacity(num_tokens),
+            transcripts: Vec::with_capacity(transcripts.len()),
+        };
+        for transcript in transcripts {
+            let first_token = packed.tokens.len();
+            let start = packed.text.len();
+            for token in transcript.tokens() {
+                let token_start = packed.text.len() - start;
+                packed.text.push_str(&TokenMetadata::text(token));
+                packed.tokens.push(PackedToken {
+                    text: token_start..packed.text.len() - start,
+                    timestep: token.timestep(),
+                    start_time: token.start_time(),
+                });
+            }
+            packed.transcripts.push(PackedRanges {
+                tokens: first_token..packed.tokens.len(),
+                text: start..packed.text.len(),
+                confidence: transcript.confidence(),
+            });
+        }
+        packed
+    }
+
+    /// Return the possible transcriptions, most likely first.
+    #[inline]
+    pub fn transcripts(&self) -> impl ExactSizeIterator<Item = PackedTranscript<'_>> + '_ {
+        self.transcripts.iter().map(move |ranges| self.view(ranges))
+    }
+
+    /// Return the transcription at `index`, where 0 is the most likely.
+    #[inline]
+    #[must_use]
+    pub fn transcript(&self, index: usize) -> Option<PackedTranscript<'_>> {
+        self.transcripts.get(index).map(|ranges| self.view(ranges))
+    }
+
+    /// Size of the transcripts array
+    #[inline]
+    #[must_use]
+    pub fn num_transcripts(&self) -> u32 {
+        self.transcripts.len() as u32
+    }
+
+    fn view(&self, ranges: &PackedRanges) -> PackedTranscript<'_> {
+        PackedTranscript {
+            text: self.text.get(ranges.text.clone()).unwrap_or_default(),
+            tokens: self.tokens.get(ranges.tokens.clone()).unwrap_or_default(),
+            confidence: ranges.confidence,
+        }
+    }
+}
+
+/// A transcript in a [`PackedMetadata`], borrowed from it.
+#[derive(Copy, Clone, Debug)]
+pub struct PackedTranscript<'a> {
+    text: &'a str,
+    tokens: &'a [PackedToken],
+    confidence: f64,
+}
+
+impl<'a> PackedTranscript<'a> {
+    /// Return the text of this transcript: its tokens joined together.
+    #[inline]
+    #[must_use]
+    pub const fn text(&self) -> &'a str {
+        self.text
+    }
+
+    /// Return an array of tokens in this transcript.
+    #[inline]
+    #[must_use]
+    pub const fn tokens(&self) -> &'a [PackedToken] {
+        self.tokens
+    }
+
+    /// Return the text of `token`, one of [`tokens`](PackedTranscript::tokens).
+    #[inline]
+    #[must_use]
+    pub fn token_text(&self, token: &PackedToken) -> &'a str {
+        self.text.get(token.text.clone()).unwrap_or_default()
+    }
+
+    /// Approximated confidence value for this transcript.
+    /// See [`CandidateTranscript::confidence`](crate::CandidateTranscript::confidence).
+    #[inline]
+    #[must_use]
+    pub const fn confidence(&self) -> f64 {
+        self.confidence
+    }
+
+    /// Group the tokens in this transcript into [`Word`](Word)s.
+    ///
+    /// See [`CandidateTranscript::words`](crate::CandidateTranscript::words).
+    #[inline]
+    #[must_use]
+    pub fn words(&self) -> Vec<Word> {
+        self.words_with(&Whitespace)
+    }
+
+    /// Group the tokens in this transcript into [`Word`](Word)s found by `segmenter`.
+    ///
+    /// See [`CandidateTranscript::words_with`](crate::CandidateTranscript::words_with).
+    #[inline]
+    #[must_use]
+    pub fn words_with(&self, segmenter: &(impl WordSegmenter + ?Sized)) -> Vec<Word> {
+        group_words(
+            self.tokens
+                .iter()
+                .map(|t| (self.token_text(t), t.timestep, t.start_time)),
+            segmenter,
+        )
+    }
+
+    /// Convert this into an [`OwnedCandidateTranscript`], with a `String` for every token.
+    #[inline]
+    #[must_use]
+    pub fn to_owned(&self) -> OwnedCandidateTranscript {
+        OwnedCandidateTranscript::new(
+            self.tokens
+                .iter()
+                .map(|t| OwnedTokenMetadata {
+                    text: self.token_text(t).to_string(),
+                    timestep: t.timestep,
+                    start_time: t.start_time,
+                })
+                .collect(),
+            self.confidence,
+        )
+    }
+}
@@ -0,0 +1,686 @@
+use crate::audio::samples_in;
+use crate::shutdown::ShutdownGuard;
+use crate::{Metadata, Model, OwnedStream, Shutdown, Transcription};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// A check that a pooled model still works, run before it is handed out again.
+///
+/// The model transcribes a short buffer of silence, which must finish before the deadline.
+/// Models that fail or take too long are thrown away and replaced,
+/// so a model instance that wedged doesn't stay in the pool forever.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HealthCheck {
+    /// How much silence to transcribe.
+    pub audio: Duration,
+    /// How long transcribing it may take.
+    pub deadline: Duration,
+}
+
+impl Default for HealthCheck {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            audio: Duration::from_millis(100),
+            deadline: Duration::from_secs(5),
+        }
+    }
+}
+
+impl HealthCheck {
+    /// Set [`audio`](HealthCheck::audio).
+    #[inline]
+    #[must_use]
+    pub const fn with_audio(mut self, audio: Duration) -> Self {
+        self.audio = audio;
+        self
+    }
+
+    /// Set [`deadline`](HealthCheck::deadline).
+    #[inline]
+    #[must_use]
+    pub const fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Transcribe the silence with `model`, without enforcing the deadline.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn run(&self, model: &mut Model) -> crate::Result<()> {
+        let silence = vec![0; samples_in(self.audio, model.get_sample_rate() as u32)];
+        model.speech_to_text(&silence).map(drop)
+    }
+
+    /// Run the check on another thread, returning the model if it passed in time.
+    ///
+    /// A model that misses the deadline is left with that thread, and freed if it ever finishes.
+    fn run_with_deadline(self, mut model: Model) -> Option<Model> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let res = self.run(&mut model);
+            let _ = tx.send((res, model));
+        });
+        match rx.recv_timeout(self.deadline) {
+            Ok((Ok(()), model)) => Some(model),
+            _ => None,
+        }
+    }
+}
+
+/// Options for a [`ModelPool`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolOptions {
+    /// Check that models still work before handing them out again.
+    ///
+    /// `None` hands models out without checking them.
+    pub health_check: Option<HealthCheck>,
+    /// The most memory the pool's models may take, in bytes.
+    ///
+    /// The pool refuses to grow past this, going by
+    /// [`model_footprint`](PoolOptions::model_footprint). `None` sets no limit.
+    pub max_memory: Option<u64>,
+    /// How much memory each model takes, in bytes,
+    /// for example from [`estimate_footprint`].
+    pub model_footprint: u64,
+}
+
+impl PoolOptions {
+    /// Set [`health_check`](PoolOptions::health_check).
+    #[inline]
+    #[must_use]
+    pub const fn with_health_check(mut self, health_check: Option<HealthCheck>) -> Self {
+        self.health_check = health_check;
+        self
+    }
+
+    /// Set [`max_memory`](PoolOptions::max_memory).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_memory(mut self, max_memory: Option<u64>) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+
+    /// Set [`model_footprint`](PoolOptions::model_footprint).
+    #[inline]
+    #[must_use]
+    pub const fn with_model_footprint(mut self, model_footprint: u64) -> Self {
+        self.model_footprint = model_footprint;
+        self
+    }
+
+    fn check_memory(&self, size: usize) -> crate::Result<()> {
+        let needed = self.model_footprint.saturating_mul(size as u64);
+        match self.max_memory {
+            Some(limit) if needed > limit => {
+                Err(crate::Error::MemoryLimitExceeded { needed, limit })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Estimate how much memory a model loaded from `model_path` takes, in bytes,
+/// for [`PoolOptions::model_footprint`].
+///
+/// This is the size of the model file, as `libstt` reads the whole model into memory.
+/// Streams and scorers take more memory on top of that.
+///
+/// # Errors
+/// Returns [`Error::Io`](crate::Error::Io) if the file could not be read.
+#[inline]
+pub fn estimate_footprint(model_path: impl AsRef<Path>) -> crate::Result<u64> {
+    Ok(std::fs::metadata(model_path)?.len())
+}
+
+/// A snapshot of how a [`ModelPool`] is doing, from [`ModelPool::stats`],
+/// for dashboards and autoscaling decisions.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolStats {
+    /// How many models the pool holds when it is full,
+    /// as last set with [`resize`](ModelPool::resize).
+    pub size: usize,
+    /// Models waiting in the pool to be handed out.
+    pub available: usize,
+    /// Models handed out and not returned yet.
+    pub in_use: usize,
+    /// Models handed out since the pool was created,
+    /// each of which usually served one request.
+    pub served: u64,
+    /// The mean time [`get`](ModelPool::get) and [`try_get`](ModelPool::try_get) took
+    /// to hand out a model, including waiting for one and checking its health.
+    pub mean_wait: Duration,
+    /// Models that failed their [health check](PoolOptions::health_check),
+    /// plus replacement models the factory failed to load.
+    pub errors: u64,
+}
+
+type Factory = Box<dyn Fn() -> crate::Result<Model> + Send + Sync>;
+
+struct Slots {
+    idle: Vec<Model>,
+    /// Models that exist, idle or handed out.
+    total: usize,
+    size: usize,
+    served: u64,
+    total_wait: Duration,
+    errors: u64,
+    /// Whether the pool was shut down, and hands out no more models.
+    closed: bool,
+}
+
+struct Shared {
+    factory: Factory,
+    options: PoolOptions,
+    slots: Mutex<Slots>,
+    returned: Condvar,
+    /// Registration with the [`Shutdown`] being watched, if any.
+    shutdown: Mutex<Option<ShutdownGuard>>,
+}
+
+impl Shared {
+    fn slots(&self) -> MutexGuard<'_, Slots> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Put a model back in the pool, or retire it if the pool shrank.
+    fn put_back(&self, model: Model) {
+        let mut slots = self.slots();
+        if slots.total > slots.size {
+            slots.total -= 1;
+            drop(slots);
+            drop(model);
+        } else {
+            slots.idle.push(model);
+            drop(slots);
+            self.returned.notify_one();
+        }
+        self.release_if_drained();
+    }
+
+    /// Once the pool is shut down and every model is back, report that it finished shutting down.
+    fn release_if_drained(&self) {
+        let slots = self.slots();
+        let drained = slots.closed && slots.total == slots.idle.len();
+        drop(slots);
+        if drained {
+            let guard = self
+                .shutdown
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take();
+            drop(guard);
+        }
+    }
+
+    /// Load models until the pool is full.
+    fn fill(&self) {
+        loop {
+            let mut slots = self.slots();
+            if slots.total >= slots.size {
+                return;
+            }
+            slots.total += 1;
+            drop(slots);
+
+            match (self.factory)() {
+                Ok(model) => self.put_back(model),
+                Err(_) => {
+                    let mut slots = self.slots();
+                    slots.total -= 1;
+                    slots.errors += 1;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// A fixed number of [`Model`]s, shared between threads,
+/// for services that transcribe several requests at once.
+///
+/// Models are loaded with the factory the pool was created with,
+/// handed out with [`get`](ModelPool::get), and returned when the [`PooledModel`] is dropped.
+/// Cloning a `ModelPool` gives another handle to the same pool.
+#[derive(Clone)]
+pub struct ModelPool {
+    shared: Arc<Shared>,
+}
+
+impl ModelPool {
+    /// Create a new `ModelPool` of `size` models loaded with `factory`,
+    /// with the default [`PoolOptions`].
+    ///
+    /// Every model is loaded up front, so a broken model path is caught straight away.
+    ///
+    /// # Errors
+    /// Passes through any errors from `factory`.
+    #[inline]
+    pub fn new(
+        size: usize,
+        factory: impl Fn() -> crate::Result<Model> + Send + Sync + 'static,
+    ) -> crate::Result<Self> {
+        Self::new_with_options(size, PoolOptions::default(), factory)
+    }
+
+    /// Create a new `ModelPool` of `size` models loaded with `factory`.
+    ///
+    /// See [`new`](ModelPool::new).
+    ///
+    /// # Errors
+    /// Returns [`Error::MemoryLimitExceeded`](crate::Error::MemoryLimitExceeded)
+    /// if `size` models would take more than [`PoolOptions::max_memory`].
+    /// Otherwise, passes through any errors from `factory`.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new_with_options(
+        size: usize,
+        options: PoolOptions,
+        factory: impl Fn() -> crate::Result<Model> + Send + Sync + 'static,
+    ) -> crate::Result<Self> {
+        options.check_memory(size)?;
+        let idle = (0..size)
+            .map(|_| factory())
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            shared: Arc::new(Shared {
+                factory: Box::new(factory),
+                options,
+                slots: Mutex::new(Slots {
+                    idle,
+                    total: size,
+                    size,
+                    served: 0,
+                    total_wait: Duration::ZERO,
+                    errors: 0,
+                    closed: false,
+                }),
+                returned: Condvar::new(),
+                shutdown: Mutex::new(None),
+            }),
+        })
+    }
+
+    /// Shut this pool down when `shutdown` is triggered.
+    ///
+    /// From then on, taking a model out of the pool returns
+    /// [`Error::ShuttingDown`](crate::Error::ShuttingDown), including for threads already
+    /// waiting for one. The pool counts as finished shutting down once every model
+    /// handed out has been returned, so streams in flight can finish.
+    ///
+    /// If `shutdown` was already triggered, the pool shuts down straight away.
+    /// A pool only watches one `Shutdown` at a time.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn watch_shutdown(&self, shutdown: &Shutdown) {
+        // the pool may be gone by the time shutdown is triggered
+        let shared = Arc::downgrade(&self.shared);
+        let guard = shutdown.register(move || {
+            if let Some(shared) = shared.upgrade() {
+                shared.slots().closed = true;
+                shared.returned.notify_all();
+                shared.release_if_drained();
+            }
+        });
+        let previous = self
+            .shared
+            .shutdown
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .replace(guard);
+        drop(previous);
+        // the hook may have run already, before there was a guard to release
+        self.shared.release_if_drained();
+    }
+
+    /// Return the [`PoolOptions`] this pool was created with.
+    #[inline]
+    #[must_use]
+    pub fn options(&self) -> &PoolOptions {
+        &self.shared.options
+    }
+
+    /// Return statistics about the pool as it is now.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn stats(&self) -> PoolStats {
+        let slots = self.shared.slots();
+        let mean_wait = if slots.served == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(slots.total_wait.as_secs_f64() / slots.served as f64)
+        };
+        PoolStats {
+            size: slots.size,
+            available: slots.idle.len(),
+            in_use: slots.total - slots.idle.len(),
+            served: slots.served,
+            mean_wait,
+            errors: slots.errors,
+        }
+    }
+
+    /// Change how many models the pool holds, so it can adapt to load without a restart.
+    ///
+    /// Growing the pool loads the new models on a background thread,
+    /// so this returns straight away, and [`get`](ModelPool::get) hands them out as they load.
+    /// Shrinking it frees idle models straight away,
+    /// and models in use as they are returned, until the pool is down to `size`.
+    ///
+    /// # Errors
+    /// Returns [`Error::MemoryLimitExceeded`](crate::Error::MemoryLimitExceeded)
+    /// if `size` models would take more than [`PoolOptions::max_memory`].
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn resize(&self, size: usize) -> crate::Result<()> {
+        self.shared.options.check_memory(size)?;
+
+        let mut slots = self.shared.slots();
+        slots.size = size;
+        let mut retired = Vec::new();
+        while slots.total > size {
+            match slots.idle.pop() {
+                Some(model) => {
+                    retired.push(model);
+                    slots.total -= 1;
+                }
+                None => break,
+            }
+        }
+        let grow = slots.total < size;
+        drop(slots);
+        drop(retired);
+
+        if grow {
+            let shared = Arc::clone(&self.shared);
+            std::thread::spawn(move || shared.fill());
+        }
+        Ok(())
+    }
+
+    /// Take a model out of the pool and start a stream on it,
+    /// waiting for a model to be returned if they are all in use.
+    ///
+    /// See [`PooledModel::into_stream`].
+    ///
+    /// # Errors
+    /// Returns [`Error::ShuttingDown`](crate::Error::ShuttingDown) if the pool
+    /// [shut down](ModelPool::watch_shutdown).
+    /// Otherwise, passes through any errors from the factory, if a model had to be replaced,
+    /// and from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn stream(&self) -> crate::Result<StreamLease> {
+        self.get()?.into_stream()
+    }
+
+    /// Take a model out of the pool, waiting for one to be returned if they are all in use.
+    ///
+    /// Models that fail the [health check](PoolOptions::health_check) are replaced
+    /// with new ones from the factory.
+    ///
+    /// # Errors
+    /// Returns [`Error::ShuttingDown`](crate::Error::ShuttingDown) if the pool
+    /// [shut down](ModelPool::watch_shutdown).
+    /// Otherwise, passes through any errors from the factory, if a model had to be replaced.
+    #[inline]
+    pub fn get(&self) -> crate::Result<PooledModel> {
+        self.checkout(true).map(|model| {
+            model.unwrap_or_else(|| unreachable!("waiting checkouts always return a model"))
+        })
+    }
+
+    /// Take a model out of the pool if one is free, without waiting.
+    ///
+    /// # Errors
+    /// Returns [`Error::ShuttingDown`](crate::Error::ShuttingDown) if the pool
+    /// [shut down](ModelPool::watch_shutdown).
+    /// Otherwise, passes through any errors from the factory, if a model had to be replaced.
+    #[inline]
+    pub fn try_get(&self) -> crate::Result<Option<PooledModel>> {
+        self.checkout(false)
+    }
+
+    fn checkout(&self, wait: bool) -> crate::Result<Option<PooledModel>> {
+        let started = Instant::now();
+        let mut slots = self.shared.slots();
+        loop {
+            if slots.closed {
+                return Err(crate::Error::ShuttingDown);
+            }
+            if let Some(model) = slots.idle.pop() {
+                // the check can take a while, so don't hold up other threads meanwhile
+                drop(slots);
+                let healthy = match self.shared.options.health_check {
+                    Some(check) => check.run_with_deadline(model),
+                    None => Some(model),
+                };
+                if let Some(model) = healthy {
+                    return Ok(Some(self.lease(model, started)));
+                }
+                slots = self.shared.slots();
+                slots.total -= 1;
+                slots.errors += 1;
+                continue;
+            }
+
+            // replace models that were thrown away
+            if slots.total < slots.size {
+                slots.total += 1;
+                drop(slots);
+                return match (self.shared.factory)() {
+                    Ok(model) => Ok(Some(self.lease(model, started))),
+                    Err(e) => {
+                        let mut slots = self.shared.slots();
+                        slots.total -= 1;
+                        slots.errors += 1;
+                        drop(slots);
+                        self.shared.returned.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+
+            if !wait {
+                return Ok(None);
+            }
+            slots = self
+                .shared
+                .returned
+                .wait(slots)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn lease(&self, model: Model, started: Instant) -> PooledModel {
+        let mut slots = self.shared.slots();
+        slots.served += 1;
+        slots.total_wait += started.elapsed();
+        drop(slots);
+
+        PooledModel {
+            model: Some(model),
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// A model taken out of a [`ModelPool`], which is returned to it when dropped.
+pub struct PooledModel {
+    model: Option<Model>,
+    shared: Arc<Shared>,
+}
+
+impl PooledModel {
+    /// Start a stream on this model, which keeps the model until the stream is finished.
+    ///
+    /// A stream's state belongs to the model it was created from,
+    /// so a stream can't move between pooled models.
+    /// The lease pins the stream to this model for its whole life,
+    /// and returns the model to the pool once the stream is finished or dropped.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    /// The model is returned to the pool if this fails.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn into_stream(mut self) -> crate::Result<StreamLease> {
+        let shared = Arc::clone(&self.shared);
+        match OwnedStream::try_new(self.take()) {
+            Ok(stream) => Ok(StreamLease {
+                stream: Some(stream),
+                shared,
+            }),
+            Err((e, model)) => {
+                shared.put_back(model);
+                Err(e)
+            }
+        }
+    }
+
+    /// Throw this model away instead of returning it to the pool,
+    /// for example after it returned an error that suggests it is broken.
+    ///
+    /// The pool loads a replacement the next time it runs out of free models.
+    #[inline]
+    pub fn discard(mut self) {
+        drop(self.take());
+        let mut slots = self.shared.slots();
+        slots.total -= 1;
+        drop(slots);
+        self.shared.returned.notify_one();
+        self.shared.release_if_drained();
+    }
+
+    fn take(&mut self) -> Model {
+        match self.model.take() {
+            Some(model) => model,
+            None => unreachable!("the model is only taken out once"),
+        }
+    }
+}
+
+impl Deref for PooledModel {
+    type Target = Model;
+
+    #[inline]
+    fn deref(&self) -> &Model {
+        match &self.model {
+            Some(model) => model,
+            None => unreachable!("the model is only taken out once"),
+        }
+    }
+}
+
+impl DerefMut for PooledModel {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Model {
+        match &mut self.model {
+            Some(model) => model,
+            None => unreachable!("the model is only taken out once"),
+        }
+    }
+}
+
+impl Drop for PooledModel {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(model) = self.model.take() {
+            self.shared.put_back(model);
+        }
+    }
+}
+
+/// A stream on a model from a [`ModelPool`], from [`PooledModel::into_stream`].
+///
+/// Dereferences to an [`OwnedStream`] for feeding audio and decoding.
+/// Finishing or dropping the lease returns the model to the pool.
+pub struct StreamLease {
+    stream: Option<OwnedStream>,
+    shared: Arc<Shared>,
+}
+
+impl StreamLease {
+    /// See [`Stream::finish_stream`](crate::Stream::finish_stream).
+    /// The model is returned to the pool.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream(mut self) -> crate::Result<String> {
+        self.finish_with(OwnedStream::finish_stream)
+    }
+
+    /// See [`Stream::finish_stream_with_metadata`](crate::Stream::finish_stream_with_metadata).
+    /// The model is returned to the pool.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream_with_metadata(mut self, num_results: u32) -> crate::Result<Metadata> {
+        self.finish_with(|stream| stream.finish_stream_with_metadata(num_results))
+    }
+
+    /// See [`Stream::finish_stream_transcription`](crate::Stream::finish_stream_transcription).
+    /// The model is returned to the pool.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream_transcription(mut self) -> crate::Result<Transcription> {
+        self.finish_with(OwnedStream::finish_stream_transcription)
+    }
+
+    fn finish_with<T>(
+        &mut self,
+        finish: impl FnOnce(OwnedStream) -> (crate::Result<T>, Model),
+    ) -> crate::Result<T> {
+        let (res, model) = finish(self.take());
+        self.shared.put_back(model);
+        res
+    }
+
+    fn take(&mut self) -> OwnedStream {
+        match self.stream.take() {
+            Some(stream) => stream,
+            None => unreachable!("the stream is only taken out once"),
+        }
+    }
+}
+
+impl Deref for StreamLease {
+    type Target = OwnedStream;
+
+    #[inline]
+    fn deref(&self) -> &OwnedStream {
+        match &self.stream {
+            Some(stream) => stream,
+            None => unreachable!("the stream is only taken out once"),
+        }
+    }
+}
+
+impl DerefMut for StreamLease {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut OwnedStream {
+        match &mut self.stream {
+            Some(stream) => stream,
+            None => unreachable!("the stream is only taken out once"),
+        }
+    }
+}
+
+impl Drop for StreamLease {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            self.shared.put_back(stream.into_model());
+        }
+    }
+}
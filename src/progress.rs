@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+/// How far through a long transcription [`Model::speech_to_text_with_progress`](crate::Model::speech_to_text_with_progress) is.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct Progress {
+    /// How much of the audio has been processed so far.
+    pub processed: Duration,
+    /// How much audio there is in total.
+    pub total: Duration,
+    /// The transcription of the audio processed so far.
+    pub partial_text: String,
+    /// How long processing has taken so far, relative to the audio processed.
+    /// Values below 1.0 are faster than real-time.
+    pub real_time_factor: f64,
+    /// Estimated time until the transcription finishes, based on the real-time factor so far.
+    pub eta: Duration,
+}
+
+impl Progress {
+    /// The fraction of the audio processed so far, between 0.0 and 1.0.
+    #[inline]
+    #[must_use]
+    pub fn fraction(&self) -> f64 {
+        if self.total.is_zero() {
+            1.0
+        } else {
+            self.processed.as_secs_f64() / self.total.as_secs_f64()
+        }
+    }
+}
+
+/// Receives [`Progress`] reports during a long transcription.
+///
+/// This is implemented for all `FnMut(&Progress)` closures.
+pub trait ProgressObserver {
+    /// Called each time another chunk of audio has been processed.
+    fn on_progress(&mut self, progress: &Progress);
+}
+
+impl<F: FnMut(&Progress)> ProgressObserver for F {
+    #[inline]
+    fn on_progress(&mut self, progress: &Progress) {
+        self(progress);
+    }
+}
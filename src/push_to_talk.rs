@@ -0,0 +1,192 @@
+use crate::audio::samples_in;
+use crate::stream::OwnedState;
+use crate::{Model, Transcription};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Tuning knobs for a [`PushToTalk`] session.
+#[derive(Copy, Clone, Debug)]
+pub struct PushToTalkConfig {
+    /// How much audio from before [`start`](PushToTalk::start) was called to include.
+    ///
+    /// People tend to start talking at the same time as (or slightly before) they press the key,
+    /// so without this the start of the first word is often cut off.
+    pub pre_roll: Duration,
+    /// How much silence to feed to the model after [`stop`](PushToTalk::stop) is called.
+    ///
+    /// The model needs some audio after the last word to finish decoding it,
+    /// and people tend to release the key as soon as they finish talking.
+    pub silence_padding: Duration,
+}
+
+impl Default for PushToTalkConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            pre_roll: Duration::from_millis(300),
+            silence_padding: Duration::from_millis(300),
+        }
+    }
+}
+
+struct Session {
+    state: OwnedState,
+    samples_fed: u64,
+    processing_time: Duration,
+}
+
+/// Transcribes audio between a [`start`](PushToTalk::start)
+/// and a [`stop`](PushToTalk::stop), for push-to-talk style voice input.
+///
+/// Audio should be pushed in with [`push_audio`](PushToTalk::push_audio) continuously,
+/// whether or not a session is in progress.
+/// Between sessions, the most recent audio is kept as pre-roll,
+/// and fed to the model as soon as the next session starts.
+pub struct PushToTalk {
+    // declared before the model, so the streaming state is always freed first
+    session: Option<Session>,
+    model: Model,
+    config: PushToTalkConfig,
+    sample_rate: u32,
+    pre_roll: VecDeque<i16>,
+}
+
+// SAFETY: the streaming state is only ever accessed through `&mut self`
+unsafe impl Send for PushToTalk {}
+unsafe impl Sync for PushToTalk {}
+
+impl PushToTalk {
+    /// Create a new `PushToTalk` using the default [`PushToTalkConfig`].
+    #[inline]
+    #[must_use]
+    pub fn new(model: Model) -> Self {
+        Self::with_config(model, PushToTalkConfig::default())
+    }
+
+    /// Create a new `PushToTalk`.
+    #[inline]
+    #[must_use]
+    pub fn with_config(model: Model, config: PushToTalkConfig) -> Self {
+        let sample_rate = model.get_sample_rate() as u32;
+
+        Self {
+            session: None,
+            model,
+            config,
+            sample_rate,
+            pre_roll: VecDeque::new(),
+        }
+    }
+
+    /// Return a reference to the [`Model`] this `PushToTalk` uses.
+    #[inline]
+    #[must_use]
+    pub const fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// Return the [`PushToTalkConfig`] this `PushToTalk` uses.
+    #[inline]
+    #[must_use]
+    pub const fn config(&self) -> &PushToTalkConfig {
+        &self.config
+    }
+
+    /// Whether a session is currently in progress.
+    #[inline]
+    #[must_use]
+    pub const fn is_talking(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Feed audio into this `PushToTalk`.
+    ///
+    /// `audio` should be 16-bit, mono audio at the model's sample rate.
+    /// If a session is in progress, it is fed to the model,
+    /// otherwise it is kept as pre-roll for the next session.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn push_audio(&mut self, audio: &[i16]) {
+        match self.session.as_mut() {
+            Some(session) => Self::feed(&mut self.model, session, audio),
+            None => {
+                let max = samples_in(self.config.pre_roll, self.sample_rate);
+                self.pre_roll.extend(audio);
+                let excess = self.pre_roll.len().saturating_sub(max);
+                self.pre_roll.drain(..excess);
+            }
+        }
+    }
+
+    /// Start a new session, feeding the pre-roll to the model.
+    ///
+    /// Does nothing if a session is already in progress.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn start(&mut self) -> crate::Result<()> {
+        if self.session.is_some() {
+            return Ok(());
+        }
+
+        let mut session = Session {
+            state: OwnedState::new(&mut self.model)?,
+            samples_fed: 0,
+            processing_time: Duration::ZERO,
+        };
+        let (a, b) = self.pre_roll.as_slices();
+        for audio in [a, b] {
+            Self::feed(&mut self.model, &mut session, audio);
+        }
+        self.pre_roll.clear();
+        self.session = Some(session);
+
+        Ok(())
+    }
+
+    /// Stop the session in progress, and return its transcription.
+    ///
+    /// Returns `None` if no session was in progress.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn stop(&mut self) -> crate::Result<Option<Transcription>> {
+        let mut session = match self.session.take() {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+
+        let padding = vec![0; samples_in(self.config.silence_padding, self.sample_rate)];
+        Self::feed(&mut self.model, &mut session, &padding);
+
+        // SAFETY: the state was created from `self.model`
+        let mut stream = unsafe { session.state.into_stream(&mut self.model) };
+        stream.samples_fed = session.samples_fed;
+        stream.processing_time = session.processing_time;
+        stream.finish_stream_transcription().map(Some)
+    }
+
+    /// Abort the session in progress, if any, discarding its audio without decoding it.
+    #[inline]
+    pub fn abort(&mut self) {
+        self.session = None;
+    }
+
+    /// Take this `PushToTalk`, and return the [`Model`] it used.
+    ///
+    /// Any session in progress is discarded.
+    #[inline]
+    #[must_use]
+    pub fn into_model(self) -> Model {
+        self.model
+    }
+
+    fn feed(model: &mut Model, session: &mut Session, audio: &[i16]) {
+        // SAFETY: the state is owned by the session and was created from `model`
+        let mut stream = unsafe { session.state.as_stream(model) };
+        stream.feed_audio(audio);
+        session.samples_fed += stream.samples_fed;
+        session.processing_time += stream.processing_time;
+    }
+}
@@ -0,0 +1,110 @@
+//! Escape hatches to the `libstt` state behind [`Model`]s and [`Stream`]s,
+//! for calling functions only available in the raw bindings in [`coqui_stt_sys`].
+//!
+//! Everything here is `unsafe`, and every function spells out what the caller has to uphold.
+//! The same rules apply throughout:
+//!
+//! * A state has exactly one owner, which frees it: a [`Model`] or [`Stream`],
+//!   or the caller after taking it out of one with [`model_into_state`] or [`stream_into_state`].
+//!   It must be freed once, with `STT_FreeModel` or `STT_FreeStream`, or by handing it back
+//!   to a wrapper with [`model_from_state`] or [`stream_from_state`], never both.
+//! * A streaming state references the model state it was created from,
+//!   which must outlive it.
+//! * `libstt` can't use one state from two threads at once. Borrowed pointers from
+//!   [`model_as_state_ptr`] and [`stream_as_state_ptr`] must not be used while
+//!   a method is being called on the wrapper they were borrowed from.
+//!
+//! Debug builds check what they cheaply can: that pointers handed to this module aren't null,
+//! and that a model isn't used by two streams at once.
+//! The `strict` feature also checks that a [`Stream`] still references its model.
+//!
+//! The methods these mirror, such as [`Model::into_inner`] and [`Stream::from_ptr`],
+//! are kept for existing code, and call through to here.
+
+use crate::{Model, Stream};
+pub use coqui_stt_sys::{ModelState, StreamingState};
+
+/// Take the state out of `model`, leaving the caller to free it.
+///
+/// # Safety
+/// The state must be freed exactly once, with `STT_FreeModel` or by recreating a [`Model`]
+/// with [`model_from_state`]. Streaming states created from it must be freed first.
+#[inline]
+#[must_use]
+pub unsafe fn model_into_state(model: Model) -> *mut ModelState {
+    std::mem::ManuallyDrop::new(model).0
+}
+
+/// Wrap `state` in a [`Model`], which frees it when dropped.
+///
+/// # Safety
+/// `state` must be a valid model state, created by `STT_CreateModel`
+/// or `STT_CreateModelFromBuffer` and not yet freed, and nothing else may free it.
+///
+/// # Panics
+/// In debug builds, panics if `state` is null.
+#[inline]
+#[must_use]
+pub const unsafe fn model_from_state(state: *mut ModelState) -> Model {
+    debug_assert!(!state.is_null(), "coqui-stt: null model state");
+    Model::from_state(state)
+}
+
+/// Borrow the state of `model`, without giving up ownership of it.
+///
+/// # Safety
+/// The pointer must not be freed, or used after `model` is dropped.
+/// It must not be used while another method is being called on `model`,
+/// or on a [`Stream`] created from it.
+#[inline]
+#[must_use]
+pub unsafe fn model_as_state_ptr(model: &mut Model) -> *mut ModelState {
+    model.0
+}
+
+/// Take the state out of `stream`, leaving the caller to free it.
+///
+/// Audio still buffered in the stream's [`Preprocessor`](crate::audio::Preprocessor)
+/// is dropped with it, without being fed to the state.
+///
+/// # Safety
+/// The state must be freed exactly once, with `STT_FreeStream` or by recreating a [`Stream`]
+/// with [`stream_from_state`], and before the [`Model`] the stream was created from.
+#[inline]
+#[must_use]
+pub unsafe fn stream_into_state(mut stream: Stream<'_>) -> *mut StreamingState {
+    stream.already_freed = true;
+    stream.state
+}
+
+/// Borrow the state of `stream`, without giving up ownership of it.
+///
+/// This is useful to call a function only available in the raw bindings mid-stream,
+/// then carry on using the `Stream`.
+///
+/// # Safety
+/// The pointer must not be freed, or used after `stream` is dropped or finished.
+/// It must not be used while another method is being called on `stream`.
+///
+/// Audio still buffered in the stream's [`Preprocessor`](crate::audio::Preprocessor)
+/// has not been fed to the state yet.
+#[inline]
+#[must_use]
+pub unsafe fn stream_as_state_ptr(stream: &mut Stream<'_>) -> *mut StreamingState {
+    stream.state
+}
+
+/// Wrap `state` in a [`Stream`] on `model`, which frees it when dropped or finished.
+///
+/// # Safety
+/// `state` must be a valid streaming state, created by `STT_CreateStream` from the state
+/// of `model` and not yet freed, and nothing else may free it.
+///
+/// # Panics
+/// In debug builds, panics if `state` is null.
+#[inline]
+#[must_use]
+pub unsafe fn stream_from_state(model: &mut Model, state: *mut StreamingState) -> Stream<'_> {
+    debug_assert!(!state.is_null(), "coqui-stt: null streaming state");
+    Stream::from_parts(model, state, false)
+}
@@ -0,0 +1,368 @@
+//! Recording the requests an [`SttService`](crate::SttService) transcribes,
+//! and replaying them against another model, for regression testing model upgrades.
+//!
+//! A [`Recorder`] writes a line of JSON to `requests.jsonl` in its directory for every request,
+//! with a hash of its audio, the options it was submitted with, its transcript and timings,
+//! and the audio itself to a WAV file named after the hash.
+//! [`replay`] transcribes the recorded audio again, such as with a new model or scorer,
+//! and the [`ReplayReport`] says how much the transcripts changed.
+//!
+//! Recorded traffic rarely comes with the right answer, so by default the report measures
+//! how far the new transcripts are from the recorded ones, as a word error rate.
+//! Filling in [`reference`](Recording::reference) for some recordings,
+//! by editing `requests.jsonl`, also gives the change in word error rate on them.
+
+use crate::audio::AudioBuffer;
+use crate::wer::{rate, word_errors};
+use crate::{Model, Priority, Transcription};
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The file in a recording directory with a line for each request.
+const LOG_FILE: &str = "requests.jsonl";
+
+/// One recorded request, as a line of `requests.jsonl`.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    /// A hash of the audio, which is stored in `<audio_hash>.wav` next to `requests.jsonl`.
+    pub audio_hash: String,
+    /// The [client](crate::SubmitOptions::client) that submitted the request.
+    pub client: Option<String>,
+    /// The [priority](crate::SubmitOptions::priority) it was submitted with.
+    pub priority: Priority,
+    /// How long the request waited in the queue before being transcribed.
+    pub waited: Duration,
+    /// What the audio was transcribed as.
+    pub transcript: String,
+    /// The confidence of the transcript.
+    pub confidence: f64,
+    /// How long the audio lasts.
+    pub audio_duration: Duration,
+    /// How long transcribing the audio took.
+    pub processing_time: Duration,
+    /// What was actually said, if someone has filled it in.
+    /// Never set by the [`Recorder`].
+    #[serde(default)]
+    pub reference: Option<String>,
+}
+
+impl Recording {
+    pub(crate) fn new(
+        client: Option<String>,
+        priority: Priority,
+        waited: Duration,
+        transcription: &Transcription,
+    ) -> Self {
+        Self {
+            audio_hash: String::new(),
+            client,
+            priority,
+            waited,
+            transcript: transcription.text.clone(),
+            confidence: transcription.confidence,
+            audio_duration: transcription.audio_duration,
+            processing_time: transcription.processing_time,
+            reference: None,
+        }
+    }
+}
+
+/// Writes [`Recording`]s of requests, and their audio, to a directory.
+///
+/// Give one to an [`SttService`](crate::SttService) with
+/// [`SttService::set_recorder`](crate::SttService::set_recorder) to record every request
+/// it transcribes, or call [`record`](Recorder::record) directly.
+/// Recordings are appended, so a directory can be recorded into across restarts,
+/// and audio that was already recorded isn't written again.
+pub struct Recorder {
+    dir: PathBuf,
+    log: BufWriter<File>,
+}
+
+impl std::fmt::Debug for Recorder {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recorder").field("dir", &self.dir).finish()
+    }
+}
+
+impl Recorder {
+    /// Create a new `Recorder`, which writes to `dir`, creating it if needed.
+    ///
+    /// # Errors
+    /// Returns an error if the directory or `requests.jsonl` couldn't be created.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn create(dir: impl Into<PathBuf>) -> crate::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(LOG_FILE))?;
+        Ok(Self {
+            dir,
+            log: BufWriter::new(log),
+        })
+    }
+
+    /// The directory this writes to.
+    #[inline]
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Record `audio`, 16-bit mono audio at `sample_rate` Hz, having been transcribed
+    /// as `transcription`, returning the recording that was written.
+    ///
+    /// # Errors
+    /// Returns an error if the audio or the recording couldn't be written.
+    #[inline]
+    pub fn record(
+        &mut self,
+        audio: &[i16],
+        sample_rate: u32,
+        transcription: &Transcription,
+    ) -> crate::Result<Recording> {
+        let mut recording =
+            Recording::new(None, Priority::default(), Duration::ZERO, transcription);
+        self.write(audio, sample_rate, &mut recording)?;
+        Ok(recording)
+    }
+
+    /// Write `audio` and `recording`, filling in its hash.
+    pub(crate) fn write(
+        &mut self,
+        audio: &[i16],
+        sample_rate: u32,
+        recording: &mut Recording,
+    ) -> crate::Result<()> {
+        recording.audio_hash = format!("{:016x}", hash(audio, sample_rate));
+        let path = self.dir.join(format!("{}.wav", recording.audio_hash));
+        if !path.exists() {
+            // write to a temporary file first, so a crash never leaves half a recording behind
+            let partial = path.with_extension("wav.partial");
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            };
+            let mut writer = WavWriter::create(&partial, spec)?;
+            for &sample in audio {
+                writer.write_sample(sample)?;
+            }
+            writer.finalize()?;
+            std::fs::rename(&partial, &path)?;
+        }
+
+        serde_json::to_writer(&mut self.log, recording)?;
+        writeln!(self.log)?;
+        Ok(self.log.flush()?)
+    }
+}
+
+/// A hash of `audio` that stays the same across Rust versions and platforms,
+/// unlike `std`'s, so recordings can be compared between builds. This is FNV-1a.
+fn hash(audio: &[i16], sample_rate: u32) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let bytes = sample_rate
+        .to_le_bytes()
+        .into_iter()
+        .chain(audio.iter().flat_map(|sample| sample.to_le_bytes()));
+    for byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// A recording transcribed again by [`replay`].
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct ReplayResult {
+    /// The recording that was replayed.
+    pub recording: Recording,
+    /// What its audio was transcribed as this time.
+    pub transcription: crate::Result<Transcription>,
+}
+
+impl ReplayResult {
+    /// Whether the transcript is different from the recorded one, or couldn't be made.
+    #[inline]
+    #[must_use]
+    pub fn changed(&self) -> bool {
+        self.transcription.as_ref().map_or(true, |transcription| {
+            transcription.text != self.recording.transcript
+        })
+    }
+
+    /// The word error rate of the new transcript, taking the recorded one as the reference,
+    /// or `None` if transcribing failed.
+    #[inline]
+    #[must_use]
+    pub fn divergence(&self) -> Option<f64> {
+        let transcription = self.transcription.as_ref().ok()?;
+        Some(word_error_rate(
+            &self.recording.transcript,
+            &transcription.text,
+        ))
+    }
+
+    /// How much the word error rate changed against the recording's
+    /// [`reference`](Recording::reference): negative if the new transcript is better.
+    /// `None` if there is no reference, or transcribing failed.
+    #[inline]
+    #[must_use]
+    pub fn wer_delta(&self) -> Option<f64> {
+        let reference = self.recording.reference.as_deref()?;
+        let transcription = self.transcription.as_ref().ok()?;
+        Some(
+            word_error_rate(reference, &transcription.text)
+                - word_error_rate(reference, &self.recording.transcript),
+        )
+    }
+}
+
+/// The outcome of [`replay`]ing a recording directory.
+#[non_exhaustive]
+#[derive(Clone, Debug, Default)]
+pub struct ReplayReport {
+    /// Each recording, in the order it was recorded, with its new transcription.
+    pub results: Vec<ReplayResult>,
+}
+
+impl ReplayReport {
+    /// The recordings whose transcripts changed, or couldn't be made.
+    #[inline]
+    pub fn changed(&self) -> impl Iterator<Item = &ReplayResult> {
+        self.results.iter().filter(|result| result.changed())
+    }
+
+    /// How many recordings couldn't be transcribed again.
+    #[inline]
+    #[must_use]
+    pub fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.transcription.is_err())
+            .count()
+    }
+
+    /// The word error rate of all the new transcripts together, taking the recorded ones
+    /// as the reference. Recordings that couldn't be transcribed are left out.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn divergence(&self) -> f64 {
+        let (errors, words) = self
+            .results
+            .iter()
+            .filter_map(|result| {
+                let transcription = result.transcription.as_ref().ok()?;
+                Some(word_errors(
+                    &result.recording.transcript,
+                    &transcription.text,
+                ))
+            })
+            .fold((0, 0), |(errors, words), (e, w)| (errors + e, words + w));
+        rate(errors, words)
+    }
+
+    /// How much the word error rate of all the recordings with a
+    /// [`reference`](Recording::reference) changed: negative if the new transcripts are better.
+    /// `None` if none have a reference that could be transcribed again.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn wer_delta(&self) -> Option<f64> {
+        let mut scored = false;
+        let (mut before, mut after, mut words) = (0, 0, 0);
+        for result in &self.results {
+            let (Some(reference), Ok(transcription)) = (
+                result.recording.reference.as_deref(),
+                result.transcription.as_ref(),
+            ) else {
+                continue;
+            };
+            let (errors, n) = word_errors(reference, &result.recording.transcript);
+            before += errors;
+            after += word_errors(reference, &transcription.text).0;
+            words += n;
+            scored = true;
+        }
+        scored.then(|| rate(after, words) - rate(before, words))
+    }
+}
+
+/// Transcribe every recording in `dir`, written by a [`Recorder`], again with `model`.
+///
+/// Audio recorded at a different sample rate from the model's is resampled.
+/// A recording failing to transcribe doesn't stop the replay; it is reported
+/// in its [`ReplayResult`].
+///
+/// # Errors
+/// Returns an error if `requests.jsonl` or a recording's audio couldn't be read.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn replay(dir: impl AsRef<Path>, model: &mut Model) -> crate::Result<ReplayReport> {
+    let dir = dir.as_ref();
+    let log = BufReader::new(File::open(dir.join(LOG_FILE))?);
+    let mut report = ReplayReport::default();
+    for line in log.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let recording: Recording = serde_json::from_str(&line)
+            .map_err(|e| crate::Error::Json(format!("invalid recording: {}", e)))?;
+
+        let reader = WavReader::open(dir.join(format!("{}.wav", recording.audio_hash)))?;
+        let spec = reader.spec();
+        let samples = reader
+            .into_samples::<i16>()
+            .collect::<Result<Vec<i16>, _>>()?;
+        let audio = AudioBuffer::new(samples, spec.channels, spec.sample_rate);
+        let transcription = model.speech_to_text_best(audio);
+        report.results.push(ReplayResult {
+            recording,
+            transcription,
+        });
+    }
+    Ok(report)
+}
+
+/// The word error rate of `hypothesis` against `reference`: how many words had to be
+/// substituted, deleted or inserted to turn one into the other,
+/// relative to the number of words in `reference`.
+///
+/// Words are separated by whitespace and compared exactly.
+/// This is 0 if both are empty, and 1 for each word of `hypothesis` if only `reference` is.
+///
+/// ```
+/// use coqui_stt::recording::word_error_rate;
+///
+/// assert_eq!(word_error_rate("hello world", "hello world"), 0.0);
+/// assert_eq!(word_error_rate("hello world", "hello there world"), 0.5);
+/// ```
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn word_error_rate(reference: &str, hypothesis: &str) -> f64 {
+    let (errors, words) = word_errors(reference, hypothesis);
+    rate(errors, words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::hash;
+
+    #[test]
+    fn hashes_are_stable() {
+        // changing these would orphan every recording made so far
+        assert_eq!(hash(&[], 0), 0x4d25_767f_9dce_13f5);
+        assert_ne!(hash(&[1, 2], 16000), hash(&[2, 1], 16000));
+        assert_ne!(hash(&[1, 2], 16000), hash(&[1, 2], 8000));
+    }
+}
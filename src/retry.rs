@@ -0,0 +1,225 @@
+use crate::audio::IntoModelAudio;
+use crate::{Metadata, Model};
+use std::time::Duration;
+
+/// How a [`RetryingModel`] retries inference that failed with a
+/// [transient](crate::Error::is_transient) error.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a call is retried before its error is returned.
+    ///
+    /// Defaults to 2.
+    pub max_retries: u32,
+    /// How long to wait before the first retry. The wait doubles after each retry.
+    ///
+    /// Defaults to 50ms.
+    pub backoff: Duration,
+    /// After this many transient failures in a row, across calls,
+    /// throw the model away and load a new one from the factory, or never if `None`.
+    ///
+    /// Defaults to 3.
+    pub reload_after: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff: Duration::from_millis(50),
+            reload_after: Some(3),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Set [`max_retries`](RetryPolicy::max_retries).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set [`backoff`](RetryPolicy::backoff).
+    #[inline]
+    #[must_use]
+    pub const fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set [`reload_after`](RetryPolicy::reload_after).
+    #[inline]
+    #[must_use]
+    pub const fn with_reload_after(mut self, reload_after: Option<u32>) -> Self {
+        self.reload_after = reload_after;
+        self
+    }
+}
+
+/// A [`Model`] that retries inference that fails with a
+/// [transient](crate::Error::is_transient) error, and reloads itself if it keeps failing.
+///
+/// TensorFlow Lite sessions occasionally wedge in long-running services,
+/// failing every inference until the model is loaded again.
+/// The model is loaded with a factory function, which should also set up the scorer
+/// and any hot words, so a reloaded model behaves like the one it replaces.
+///
+/// With the `tracing` feature, retries and reloads are logged as warnings.
+pub struct RetryingModel {
+    model: Model,
+    factory: Box<dyn Fn() -> crate::Result<Model> + Send + Sync>,
+    policy: RetryPolicy,
+    failures: u32,
+}
+
+impl RetryingModel {
+    /// Create a new `RetryingModel` with the default [`RetryPolicy`],
+    /// loading its model with `factory`.
+    ///
+    /// # Errors
+    /// Passes through any errors from `factory`.
+    #[inline]
+    pub fn new(
+        factory: impl Fn() -> crate::Result<Model> + Send + Sync + 'static,
+    ) -> crate::Result<Self> {
+        Self::new_with_policy(factory, RetryPolicy::default())
+    }
+
+    /// Create a new `RetryingModel` with the given [`RetryPolicy`],
+    /// loading its model with `factory`.
+    ///
+    /// # Errors
+    /// Passes through any errors from `factory`.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new_with_policy(
+        factory: impl Fn() -> crate::Result<Model> + Send + Sync + 'static,
+        policy: RetryPolicy,
+    ) -> crate::Result<Self> {
+        Ok(Self {
+            model: factory()?,
+            factory: Box::new(factory),
+            policy,
+            failures: 0,
+        })
+    }
+
+    /// Return the model currently in use.
+    #[inline]
+    #[must_use]
+    pub const fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// Return the model currently in use, mutably.
+    ///
+    /// Changes made to it, such as added hot words, are lost if it is reloaded.
+    #[inline]
+    #[must_use]
+    pub fn model_mut(&mut self) -> &mut Model {
+        &mut self.model
+    }
+
+    /// Return the model currently in use, consuming this `RetryingModel`.
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> Model {
+        self.model
+    }
+
+    /// Retrying version of [`Model::speech_to_text`].
+    ///
+    /// # Errors
+    /// Passes through the last error from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn speech_to_text<'a>(&mut self, buffer: impl IntoModelAudio<'a>) -> crate::Result<String> {
+        let buffer = buffer.into_model_audio(self.model.get_sample_rate() as u32)?;
+        self.retry(|model| model.speech_to_text(&*buffer))
+    }
+
+    /// Retrying version of [`Model::speech_to_text_with_metadata`].
+    ///
+    /// # Errors
+    /// Passes through the last error from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn speech_to_text_with_metadata<'a>(
+        &mut self,
+        buffer: impl IntoModelAudio<'a>,
+        num_results: u32,
+    ) -> crate::Result<Metadata> {
+        // converted once, rather than on every attempt
+        let buffer = buffer.into_model_audio(self.model.get_sample_rate() as u32)?;
+        self.retry(|model| model.speech_to_text_with_metadata(&*buffer, num_results))
+    }
+
+    /// Run `f` with the model, retrying it according to the [`RetryPolicy`].
+    ///
+    /// `f` may run several times, possibly with a different model each time,
+    /// so it shouldn't have side effects beyond the model.
+    ///
+    /// # Errors
+    /// Passes through the last error from `f`.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn retry<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Model) -> crate::Result<T>,
+    ) -> crate::Result<T> {
+        let mut backoff = self.policy.backoff;
+        let mut retries = 0;
+        loop {
+            let e = match f(&mut self.model) {
+                Ok(res) => {
+                    self.failures = 0;
+                    return Ok(res);
+                }
+                Err(e) if e.is_transient() => e,
+                Err(e) => return Err(e),
+            };
+
+            self.failures = self.failures.saturating_add(1);
+            if self
+                .policy
+                .reload_after
+                .is_some_and(|after| self.failures >= after)
+            {
+                self.reload();
+            }
+            if retries == self.policy.max_retries {
+                return Err(e);
+            }
+
+            retries += 1;
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                retry = retries,
+                "inference failed, retrying"
+            );
+            std::thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2);
+        }
+    }
+
+    /// Replace the model with a new one from the factory, keeping the old one if that fails.
+    fn reload(&mut self) {
+        match (self.factory)() {
+            Ok(model) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(failures = self.failures, "reloaded a failing model");
+                self.model = model;
+                self.failures = 0;
+            }
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    "could not reload a failing model"
+                );
+                #[cfg(not(feature = "tracing"))]
+                let _ = e;
+            }
+        }
+    }
+}
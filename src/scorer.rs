@@ -0,0 +1,346 @@
+use std::collections::HashSet;
+use std::io::{BufRead, Read};
+use std::path::Path;
+
+/// What the start of every scorer holds: a KenLM language model in its binary format.
+const KENLM_MAGIC: &[u8] = b"mmap lm http://kheafield.com/code";
+/// The magic number of the vocabulary trie, `'TRIE'` as a little-endian `int32_t`.
+const TRIE_MAGIC: &[u8] = b"EIRT";
+/// The version of the trie that `libstt` loads.
+const TRIE_VERSION: i32 = 6;
+/// The trie header: magic, version, UTF-8 mode, then the default alpha and beta.
+const TRIE_HEADER_LEN: usize = 4 + 4 + 1 + 8 + 8;
+/// The start of the OpenFST header of the trie, which directly follows the trie header:
+/// the FST magic number, then the length-prefixed type of the FST.
+const FST_SIGNATURE: &[u8] = b"\xd6\xfd\xb2\x7e\x05\x00\x00\x00const";
+
+/// The vocabulary of a scorer, for checking which words it knows.
+///
+/// `libstt` doesn't expose the vocabulary of a loaded scorer, and the `.scorer` package
+/// stores it as a compiled trie, so the vocabulary is read from the files the scorer
+/// was built from instead: the KenLM ARPA language model, or the word list passed to
+/// `generate_scorer_package`.
+///
+/// Words the scorer doesn't know are all but impossible to transcribe with it,
+/// even as hot words, so [`coverage`](Scorer::coverage) is the first thing to check
+/// when domain terms don't come out right.
+#[derive(Clone, Debug, Default)]
+pub struct Scorer {
+    vocabulary: HashSet<String>,
+}
+
+impl Scorer {
+    /// Read the vocabulary from the unigrams of a KenLM language model in ARPA format.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidScorer`](crate::Error::InvalidScorer)
+    /// if there is no `\1-grams:` section, and [`Error::Io`](crate::Error::Io)
+    /// if `reader` could not be read.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn from_arpa(reader: impl BufRead) -> crate::Result<Self> {
+        let mut vocabulary = HashSet::new();
+        let mut in_unigrams = false;
+        let mut found = false;
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.starts_with('\\') {
+                in_unigrams = line == "\\1-grams:";
+                found |= in_unigrams;
+                continue;
+            }
+            if !in_unigrams {
+                continue;
+            }
+            // each unigram is `log10 probability, word, [backoff weight]`
+            if let Some(word) = line.split_whitespace().nth(1) {
+                if !matches!(word, "<s>" | "</s>" | "<unk>") {
+                    vocabulary.insert(word.to_string());
+                }
+            }
+        }
+
+        if !found {
+            return Err(crate::Error::InvalidScorer);
+        }
+        Ok(Self { vocabulary })
+    }
+
+    /// Read the vocabulary from a list of words, separated by whitespace.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if `reader` could not be read.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn from_vocabulary(reader: impl BufRead) -> crate::Result<Self> {
+        let mut vocabulary = HashSet::new();
+        for line in reader.lines() {
+            vocabulary.extend(line?.split_whitespace().map(ToString::to_string));
+        }
+        Ok(Self { vocabulary })
+    }
+
+    /// Check that the scorer package at `path` is one `libstt` can load,
+    /// without loading it or a model.
+    ///
+    /// This reads the file through once, looking for the vocabulary trie after the
+    /// language model, and checks the trie's header, so deployment tooling can catch
+    /// a truncated or mismatched scorer before it ships.
+    /// It doesn't check the language model or trie themselves.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if the file could not be read,
+    /// [`Error::ScorerInvalidHeader`](crate::Error::ScorerInvalidHeader) if it doesn't start
+    /// with a KenLM language model, [`Error::ScorerNoTrie`](crate::Error::ScorerNoTrie)
+    /// if there is no trie after it, [`Error::ScorerInvalidTrie`](crate::Error::ScorerInvalidTrie)
+    /// if the trie's header is invalid, and
+    /// [`Error::ScorerVersionMismatch`](crate::Error::ScorerVersionMismatch)
+    /// if the trie is from a version of `libstt` that can't be loaded.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn validate(path: impl AsRef<Path>) -> crate::Result<ScorerInfo> {
+        let file = std::fs::File::open(path)?;
+        let len = file.metadata()?.len();
+        let (trie_offset, header) = find_trie(file, 1 << 20)?;
+        if &header[..4] != TRIE_MAGIC {
+            return Err(crate::Error::ScorerInvalidTrie);
+        }
+        if i32::from_le_bytes(array(&header[4..8])) != TRIE_VERSION {
+            return Err(crate::Error::ScorerVersionMismatch);
+        }
+        Ok(ScorerInfo {
+            len,
+            trie_offset,
+            utf8: header[8] != 0,
+            alpha: f64::from_le_bytes(array(&header[9..17])),
+            beta: f64::from_le_bytes(array(&header[17..25])),
+        })
+    }
+
+    /// The number of words in the vocabulary.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.vocabulary.len()
+    }
+
+    /// Whether the vocabulary is empty.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.vocabulary.is_empty()
+    }
+
+    /// Whether `word` is in the vocabulary.
+    ///
+    /// Words are matched exactly. Scorers are usually built from lowercase text,
+    /// so lowercase words before checking them.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, word: &str) -> bool {
+        self.vocabulary.contains(word)
+    }
+
+    /// Check which of `words` are in the vocabulary.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn coverage(&self, words: &[&str]) -> CoverageReport {
+        let (in_vocabulary, out_of_vocabulary) = words
+            .iter()
+            .map(ToString::to_string)
+            .partition(|word| self.contains(word));
+        CoverageReport {
+            in_vocabulary,
+            out_of_vocabulary,
+        }
+    }
+}
+
+/// What [`Scorer::validate`] found in a scorer package.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScorerInfo {
+    /// The size of the package, in bytes.
+    pub len: u64,
+    /// Where the vocabulary trie starts, which is also the size of the language model.
+    pub trie_offset: u64,
+    /// Whether the scorer works on UTF-8 bytes, rather than the characters of an alphabet.
+    pub utf8: bool,
+    /// The default language model weight, used until
+    /// [`set_scorer_alpha_beta`](crate::Model::set_scorer_alpha_beta) is called.
+    pub alpha: f64,
+    /// The default word insertion weight, used until
+    /// [`set_scorer_alpha_beta`](crate::Model::set_scorer_alpha_beta) is called.
+    pub beta: f64,
+}
+
+/// `bytes` as an array, which they always fit exactly.
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    bytes.try_into().unwrap_or([0; N])
+}
+
+/// Find the vocabulary trie in a scorer package read from `reader`, `chunk` bytes at a time,
+/// returning its offset and header.
+fn find_trie(mut reader: impl Read, chunk: usize) -> crate::Result<(u64, Vec<u8>)> {
+    // enough of the last chunk to hold a trie header, and a signature it cut off
+    let carry = TRIE_HEADER_LEN + FST_SIGNATURE.len() - 1;
+    let mut buffer = Vec::with_capacity(carry + chunk);
+    // the offset in the file of the start of `buffer`
+    let mut offset = 0_u64;
+    let mut started = false;
+    loop {
+        let kept = buffer.len();
+        buffer.resize(kept + chunk, 0);
+        let read = reader.read(&mut buffer[kept..])?;
+        buffer.truncate(kept + read);
+        if !started && (buffer.len() >= KENLM_MAGIC.len() || read == 0) {
+            if !buffer.starts_with(KENLM_MAGIC) {
+                return Err(crate::Error::ScorerInvalidHeader);
+            }
+            started = true;
+        }
+        if started {
+            let found = buffer
+                .windows(FST_SIGNATURE.len())
+                .enumerate()
+                .skip(TRIE_HEADER_LEN)
+                .find(|(_, window)| *window == FST_SIGNATURE);
+            if let Some((at, _)) = found {
+                let start = at - TRIE_HEADER_LEN;
+                return Ok((offset + start as u64, buffer[start..at].to_vec()));
+            }
+        }
+        if read == 0 {
+            return Err(crate::Error::ScorerNoTrie);
+        }
+        if started && buffer.len() > carry {
+            let dropped = buffer.len() - carry;
+            buffer.drain(..dropped);
+            offset += dropped as u64;
+        }
+    }
+}
+
+/// Which words a [`Scorer`] knows, from [`Scorer::coverage`].
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoverageReport {
+    /// Words in the vocabulary, in the order they were checked.
+    pub in_vocabulary: Vec<String>,
+    /// Words not in the vocabulary, in the order they were checked.
+    pub out_of_vocabulary: Vec<String>,
+}
+
+impl CoverageReport {
+    /// The fraction of words checked that are in the vocabulary,
+    /// or 1 if no words were checked.
+    #[inline]
+    #[must_use]
+    pub fn coverage(&self) -> f64 {
+        let total = self.in_vocabulary.len() + self.out_of_vocabulary.len();
+        if total == 0 {
+            return 1.0;
+        }
+        self.in_vocabulary.len() as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_trie, Scorer, FST_SIGNATURE, KENLM_MAGIC};
+    use crate::Error;
+
+    const ARPA: &str = "\\data\\
+ngram 1=5
+ngram 2=1
+
+\\1-grams:
+-1.0\t<s>\t-0.3
+-1.0\t</s>
+-2.0\t<unk>
+-1.5\thello\t-0.2
+-1.7\tworld
+
+\\2-grams:
+-0.5\thello world
+
+\\end\\
+";
+
+    #[test]
+    fn reads_arpa_unigrams() {
+        let scorer = Scorer::from_arpa(ARPA.as_bytes()).unwrap();
+        assert_eq!(scorer.len(), 2);
+
+        let report = scorer.coverage(&["hello", "kubernetes", "world"]);
+        assert_eq!(report.in_vocabulary, ["hello", "world"]);
+        assert_eq!(report.out_of_vocabulary, ["kubernetes"]);
+        assert!((report.coverage() - 2.0 / 3.0).abs() < 1e-9);
+
+        assert!(Scorer::from_arpa("hello world".as_bytes()).is_err());
+    }
+
+    /// A scorer package with a trie of `version`, `magic` and default alpha and beta.
+    fn package(magic: &[u8], version: i32) -> Vec<u8> {
+        let mut package = KENLM_MAGIC.to_vec();
+        package.extend_from_slice(b" format version 5\n\0");
+        package.resize(100, 7);
+        package.extend_from_slice(magic);
+        package.extend_from_slice(&version.to_le_bytes());
+        package.push(0);
+        package.extend_from_slice(&0.9_f64.to_le_bytes());
+        package.extend_from_slice(&1.2_f64.to_le_bytes());
+        package.extend_from_slice(FST_SIGNATURE);
+        package.extend_from_slice(&[0; 64]);
+        package
+    }
+
+    #[test]
+    fn validates_scorer_packages() {
+        let dir = std::env::temp_dir().join(format!("coqui-stt-scorer-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kenlm.scorer");
+        let write = |contents: &[u8]| std::fs::write(&path, contents).unwrap();
+
+        write(&package(b"EIRT", 6));
+        let info = Scorer::validate(&path).unwrap();
+        assert_eq!((info.len, info.trie_offset, info.utf8), (202, 100, false));
+        assert_eq!((info.alpha, info.beta), (0.9, 1.2));
+
+        write(&package(b"EIRT", 5));
+        assert!(matches!(
+            Scorer::validate(&path),
+            Err(Error::ScorerVersionMismatch)
+        ));
+        write(&package(b"TRIE", 6));
+        assert!(matches!(
+            Scorer::validate(&path),
+            Err(Error::ScorerInvalidTrie)
+        ));
+        write(&package(b"EIRT", 6)[..130]);
+        assert!(matches!(Scorer::validate(&path), Err(Error::ScorerNoTrie)));
+        write(b"hello world");
+        assert!(matches!(
+            Scorer::validate(&path),
+            Err(Error::ScorerInvalidHeader)
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn finds_tries_across_chunks() {
+        let package = package(b"EIRT", 6);
+        for chunk in [1, 7, 30, 125, 1000] {
+            let (offset, header) = find_trie(&package[..], chunk).unwrap();
+            assert_eq!((offset, &header[..4]), (100, &b"EIRT"[..]));
+        }
+    }
+
+    #[test]
+    fn reads_word_lists() {
+        let scorer = Scorer::from_vocabulary("hello world\nfake  speech\n".as_bytes()).unwrap();
+        assert_eq!(scorer.len(), 4);
+        assert!(scorer.contains("speech"));
+    }
+}
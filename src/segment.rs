@@ -0,0 +1,104 @@
+//! Strategies for grouping the tokens of a transcript into words.
+//!
+//! [`CandidateTranscript::words`](crate::CandidateTranscript::words) splits on whitespace,
+//! which works for languages that put spaces between words.
+//! Models for languages that don't, such as Chinese or Japanese, output tokens without spaces,
+//! so [`CandidateTranscript::words_with`](crate::CandidateTranscript::words_with)
+//! takes a [`WordSegmenter`] instead: `UnicodeWords` with the `unicode-segmentation` feature,
+//! or any function from text to word ranges.
+
+use std::ops::Range;
+
+/// Splits the text of a transcript into words.
+///
+/// Implemented for closures taking the text and returning the byte ranges of its words,
+/// for bringing your own segmentation, such as a dictionary-based one.
+pub trait WordSegmenter {
+    /// Return the byte ranges of the words in `text`, in order.
+    ///
+    /// Ranges that are empty, or don't fall on character boundaries of `text`, are ignored.
+    fn split(&self, text: &str) -> Vec<Range<usize>>;
+}
+
+impl<F: Fn(&str) -> Vec<Range<usize>>> WordSegmenter for F {
+    #[inline]
+    fn split(&self, text: &str) -> Vec<Range<usize>> {
+        self(text)
+    }
+}
+
+/// Words are separated by whitespace. This is the default.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Whitespace;
+
+impl WordSegmenter for Whitespace {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn split(&self, text: &str) -> Vec<Range<usize>> {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (i, c) in text.char_indices() {
+            match (c.is_whitespace(), start) {
+                (true, Some(s)) => {
+                    words.push(s..i);
+                    start = None;
+                }
+                (false, None) => start = Some(i),
+                _ => {}
+            }
+        }
+        words.extend(start.map(|s| s..text.len()));
+        words
+    }
+}
+
+/// Words are found with the Unicode word boundary rules of
+/// [UAX #29](https://www.unicode.org/reports/tr29/), skipping punctuation and whitespace.
+///
+/// These rules don't use a dictionary, so every Han character
+/// and every run of kana becomes a word of its own,
+/// which is coarse but gives each one its own timing.
+#[cfg(feature = "unicode-segmentation")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UnicodeWords;
+
+#[cfg(feature = "unicode-segmentation")]
+impl WordSegmenter for UnicodeWords {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn split(&self, text: &str) -> Vec<Range<usize>> {
+        use unicode_segmentation::UnicodeSegmentation;
+
+        text.split_word_bound_indices()
+            .filter(|(_, word)| word.chars().any(char::is_alphanumeric))
+            .map(|(i, word)| i..i + word.len())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Whitespace, WordSegmenter};
+
+    #[test]
+    fn whitespace_splits_runs() {
+        assert_eq!(Whitespace.split(" hello  world"), [1..6, 8..13]);
+        assert!(Whitespace.split("   ").is_empty());
+    }
+
+    #[cfg(feature = "unicode-segmentation")]
+    #[test]
+    fn unicode_words_split_without_spaces() {
+        let text = "我爱你, ok";
+        let words: Vec<_> = super::UnicodeWords
+            .split(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        assert_eq!(words, ["我", "爱", "你", "ok"]);
+    }
+
+    #[test]
+    fn closures_are_segmenters() {
+        let pairs = |text: &str| (0..text.len()).step_by(2).map(|i| i..i + 2).collect();
+        assert_eq!(pairs.split("abcd"), [0..2, 2..4]);
+    }
+}
@@ -0,0 +1,938 @@
+use crate::audio::{duration_of, samples_in, IntoModelAudio};
+use crate::failover::{Failover, Route};
+use crate::oneshot;
+use crate::{FailoverCause, FailoverEvent, FailoverPolicy, Model, ModelPool, Transcription};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Condvar, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Options for an [`SttService`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ServiceOptions {
+    /// The most requests that may wait to be transcribed, from all clients together.
+    /// Requests past this are refused with [`Error::QueueFull`](crate::Error::QueueFull).
+    pub max_queued: usize,
+    /// The most requests one client may have waiting to be transcribed.
+    /// `None` sets no limit beyond [`max_queued`](ServiceOptions::max_queued).
+    pub max_queued_per_client: Option<usize>,
+    /// The deadline for requests that don't set their own.
+    /// See [`SubmitOptions::deadline`]. `None` lets them wait as long as it takes.
+    pub default_deadline: Option<Duration>,
+    /// The longest a request may be projected to take, from being submitted
+    /// to being transcribed, for it to be accepted.
+    ///
+    /// The projection goes by the audio waiting ahead of the request and being transcribed,
+    /// and the [real-time factor](SttService::real_time_factor) measured so far,
+    /// so requests are refused with [`Error::Overloaded`](crate::Error::Overloaded)
+    /// during load spikes, rather than piling up and all being late.
+    /// Requests are accepted until the first one has been transcribed.
+    /// `None` accepts requests whatever their projected latency.
+    pub max_latency: Option<Duration>,
+    /// When to switch to the [standby model](SttService::set_standby), if there is one.
+    pub failover: FailoverPolicy,
+}
+
+impl Default for ServiceOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_queued: 64,
+            max_queued_per_client: None,
+            default_deadline: None,
+            max_latency: None,
+            failover: FailoverPolicy::default(),
+        }
+    }
+}
+
+impl ServiceOptions {
+    /// Set [`max_queued`](ServiceOptions::max_queued).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_queued(mut self, max_queued: usize) -> Self {
+        self.max_queued = max_queued;
+        self
+    }
+
+    /// Set [`max_queued_per_client`](ServiceOptions::max_queued_per_client).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_queued_per_client(mut self, max_queued: Option<usize>) -> Self {
+        self.max_queued_per_client = max_queued;
+        self
+    }
+
+    /// Set [`default_deadline`](ServiceOptions::default_deadline).
+    #[inline]
+    #[must_use]
+    pub const fn with_default_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.default_deadline = deadline;
+        self
+    }
+
+    /// Set [`max_latency`](ServiceOptions::max_latency).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_latency(mut self, max_latency: Option<Duration>) -> Self {
+        self.max_latency = max_latency;
+        self
+    }
+
+    /// Set [`failover`](ServiceOptions::failover).
+    #[inline]
+    #[must_use]
+    pub const fn with_failover(mut self, failover: FailoverPolicy) -> Self {
+        self.failover = failover;
+        self
+    }
+}
+
+/// How urgently an [`SttService`] should transcribe a request.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Priority {
+    /// Someone is waiting for the result, such as a voice command.
+    /// These are transcribed before any batch requests.
+    #[default]
+    Interactive,
+    /// Nobody is waiting for the result straight away, such as transcribing an archive.
+    /// These are only transcribed while no interactive requests are waiting.
+    Batch,
+}
+
+impl Priority {
+    const fn index(self) -> usize {
+        match self {
+            Self::Interactive => 0,
+            Self::Batch => 1,
+        }
+    }
+}
+
+/// Options for one request to an [`SttService`].
+#[non_exhaustive]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubmitOptions {
+    /// Who the request is from, such as a user or an API key.
+    ///
+    /// Clients take turns, so one submitting many requests at once
+    /// only holds up its own, not everyone else's.
+    /// Requests without a client share a turn.
+    pub client: Option<String>,
+    /// How long the request may wait for a model, from when it was submitted.
+    ///
+    /// A request still waiting at its deadline is answered with
+    /// [`Error::DeadlineExceeded`](crate::Error::DeadlineExceeded) instead of being transcribed.
+    /// Transcribing can't be interrupted, so this doesn't limit how long that takes.
+    /// `None` uses the service's [`default_deadline`](ServiceOptions::default_deadline).
+    pub deadline: Option<Duration>,
+    /// When the request must have started being transcribed by, such as the deadline
+    /// of the job it is part of.
+    ///
+    /// This works like [`deadline`](SubmitOptions::deadline); if both are set,
+    /// whichever passes first applies. A request submitted after this has passed
+    /// is refused straight away.
+    pub deadline_at: Option<Instant>,
+    /// How urgently the request should be transcribed. Interactive by default.
+    pub priority: Priority,
+}
+
+impl SubmitOptions {
+    /// Set [`client`](SubmitOptions::client).
+    #[inline]
+    #[must_use]
+    pub fn with_client(mut self, client: impl Into<String>) -> Self {
+        self.client = Some(client.into());
+        self
+    }
+
+    /// Set [`deadline`](SubmitOptions::deadline).
+    #[inline]
+    #[must_use]
+    pub const fn with_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Set [`deadline_at`](SubmitOptions::deadline_at).
+    #[inline]
+    #[must_use]
+    pub const fn with_deadline_at(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline_at = deadline;
+        self
+    }
+
+    /// Set [`priority`](SubmitOptions::priority).
+    #[inline]
+    #[must_use]
+    pub const fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+type Reply = oneshot::Sender<crate::Result<Transcription>>;
+
+struct Request {
+    audio: Vec<i16>,
+    deadline: Option<Instant>,
+    reply: Reply,
+    #[cfg(feature = "record")]
+    origin: Origin,
+}
+
+/// What a request was submitted with, for [`Recorder`](crate::recording::Recorder)s.
+#[cfg(feature = "record")]
+struct Origin {
+    client: Option<String>,
+    priority: Priority,
+    submitted: Instant,
+}
+
+impl Request {
+    fn expired(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| deadline <= now)
+    }
+}
+
+/// The requests of one [`Priority`], in a lane for each client.
+#[derive(Default)]
+struct Lanes {
+    lanes: HashMap<Option<String>, VecDeque<Request>>,
+    /// Clients with requests waiting, in the order they get their next turn.
+    turns: VecDeque<Option<String>>,
+}
+
+impl Lanes {
+    fn push(&mut self, client: Option<String>, request: Request) {
+        let lane = self.lanes.entry(client.clone()).or_default();
+        lane.push_back(request);
+        if lane.len() == 1 {
+            self.turns.push_back(client);
+        }
+    }
+
+    /// Take the next client's oldest request, and send that client to the back of the line.
+    fn pop(&mut self) -> Option<Request> {
+        let client = self.turns.pop_front()?;
+        let lane = self.lanes.get_mut(&client)?;
+        let request = lane.pop_front()?;
+        if lane.is_empty() {
+            self.lanes.remove(&client);
+        } else {
+            self.turns.push_back(client);
+        }
+        Some(request)
+    }
+
+    /// Answer requests whose deadline passed while they waited.
+    fn expire(&mut self, now: Instant) {
+        for lane in self.lanes.values_mut() {
+            let mut i = 0;
+            while i < lane.len() {
+                match lane.get(i) {
+                    Some(request) if request.expired(now) => {
+                        if let Some(request) = lane.remove(i) {
+                            request.reply.send(Err(crate::Error::DeadlineExceeded));
+                        }
+                    }
+                    _ => i += 1,
+                }
+            }
+        }
+        self.lanes.retain(|_, lane| !lane.is_empty());
+        let lanes = &self.lanes;
+        self.turns.retain(|client| lanes.contains_key(client));
+    }
+
+    fn len(&self) -> usize {
+        self.lanes.values().map(VecDeque::len).sum()
+    }
+
+    fn len_for(&self, client: &Option<String>) -> usize {
+        self.lanes.get(client).map_or(0, VecDeque::len)
+    }
+
+    fn samples(&self) -> usize {
+        self.lanes
+            .values()
+            .flatten()
+            .map(|request| request.audio.len())
+            .sum()
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.lanes
+            .values()
+            .flatten()
+            .filter_map(|request| request.deadline)
+            .min()
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    /// Interactive requests, then batch requests.
+    priorities: [Lanes; 2],
+    len: usize,
+    /// Samples being transcribed right now.
+    in_flight: usize,
+    /// A moving average of the real-time factor of transcribing requests,
+    /// once one has been transcribed.
+    real_time_factor: Option<f64>,
+    closed: bool,
+}
+
+impl Queue {
+    fn push(&mut self, priority: Priority, client: Option<String>, request: Request) {
+        self.priorities[priority.index()].push(client, request);
+        self.len += 1;
+    }
+
+    /// Take the next request of the highest priority waiting.
+    fn pop(&mut self) -> Option<Request> {
+        let request = self.priorities.iter_mut().find_map(Lanes::pop)?;
+        self.len -= 1;
+        self.in_flight += request.audio.len();
+        Some(request)
+    }
+
+    /// Record that a request of `samples` samples was transcribed,
+    /// taking `real_time_factor` times as long as the audio lasts.
+    fn done(&mut self, samples: usize, real_time_factor: Option<f64>) {
+        self.in_flight = self.in_flight.saturating_sub(samples);
+        if let Some(measured) = real_time_factor.filter(|rtf| rtf.is_finite()) {
+            self.real_time_factor = Some(match self.real_time_factor {
+                Some(average) => average + (measured - average) * RTF_SMOOTHING,
+                None => measured,
+            });
+        }
+    }
+
+    /// The samples a request of `priority` would wait for: those being transcribed,
+    /// and those queued at the same or a higher priority.
+    fn samples_ahead(&self, priority: Priority) -> usize {
+        let queued: usize = self.priorities[..=priority.index()]
+            .iter()
+            .map(Lanes::samples)
+            .sum();
+        queued + self.in_flight
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.priorities
+            .iter_mut()
+            .for_each(|lanes| lanes.expire(now));
+        self.len = self.priorities.iter().map(Lanes::len).sum();
+    }
+
+    /// The number of requests `client` has waiting, of any priority.
+    fn len_for(&self, client: &Option<String>) -> usize {
+        self.priorities
+            .iter()
+            .map(|lanes| lanes.len_for(client))
+            .sum()
+    }
+
+    fn next_deadline(&self) -> Option<Instant> {
+        self.priorities
+            .iter()
+            .filter_map(Lanes::next_deadline)
+            .min()
+    }
+}
+
+/// How much each request moves the average real-time factor towards its own.
+const RTF_SMOOTHING: f64 = 0.2;
+
+struct Shared {
+    pool: ModelPool,
+    options: ServiceOptions,
+    sample_rate: u32,
+    workers: usize,
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    standby: Mutex<Option<Model>>,
+    failover: Mutex<Failover>,
+    #[cfg(feature = "record")]
+    recorder: Mutex<Option<crate::recording::Recorder>>,
+}
+
+impl Shared {
+    fn queue(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for the next request to transcribe, or `None` once the service is gone
+    /// and every request left has been answered.
+    fn next(&self) -> Option<Request> {
+        let mut queue = self.queue();
+        loop {
+            queue.expire(Instant::now());
+            if let Some(request) = queue.pop() {
+                return Some(request);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = match queue.next_deadline() {
+                Some(deadline) => {
+                    let wait = deadline.saturating_duration_since(Instant::now());
+                    self.ready
+                        .wait_timeout(queue, wait)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self.ready.wait(queue).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    /// How long a request of `priority` with `samples` samples is projected to take
+    /// to be transcribed, or `None` if nothing has been transcribed to go by yet.
+    fn projected_latency(
+        &self,
+        queue: &Queue,
+        priority: Priority,
+        samples: usize,
+    ) -> Option<Duration> {
+        let rtf = queue.real_time_factor?;
+        let audio = duration_of(
+            (queue.samples_ahead(priority) + samples) as u64,
+            self.sample_rate,
+        );
+        Duration::try_from_secs_f64(audio.as_secs_f64() * rtf / self.workers.max(1) as f64).ok()
+    }
+
+    fn failover(&self) -> MutexGuard<'_, Failover> {
+        self.failover.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn standby(&self) -> MutexGuard<'_, Option<Model>> {
+        self.standby.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Transcribe `audio` with the primary model from the pool, or the standby if failed over,
+    /// keeping track of the primary's health.
+    fn transcribe(&self, audio: &[i16], deadline: Option<Instant>) -> crate::Result<Transcription> {
+        let policy = &self.options.failover;
+        let route = self.failover().route(policy);
+        if route == Route::Standby {
+            if let Some(res) = self.transcribe_standby(audio, deadline) {
+                return res;
+            }
+        }
+
+        let res = self.pool.get().and_then(|mut model| {
+            // waiting for the model may have taken until past the deadline
+            if deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+                return Err(crate::Error::DeadlineExceeded);
+            }
+            model.speech_to_text_best(audio)
+        });
+        let can_fail_over = self.standby().is_some();
+        match &res {
+            Err(crate::Error::DeadlineExceeded | crate::Error::ShuttingDown) => {
+                self.failover().inconclusive(route);
+            }
+            Err(e) => {
+                let cause = FailoverCause::Errors(e.clone());
+                self.failover().strike(route, cause, policy, can_fail_over);
+                // give the request another chance, rather than failing it with the primary
+                if let Some(res) = self.transcribe_standby(audio, deadline) {
+                    return res;
+                }
+            }
+            Ok(transcription) => match policy.latency_budget {
+                Some(budget) if transcription.processing_time > budget => {
+                    let cause = FailoverCause::TooSlow(transcription.processing_time);
+                    self.failover().strike(route, cause, policy, can_fail_over);
+                }
+                _ => self.failover().success(route),
+            },
+        }
+        res
+    }
+
+    /// Transcribe `audio` with the standby model, or return `None` if there isn't one.
+    fn transcribe_standby(
+        &self,
+        audio: &[i16],
+        deadline: Option<Instant>,
+    ) -> Option<crate::Result<Transcription>> {
+        let mut standby = self.standby();
+        let model = standby.as_mut()?;
+        if deadline.is_some_and(|deadline| deadline <= Instant::now()) {
+            return Some(Err(crate::Error::DeadlineExceeded));
+        }
+        Some(model.speech_to_text_best(audio))
+    }
+
+    fn work(&self) {
+        while let Some(request) = self.next() {
+            let Request {
+                audio,
+                deadline,
+                reply,
+                #[cfg(feature = "record")]
+                origin,
+            } = request;
+            let samples = audio.len();
+            #[cfg(feature = "record")]
+            let waited = origin.submitted.elapsed();
+            let res = self.transcribe(&audio, deadline);
+            let real_time_factor = res.as_ref().ok().and_then(|transcription| {
+                (!transcription.audio_duration.is_zero()).then(|| {
+                    transcription.processing_time.as_secs_f64()
+                        / transcription.audio_duration.as_secs_f64()
+                })
+            });
+            self.queue().done(samples, real_time_factor);
+            #[cfg(feature = "record")]
+            let recording = res.as_ref().ok().map(|transcription| {
+                crate::recording::Recording::new(
+                    origin.client,
+                    origin.priority,
+                    waited,
+                    transcription,
+                )
+            });
+            reply.send(res);
+            #[cfg(feature = "record")]
+            if let Some(recording) = recording {
+                self.record(&audio, recording);
+            }
+        }
+    }
+
+    /// Write `recording` with the recorder, if there is one,
+    /// dropping the recorder if that fails.
+    #[cfg(feature = "record")]
+    fn record(&self, audio: &[i16], mut recording: crate::recording::Recording) {
+        let mut recorder = self.recorder.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(writer) = recorder.as_mut() else {
+            return;
+        };
+        if let Err(error) = writer.write(audio, self.sample_rate, &mut recording) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(%error, dir = ?writer.dir(), "stopped recording requests");
+            #[cfg(not(feature = "tracing"))]
+            let _ = error;
+            *recorder = None;
+        }
+    }
+}
+
+/// Stops the workers once the last handle to the service is dropped.
+struct Closer(Arc<Shared>);
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        self.0.queue().closed = true;
+        self.0.ready.notify_all();
+    }
+}
+
+/// A transcription service over a [`ModelPool`], for servers:
+/// requests are queued up, taken from each client in turn,
+/// and given up on if they wait past their deadline.
+///
+/// [Interactive](Priority::Interactive) requests are always taken before
+/// [batch](Priority::Batch) ones, so both can share one set of models;
+/// batch requests with deadlines are given up on, rather than transcribed late,
+/// if interactive ones keep the models busy.
+///
+/// Each model in the pool gets a worker thread, which takes the next request,
+/// takes a model from the pool and transcribes the request with it.
+/// Workers are started when the service is created,
+/// so [resizing](ModelPool::resize) the pool afterwards doesn't change how many there are.
+///
+/// Cloning an `SttService` gives another handle to the same service.
+/// Once every handle is dropped, the requests already queued are still transcribed,
+/// and then the workers exit.
+#[derive(Clone)]
+pub struct SttService {
+    closer: Arc<Closer>,
+}
+
+impl SttService {
+    /// Create a new `SttService` over `pool`, with the default [`ServiceOptions`].
+    ///
+    /// # Errors
+    /// See [`new_with_options`](SttService::new_with_options).
+    #[inline]
+    pub fn new(pool: ModelPool) -> crate::Result<Self> {
+        Self::new_with_options(pool, ServiceOptions::default())
+    }
+
+    /// Create a new `SttService` over `pool`.
+    ///
+    /// A model is taken from the pool briefly, to find the sample rate audio is converted to.
+    ///
+    /// # Errors
+    /// Returns [`Error::NoModel`](crate::Error::NoModel) if the pool holds no models.
+    /// Otherwise, passes through any errors from taking a model from the pool.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn new_with_options(pool: ModelPool, options: ServiceOptions) -> crate::Result<Self> {
+        let workers = pool.stats().size;
+        if workers == 0 {
+            return Err(crate::Error::NoModel);
+        }
+        let sample_rate = pool.get()?.get_sample_rate() as u32;
+
+        let shared = Arc::new(Shared {
+            pool,
+            options,
+            sample_rate,
+            workers,
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            standby: Mutex::new(None),
+            failover: Mutex::new(Failover::default()),
+            #[cfg(feature = "record")]
+            recorder: Mutex::new(None),
+        });
+        for _ in 0..workers {
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || shared.work());
+        }
+        Ok(Self {
+            closer: Arc::new(Closer(shared)),
+        })
+    }
+
+    /// Queue up `audio` to be transcribed, returning a future that resolves to its transcription.
+    ///
+    /// The request is queued straight away, and still transcribed if the future is dropped.
+    /// The future doesn't depend on any particular async runtime,
+    /// and [`Submission::wait`] blocks on it instead.
+    ///
+    /// It resolves to [`Error::QueueFull`](crate::Error::QueueFull) if the queue,
+    /// or the client's share of it, is full,
+    /// to [`Error::Overloaded`](crate::Error::Overloaded) if the request is projected
+    /// to take longer than [`max_latency`](ServiceOptions::max_latency),
+    /// and to [`Error::DeadlineExceeded`](crate::Error::DeadlineExceeded)
+    /// if the request's deadline passed before a model was free.
+    /// Otherwise, it passes through any errors from converting `audio`, the pool,
+    /// and the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn submit<'a>(&self, audio: impl IntoModelAudio<'a>, options: SubmitOptions) -> Submission {
+        match self.enqueue(audio, options) {
+            Ok(rx) => Submission(Ok(rx)),
+            Err(e) => Submission(Err(Some(e))),
+        }
+    }
+
+    fn enqueue<'a>(
+        &self,
+        audio: impl IntoModelAudio<'a>,
+        options: SubmitOptions,
+    ) -> crate::Result<oneshot::Receiver<crate::Result<Transcription>>> {
+        let shared = self.shared();
+        let submitted = Instant::now();
+        let deadline = options
+            .deadline
+            .or(shared.options.default_deadline)
+            .and_then(|deadline| submitted.checked_add(deadline));
+        let deadline = match (deadline, options.deadline_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if options.deadline_at.is_some_and(|at| at <= submitted) {
+            return Err(crate::Error::DeadlineExceeded);
+        }
+        let audio = audio.into_model_audio(shared.sample_rate)?.into_owned();
+
+        let mut queue = shared.queue();
+        queue.expire(Instant::now());
+        let waiting = queue.len_for(&options.client);
+        let client_full = shared
+            .options
+            .max_queued_per_client
+            .is_some_and(|max| waiting >= max);
+        if queue.len >= shared.options.max_queued || client_full {
+            return Err(crate::Error::QueueFull);
+        }
+        if let Some(limit) = shared.options.max_latency {
+            match shared.projected_latency(&queue, options.priority, audio.len()) {
+                Some(projected) if projected > limit => {
+                    return Err(crate::Error::Overloaded { projected, limit })
+                }
+                _ => {}
+            }
+        }
+
+        let (reply, rx) = oneshot::channel();
+        let request = Request {
+            audio,
+            deadline,
+            reply,
+            #[cfg(feature = "record")]
+            origin: Origin {
+                client: options.client.clone(),
+                priority: options.priority,
+                submitted,
+            },
+        };
+        queue.push(options.priority, options.client, request);
+        drop(queue);
+        shared.ready.notify_one();
+        Ok(rx)
+    }
+
+    /// Set the model to transcribe with when the primary models of the pool keep failing
+    /// or are too slow, according to the [`FailoverPolicy`], such as a smaller, faster one.
+    ///
+    /// Requests that fail on the primary model are also tried again on the standby.
+    /// The standby transcribes one request at a time, so it is only meant
+    /// to keep the service available until the primary recovers.
+    ///
+    /// # Errors
+    /// Returns [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate)
+    /// if `standby` expects a different sample rate from the pool's models.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn set_standby(&self, standby: Model) -> crate::Result<()> {
+        let shared = self.shared();
+        let sample_rate = standby.get_sample_rate() as u32;
+        if sample_rate != shared.sample_rate {
+            return Err(crate::Error::UnsupportedSampleRate(sample_rate));
+        }
+        let previous = shared.standby().replace(standby);
+        drop(previous);
+        Ok(())
+    }
+
+    /// Record every request transcribed from now on with `recorder`, or stop recording with `None`.
+    ///
+    /// Requests are recorded after they have been answered, so recording doesn't add to their
+    /// latency, but it does hold up the worker from taking the next request.
+    /// Requests that fail aren't recorded. If writing a recording fails, recording stops,
+    /// and with the `tracing` feature, a warning is logged.
+    #[cfg(feature = "record")]
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn set_recorder(&self, recorder: Option<crate::recording::Recorder>) {
+        let shared = self.shared();
+        let previous = std::mem::replace(
+            &mut *shared.recorder.lock().unwrap_or_else(|e| e.into_inner()),
+            recorder,
+        );
+        drop(previous);
+    }
+
+    /// Whether requests are being transcribed with the standby model.
+    #[inline]
+    #[must_use]
+    pub fn is_failed_over(&self) -> bool {
+        self.shared().failover().is_failed_over()
+    }
+
+    /// Subscribe to the service failing over to its standby model and recovering.
+    ///
+    /// Events are sent as they happen, to every subscriber;
+    /// subscribers that were dropped stop receiving them.
+    #[inline]
+    #[must_use]
+    pub fn failover_events(&self) -> mpsc::Receiver<FailoverEvent> {
+        self.shared().failover().subscribe()
+    }
+
+    /// A moving average of how long transcribing requests has taken,
+    /// relative to how long their audio lasts, or `None` until one has been transcribed.
+    ///
+    /// Below 1.0, requests are transcribed faster than real time.
+    #[inline]
+    #[must_use]
+    pub fn real_time_factor(&self) -> Option<f64> {
+        self.shared().queue().real_time_factor
+    }
+
+    /// How long a request of `priority` with `audio` worth of audio
+    /// would be projected to take, if it was submitted now.
+    /// See [`ServiceOptions::max_latency`].
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn projected_latency(&self, priority: Priority, audio: Duration) -> Option<Duration> {
+        let shared = self.shared();
+        let samples = samples_in(audio, shared.sample_rate);
+        shared.projected_latency(&shared.queue(), priority, samples)
+    }
+
+    /// The number of requests waiting for a model.
+    #[inline]
+    #[must_use]
+    pub fn queued(&self) -> usize {
+        self.shared().queue().len
+    }
+
+    /// Return the pool this service transcribes with.
+    #[inline]
+    #[must_use]
+    pub fn pool(&self) -> &ModelPool {
+        &self.shared().pool
+    }
+
+    /// Return the [`ServiceOptions`] this service was created with.
+    #[inline]
+    #[must_use]
+    pub fn options(&self) -> &ServiceOptions {
+        &self.shared().options
+    }
+
+    fn shared(&self) -> &Shared {
+        &self.closer.0
+    }
+}
+
+/// A request submitted to an [`SttService`], which resolves to its transcription.
+#[must_use = "futures do nothing unless polled"]
+pub struct Submission(
+    Result<oneshot::Receiver<crate::Result<Transcription>>, Option<crate::Error>>,
+);
+
+impl Submission {
+    /// Block until the request has been transcribed, and return its transcription.
+    ///
+    /// # Errors
+    /// See [`SttService::submit`].
+    #[inline]
+    pub fn wait(self) -> crate::Result<Transcription> {
+        match self.0 {
+            Ok(rx) => rx.recv().unwrap_or(Err(crate::Error::WorkerPanicked)),
+            Err(e) => Err(e.unwrap_or(crate::Error::Unknown)),
+        }
+    }
+}
+
+impl Future for Submission {
+    type Output = crate::Result<Transcription>;
+
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match &mut self.0 {
+            Ok(rx) => Pin::new(rx)
+                .poll(cx)
+                .map(|res| res.unwrap_or(Err(crate::Error::WorkerPanicked))),
+            Err(e) => Poll::Ready(Err(e.take().unwrap_or(crate::Error::Unknown))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Priority, Queue, Request};
+    use crate::oneshot;
+    use std::time::{Duration, Instant};
+
+    fn request(
+        audio: i16,
+        deadline: Option<Instant>,
+    ) -> (
+        Request,
+        oneshot::Receiver<crate::Result<crate::Transcription>>,
+    ) {
+        let (reply, rx) = oneshot::channel();
+        let request = Request {
+            audio: vec![audio],
+            deadline,
+            reply,
+            #[cfg(feature = "record")]
+            origin: super::Origin {
+                client: None,
+                priority: Priority::Interactive,
+                submitted: Instant::now(),
+            },
+        };
+        (request, rx)
+    }
+
+    #[test]
+    fn clients_take_turns() {
+        let mut queue = Queue::default();
+        let mut replies = Vec::new();
+        for (client, audio) in [("a", 1), ("a", 2), ("a", 3), ("b", 4)] {
+            let (request, rx) = request(audio, None);
+            queue.push(Priority::Interactive, Some(client.to_string()), request);
+            replies.push(rx);
+        }
+        let (request, rx) = request(5, None);
+        queue.push(Priority::Interactive, None, request);
+        replies.push(rx);
+
+        assert_eq!(queue.len, 5);
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop())
+            .map(|request| request.audio[0])
+            .collect();
+        assert_eq!(order, [1, 4, 5, 2, 3]);
+        assert_eq!(queue.len, 0);
+        assert!(queue.priorities[0].turns.is_empty());
+    }
+
+    #[test]
+    fn interactive_requests_go_first() {
+        let mut queue = Queue::default();
+        let mut replies = Vec::new();
+        for (priority, client, audio) in [
+            (Priority::Batch, "a", 1),
+            (Priority::Batch, "b", 2),
+            (Priority::Interactive, "a", 3),
+            (Priority::Interactive, "a", 4),
+        ] {
+            let (request, rx) = request(audio, None);
+            queue.push(priority, Some(client.to_string()), request);
+            replies.push(rx);
+        }
+
+        assert_eq!(queue.len_for(&Some("a".to_string())), 3);
+        let order: Vec<_> = std::iter::from_fn(|| queue.pop())
+            .map(|request| request.audio[0])
+            .collect();
+        assert_eq!(order, [3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn real_time_factor_is_averaged() {
+        let mut queue = Queue::default();
+        let (batch, _batch_rx) = request(1, None);
+        queue.push(Priority::Batch, None, batch);
+        let (interactive, _interactive_rx) = request(2, None);
+        queue.push(Priority::Interactive, None, interactive);
+        assert_eq!(queue.samples_ahead(Priority::Interactive), 1);
+        assert_eq!(queue.samples_ahead(Priority::Batch), 2);
+
+        let popped = queue.pop().unwrap();
+        assert_eq!(queue.samples_ahead(Priority::Interactive), 1);
+        queue.done(popped.audio.len(), Some(0.5));
+        assert_eq!(queue.samples_ahead(Priority::Interactive), 0);
+        assert_eq!(queue.real_time_factor, Some(0.5));
+        queue.done(0, Some(1.5));
+        assert_eq!(queue.real_time_factor, Some(0.7));
+        queue.done(0, None);
+        queue.done(0, Some(f64::INFINITY));
+        assert_eq!(queue.real_time_factor, Some(0.7));
+    }
+
+    #[test]
+    fn expired_requests_are_answered() {
+        let now = Instant::now();
+        let mut queue = Queue::default();
+        let (late, late_rx) = request(1, Some(now));
+        let (on_time, _on_time_rx) = request(2, Some(now + Duration::from_secs(60)));
+        queue.push(Priority::Batch, Some("a".to_string()), late);
+        queue.push(Priority::Interactive, Some("b".to_string()), on_time);
+        assert_eq!(queue.next_deadline(), Some(now));
+
+        queue.expire(now);
+        assert!(matches!(
+            late_rx.recv(),
+            Some(Err(crate::Error::DeadlineExceeded))
+        ));
+        assert_eq!(queue.len, 1);
+        assert!(queue.priorities[1].turns.is_empty());
+        assert_eq!(queue.pop().map(|request| request.audio[0]), Some(2));
+    }
+}
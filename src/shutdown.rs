@@ -0,0 +1,241 @@
+use crate::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct State {
+    triggered: bool,
+    hooks: Vec<(u64, Hook)>,
+    next_id: u64,
+    /// Registered components that haven't finished shutting down.
+    active: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    finished: Condvar,
+}
+
+/// A handle for shutting down the background work of this crate cleanly.
+///
+/// Pass it to [`ThreadSafeStream::watch_shutdown`](crate::ThreadSafeStream::watch_shutdown),
+/// [`ModelPool::watch_shutdown`](crate::ModelPool::watch_shutdown),
+/// or `WatchConfig::with_shutdown`, then call [`shutdown`](Shutdown::shutdown)
+/// when the application is about to exit. Once it is triggered:
+///
+/// * streams stop accepting audio, finish decoding what they have, and deliver the result
+///   to their [watchdog](crate::ThreadSafeStream::new_with_watchdog) callback,
+/// * pools stop handing out models, and count as finished once every model is returned,
+/// * watched directories stop being scanned, and their workers exit
+///   once the files already picked up are transcribed.
+///
+/// Cloning a `Shutdown` returns another handle to the same one.
+#[derive(Clone, Default)]
+pub struct Shutdown(Arc<Inner>);
+
+impl std::fmt::Debug for Shutdown {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("Shutdown")
+            .field("triggered", &state.triggered)
+            .field("active", &state.active)
+            .finish()
+    }
+}
+
+impl Shutdown {
+    /// Create a new `Shutdown` that hasn't been triggered.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether shutdown has started.
+    #[inline]
+    #[must_use]
+    pub fn is_triggered(&self) -> bool {
+        self.state().triggered
+    }
+
+    /// The number of components watching this handle that haven't finished shutting down yet.
+    #[inline]
+    #[must_use]
+    pub fn active(&self) -> usize {
+        self.state().active
+    }
+
+    /// Start shutting down, without waiting for anything to finish.
+    ///
+    /// Calling this more than once does nothing.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn trigger(&self) {
+        let hooks = {
+            let mut state = self.state();
+            state.triggered = true;
+            std::mem::take(&mut state.hooks)
+        };
+        // hooks may finish their component straight away, which needs the lock
+        for (_, hook) in hooks {
+            hook();
+        }
+    }
+
+    /// Wait up to `deadline` for every component watching this handle to finish shutting down.
+    ///
+    /// Returns `true` if they all finished in time.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn wait(&self, deadline: Duration) -> bool {
+        let started = Instant::now();
+        let mut state = self.state();
+        while state.active > 0 {
+            let remaining = match deadline.checked_sub(started.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => remaining,
+                _ => return false,
+            };
+            state = self
+                .0
+                .finished
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        true
+    }
+
+    /// [Trigger](Shutdown::trigger) shutdown, and [wait](Shutdown::wait) up to `deadline`
+    /// for everything to finish.
+    ///
+    /// Returns `true` if everything finished in time.
+    #[inline]
+    pub fn shutdown(&self, deadline: Duration) -> bool {
+        self.trigger();
+        self.wait(deadline)
+    }
+
+    /// Register a component, which `hook` starts shutting down,
+    /// and which has finished once the returned guard is dropped.
+    ///
+    /// If shutdown has already started, `hook` runs straight away.
+    pub(crate) fn register(&self, hook: impl FnOnce() + Send + 'static) -> ShutdownGuard {
+        let mut state = self.state();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.active += 1;
+        let guard = ShutdownGuard {
+            shutdown: self.clone(),
+            id,
+        };
+        if state.triggered {
+            drop(state);
+            hook();
+        } else {
+            state.hooks.push((id, Box::new(hook)));
+        }
+        guard
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.0.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A component registered with a [`Shutdown`], which counts as finished once this is dropped.
+pub(crate) struct ShutdownGuard {
+    shutdown: Shutdown,
+    id: u64,
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        let mut state = self.shutdown.state();
+        let hook = state
+            .hooks
+            .iter()
+            .position(|(id, _)| *id == self.id)
+            .map(|i| state.hooks.swap_remove(i));
+        state.active -= 1;
+        drop(state);
+        // the hook may hold on to channels its component waits on, so free it outside the lock
+        drop(hook);
+        self.shutdown.0.finished.notify_all();
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::Shutdown;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn hooks_run_once_and_guards_are_waited_for() {
+        let shutdown = Shutdown::new();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let hook = {
+            let runs = Arc::clone(&runs);
+            move || {
+                runs.fetch_add(1, Ordering::Relaxed);
+            }
+        };
+        let guard = shutdown.register(hook.clone());
+        drop(shutdown.register(hook.clone()));
+        assert_eq!(shutdown.active(), 1);
+
+        assert!(!shutdown.shutdown(Duration::from_millis(10)));
+        shutdown.trigger();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        let waiter = {
+            let shutdown = shutdown.clone();
+            std::thread::spawn(move || shutdown.wait(Duration::from_secs(10)))
+        };
+        drop(guard);
+        assert!(waiter.join().unwrap());
+
+        // components registered late start shutting down straight away
+        drop(shutdown.register(hook));
+        assert_eq!(runs.load(Ordering::Relaxed), 2);
+        assert_eq!(shutdown.active(), 0);
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::Shutdown;
+    use loom::sync::atomic::{AtomicUsize, Ordering};
+    use loom::sync::Arc;
+    use loom::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn hooks_registered_during_trigger_run_exactly_once() {
+        loom::model(|| {
+            let shutdown = Shutdown::new();
+            let runs = Arc::new(AtomicUsize::new(0));
+            let registrar = {
+                let shutdown = shutdown.clone();
+                let runs = Arc::clone(&runs);
+                thread::spawn(move || {
+                    shutdown.register(move || {
+                        runs.fetch_add(1, Ordering::SeqCst);
+                    })
+                })
+            };
+            shutdown.trigger();
+            let guard = registrar.join().unwrap();
+            assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+            let waiter = {
+                let shutdown = shutdown.clone();
+                thread::spawn(move || shutdown.wait(Duration::from_secs(60)))
+            };
+            drop(guard);
+            assert!(waiter.join().unwrap());
+        });
+    }
+}
@@ -0,0 +1,803 @@
+//! Writing transcripts out as they are produced.
+//!
+//! A [`TranscriptSink`] receives each [`Segment`] as soon as it is finished,
+//! so long jobs don't need to hold every result in memory until the end.
+//! Pass one to [`Transcriber::push_audio_to`](crate::Transcriber::push_audio_to)
+//! and [`Transcriber::finish_to`](crate::Transcriber::finish_to).
+//!
+//! [`TextSink`], [`SrtSink`] and [`VttSink`] are always available,
+//! and the `jsonl` feature enables `JsonlSink`.
+//! The subtitle sinks can be styled with [`SubtitleOptions`].
+//!
+//! The sinks here don't flush their writer after every segment, leaving that to the writer:
+//! wrap it in a [`BufWriter`] to batch up small writes, as the `create` constructors do.
+//! [`TranscriptSink::flush`] flushes whatever is left.
+//! [`TextSink`] and the subtitle sinks skip segments without any text,
+//! rather than writing out blank lines.
+
+use crate::Segment;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// A destination for transcribed segments.
+pub trait TranscriptSink {
+    /// Write out a finished segment.
+    ///
+    /// # Errors
+    /// Returns an error if the segment could not be written.
+    fn write_segment(&mut self, segment: &Segment) -> crate::Result<()>;
+
+    /// Flush anything buffered.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// # Errors
+    /// Returns an error if flushing failed.
+    #[inline]
+    fn flush(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: TranscriptSink + ?Sized> TranscriptSink for &mut T {
+    #[inline]
+    fn write_segment(&mut self, segment: &Segment) -> crate::Result<()> {
+        (**self).write_segment(segment)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> crate::Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<T: TranscriptSink + ?Sized> TranscriptSink for Box<T> {
+    #[inline]
+    fn write_segment(&mut self, segment: &Segment) -> crate::Result<()> {
+        (**self).write_segment(segment)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> crate::Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Writes the text of each segment on a line of its own.
+pub struct TextSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TextSink<W> {
+    /// Create a new `TextSink` writing to `writer`.
+    #[inline]
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Take this `TextSink`, and return the writer it wrote to.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl TextSink<BufWriter<File>> {
+    /// Create a new `TextSink` writing to a file at `path`, replacing it if it exists.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be created.
+    #[inline]
+    pub fn create(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TranscriptSink for TextSink<W> {
+    #[inline]
+    fn write_segment(&mut self, segment: &Segment) -> crate::Result<()> {
+        if segment.text.trim().is_empty() {
+            return Ok(());
+        }
+        Ok(writeln!(self.writer, "{}", segment.text)?)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> crate::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// How [`SrtSink`] and [`VttSink`] lay out subtitles.
+///
+/// By default, each segment is written as a single cue, on a single line.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubtitleOptions {
+    /// Wrap lines longer than this many characters between words.
+    ///
+    /// A single word longer than this gets a line of its own.
+    pub max_chars_per_line: Option<usize>,
+    /// Split segments that wrap onto more than this many lines into several cues,
+    /// timed by the words they start with.
+    pub max_lines: Option<usize>,
+    /// Show every cue for at least this long, even if the words in it were spoken faster.
+    ///
+    /// Cues after one that is held on screen longer are pushed back,
+    /// so the cues of a segment never overlap.
+    pub min_duration: Duration,
+    /// Tag every word with the time it was spoken, for players that highlight words
+    /// as they are spoken, karaoke style.
+    ///
+    /// Only WebVTT has a standard way to do this, so [`SrtSink`] ignores it.
+    pub karaoke_word_tags: bool,
+}
+
+impl SubtitleOptions {
+    /// Set [`max_chars_per_line`](SubtitleOptions::max_chars_per_line).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_chars_per_line(mut self, max_chars_per_line: Option<usize>) -> Self {
+        self.max_chars_per_line = max_chars_per_line;
+        self
+    }
+
+    /// Set [`max_lines`](SubtitleOptions::max_lines).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_lines(mut self, max_lines: Option<usize>) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Set [`min_duration`](SubtitleOptions::min_duration).
+    #[inline]
+    #[must_use]
+    pub const fn with_min_duration(mut self, min_duration: Duration) -> Self {
+        self.min_duration = min_duration;
+        self
+    }
+
+    /// Set [`karaoke_word_tags`](SubtitleOptions::karaoke_word_tags).
+    #[inline]
+    #[must_use]
+    pub const fn with_karaoke_word_tags(mut self, karaoke_word_tags: bool) -> Self {
+        self.karaoke_word_tags = karaoke_word_tags;
+        self
+    }
+}
+
+/// A line of a cue: each word, with the offset it starts at.
+type Line<'a> = Vec<(Duration, &'a str)>;
+
+/// A single subtitle, shown from `start` to `end`.
+struct Cue<'a> {
+    start: Duration,
+    end: Duration,
+    lines: Vec<Line<'a>>,
+}
+
+/// Split `segment` into cues, as laid out by `options`.
+fn cues<'a>(segment: &'a Segment, options: &SubtitleOptions) -> Vec<Cue<'a>> {
+    let mut lines: Vec<Line<'a>> = Vec::new();
+    let mut width = 0;
+    for (start, word) in timed_words(segment) {
+        let len = word.chars().count();
+        match lines.last_mut() {
+            Some(line)
+                if options
+                    .max_chars_per_line
+                    .is_none_or(|max| width + 1 + len <= max) =>
+            {
+                line.push((start, word));
+                width += 1 + len;
+            }
+            _ => {
+                lines.push(vec![(start, word)]);
+                width = len;
+            }
+        }
+    }
+    if lines.is_empty() {
+        lines.push(Vec::new());
+    }
+
+    let chunks: Vec<_> = lines
+        .chunks(options.max_lines.unwrap_or(usize::MAX).max(1))
+        .collect();
+    let first_word = |chunk: &[Line<'a>]| chunk.first().and_then(|line| line.first()).map(|w| w.0);
+    let mut cues: Vec<Cue<'a>> = Vec::with_capacity(chunks.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        let mut start = if i == 0 {
+            segment.start
+        } else {
+            first_word(chunk).unwrap_or(segment.start)
+        };
+        // a cue held on screen for the minimum duration pushes back the one after it
+        if let Some(previous) = cues.last() {
+            start = start.max(previous.end);
+        }
+        let end = chunks
+            .get(i + 1)
+            .and_then(|next| first_word(next))
+            .unwrap_or(segment.end)
+            .max(start + options.min_duration);
+        cues.push(Cue {
+            start,
+            end,
+            lines: chunk.to_vec(),
+        });
+    }
+    cues
+}
+
+/// The words of `segment` with their start offsets.
+///
+/// Segments without word timings have their text split on whitespace,
+/// with the start of each word estimated from how far into the text it is.
+fn timed_words(segment: &Segment) -> Vec<(Duration, &str)> {
+    if !segment.words.is_empty() {
+        return segment
+            .words
+            .iter()
+            .map(|word| (word.start, word.text.as_str()))
+            .collect();
+    }
+
+    let total = segment.text.chars().count().max(1) as u32;
+    let span = segment.end.saturating_sub(segment.start);
+    let mut chars = 0;
+    segment
+        .text
+        .split(' ')
+        .filter_map(|word| {
+            let start = segment.start + span * chars / total;
+            chars += word.chars().count() as u32 + 1;
+            (!word.is_empty()).then_some((start, word))
+        })
+        .collect()
+}
+
+/// Writes segments as SubRip (`.srt`) subtitles.
+pub struct SrtSink<W: Write> {
+    writer: W,
+    index: u64,
+    options: SubtitleOptions,
+}
+
+impl<W: Write> SrtSink<W> {
+    /// Create a new `SrtSink` writing to `writer`, with the default [`SubtitleOptions`].
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            index: 0,
+            options: SubtitleOptions::default(),
+        }
+    }
+
+    /// Lay out subtitles with `options`, returning the `SrtSink`.
+    #[inline]
+    #[must_use]
+    pub fn with_options(mut self, options: SubtitleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Take this `SrtSink`, and return the writer it wrote to.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl SrtSink<BufWriter<File>> {
+    /// Create a new `SrtSink` writing to a file at `path`, replacing it if it exists.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be created.
+    #[inline]
+    pub fn create(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TranscriptSink for SrtSink<W> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn write_segment(&mut self, segment: &Segment) -> crate::Result<()> {
+        if segment.text.trim().is_empty() {
+            return Ok(());
+        }
+        for cue in cues(segment, &self.options) {
+            self.index += 1;
+            writeln!(
+                self.writer,
+                "{}\n{} --> {}",
+                self.index,
+                Timestamp(cue.start, ','),
+                Timestamp(cue.end, ',')
+            )?;
+            for line in &cue.lines {
+                let words: Vec<_> = line.iter().map(|(_, word)| *word).collect();
+                writeln!(self.writer, "{}", words.join(" "))?;
+            }
+            writeln!(self.writer)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> crate::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Writes segments as WebVTT (`.vtt`) subtitles.
+pub struct VttSink<W: Write> {
+    writer: W,
+    wrote_header: bool,
+    options: SubtitleOptions,
+}
+
+impl<W: Write> VttSink<W> {
+    /// Create a new `VttSink` writing to `writer`, with the default [`SubtitleOptions`].
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            wrote_header: false,
+            options: SubtitleOptions::default(),
+        }
+    }
+
+    /// Lay out subtitles with `options`, returning the `VttSink`.
+    #[inline]
+    #[must_use]
+    pub fn with_options(mut self, options: SubtitleOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Take this `VttSink`, and return the writer it wrote to.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_header(&mut self) -> crate::Result<()> {
+        if !self.wrote_header {
+            self.writer.write_all(b"WEBVTT\n\n")?;
+            self.wrote_header = true;
+        }
+        Ok(())
+    }
+}
+
+impl VttSink<BufWriter<File>> {
+    /// Create a new `VttSink` writing to a file at `path`, replacing it if it exists.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be created.
+    #[inline]
+    pub fn create(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> TranscriptSink for VttSink<W> {
+    #[allow(clippy::missing_inline_in_public_items)]
+    fn write_segment(&mut self, segment: &Segment) -> crate::Result<()> {
+        if segment.text.trim().is_empty() {
+            return Ok(());
+        }
+        self.write_header()?;
+        for cue in cues(segment, &self.options) {
+            writeln!(
+                self.writer,
+                "{} --> {}",
+                Timestamp(cue.start, '.'),
+                Timestamp(cue.end, '.')
+            )?;
+            for line in &cue.lines {
+                let words: Vec<_> = line
+                    .iter()
+                    .map(|(start, word)| {
+                        if !self.options.karaoke_word_tags {
+                            (*word).to_string()
+                        } else if *start > cue.start && *start < cue.end {
+                            format!("<{}><c>{}</c>", Timestamp(*start, '.'), word)
+                        } else {
+                            format!("<c>{}</c>", word)
+                        }
+                    })
+                    .collect();
+                writeln!(self.writer, "{}", words.join(" "))?;
+            }
+            writeln!(self.writer)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> crate::Result<()> {
+        self.write_header()?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+/// Formats a duration as `HH:MM:SS`, then the separator, then `mmm`,
+/// rounded to the nearest millisecond.
+struct Timestamp(Duration, char);
+
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let millis = (self.0.as_nanos() + 500_000) / 1_000_000;
+        let secs = millis / 1000;
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:03}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            self.1,
+            millis % 1000
+        )
+    }
+}
+
+/// Writes each segment as a line of JSON.
+///
+/// Unlike the other sinks, this writes segments without any text too,
+/// as their timings and confidence are still useful.
+#[cfg(feature = "jsonl")]
+pub struct JsonlSink<W: Write> {
+    writer: W,
+}
+
+#[cfg(feature = "jsonl")]
+impl<W: Write> JsonlSink<W> {
+    /// Create a new `JsonlSink` writing to `writer`.
+    #[inline]
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Take this `JsonlSink`, and return the writer it wrote to.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "jsonl")]
+impl JsonlSink<BufWriter<File>> {
+    /// Create a new `JsonlSink` writing to a file at `path`, replacing it if it exists.
+    ///
+    /// # Errors
+    /// Returns an error if the file could not be created.
+    #[inline]
+    pub fn create(path: impl AsRef<Path>) -> crate::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+#[cfg(feature = "jsonl")]
+impl<W: Write> TranscriptSink for JsonlSink<W> {
+    #[inline]
+    fn write_segment(&mut self, segment: &Segment) -> crate::Result<()> {
+        serde_json::to_writer(&mut self.writer, segment)?;
+        Ok(writeln!(self.writer)?)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> crate::Result<()> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, start: Duration, end: Duration) -> Segment {
+        Segment {
+            text: text.to_string(),
+            start,
+            end,
+            words: Vec::new(),
+            tokens: Vec::new(),
+            confidence: 0.0,
+            speaker: None,
+            model_generation: 0,
+        }
+    }
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            segment("hello world", secs(0.0), secs(1.9994)),
+            segment("", secs(2.0), secs(3.0)),
+            segment("fake speech", secs(3599.5), secs(3599.9996)),
+            segment(" ", secs(3600.0), secs(3601.0)),
+            segment("hello again", secs(3661.25), secs(3662.0)),
+        ]
+    }
+
+    fn write_all(sink: &mut impl TranscriptSink) {
+        for segment in segments() {
+            sink.write_segment(&segment).unwrap();
+        }
+        sink.flush().unwrap();
+    }
+
+    /// Counts how many times it was flushed.
+    #[derive(Default)]
+    struct Flushes(Vec<u8>, usize);
+
+    impl Write for Flushes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.1 += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn timestamps_round_to_the_millisecond() {
+        let formatted = |duration: Duration| Timestamp(duration, ',').to_string();
+        assert_eq!(formatted(Duration::ZERO), "00:00:00,000");
+        assert_eq!(formatted(Duration::from_micros(1499)), "00:00:00,001");
+        assert_eq!(formatted(Duration::from_micros(1500)), "00:00:00,002");
+        assert_eq!(formatted(Duration::from_micros(59_999_500)), "00:01:00,000");
+        assert_eq!(formatted(secs(3599.9996)), "01:00:00,000");
+        assert_eq!(formatted(secs(3661.25)), "01:01:01,250");
+        assert_eq!(
+            Timestamp(Duration::from_secs(100 * 3600 + 1), '.').to_string(),
+            "100:00:01.000"
+        );
+    }
+
+    #[test]
+    fn text_sink_writes_a_line_per_segment() {
+        let mut sink = TextSink::new(Vec::new());
+        write_all(&mut sink);
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "hello world\nfake speech\nhello again\n"
+        );
+    }
+
+    #[test]
+    fn srt_sink_numbers_cues() {
+        let mut sink = SrtSink::new(Vec::new());
+        write_all(&mut sink);
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "1\n\
+             00:00:00,000 --> 00:00:01,999\n\
+             hello world\n\
+             \n\
+             2\n\
+             00:59:59,500 --> 01:00:00,000\n\
+             fake speech\n\
+             \n\
+             3\n\
+             01:01:01,250 --> 01:01:02,000\n\
+             hello again\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn vtt_sink_writes_the_header_once() {
+        let mut sink = VttSink::new(Vec::new());
+        write_all(&mut sink);
+        assert_eq!(
+            String::from_utf8(sink.into_inner()).unwrap(),
+            "WEBVTT\n\
+             \n\
+             00:00:00.000 --> 00:00:01.999\n\
+             hello world\n\
+             \n\
+             00:59:59.500 --> 01:00:00.000\n\
+             fake speech\n\
+             \n\
+             01:01:01.250 --> 01:01:02.000\n\
+             hello again\n\
+             \n"
+        );
+
+        // an empty transcript is still a valid file
+        let mut sink = VttSink::new(Vec::new());
+        sink.write_segment(&segment("", secs(0.0), secs(1.0)))
+            .unwrap();
+        assert!(sink.writer.is_empty());
+        sink.flush().unwrap();
+        assert_eq!(sink.into_inner(), b"WEBVTT\n\n");
+    }
+
+    #[test]
+    fn sinks_only_flush_when_asked() {
+        let mut text = TextSink::new(Flushes::default());
+        let mut srt = SrtSink::new(Flushes::default());
+        let mut vtt = VttSink::new(Flushes::default());
+        let sinks: [&mut dyn TranscriptSink; 3] = [&mut text, &mut srt, &mut vtt];
+        for sink in sinks {
+            for segment in segments() {
+                sink.write_segment(&segment).unwrap();
+            }
+            sink.flush().unwrap();
+        }
+        assert_eq!(text.into_inner().1, 1);
+        assert_eq!(srt.into_inner().1, 1);
+        assert_eq!(vtt.into_inner().1, 1);
+    }
+
+    /// A segment of `words`, each with the millisecond it starts at, ending at `end_ms`.
+    fn spoken(words: &[(u64, &str)], end_ms: u64) -> Segment {
+        let text = words.iter().map(|(_, word)| *word).collect::<Vec<_>>();
+        let mut segment = segment(
+            &text.join(" "),
+            Duration::from_millis(words[0].0),
+            Duration::from_millis(end_ms),
+        );
+        segment.words = words
+            .iter()
+            .map(|&(ms, word)| crate::Word {
+                text: word.to_string(),
+                start: Duration::from_millis(ms),
+                timestep: (ms / 20) as u32,
+                speaker: None,
+            })
+            .collect();
+        segment
+    }
+
+    fn srt(options: SubtitleOptions, segment: &Segment) -> String {
+        let mut sink = SrtSink::new(Vec::new()).with_options(options);
+        sink.write_segment(segment).unwrap();
+        String::from_utf8(sink.into_inner()).unwrap()
+    }
+
+    fn vtt(options: SubtitleOptions, segment: &Segment) -> String {
+        let mut sink = VttSink::new(Vec::new()).with_options(options);
+        sink.write_segment(segment).unwrap();
+        String::from_utf8(sink.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn lines_wrap_between_words() {
+        let segment = spoken(
+            &[
+                (0, "the"),
+                (200, "quick"),
+                (500, "brown"),
+                (800, "fox"),
+                (1000, "outmanoeuvres"),
+                (1800, "me"),
+            ],
+            2000,
+        );
+        let options = SubtitleOptions::default().with_max_chars_per_line(Some(9));
+        assert_eq!(
+            srt(options, &segment),
+            "1\n\
+             00:00:00,000 --> 00:00:02,000\n\
+             the quick\n\
+             brown fox\n\
+             outmanoeuvres\n\
+             me\n\
+             \n"
+        );
+
+        // cues after the first start with their first word
+        let options = options.with_max_lines(Some(2));
+        assert_eq!(
+            srt(options, &segment),
+            "1\n\
+             00:00:00,000 --> 00:00:01,000\n\
+             the quick\n\
+             brown fox\n\
+             \n\
+             2\n\
+             00:00:01,000 --> 00:00:02,000\n\
+             outmanoeuvres\n\
+             me\n\
+             \n"
+        );
+
+        // without word timings, words are timed by how far into the text they are:
+        // 20 of the 36 characters in
+        let mut untimed = segment.clone();
+        untimed.words.clear();
+        assert_eq!(
+            srt(options, &untimed),
+            "1\n\
+             00:00:00,000 --> 00:00:01,111\n\
+             the quick\n\
+             brown fox\n\
+             \n\
+             2\n\
+             00:00:01,111 --> 00:00:02,000\n\
+             outmanoeuvres\n\
+             me\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn short_cues_push_back_the_next_one() {
+        let segment = spoken(&[(0, "hello"), (300, "world"), (600, "again")], 1200);
+        let options = SubtitleOptions::default()
+            .with_max_chars_per_line(Some(5))
+            .with_max_lines(Some(1))
+            .with_min_duration(Duration::from_millis(500));
+        assert_eq!(
+            vtt(options, &segment),
+            "WEBVTT\n\
+             \n\
+             00:00:00.000 --> 00:00:00.500\n\
+             hello\n\
+             \n\
+             00:00:00.500 --> 00:00:01.000\n\
+             world\n\
+             \n\
+             00:00:01.000 --> 00:00:01.500\n\
+             again\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn karaoke_tags_time_every_word_after_the_first() {
+        let segment = spoken(&[(1000, "hello"), (1500, "fake"), (2250, "world")], 3000);
+        let options = SubtitleOptions::default()
+            .with_max_chars_per_line(Some(10))
+            .with_karaoke_word_tags(true);
+        assert_eq!(
+            vtt(options, &segment),
+            "WEBVTT\n\
+             \n\
+             00:00:01.000 --> 00:00:03.000\n\
+             <c>hello</c> <00:00:01.500><c>fake</c>\n\
+             <00:00:02.250><c>world</c>\n\
+             \n"
+        );
+
+        // SubRip has no way to tag words
+        assert_eq!(
+            srt(options, &segment),
+            "1\n\
+             00:00:01,000 --> 00:00:03,000\n\
+             hello fake\n\
+             world\n\
+             \n"
+        );
+    }
+
+    #[cfg(feature = "jsonl")]
+    #[test]
+    fn jsonl_sink_keeps_segments_without_text() {
+        let mut sink = JsonlSink::new(Flushes::default());
+        write_all(&mut sink);
+        let Flushes(written, flushes) = sink.into_inner();
+        assert_eq!(flushes, 1);
+        let texts: Vec<_> = String::from_utf8(written)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Segment>(line).unwrap().text)
+            .collect();
+        assert_eq!(
+            texts,
+            ["hello world", "", "fake speech", " ", "hello again"]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Counts of the audio that went through a [`Transcriber`](crate::Transcriber)
+/// or [`ThreadSafeStream`](crate::ThreadSafeStream), and of the audio that was lost along the way.
+///
+/// All counts are in samples. A sample can be counted as both fed and dropped,
+/// if it was fed to a stream that was then discarded.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct InferenceStats {
+    /// Samples pushed in by the caller.
+    pub samples_received: u64,
+    /// Samples fed to the model.
+    pub samples_fed: u64,
+    /// Samples waiting in a queue to be fed to the model.
+    pub samples_queued: u64,
+    /// Samples dropped because the queue in front of the model was full.
+    pub dropped_backpressure: u64,
+    /// Samples dropped because the voice activity detector judged them not to be speech:
+    /// silence outside of utterances and its pre-roll, and utterances that were too short.
+    pub dropped_gate: u64,
+    /// Samples that never made it into a final decode,
+    /// because they were discarded before the stream or utterance was finished.
+    pub dropped_cancelled: u64,
+}
+
+impl InferenceStats {
+    /// The total number of samples dropped, for any reason.
+    #[inline]
+    #[must_use]
+    pub const fn dropped(&self) -> u64 {
+        self.dropped_backpressure + self.dropped_gate + self.dropped_cancelled
+    }
+}
+
+/// A live view of the [`InferenceStats`] of a pipeline,
+/// that can be read from any thread, even after the pipeline is gone.
+#[derive(Clone, Debug, Default)]
+pub struct StatsHandle(Arc<Counters>);
+
+#[derive(Debug, Default)]
+struct Counters {
+    received: AtomicU64,
+    fed: AtomicU64,
+    queued: AtomicU64,
+    backpressure: AtomicU64,
+    gate: AtomicU64,
+    cancelled: AtomicU64,
+}
+
+impl StatsHandle {
+    /// Return the counts as they are now.
+    #[inline]
+    #[must_use]
+    pub fn snapshot(&self) -> InferenceStats {
+        InferenceStats {
+            samples_received: self.0.received.load(Ordering::Relaxed),
+            samples_fed: self.0.fed.load(Ordering::Relaxed),
+            samples_queued: self.0.queued.load(Ordering::Relaxed),
+            dropped_backpressure: self.0.backpressure.load(Ordering::Relaxed),
+            dropped_gate: self.0.gate.load(Ordering::Relaxed),
+            dropped_cancelled: self.0.cancelled.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn received(&self, samples: usize) {
+        self.0.received.fetch_add(samples as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn fed(&self, samples: usize) {
+        self.0.fed.fetch_add(samples as u64, Ordering::Relaxed);
+    }
+
+    /// Queue up `samples`, unless that would put more than `max` in the queue.
+    /// If `oversized` is set, more than `max` samples are still queued when nothing else is.
+    /// Returns whether they were queued.
+    pub(crate) fn try_queue(&self, samples: usize, max: Option<usize>, oversized: bool) -> bool {
+        let samples = samples as u64;
+        self.0
+            .queued
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |queued| match max {
+                Some(_) if oversized && queued == 0 => Some(samples),
+                Some(max) if queued + samples > max as u64 => None,
+                _ => Some(queued + samples),
+            })
+            .is_ok()
+    }
+
+    pub(crate) fn dropped_backpressure(&self, samples: usize) {
+        self.0
+            .backpressure
+            .fetch_add(samples as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dequeued(&self, samples: usize) {
+        self.0.queued.fetch_sub(samples as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn gated(&self, samples: u64) {
+        self.0.gate.fetch_add(samples, Ordering::Relaxed);
+    }
+
+    pub(crate) fn cancelled(&self, samples: u64) {
+        self.0.cancelled.fetch_add(samples, Ordering::Relaxed);
+    }
+}
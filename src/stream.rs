@@ -1,5 +1,47 @@
-use crate::{Metadata, Model};
+use crate::audio::{duration_of, samples_in, PcmFormat, Preprocessor};
+use crate::{Metadata, Model, Transcription};
 use std::ffi::CStr;
+use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+/// An operation on a [`Stream`], as reported to its timing observer.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StreamOperation {
+    /// Audio was fed to the stream.
+    Feed,
+    /// An intermediate decode was computed.
+    IntermediateDecode,
+    /// An intermediate decode was computed after flushing buffers.
+    IntermediateDecodeWithBufferFlush,
+    /// Buffers were flushed, without keeping the intermediate decode.
+    FlushBuffers,
+    /// The stream was finished and the final decode was computed.
+    Finish,
+}
+
+/// How long an operation on a [`Stream`] took.
+///
+/// See [`Stream::set_timing_observer`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug)]
+pub struct StreamTiming {
+    /// The operation that was timed.
+    pub operation: StreamOperation,
+    /// Time spent in the stream's [`Preprocessor`], if any.
+    pub preprocess: Duration,
+    /// Time spent in `libstt`, feeding audio to the model or decoding.
+    pub duration: Duration,
+    /// Number of samples fed to the model by this operation, after preprocessing.
+    pub samples: usize,
+    /// How long the call waited in a [`ThreadSafeStream`](crate::ThreadSafeStream)'s queue
+    /// before its worker thread started running it.
+    ///
+    /// Only the first operation run for each call reports this.
+    /// It is `None` for the others, and for streams used directly.
+    pub queue_wait: Option<Duration>,
+}
 
 /// Streaming inference state.
 pub struct Stream<'a> {
@@ -8,6 +50,70 @@ pub struct Stream<'a> {
     /// True if this state has already been freed.
     /// This is used to prevent double-freeing.
     pub(crate) already_freed: bool,
+    /// Processing applied to audio before it is fed to the model.
+    pub(crate) preprocessor: Option<Box<dyn Preprocessor>>,
+    /// Scratch space for the output of `preprocessor`.
+    pub(crate) processed: Vec<i16>,
+    /// Scratch space for samples decoded from bytes, or silence, before they are fed.
+    pub(crate) decoded: Vec<i16>,
+    /// Called with the timing of every operation on this stream.
+    pub(crate) timing_observer: Option<Box<dyn Fn(StreamTiming) + Send>>,
+    /// Total number of samples fed to the model, after preprocessing.
+    pub(crate) samples_fed: u64,
+    /// Total time spent preprocessing, feeding and decoding.
+    pub(crate) processing_time: Duration,
+    /// How long the call behind the next operation waited in a queue, to report to the observer.
+    pub(crate) queue_wait: Option<Duration>,
+    /// A trailing byte from the last call to `feed_audio_bytes` that didn't make up a whole sample.
+    pub(crate) partial_sample: Option<u8>,
+    /// The bytes of a character cut off at the end of the last intermediate decode.
+    pub(crate) partial_utf8: Vec<u8>,
+    /// The model state this stream was created with, to check it is still the one referenced.
+    #[cfg(feature = "strict")]
+    pub(crate) origin: *mut coqui_stt_sys::ModelState,
+}
+
+/// A streaming state that is freed when dropped,
+/// for types that own both it and the [`Model`] it was created from.
+///
+/// Owners must declare this before the model, so it is always freed before the model is.
+pub(crate) struct OwnedState(NonNull<coqui_stt_sys::StreamingState>);
+
+impl OwnedState {
+    /// Create a new streaming state from `model`.
+    pub(crate) fn new(model: &mut Model) -> crate::Result<Self> {
+        let stream = Stream::from_model(model)?;
+        // SAFETY: the state is immediately wrapped in an `OwnedState`, which frees it
+        let state = unsafe { stream.into_state() };
+        NonNull::new(state).map(Self).ok_or(crate::Error::Unknown)
+    }
+
+    /// Use this state through a [`Stream`], without handing over ownership of it.
+    ///
+    /// # Safety
+    /// `model` must be the model this state was created from.
+    pub(crate) unsafe fn as_stream<'a>(&self, model: &'a mut Model) -> Stream<'a> {
+        // this `OwnedState` remains responsible for freeing the state
+        Stream::from_parts(model, self.0.as_ptr(), true)
+    }
+
+    /// Hand ownership of this state over to a [`Stream`].
+    ///
+    /// # Safety
+    /// `model` must be the model this state was created from.
+    pub(crate) unsafe fn into_stream(self, model: &mut Model) -> Stream<'_> {
+        let state = ManuallyDrop::new(self);
+        Stream::from_parts(model, state.0.as_ptr(), false)
+    }
+}
+
+impl Drop for OwnedState {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the state is only ever freed here, or by the `Stream` it was handed over to,
+        // and the model it references outlives it
+        unsafe { coqui_stt_sys::STT_FreeStream(self.0.as_ptr()) }
+    }
 }
 
 // NOTE:
@@ -17,18 +123,60 @@ pub struct Stream<'a> {
 // the compiler statically enforces that this is used from
 // only one thread at a time with mutable references on all
 // functions that access the C API.
+// That promise can be broken with `into_state` and `from_ptr`,
+// which debug builds catch at runtime with `InUse`,
+// and the `unsync` feature removes the Sync implementation altogether.
 unsafe impl Send for Stream<'_> {}
+#[cfg(not(feature = "unsync"))]
 unsafe impl Sync for Stream<'_> {}
 
 impl Drop for Stream<'_> {
     #[inline]
     fn drop(&mut self) {
         if !self.already_freed {
+            let _in_use = InUse::new(self.model);
             unsafe { coqui_stt_sys::STT_FreeStream(self.state) }
         }
     }
 }
 
+/// Marks the model of a stream as in use by a call into `libstt` for as long as it is alive.
+///
+/// Two `Stream`s recreated from the same pointer with [`Stream::from_ptr`]
+/// can be used from different threads at the same time, which `libstt` does not support.
+/// Both reference the same [`Model`], so in debug builds, its flag catches this and panics,
+/// rather than corrupting the state. In release builds, this does nothing.
+///
+/// This holds a pointer rather than a reference to the flag, so the stream can still be
+/// borrowed mutably during the call. Guards never outlive the call they were made for,
+/// so never outlive the model.
+struct InUse(#[cfg(debug_assertions)] *const std::sync::atomic::AtomicBool);
+
+impl InUse {
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    // deliberately loud: carrying on would corrupt the state in `libstt`
+    #[allow(clippy::panic)]
+    fn new(model: &Model) -> Self {
+        #[cfg(debug_assertions)]
+        {
+            if model.1.swap(true, std::sync::atomic::Ordering::Acquire) {
+                panic!("coqui-stt: a model was used by two streams at once");
+            }
+            Self(&model.1)
+        }
+        #[cfg(not(debug_assertions))]
+        Self()
+    }
+}
+
+impl Drop for InUse {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        // SAFETY: the model outlives the guard
+        unsafe { &*self.0 }.store(false, std::sync::atomic::Ordering::Release);
+    }
+}
+
 impl<'a> Stream<'a> {
     /// Create a new `Stream` from a [`Model`](Model).
     ///
@@ -41,23 +189,20 @@ impl<'a> Stream<'a> {
         let retval =
             unsafe { coqui_stt_sys::STT_CreateStream(model.0, std::ptr::addr_of_mut!(state)) };
 
-        if let Some(e) = crate::Error::from_c_int(retval) {
+        if let Some(e) = crate::Error::from_c_call("STT_CreateStream", retval) {
             return Err(e);
         }
 
         if state.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_CreateStream"));
         }
 
-        Ok(Self {
-            model,
-            state,
-            already_freed: false,
-        })
+        Ok(Self::from_parts(model, state, false))
     }
 
     /// Get the inner pointer to the [`StreamingState`](coqui_stt_sys::StreamingState)
     /// of this `Stream`.
+    /// See [`raw::stream_into_state`](crate::raw::stream_into_state), which this calls.
     ///
     /// # Safety
     /// Once this is called, the memory management of the `Stream` is no longer handled for you.
@@ -74,13 +219,31 @@ impl<'a> Stream<'a> {
     /// [`from_ptr`]: Stream::from_ptr
     #[inline]
     #[must_use]
-    pub unsafe fn into_state(mut self) -> *mut coqui_stt_sys::StreamingState {
-        self.already_freed = true;
-        self.state
+    pub unsafe fn into_state(self) -> *mut coqui_stt_sys::StreamingState {
+        crate::raw::stream_into_state(self)
+    }
+
+    /// Get the inner pointer to the [`StreamingState`](coqui_stt_sys::StreamingState)
+    /// of this `Stream`, without giving up ownership of it.
+    ///
+    /// This is useful to call a function only available in the raw bindings mid-stream,
+    /// then carry on using this `Stream`.
+    /// See [`raw::stream_as_state_ptr`](crate::raw::stream_as_state_ptr), which this calls.
+    ///
+    /// # Safety
+    /// The pointer must not be freed, or used after this `Stream` is dropped or finished.
+    /// It must not be used while another method is being called on this `Stream`.
+    ///
+    /// Audio still buffered in this stream's [`Preprocessor`] has not been fed to the state yet.
+    #[inline]
+    #[must_use]
+    pub unsafe fn as_state_ptr(&mut self) -> *mut coqui_stt_sys::StreamingState {
+        crate::raw::stream_as_state_ptr(self)
     }
 
     /// Recreate a `Stream` with a pointer to a [`StreamingState`]
     /// and a pointer to the model the [`StreamingState`] references.
+    /// See [`raw::stream_from_state`](crate::raw::stream_from_state), which this calls.
     ///
     /// # Safety
     /// * The `state` must point to a valid [`StreamingState`].
@@ -93,10 +256,31 @@ impl<'a> Stream<'a> {
         model: &'a mut Model,
         state: *mut coqui_stt_sys::StreamingState,
     ) -> Stream<'a> {
+        crate::raw::stream_from_state(model, state)
+    }
+
+    pub(crate) const fn from_parts(
+        model: &'a mut Model,
+        state: *mut coqui_stt_sys::StreamingState,
+        already_freed: bool,
+    ) -> Stream<'a> {
+        #[cfg(feature = "strict")]
+        let origin = model.0;
         Self {
             model,
             state,
-            already_freed: false,
+            already_freed,
+            preprocessor: None,
+            processed: Vec::new(),
+            decoded: Vec::new(),
+            timing_observer: None,
+            samples_fed: 0,
+            processing_time: Duration::ZERO,
+            queue_wait: None,
+            partial_sample: None,
+            partial_utf8: Vec::new(),
+            #[cfg(feature = "strict")]
+            origin,
         }
     }
 
@@ -114,9 +298,243 @@ impl<'a> Stream<'a> {
         self.model
     }
 
+    /// Check that this stream can still safely be used, and abort the process if it can't.
+    ///
+    /// This catches misuse that would otherwise be undefined behaviour inside `libstt`,
+    /// such as swapping out the [`Model`] behind [`model_mut`](Stream::model_mut)
+    /// for another one, then dropping the original while this stream still uses it.
+    ///
+    /// With the `strict` feature enabled, this is checked before every call into `libstt`.
+    /// Aborting (rather than panicking) makes sure nothing else touches the state on the way out.
+    #[cfg(feature = "strict")]
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn debug_assert_liveness(&self) {
+        let problem = if self.state.is_null() {
+            "the streaming state is null"
+        } else if self.model.0.is_null() {
+            "the model state is null"
+        } else if self.model.0 != self.origin {
+            "the model was replaced after the stream was created"
+        } else {
+            return;
+        };
+
+        eprintln!(
+            "coqui-stt: stream used after it became invalid: {}",
+            problem
+        );
+        std::process::abort();
+    }
+
+    /// Check the stream can still be used, and mark it as in use until the guard is dropped.
+    #[inline]
+    fn enter(&self) -> InUse {
+        #[cfg(feature = "strict")]
+        self.debug_assert_liveness();
+        InUse::new(self.model)
+    }
+
+    /// Set the [`Preprocessor`] applied to all audio fed to this stream from now on.
+    ///
+    /// To apply several, chain them together with a [`Pipeline`](crate::audio::Pipeline).
+    ///
+    /// Any audio still buffered in the previous preprocessor is fed to the model first.
+    ///
+    /// # Errors
+    /// Returns an error if the preprocessor does not support the model's sample rate.
+    #[inline]
+    pub fn set_preprocessor(
+        &mut self,
+        preprocessor: impl Preprocessor + 'static,
+    ) -> crate::Result<()> {
+        self._set_preprocessor(Box::new(preprocessor))
+    }
+
+    fn _set_preprocessor(&mut self, mut preprocessor: Box<dyn Preprocessor>) -> crate::Result<()> {
+        preprocessor.prepare(self.model.get_sample_rate() as u32)?;
+        self.flush_preprocessor();
+        self.preprocessor = Some(preprocessor);
+        Ok(())
+    }
+
+    /// Remove the [`Preprocessor`] from this stream, returning it.
+    ///
+    /// Any audio still buffered in it is fed to the model first.
+    #[inline]
+    pub fn take_preprocessor(&mut self) -> Option<Box<dyn Preprocessor>> {
+        self.flush_preprocessor();
+        self.preprocessor.take()
+    }
+
+    /// Set a function to be called with the [`StreamTiming`] of every operation on this stream.
+    ///
+    /// This is useful for pinpointing where latency comes from:
+    /// preprocessing, feeding audio through the acoustic model, or decoding.
+    ///
+    /// The observer is called on the thread performing the operation,
+    /// right after it completes, so it should return quickly.
+    #[inline]
+    pub fn set_timing_observer(&mut self, observer: impl Fn(StreamTiming) + Send + 'static) {
+        self.timing_observer = Some(Box::new(observer));
+    }
+
+    /// Remove the timing observer set with [`set_timing_observer`](Stream::set_timing_observer).
+    #[inline]
+    pub fn clear_timing_observer(&mut self) {
+        self.timing_observer = None;
+    }
+
+    fn observe(
+        &mut self,
+        operation: StreamOperation,
+        preprocess: Duration,
+        duration: Duration,
+        samples: usize,
+    ) {
+        self.samples_fed += samples as u64;
+        self.processing_time += preprocess + duration;
+        let queue_wait = self.queue_wait.take();
+
+        if let Some(observer) = self.timing_observer.as_ref() {
+            observer(StreamTiming {
+                operation,
+                preprocess,
+                duration,
+                samples,
+                queue_wait,
+            });
+        }
+    }
+
     /// Feed audio samples to an ongoing streaming inference.
+    ///
+    /// If a [`Preprocessor`] is set, the audio is passed through it first.
     #[inline]
     pub fn feed_audio(&mut self, buffer: &[i16]) {
+        let start = Instant::now();
+        let mut processed = std::mem::take(&mut self.processed);
+        processed.clear();
+        let audio = match self.preprocessor.as_mut() {
+            Some(preprocessor) => {
+                preprocessor.process(buffer, &mut processed);
+                &processed[..]
+            }
+            None => buffer,
+        };
+        let preprocess = start.elapsed();
+
+        self.feed_unprocessed(audio);
+        self.observe(
+            StreamOperation::Feed,
+            preprocess,
+            start.elapsed() - preprocess,
+            audio.len(),
+        );
+        self.processed = processed;
+    }
+
+    /// Reserve room for `samples` samples in the buffers used to feed audio,
+    /// for callers that know how much audio each call will carry.
+    ///
+    /// Audio fed through a [`Preprocessor`], or with
+    /// [`feed_audio_bytes`](Stream::feed_audio_bytes) or [`feed_silence`](Stream::feed_silence),
+    /// is copied into buffers owned by the stream. These grow to fit the largest call and are
+    /// reused, so this only saves the first few allocations, but it lets a stream fed
+    /// a 20ms packet at a time reach a steady state without allocating at all.
+    #[inline]
+    pub fn reserve_audio_hint(&mut self, samples: usize) {
+        self.processed
+            .reserve(samples.saturating_sub(self.processed.len()));
+        self.decoded
+            .reserve(samples.saturating_sub(self.decoded.len()));
+    }
+
+    /// Feed `duration` of silence to an ongoing streaming inference.
+    ///
+    /// This is useful to let the model finish decoding the last word
+    /// when the audio ends abruptly. Like any other audio,
+    /// the silence is passed through the [`Preprocessor`] if one is set.
+    #[inline]
+    pub fn feed_silence(&mut self, duration: Duration) {
+        let samples = samples_in(duration, self.model.get_sample_rate() as u32);
+        let mut silence = std::mem::take(&mut self.decoded);
+        silence.clear();
+        silence.resize(samples, 0);
+        self.feed_audio(&silence);
+        self.decoded = silence;
+    }
+
+    /// Feed raw PCM audio in the given [`PcmFormat`] to an ongoing streaming inference.
+    ///
+    /// `bytes` doesn't have to contain a whole number of samples:
+    /// a trailing partial sample is kept until the next call,
+    /// so audio can be fed straight from network packets as they arrive.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn feed_audio_bytes(&mut self, mut bytes: &[u8], format: PcmFormat) {
+        let mut samples = std::mem::take(&mut self.decoded);
+        samples.clear();
+
+        if let Some(first) = self.partial_sample.take() {
+            match bytes.split_first() {
+                Some((&second, rest)) => {
+                    format.decode_into(&[first, second], &mut samples);
+                    bytes = rest;
+                }
+                None => {
+                    self.partial_sample = Some(first);
+                    self.decoded = samples;
+                    return;
+                }
+            }
+        }
+        let (whole, rest) = bytes.split_at(bytes.len() - bytes.len() % format.bytes_per_sample());
+        format.decode_into(whole, &mut samples);
+        self.partial_sample = rest.first().copied();
+
+        self.feed_audio(&samples);
+        self.decoded = samples;
+    }
+
+    /// Feed raw signed 16-bit little-endian PCM audio to an ongoing streaming inference.
+    ///
+    /// See [`feed_audio_bytes`](Stream::feed_audio_bytes).
+    #[inline]
+    pub fn feed_audio_bytes_le(&mut self, bytes: &[u8]) {
+        self.feed_audio_bytes(bytes, PcmFormat::S16Le);
+    }
+
+    /// Feed raw signed 16-bit big-endian PCM audio to an ongoing streaming inference.
+    ///
+    /// See [`feed_audio_bytes`](Stream::feed_audio_bytes).
+    #[inline]
+    pub fn feed_audio_bytes_be(&mut self, bytes: &[u8]) {
+        self.feed_audio_bytes(bytes, PcmFormat::S16Be);
+    }
+
+    /// Feed any audio still buffered in the preprocessor to the model.
+    ///
+    /// Returns the time spent in the preprocessor, and the number of samples fed.
+    fn flush_preprocessor(&mut self) -> (Duration, usize) {
+        let preprocessor = match self.preprocessor.as_mut() {
+            Some(preprocessor) => preprocessor,
+            None => return (Duration::ZERO, 0),
+        };
+
+        let start = Instant::now();
+        let mut processed = std::mem::take(&mut self.processed);
+        processed.clear();
+        preprocessor.flush(&mut processed);
+        let preprocess = start.elapsed();
+
+        self.feed_unprocessed(&processed);
+        let samples = processed.len();
+        self.processed = processed;
+
+        (preprocess, samples)
+    }
+
+    fn feed_unprocessed(&mut self, buffer: &[i16]) {
+        let _in_use = self.enter();
         unsafe {
             coqui_stt_sys::STT_FeedAudioContent(
                 self.state,
@@ -128,14 +546,28 @@ impl<'a> Stream<'a> {
 
     /// Compute the intermediate decoding of an ongoing streaming inference.
     ///
+    /// If the decoded text ends part way through a multibyte character,
+    /// the partial character is held back instead of failing the whole decode,
+    /// and is available from [`pending_utf8`](Stream::pending_utf8).
+    /// Every intermediate decode covers all the audio fed so far,
+    /// so the character is returned in full once a later decode completes it.
+    ///
     /// # Errors
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[allow(clippy::missing_inline_in_public_items)]
     pub fn intermediate_decode(&mut self) -> crate::Result<String> {
+        let _in_use = self.enter();
+        let start = Instant::now();
         let ptr = unsafe { coqui_stt_sys::STT_IntermediateDecode(self.state as *const _) };
+        self.observe(
+            StreamOperation::IntermediateDecode,
+            Duration::ZERO,
+            start.elapsed(),
+            0,
+        );
 
         if ptr.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_IntermediateDecode"));
         }
 
         // SAFETY: STT_SpeechToText will always return a valid CStr
@@ -146,7 +578,7 @@ impl<'a> Stream<'a> {
         // SAFETY: the pointer the string points to is not used anywhere after this call
         unsafe { coqui_stt_sys::STT_FreeString(ptr) }
 
-        Ok(String::from_utf8(unchecked_str)?)
+        self.lenient_utf8(unchecked_str)
     }
 
     /// Compute the intermediate decoding of an ongoing streaming inference,
@@ -159,14 +591,48 @@ impl<'a> Stream<'a> {
         &mut self,
         num_results: u32,
     ) -> crate::Result<Metadata> {
+        let _in_use = self.enter();
+        let start = Instant::now();
         let ptr =
             unsafe { coqui_stt_sys::STT_IntermediateDecodeWithMetadata(self.state, num_results) };
+        self.observe(
+            StreamOperation::IntermediateDecode,
+            Duration::ZERO,
+            start.elapsed(),
+            0,
+        );
+
+        crate::Metadata::new("STT_IntermediateDecodeWithMetadata", ptr)
+    }
+
+    /// **EXPERIMENTAL**: Process all audio fed so far through the acoustic model,
+    /// such as at a segment boundary, without wanting the intermediate text.
+    ///
+    /// `libstt` has no call that only flushes buffers, so this is
+    /// [`intermediate_decode_with_buffer_flush`](Stream::intermediate_decode_with_buffer_flush)
+    /// with the text freed as soon as it is returned, instead of being copied into a `String`.
+    /// The same caveats about calling it too often apply.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn flush_buffers(&mut self) -> crate::Result<()> {
+        let _in_use = self.enter();
+        let start = Instant::now();
+        let ptr = unsafe { coqui_stt_sys::STT_IntermediateDecodeFlushBuffers(self.state) };
+        self.observe(
+            StreamOperation::FlushBuffers,
+            Duration::ZERO,
+            start.elapsed(),
+            0,
+        );
 
         if ptr.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_IntermediateDecodeFlushBuffers"));
         }
-
-        Ok(crate::Metadata::new(ptr))
+        // SAFETY: the string was just returned by `libstt`, and is never read
+        unsafe { coqui_stt_sys::STT_FreeString(ptr) }
+        Ok(())
     }
 
     /// **EXPERIMENTAL**: Compute the intermediate decoding of an ongoing streaming inference,
@@ -179,14 +645,25 @@ impl<'a> Stream<'a> {
     /// Calling this function too often will also degrade transcription accuracy due to
     /// trashing of the LSTM hidden state vectors.
     ///
+    /// A partial character at the end of the text is held back,
+    /// as in [`intermediate_decode`](Stream::intermediate_decode).
+    ///
     /// # Errors
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[allow(clippy::missing_inline_in_public_items)]
     pub fn intermediate_decode_with_buffer_flush(&mut self) -> crate::Result<String> {
+        let _in_use = self.enter();
+        let start = Instant::now();
         let ptr = unsafe { coqui_stt_sys::STT_IntermediateDecodeFlushBuffers(self.state) };
+        self.observe(
+            StreamOperation::IntermediateDecodeWithBufferFlush,
+            Duration::ZERO,
+            start.elapsed(),
+            0,
+        );
 
         if ptr.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_IntermediateDecodeFlushBuffers"));
         }
 
         // SAFETY: STT_SpeechToText will always return a valid CStr
@@ -197,7 +674,7 @@ impl<'a> Stream<'a> {
         // SAFETY: the pointer the string points to is not used anywhere after this call
         unsafe { coqui_stt_sys::STT_FreeString(ptr) }
 
-        Ok(String::from_utf8(unchecked_str)?)
+        self.lenient_utf8(unchecked_str)
     }
 
     /// **EXPERIMENTAL**: Compute the intermediate decoding of an ongoing streaming inference,
@@ -220,15 +697,42 @@ impl<'a> Stream<'a> {
         &mut self,
         num_results: u32,
     ) -> crate::Result<Metadata> {
+        let _in_use = self.enter();
+        let start = Instant::now();
         let ptr = unsafe {
             coqui_stt_sys::STT_IntermediateDecodeWithMetadataFlushBuffers(self.state, num_results)
         };
+        self.observe(
+            StreamOperation::IntermediateDecodeWithBufferFlush,
+            Duration::ZERO,
+            start.elapsed(),
+            0,
+        );
+
+        crate::Metadata::new("STT_IntermediateDecodeWithMetadataFlushBuffers", ptr)
+    }
 
-        if ptr.is_null() {
-            return Err(crate::Error::Unknown);
-        }
+    /// The bytes of a multibyte character that was cut off at the end
+    /// of the last intermediate decode, and held back from its text.
+    ///
+    /// Empty if the last intermediate decode ended on a character boundary.
+    #[inline]
+    #[must_use]
+    pub fn pending_utf8(&self) -> &[u8] {
+        &self.partial_utf8
+    }
 
-        Ok(crate::Metadata::new(ptr))
+    /// Convert the text of an intermediate decode to a `String`,
+    /// holding back a character cut off at the end.
+    fn lenient_utf8(&mut self, mut bytes: Vec<u8>) -> crate::Result<String> {
+        self.partial_utf8.clear();
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            // no error length means the text ends part way through a character
+            if e.error_len().is_none() {
+                self.partial_utf8 = bytes.split_off(e.valid_up_to());
+            }
+        }
+        Ok(String::from_utf8(bytes)?)
     }
 
     /// Compute the final decoding of an ongoing streaming inference and
@@ -241,12 +745,23 @@ impl<'a> Stream<'a> {
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[allow(clippy::missing_inline_in_public_items)]
     pub fn finish_stream(mut self) -> crate::Result<String> {
+        crate::blocking_check::check("Stream::finish_stream");
+        let start = Instant::now();
+        let (preprocess, samples) = self.flush_preprocessor();
+        let in_use = self.enter();
         let ptr = unsafe { coqui_stt_sys::STT_FinishStream(self.state) };
+        drop(in_use);
 
         self.already_freed = true;
+        self.observe(
+            StreamOperation::Finish,
+            preprocess,
+            start.elapsed() - preprocess,
+            samples,
+        );
 
         if ptr.is_null() {
-            return Err(crate::Error::Unknown);
+            return Err(crate::Error::Unknown.context("STT_FinishStream"));
         }
 
         // SAFETY: STT_SpeechToText will always return a valid CStr
@@ -274,14 +789,95 @@ impl<'a> Stream<'a> {
     /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
     #[inline]
     pub fn finish_stream_with_metadata(mut self, num_results: u32) -> crate::Result<Metadata> {
+        crate::blocking_check::check("Stream::finish_stream_with_metadata");
+        let start = Instant::now();
+        let (preprocess, samples) = self.flush_preprocessor();
+        let in_use = self.enter();
         let ptr = unsafe { coqui_stt_sys::STT_FinishStreamWithMetadata(self.state, num_results) };
+        drop(in_use);
 
         self.already_freed = true;
+        self.observe(
+            StreamOperation::Finish,
+            preprocess,
+            start.elapsed() - preprocess,
+            samples,
+        );
+
+        crate::Metadata::new("STT_FinishStreamWithMetadata", ptr)
+    }
 
-        if ptr.is_null() {
-            return Err(crate::Error::Unknown);
-        }
+    /// Compute the final decoding of an ongoing streaming inference
+    /// and return the best transcription as a [`Transcription`].
+    /// Signals the end of an ongoing streaming inference.
+    ///
+    /// Destroys this stream object.
+    ///
+    /// The [`audio_duration`](Transcription::audio_duration) is the total length of the audio
+    /// fed to the model, and the [`processing_time`](Transcription::processing_time)
+    /// is the total time spent preprocessing, feeding and decoding audio on this stream.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn finish_stream_transcription(mut self) -> crate::Result<Transcription> {
+        crate::blocking_check::check("Stream::finish_stream_transcription");
+        let model_info = self.model.info();
+        let start = Instant::now();
+        let (preprocess, samples) = self.flush_preprocessor();
+        let in_use = self.enter();
+        let ptr = unsafe { coqui_stt_sys::STT_FinishStreamWithMetadata(self.state, 1) };
+        drop(in_use);
 
-        Ok(crate::Metadata::new(ptr))
+        self.already_freed = true;
+        self.observe(
+            StreamOperation::Finish,
+            preprocess,
+            start.elapsed() - preprocess,
+            samples,
+        );
+
+        let metadata = crate::Metadata::new("STT_FinishStreamWithMetadata", ptr)?;
+
+        let audio_duration = duration_of(self.samples_fed, model_info.sample_rate);
+        Ok(Transcription::from_metadata(
+            &metadata,
+            audio_duration,
+            self.processing_time,
+            model_info,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "a model was used by two streams at once")]
+    fn overlapping_calls_on_one_model_panic() {
+        let model = Model::new("model").unwrap();
+        let _first = InUse::new(&model);
+        let _second = InUse::new(&model);
+    }
+
+    #[test]
+    fn models_are_released_after_each_call() {
+        let mut model = Model::new("model").unwrap();
+        let mut other = Model::new("model").unwrap();
+        {
+            let _first = InUse::new(&model);
+            let _other = InUse::new(&other);
+        }
+        drop(InUse::new(&model));
+
+        let mut stream = model.as_streaming().unwrap();
+        stream.feed_audio(&[0; 160]);
+        let mut other_stream = other.as_streaming().unwrap();
+        other_stream.feed_audio(&[0; 160]);
+        assert!(stream.intermediate_decode().is_ok());
+        assert!(other_stream.finish_stream().is_ok());
+        assert!(stream.finish_stream().is_ok());
     }
 }
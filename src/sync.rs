@@ -0,0 +1,7 @@
+// The synchronization primitives used by the worker handoff and `Shutdown`,
+// swapped for loom's when testing with `--cfg loom`.
+
+#[cfg(loom)]
+pub(crate) use loom::sync::{Arc, Condvar, Mutex, MutexGuard};
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Arc, Condvar, Mutex, MutexGuard};
@@ -0,0 +1,260 @@
+//! Helpers for testing code built on this crate without a live audio source.
+//!
+//! [`replay_realtime`] feeds recorded audio in small chunks at the pace it would arrive
+//! from a microphone, so endpointing, stabilization of intermediate results
+//! and timeouts behave as they would live.
+//! [`ReplayOptions`] adds network-style jitter, from a seed, so runs are repeatable.
+//!
+//! [`tone`], [`silence`] and [`concat()`] build synthetic audio,
+//! so tests don't need recordings checked in alongside them.
+
+use crate::audio::{duration_of, samples_in};
+use crate::vad::VoiceActivityDetector;
+use crate::{OwnedStream, Stream, Transcriber, TranscriptEvent};
+use std::time::{Duration, Instant};
+
+/// Something audio can be replayed into with [`replay_realtime`].
+pub trait ReplayTarget {
+    /// What feeding audio returns, such as the [`TranscriptEvent`]s of a [`Transcriber`].
+    type Event;
+
+    /// The sample rate audio is fed at, in Hz.
+    fn sample_rate(&self) -> u32;
+
+    /// Feed one chunk of audio, returning any events it caused.
+    ///
+    /// # Errors
+    /// Returns an error if the audio couldn't be fed.
+    fn feed(&mut self, chunk: &[i16]) -> crate::Result<Vec<Self::Event>>;
+}
+
+impl ReplayTarget for Stream<'_> {
+    type Event = ();
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.model().get_sample_rate() as u32
+    }
+
+    #[inline]
+    fn feed(&mut self, chunk: &[i16]) -> crate::Result<Vec<()>> {
+        self.feed_audio(chunk);
+        Ok(Vec::new())
+    }
+}
+
+impl ReplayTarget for OwnedStream {
+    type Event = ();
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.model().get_sample_rate() as u32
+    }
+
+    #[inline]
+    fn feed(&mut self, chunk: &[i16]) -> crate::Result<Vec<()>> {
+        self.feed_audio(chunk);
+        Ok(Vec::new())
+    }
+}
+
+impl<V: VoiceActivityDetector> ReplayTarget for Transcriber<V> {
+    type Event = TranscriptEvent;
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.model().get_sample_rate() as u32
+    }
+
+    #[inline]
+    fn feed(&mut self, chunk: &[i16]) -> crate::Result<Vec<TranscriptEvent>> {
+        self.push_audio(chunk)
+    }
+}
+
+/// How [`replay_realtime_with`] paces audio.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub struct ReplayOptions {
+    /// How fast to replay, relative to real time: 2.0 is twice as fast.
+    /// Anything that isn't a positive number feeds every chunk straight away.
+    pub speed: f32,
+    /// How much audio is fed at a time, like the buffers of an audio callback.
+    pub chunk: Duration,
+    /// The most each chunk may arrive late, chosen at random for each one.
+    /// Chunks never overtake each other, so a late chunk holds up the ones after it,
+    /// which then arrive in a burst, as they would over a network.
+    pub jitter: Duration,
+    /// The seed for the jitter, so the same options give the same delays.
+    pub seed: u64,
+}
+
+impl Default for ReplayOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            chunk: Duration::from_millis(20),
+            jitter: Duration::ZERO,
+            seed: 0,
+        }
+    }
+}
+
+impl ReplayOptions {
+    /// Set [`speed`](ReplayOptions::speed).
+    #[inline]
+    #[must_use]
+    pub const fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Set [`chunk`](ReplayOptions::chunk).
+    #[inline]
+    #[must_use]
+    pub const fn with_chunk(mut self, chunk: Duration) -> Self {
+        self.chunk = chunk;
+        self
+    }
+
+    /// Set [`jitter`](ReplayOptions::jitter) and its [`seed`](ReplayOptions::seed).
+    #[inline]
+    #[must_use]
+    pub const fn with_jitter(mut self, jitter: Duration, seed: u64) -> Self {
+        self.jitter = jitter;
+        self.seed = seed;
+        self
+    }
+}
+
+/// Feed `audio` into `target` in 20 ms chunks, at `speed` times real time,
+/// returning every event this caused.
+///
+/// `audio` should be 16-bit, mono audio at the target's sample rate.
+/// This blocks until all of it has been fed. See [`replay_realtime_with`] for more options.
+///
+/// # Errors
+/// Stops at, and returns, the first error from feeding the target.
+#[inline]
+pub fn replay_realtime<T: ReplayTarget + ?Sized>(
+    target: &mut T,
+    audio: &[i16],
+    speed: f32,
+) -> crate::Result<Vec<T::Event>> {
+    replay_realtime_with(target, audio, &ReplayOptions::default().with_speed(speed))
+}
+
+/// Feed `audio` into `target` paced by `options`, returning every event this caused.
+///
+/// # Errors
+/// Stops at, and returns, the first error from feeding the target.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn replay_realtime_with<T: ReplayTarget + ?Sized>(
+    target: &mut T,
+    audio: &[i16],
+    options: &ReplayOptions,
+) -> crate::Result<Vec<T::Event>> {
+    let sample_rate = target.sample_rate();
+    let chunk_len = samples_in(options.chunk, sample_rate).max(1);
+    let paced = options.speed > 0.0 && options.speed.is_finite();
+    let mut jitter = XorShift::new(options.seed);
+
+    let start = Instant::now();
+    let mut due = Duration::ZERO;
+    let mut events = Vec::new();
+    for (i, chunk) in audio.chunks(chunk_len).enumerate() {
+        if paced {
+            // a chunk can only be delivered once it has all been captured
+            let captured = duration_of(((i + 1) * chunk_len) as u64, sample_rate);
+            let late = options.jitter.mul_f64(jitter.next_fraction());
+            let at = (captured + late).as_secs_f64() / f64::from(options.speed);
+            due = due.max(Duration::try_from_secs_f64(at).unwrap_or(Duration::MAX));
+            if let Some(wait) = due.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        events.extend(target.feed(chunk)?);
+    }
+    Ok(events)
+}
+
+/// Generate a sine wave at `freq` Hz, lasting `duration`, as 16-bit mono audio at `sample_rate` Hz.
+///
+/// The wave peaks at half of full scale, loud enough for voice activity detectors
+/// to take it as speech, without clipping when mixed with other audio.
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn tone(freq: f32, duration: Duration, sample_rate: u32) -> Vec<i16> {
+    let step = std::f64::consts::TAU * f64::from(freq) / f64::from(sample_rate.max(1));
+    (0..samples_in(duration, sample_rate))
+        .map(|i| ((i as f64 * step).sin() * f64::from(i16::MAX / 2)).round() as i16)
+        .collect()
+}
+
+/// Generate `duration` of silence, as 16-bit mono audio at `sample_rate` Hz.
+#[inline]
+#[must_use]
+pub fn silence(duration: Duration, sample_rate: u32) -> Vec<i16> {
+    vec![0; samples_in(duration, sample_rate)]
+}
+
+/// Join pieces of audio into one, such as tones and silences making up an utterance.
+///
+/// ```
+/// use coqui_stt::testing::{concat, silence, tone};
+/// use std::time::Duration;
+///
+/// let ms = Duration::from_millis;
+/// let audio = concat([silence(ms(200), 16000), tone(440.0, ms(500), 16000), silence(ms(700), 16000)]);
+/// assert_eq!(audio.len(), 16000 * 14 / 10);
+/// ```
+#[allow(clippy::missing_inline_in_public_items)]
+#[must_use]
+pub fn concat<A: AsRef<[i16]>>(pieces: impl IntoIterator<Item = A>) -> Vec<i16> {
+    let mut audio = Vec::new();
+    for piece in pieces {
+        audio.extend_from_slice(piece.as_ref());
+    }
+    audio
+}
+
+/// A small, seedable random number generator, so jitter is the same on every run.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // xorshift gets stuck at zero, so mix the seed first
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    /// A number from 0 to 1.
+    fn next_fraction(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{concat, silence, tone};
+    use std::time::Duration;
+
+    #[test]
+    fn tones_have_the_right_pitch_and_length() {
+        let wave = tone(1000.0, Duration::from_millis(10), 16000);
+        assert_eq!(wave.len(), 160);
+        // 16 samples per cycle, starting at zero and peaking a quarter of the way through
+        assert_eq!(wave[0], 0);
+        assert_eq!(wave[4], i16::MAX / 2);
+        assert_eq!(wave[12], -(i16::MAX / 2));
+        assert_eq!(wave[16], 0);
+
+        let audio = concat([&silence(Duration::from_millis(5), 16000), &wave[..8]]);
+        assert_eq!(audio.len(), 88);
+        assert!(audio[..80].iter().all(|&s| s == 0));
+        assert_eq!(audio[80..], wave[..8]);
+    }
+}
@@ -0,0 +1,1129 @@
+use crate::job_queue::{self, JobExecutor, JobQueue};
+use crate::oneshot::{self, Sender};
+use crate::shutdown::ShutdownGuard;
+use crate::{
+    InferenceStats, Metadata, Model, OwnedStream, Shutdown, StatsHandle, StreamTiming, Word,
+};
+use std::future::Future;
+use std::ops::{ControlFlow, Range};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Options for a [`ThreadSafeStream`].
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StreamOptions {
+    /// How many candidate transcripts the metadata methods return
+    /// when not told otherwise.
+    pub default_num_results: u32,
+    /// Whether intermediate decodes flush the model's buffers first.
+    ///
+    /// See [`Stream::intermediate_decode_with_buffer_flush`](crate::Stream::intermediate_decode_with_buffer_flush).
+    pub auto_flush: bool,
+    /// Split audio into chunks of at most this many samples before feeding it to the model,
+    /// so a large buffer doesn't hold up an intermediate decode queued behind it for too long.
+    ///
+    /// `None` feeds audio in one go.
+    pub chunk_size: Option<usize>,
+    /// Drop audio fed while more than this many samples are already waiting to be fed to the model,
+    /// so a model that can't keep up doesn't build an ever-growing backlog.
+    ///
+    /// Dropped audio is counted in [`InferenceStats::dropped_backpressure`].
+    /// [`ThreadSafeStream::feed_audio_async`] waits for room instead of dropping audio.
+    /// `None` never drops audio.
+    pub max_queued_samples: Option<usize>,
+    /// Finish the stream automatically if no audio is fed for this long,
+    /// freeing its state, so abandoned streams don't hold on to resources.
+    ///
+    /// The result is delivered to the callback passed to
+    /// [`ThreadSafeStream::new_with_watchdog`], or discarded if there is none.
+    /// Audio fed afterwards starts a new stream on the same model,
+    /// so finishing the stream by hand only returns what was fed since.
+    /// `None` never finishes streams automatically.
+    pub idle_timeout: Option<Duration>,
+    /// Pin the worker thread to the CPU core with this ID,
+    /// so sustained decoding doesn't migrate between cores.
+    ///
+    /// See [`pin_current_thread`](crate::pin_current_thread). `None` leaves it unpinned.
+    #[cfg(feature = "core-affinity")]
+    pub core: Option<usize>,
+}
+
+impl Default for StreamOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            default_num_results: 1,
+            auto_flush: false,
+            chunk_size: None,
+            max_queued_samples: None,
+            idle_timeout: None,
+            #[cfg(feature = "core-affinity")]
+            core: None,
+        }
+    }
+}
+
+impl StreamOptions {
+    /// Set [`default_num_results`](StreamOptions::default_num_results).
+    #[inline]
+    #[must_use]
+    pub const fn with_default_num_results(mut self, num_results: u32) -> Self {
+        self.default_num_results = num_results;
+        self
+    }
+
+    /// Set [`auto_flush`](StreamOptions::auto_flush).
+    #[inline]
+    #[must_use]
+    pub const fn with_auto_flush(mut self, auto_flush: bool) -> Self {
+        self.auto_flush = auto_flush;
+        self
+    }
+
+    /// Set [`chunk_size`](StreamOptions::chunk_size).
+    #[inline]
+    #[must_use]
+    pub const fn with_chunk_size(mut self, chunk_size: Option<usize>) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Set [`max_queued_samples`](StreamOptions::max_queued_samples).
+    #[inline]
+    #[must_use]
+    pub const fn with_max_queued_samples(mut self, max_queued_samples: Option<usize>) -> Self {
+        self.max_queued_samples = max_queued_samples;
+        self
+    }
+
+    /// Set [`idle_timeout`](StreamOptions::idle_timeout).
+    #[inline]
+    #[must_use]
+    pub const fn with_idle_timeout(mut self, idle_timeout: Option<Duration>) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Set [`core`](StreamOptions::core).
+    #[cfg(feature = "core-affinity")]
+    #[inline]
+    #[must_use]
+    pub const fn with_core(mut self, core: Option<usize>) -> Self {
+        self.core = core;
+        self
+    }
+}
+
+enum Audio {
+    Owned(Vec<i16>),
+    Shared(Arc<[i16]>, Range<usize>),
+}
+
+impl Audio {
+    fn as_slice(&self) -> &[i16] {
+        match self {
+            Self::Owned(audio) => audio,
+            Self::Shared(audio, range) => &audio[range.clone()],
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Owned(audio) => audio.len(),
+            Self::Shared(_, range) => range.len(),
+        }
+    }
+}
+
+/// State shared between a [`ThreadSafeStream`] and its worker thread.
+#[derive(Default)]
+struct Shared {
+    stats: StatsHandle,
+    cancelled: AtomicBool,
+    /// Tasks waiting for room in the queue.
+    waiters: Mutex<Vec<Waker>>,
+    /// Registration with the [`Shutdown`] being watched, if any.
+    shutdown: Mutex<Option<ShutdownGuard>>,
+}
+
+impl Shared {
+    /// Queue up `samples` if there is room, registering `waker` to be woken once there might be if not.
+    fn try_queue(&self, samples: usize, max: Option<usize>, waker: &Waker) -> bool {
+        let mut waiters = self.waiters.lock().unwrap_or_else(|e| e.into_inner());
+        let queued = self.stats.try_queue(samples, max, true);
+        if !queued && !waiters.iter().any(|w| w.will_wake(waker)) {
+            waiters.push(waker.clone());
+        }
+        queued
+    }
+
+    fn dequeued(&self, samples: usize) {
+        self.stats.dequeued(samples);
+        let waiters = std::mem::take(&mut *self.waiters.lock().unwrap_or_else(|e| e.into_inner()));
+        waiters.into_iter().for_each(Waker::wake);
+    }
+
+    /// Stop watching for shutdown, or report that this stream finished shutting down.
+    fn release_shutdown(&self) {
+        let guard = self
+            .shutdown
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        drop(guard);
+    }
+}
+
+/// An operation for a [`ThreadSafeStream`] to run on its worker thread,
+/// as part of a batch passed to [`run`](ThreadSafeStream::run).
+#[non_exhaustive]
+pub enum StreamJob {
+    /// See [`ThreadSafeStream::feed_audio`].
+    Feed(Vec<i16>),
+    /// See [`ThreadSafeStream::feed_audio_shared`].
+    FeedShared(Arc<[i16]>),
+    /// See [`ThreadSafeStream::intermediate_decode`].
+    IntermediateDecode,
+    /// See [`ThreadSafeStream::intermediate_decode_with_num_results`].
+    IntermediateDecodeWithMetadata(u32),
+    /// See [`Stream::flush_buffers`](crate::Stream::flush_buffers).
+    FlushBuffers,
+}
+
+/// What a [`StreamJob`] produced.
+#[non_exhaustive]
+pub enum JobOutput {
+    /// The audio was queued up and fed to the model.
+    Fed,
+    /// The audio was dropped, because the queue was full.
+    /// See [`max_queued_samples`](StreamOptions::max_queued_samples).
+    Dropped,
+    /// The result of [`StreamJob::IntermediateDecode`].
+    Text(String),
+    /// The result of [`StreamJob::IntermediateDecodeWithMetadata`].
+    Metadata(Metadata),
+    /// The model's buffers were flushed.
+    Flushed,
+}
+
+enum Job {
+    Feed(Audio),
+    /// Jobs from [`ThreadSafeStream::run`], where `None` is audio dropped for backpressure.
+    Batch(
+        Vec<Option<StreamJob>>,
+        Sender<Vec<crate::Result<JobOutput>>>,
+    ),
+    IntermediateDecode(Sender<crate::Result<String>>),
+    IntermediateDecodeWithMetadata(u32, Sender<crate::Result<Metadata>>),
+    Finish(Sender<crate::Result<String>>),
+    FinishWithMetadata(u32, Sender<crate::Result<Metadata>>),
+    FinishWithWords(Sender<crate::Result<Vec<Word>>>),
+    SetTimingObserver(Option<TimingObserver>),
+    Shutdown,
+}
+
+/// A [`Job`], along with when it was queued up.
+struct Queued {
+    job: Job,
+    at: Instant,
+}
+
+impl Queued {
+    fn new(job: Job) -> Self {
+        Self {
+            job,
+            at: Instant::now(),
+        }
+    }
+}
+
+/// A stream that runs on its own worker thread,
+/// so it can be shared between threads and used through `&self`.
+///
+/// Calls are queued up and run in the order they were made.
+/// Feeding audio returns immediately, while decoding waits for the result.
+///
+/// # Panics
+/// If the worker thread panics, audio fed afterwards is dropped, and decoding returns
+/// [`Error::WorkerPanicked`](crate::Error::WorkerPanicked).
+/// The model is lost with the worker, so [`finish_stream`](ThreadSafeStream::finish_stream)
+/// and [`into_model`](ThreadSafeStream::into_model), which have to return it,
+/// resume the worker's panic instead. Dropping the stream does not.
+pub struct ThreadSafeStream {
+    jobs: JobQueue<Queued>,
+    worker: Option<JoinHandle<Model>>,
+    /// Closed once the worker thread is about to exit, so it can be joined without blocking.
+    exited: oneshot::Receiver<()>,
+    options: StreamOptions,
+    shared: Arc<Shared>,
+}
+
+impl ThreadSafeStream {
+    /// Create a new `ThreadSafeStream` with the default [`StreamOptions`], taking ownership of `model`.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn new(model: Model) -> crate::Result<Self> {
+        Self::new_with_options(model, StreamOptions::default())
+    }
+
+    /// Create a new `ThreadSafeStream`, taking ownership of `model`.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn new_with_options(model: Model, options: StreamOptions) -> crate::Result<Self> {
+        Self::spawn(model, options, None)
+    }
+
+    /// Create a new `ThreadSafeStream`, taking ownership of `model`,
+    /// that calls `on_idle` with the result every time the stream is finished
+    /// because of the [`idle_timeout`](StreamOptions::idle_timeout),
+    /// or because it [shut down](ThreadSafeStream::watch_shutdown).
+    ///
+    /// The result has [`default_num_results`](StreamOptions::default_num_results)
+    /// candidate transcripts. `on_idle` is called on the worker thread,
+    /// so it shouldn't block for long; sending the result down a channel works well.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn new_with_watchdog(
+        model: Model,
+        options: StreamOptions,
+        on_idle: impl FnMut(crate::Result<Metadata>) + Send + 'static,
+    ) -> crate::Result<Self> {
+        Self::spawn(model, options, Some(Box::new(on_idle)))
+    }
+
+    fn spawn(model: Model, options: StreamOptions, on_idle: Option<OnIdle>) -> crate::Result<Self> {
+        let stream = OwnedStream::new(model)?;
+        let jobs = JobQueue::new();
+        let shared = Arc::new(Shared::default());
+        let (exiting, exited) = oneshot::channel::<()>();
+        let worker = {
+            let jobs = AbandonOnExit(jobs.clone());
+            let shared = Arc::clone(&shared);
+            std::thread::spawn(move || {
+                // dropped as the thread exits, even by panicking
+                let _exiting = exiting;
+                #[cfg(feature = "core-affinity")]
+                if let Some(core) = options.core {
+                    crate::pin_current_thread(core);
+                }
+                work(stream, &jobs.0, options, &shared, on_idle)
+            })
+        };
+
+        Ok(Self {
+            jobs,
+            worker: Some(worker),
+            exited,
+            options,
+            shared,
+        })
+    }
+
+    /// Shut this stream down when `shutdown` is triggered.
+    ///
+    /// Audio queued before then is still fed to the model. The stream is then finished,
+    /// with the result going to the [watchdog](ThreadSafeStream::new_with_watchdog)
+    /// callback if audio was fed since the stream was created, and its state is freed.
+    /// From then on, audio fed is dropped, and decoding and finishing return
+    /// [`Error::ShuttingDown`](crate::Error::ShuttingDown).
+    /// The worker thread keeps the model until this `ThreadSafeStream` is dropped or finished.
+    ///
+    /// If `shutdown` was already triggered, the stream shuts down straight away.
+    /// A stream only watches one `Shutdown` at a time.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn watch_shutdown(&self, shutdown: &Shutdown) {
+        let jobs = self.jobs.clone();
+        let guard = shutdown.register(move || {
+            let _ = jobs.push(Queued::new(Job::Shutdown));
+        });
+        let previous = self
+            .shared
+            .shutdown
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .replace(guard);
+        drop(previous);
+    }
+
+    /// Return the [`StreamOptions`] this stream was created with.
+    #[inline]
+    #[must_use]
+    pub const fn options(&self) -> &StreamOptions {
+        &self.options
+    }
+
+    /// Return the [`InferenceStats`] of this stream so far.
+    #[inline]
+    #[must_use]
+    pub fn stats(&self) -> InferenceStats {
+        self.shared.stats.snapshot()
+    }
+
+    /// Return a [`StatsHandle`] for this stream,
+    /// which can still be read after the stream is finished or discarded.
+    #[inline]
+    #[must_use]
+    pub fn stats_handle(&self) -> StatsHandle {
+        self.shared.stats.clone()
+    }
+
+    /// Set a function to be called with the [`StreamTiming`] of every operation
+    /// the worker thread runs, including how long each call waited in the queue
+    /// as its [`queue_wait`](StreamTiming::queue_wait).
+    ///
+    /// It applies from the next call queued up, and keeps applying
+    /// after the [`idle_timeout`](StreamOptions::idle_timeout) frees the stream.
+    /// It is called on the worker thread, so it should return quickly.
+    /// See [`Stream::set_timing_observer`](crate::Stream::set_timing_observer).
+    #[inline]
+    pub fn set_timing_observer(&self, observer: impl Fn(StreamTiming) + Send + Sync + 'static) {
+        self.send(Job::SetTimingObserver(Some(Arc::new(observer))));
+    }
+
+    /// Remove the timing observer set with
+    /// [`set_timing_observer`](ThreadSafeStream::set_timing_observer).
+    #[inline]
+    pub fn clear_timing_observer(&self) {
+        self.send(Job::SetTimingObserver(None));
+    }
+
+    /// Queue up audio to be fed to the model.
+    ///
+    /// This returns as soon as the audio is queued.
+    /// The audio is dropped instead if the queue is full;
+    /// see [`max_queued_samples`](StreamOptions::max_queued_samples).
+    #[inline]
+    pub fn feed_audio(&self, buffer: Vec<i16>) {
+        self.feed(Audio::Owned(buffer));
+    }
+
+    /// Queue up audio shared with other consumers to be fed to the model.
+    ///
+    /// Only the reference count is sent to the worker thread,
+    /// so the same recording can be fed to several streams without copying it for each one.
+    #[inline]
+    pub fn feed_audio_shared(&self, buffer: Arc<[i16]>) {
+        let len = buffer.len();
+        self.feed(Audio::Shared(buffer, 0..len));
+    }
+
+    /// Queue up part of a buffer of audio shared with other consumers to be fed to the model.
+    ///
+    /// See [`feed_audio_shared`](ThreadSafeStream::feed_audio_shared).
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidAudio`](crate::Error::InvalidAudio)
+    /// if `range` is out of bounds of `buffer`, and queues up nothing.
+    #[inline]
+    pub fn feed_audio_range(&self, buffer: Arc<[i16]>, range: Range<usize>) -> crate::Result<()> {
+        // check the range here, so the worker never sees a bad one
+        if buffer.get(range.clone()).is_none() {
+            return Err(crate::Error::InvalidAudio(format!(
+                "range {:?} is out of bounds of {} samples",
+                range,
+                buffer.len()
+            )));
+        }
+        self.feed(Audio::Shared(buffer, range));
+        Ok(())
+    }
+
+    /// Compute the intermediate decoding of the audio fed so far.
+    ///
+    /// The model's buffers are flushed first if
+    /// [`auto_flush`](StreamOptions::auto_flush) is set.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode(&self) -> crate::Result<String> {
+        self.request(Job::IntermediateDecode)
+    }
+
+    /// Compute the intermediate decoding of the audio fed so far,
+    /// with [`default_num_results`](StreamOptions::default_num_results) candidate transcripts.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode_with_metadata(&self) -> crate::Result<Metadata> {
+        self.intermediate_decode_with_num_results(self.options.default_num_results)
+    }
+
+    /// Compute the intermediate decoding of the audio fed so far,
+    /// with up to `num_results` candidate transcripts.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn intermediate_decode_with_num_results(
+        &self,
+        num_results: u32,
+    ) -> crate::Result<Metadata> {
+        self.request(|tx| Job::IntermediateDecodeWithMetadata(num_results, tx))
+    }
+
+    /// Run `jobs` on the worker thread one after another, with nothing else in between,
+    /// and return what each one produced, in the same order.
+    ///
+    /// This takes one round trip to the worker thread however many jobs there are,
+    /// so feeding audio and decoding it straight away only waits once.
+    /// A job that fails doesn't stop the ones after it.
+    /// Audio is dropped when the queue is full, just like with
+    /// [`feed_audio`](ThreadSafeStream::feed_audio), which is reported as [`JobOutput::Dropped`].
+    #[inline]
+    pub fn run(&self, jobs: impl IntoIterator<Item = StreamJob>) -> Vec<crate::Result<JobOutput>> {
+        let (len, rx) = self.batch(jobs);
+        rx.recv().unwrap_or_else(|| batch_failed(len))
+    }
+
+    /// Like [`run`](ThreadSafeStream::run), but waits for the results asynchronously.
+    ///
+    /// The jobs are queued up straight away, so they still run if this future is dropped.
+    /// It doesn't depend on any particular async runtime.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub async fn run_async(
+        &self,
+        jobs: impl IntoIterator<Item = StreamJob>,
+    ) -> Vec<crate::Result<JobOutput>> {
+        let (len, rx) = self.batch(jobs);
+        rx.await.unwrap_or_else(|| batch_failed(len))
+    }
+
+    fn batch(
+        &self,
+        jobs: impl IntoIterator<Item = StreamJob>,
+    ) -> (usize, oneshot::Receiver<Vec<crate::Result<JobOutput>>>) {
+        let stats = &self.shared.stats;
+        let jobs: Vec<_> = jobs
+            .into_iter()
+            .map(|job| {
+                let len = match &job {
+                    StreamJob::Feed(audio) => audio.len(),
+                    StreamJob::FeedShared(audio) => audio.len(),
+                    _ => return Some(job),
+                };
+                stats.received(len);
+                if stats.try_queue(len, self.options.max_queued_samples, false) {
+                    Some(job)
+                } else {
+                    stats.dropped_backpressure(len);
+                    None
+                }
+            })
+            .collect();
+        let len = jobs.len();
+
+        let (tx, rx) = oneshot::channel();
+        self.send(Job::Batch(jobs, tx));
+        (len, rx)
+    }
+
+    /// Compute the final decoding of the audio fed, and return the model along with it.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream(self) -> (crate::Result<String>, Model) {
+        self.finish_with(Job::Finish)
+    }
+
+    /// Compute the final decoding of the audio fed,
+    /// with [`default_num_results`](StreamOptions::default_num_results) candidate transcripts,
+    /// and return the model along with it.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream_with_metadata(self) -> (crate::Result<Metadata>, Model) {
+        let num_results = self.options.default_num_results;
+        self.finish_with(|tx| Job::FinishWithMetadata(num_results, tx))
+    }
+
+    /// Compute the final decoding of the audio fed, with up to `num_results` candidate transcripts,
+    /// and return the model along with it.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream_with_num_results(
+        self,
+        num_results: u32,
+    ) -> (crate::Result<Metadata>, Model) {
+        self.finish_with(|tx| Job::FinishWithMetadata(num_results, tx))
+    }
+
+    /// Compute the final decoding of the audio fed, and return the words of the most likely
+    /// transcript, with when each one starts, along with the model.
+    ///
+    /// The words are grouped from the transcript's tokens on the worker thread,
+    /// so only they are sent back, rather than the whole [`Metadata`].
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[inline]
+    pub fn finish_stream_words(self) -> (crate::Result<Vec<Word>>, Model) {
+        self.finish_with(Job::FinishWithWords)
+    }
+
+    /// Discard this stream without decoding it, and return the [`Model`] it owned.
+    ///
+    /// Audio still queued is dropped without being fed to the model.
+    #[inline]
+    #[must_use]
+    pub fn into_model(mut self) -> Model {
+        match self.cancel() {
+            Ok(model) => model,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Queue up audio to be fed to the model, waiting for room in the queue
+    /// if it is limited by [`max_queued_samples`](StreamOptions::max_queued_samples).
+    /// A buffer bigger than the limit on its own waits for the queue to empty.
+    ///
+    /// This future is cancellation safe: the buffer is queued in one go, as it completes,
+    /// and only split into [chunks](StreamOptions::chunk_size) by the worker thread afterwards.
+    /// If it is dropped before completing, for example by losing a `select!`,
+    /// none of the audio has been queued, and it isn't counted as received.
+    ///
+    /// It doesn't depend on any particular async runtime.
+    #[inline]
+    pub fn feed_audio_async(&self, buffer: Vec<i16>) -> FeedAudio<'_> {
+        FeedAudio {
+            stream: self,
+            audio: Some(Audio::Owned(buffer)),
+        }
+    }
+
+    fn feed(&self, audio: Audio) {
+        let stats = &self.shared.stats;
+        stats.received(audio.len());
+        if stats.try_queue(audio.len(), self.options.max_queued_samples, false) {
+            self.send(Job::Feed(audio));
+        } else {
+            stats.dropped_backpressure(audio.len());
+        }
+    }
+
+    /// Send `job` to the worker. If the worker panicked, the job is dropped,
+    /// which closes any channel it would have replied on.
+    fn send(&self, job: Job) {
+        let _ = self.jobs.push(Queued::new(job));
+    }
+
+    fn request<T>(&self, job: impl FnOnce(Sender<crate::Result<T>>) -> Job) -> crate::Result<T> {
+        self.start(job)
+            .recv()
+            .unwrap_or(Err(crate::Error::WorkerPanicked))
+    }
+
+    async fn request_async<T>(
+        &self,
+        job: impl FnOnce(Sender<crate::Result<T>>) -> Job,
+    ) -> crate::Result<T> {
+        self.start(job)
+            .await
+            .unwrap_or(Err(crate::Error::WorkerPanicked))
+    }
+
+    fn start<T>(&self, job: impl FnOnce(Sender<T>) -> Job) -> oneshot::Receiver<T> {
+        let (tx, rx) = oneshot::channel();
+        self.send(job(tx));
+        rx
+    }
+
+    fn finish_with<T>(
+        mut self,
+        job: impl FnOnce(Sender<crate::Result<T>>) -> Job,
+    ) -> (crate::Result<T>, Model) {
+        let res = self.request(job);
+        match self.join() {
+            Ok(model) => (res, model),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    async fn finish_async_with<T>(
+        mut self,
+        job: impl FnOnce(Sender<crate::Result<T>>) -> Job,
+    ) -> (crate::Result<T>, Model) {
+        let res = self.request_async(job).await;
+        match self.join_async().await {
+            Ok(model) => (res, model),
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    fn cancel(&mut self) -> std::thread::Result<Model> {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+        self.join()
+    }
+
+    fn join(&mut self) -> std::thread::Result<Model> {
+        self.shared.release_shutdown();
+        // the worker stops once it has run the jobs already queued
+        self.jobs.close();
+        match self.worker.take() {
+            Some(worker) => worker.join(),
+            None => unreachable!("the worker is only joined once"),
+        }
+    }
+
+    /// Like [`join`](ThreadSafeStream::join), but waits for the worker to run
+    /// the jobs still queued asynchronously, leaving only the thread's exit to block on.
+    async fn join_async(&mut self) -> std::thread::Result<Model> {
+        self.shared.release_shutdown();
+        self.jobs.close();
+        (&mut self.exited).await;
+        self.join()
+    }
+}
+
+/// Generates async variants of the methods that wait for the worker thread,
+/// which queue up the same job and await its reply instead of blocking on it.
+///
+/// `&self` methods take the job to send, and `self` methods, which finish the stream,
+/// take the job to finish it with.
+macro_rules! async_variants {
+    ($(
+        $(#[$attr:meta])*
+        $name:ident(&self $(, $arg:ident: $ty:ty)*) -> $out:ty = $job:expr;
+    )*) => {$(
+        $(#[$attr])*
+        ///
+        /// It doesn't depend on any particular async runtime.
+        #[allow(clippy::missing_inline_in_public_items)]
+        pub async fn $name(&self $(, $arg: $ty)*) -> crate::Result<$out> {
+            let job = $job;
+            self.request_async(|tx| job(self, tx)).await
+        }
+    )*};
+    ($(
+        $(#[$attr:meta])*
+        $name:ident(self $(, $arg:ident: $ty:ty)*) -> $out:ty = $job:expr;
+    )*) => {$(
+        $(#[$attr])*
+        ///
+        /// The final decode is queued up straight away, so dropping this future
+        /// before it completes still waits for the worker to finish, blocking the calling thread.
+        /// It doesn't depend on any particular async runtime.
+        #[allow(clippy::missing_inline_in_public_items)]
+        pub async fn $name(self $(, $arg: $ty)*) -> (crate::Result<$out>, Model) {
+            let job = ($job)(&self);
+            self.finish_async_with(job).await
+        }
+    )*};
+}
+
+impl ThreadSafeStream {
+    async_variants! {
+        /// Like [`intermediate_decode`](ThreadSafeStream::intermediate_decode),
+        /// but waits for the result asynchronously.
+        intermediate_decode_async(&self) -> String =
+            |_: &Self, tx| Job::IntermediateDecode(tx);
+        /// Like [`intermediate_decode_with_metadata`](ThreadSafeStream::intermediate_decode_with_metadata),
+        /// but waits for the result asynchronously.
+        intermediate_decode_with_metadata_async(&self) -> Metadata =
+            |this: &Self, tx| Job::IntermediateDecodeWithMetadata(this.options.default_num_results, tx);
+        /// Like [`intermediate_decode_with_num_results`](ThreadSafeStream::intermediate_decode_with_num_results),
+        /// but waits for the result asynchronously.
+        intermediate_decode_with_num_results_async(&self, num_results: u32) -> Metadata =
+            |_: &Self, tx| Job::IntermediateDecodeWithMetadata(num_results, tx);
+    }
+
+    async_variants! {
+        /// Like [`finish_stream`](ThreadSafeStream::finish_stream),
+        /// but waits for the result asynchronously.
+        finish_stream_async(self) -> String = |_: &Self| Job::Finish;
+        /// Like [`finish_stream_with_metadata`](ThreadSafeStream::finish_stream_with_metadata),
+        /// but waits for the result asynchronously.
+        finish_stream_with_metadata_async(self) -> Metadata = |this: &Self| {
+            let num_results = this.options.default_num_results;
+            move |tx| Job::FinishWithMetadata(num_results, tx)
+        };
+        /// Like [`finish_stream_with_num_results`](ThreadSafeStream::finish_stream_with_num_results),
+        /// but waits for the result asynchronously.
+        finish_stream_with_num_results_async(self, num_results: u32) -> Metadata =
+            |_: &Self| move |tx| Job::FinishWithMetadata(num_results, tx);
+        /// Like [`finish_stream_words`](ThreadSafeStream::finish_stream_words),
+        /// but waits for the result asynchronously.
+        finish_stream_words_async(self) -> Vec<Word> = |_: &Self| Job::FinishWithWords;
+    }
+
+    /// Like [`into_model`](ThreadSafeStream::into_model),
+    /// but waits asynchronously for the worker to get through the jobs still queued.
+    ///
+    /// It doesn't depend on any particular async runtime.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub async fn into_model_async(mut self) -> Model {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+        match self.join_async().await {
+            Ok(model) => model,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for ThreadSafeStream {
+    #[inline]
+    fn drop(&mut self) {
+        if self.worker.is_some() {
+            // the worker's panic, if any, was already reported on its own thread
+            drop(self.cancel());
+        }
+    }
+}
+
+/// A future that queues up audio for a [`ThreadSafeStream`],
+/// returned by [`feed_audio_async`](ThreadSafeStream::feed_audio_async).
+#[must_use = "futures do nothing unless polled"]
+pub struct FeedAudio<'a> {
+    stream: &'a ThreadSafeStream,
+    audio: Option<Audio>,
+}
+
+impl Future for FeedAudio<'_> {
+    type Output = ();
+
+    #[inline]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let len = match &this.audio {
+            Some(audio) => audio.len(),
+            None => return Poll::Ready(()),
+        };
+        let stream = this.stream;
+        if !stream
+            .shared
+            .try_queue(len, stream.options.max_queued_samples, cx.waker())
+        {
+            return Poll::Pending;
+        }
+
+        // the samples are reserved in the queue, nothing after this can be interrupted
+        if let Some(audio) = this.audio.take() {
+            stream.shared.stats.received(len);
+            stream.send(Job::Feed(audio));
+        }
+        Poll::Ready(())
+    }
+}
+
+/// The results of a batch of `len` jobs the worker panicked before running.
+fn batch_failed(len: usize) -> Vec<crate::Result<JobOutput>> {
+    (0..len)
+        .map(|_| Err(crate::Error::WorkerPanicked))
+        .collect()
+}
+
+/// Abandons the job queue when the worker thread exits, even by panicking,
+/// so jobs sent afterwards are dropped instead of waiting forever for an answer.
+struct AbandonOnExit(JobQueue<Queued>);
+
+impl Drop for AbandonOnExit {
+    fn drop(&mut self) {
+        self.0.abandon();
+    }
+}
+
+/// The callback the watchdog delivers results to.
+type OnIdle = Box<dyn FnMut(crate::Result<Metadata>) + Send>;
+
+/// The timing observer of a [`ThreadSafeStream`], shared with every stream its worker creates.
+type TimingObserver = Arc<dyn Fn(StreamTiming) + Send + Sync>;
+
+fn install_timing_observer(stream: &mut OwnedStream, observer: Option<&TimingObserver>) {
+    match observer {
+        Some(observer) => {
+            let observer = Arc::clone(observer);
+            stream.set_timing_observer(move |timing| observer(timing));
+        }
+        None => stream.clear_timing_observer(),
+    }
+}
+
+/// The streaming state of the worker thread, which is freed when the watchdog finishes it,
+/// and created again when it is next needed.
+///
+/// Exactly one of `stream` and `idle` is set.
+struct State {
+    stream: Option<OwnedStream>,
+    idle: Option<Model>,
+    /// Set on every stream created, so it outlives the watchdog finishing them.
+    observer: Option<TimingObserver>,
+    /// How long the job being run waited in the queue, until the stream reports it.
+    queue_wait: Option<Duration>,
+}
+
+impl State {
+    fn stream(&mut self) -> crate::Result<&mut OwnedStream> {
+        if let Some(model) = self.idle.take() {
+            match OwnedStream::try_new(model) {
+                Ok(mut stream) => {
+                    install_timing_observer(&mut stream, self.observer.as_ref());
+                    self.stream = Some(stream);
+                }
+                Err((e, model)) => {
+                    self.idle = Some(model);
+                    return Err(e);
+                }
+            }
+        }
+        let stream = self.stream.as_mut().ok_or(crate::Error::Unknown)?;
+        if let Some(wait) = self.queue_wait.take() {
+            stream.set_queue_wait(wait);
+        }
+        Ok(stream)
+    }
+
+    fn set_timing_observer(&mut self, observer: Option<TimingObserver>) {
+        if let Some(stream) = self.stream.as_mut() {
+            install_timing_observer(stream, observer.as_ref());
+        }
+        self.observer = observer;
+    }
+
+    /// Finish the stream with `finish`, creating it first if it is idle.
+    fn finish<T>(
+        mut self,
+        finish: impl FnOnce(OwnedStream) -> (crate::Result<T>, Model),
+    ) -> (crate::Result<T>, Model) {
+        if let Err(e) = self.stream() {
+            return (Err(e), self.into_model());
+        }
+        match self.stream {
+            Some(stream) => finish(stream),
+            None => unreachable!("the stream was just created"),
+        }
+    }
+
+    fn into_model(self) -> Model {
+        match (self.stream, self.idle) {
+            (Some(stream), _) => stream.into_model(),
+            (None, Some(model)) => model,
+            (None, None) => unreachable!("the worker always has a stream or a model"),
+        }
+    }
+}
+
+/// The worker thread's side of a [`ThreadSafeStream`].
+struct Worker<'a> {
+    state: State,
+    options: StreamOptions,
+    shared: &'a Shared,
+    on_idle: Option<OnIdle>,
+    /// Every sample received and not dropped for backpressure,
+    /// which is lost if the stream isn't finished.
+    undecoded: u64,
+    /// When audio was last fed, if any was fed since the stream was created.
+    last_feed: Option<Instant>,
+    /// Whether the stream was shut down, after which nothing more is accepted.
+    shut_down: bool,
+}
+
+impl Worker<'_> {
+    fn feed(&mut self, audio: Audio) {
+        let len = audio.len();
+        if self.shut_down {
+            self.shared.stats.cancelled(len as u64);
+        } else {
+            self.undecoded += len as u64;
+            if !self.shared.cancelled.load(Ordering::Relaxed) {
+                if let Ok(stream) = self.state.stream() {
+                    let audio = audio.as_slice();
+                    match self.options.chunk_size {
+                        Some(chunk_size) => {
+                            for chunk in audio.chunks(chunk_size.max(1)) {
+                                stream.feed_audio(chunk);
+                            }
+                        }
+                        None => stream.feed_audio(audio),
+                    }
+                    self.shared.stats.fed(len);
+                    self.last_feed = Some(Instant::now());
+                }
+            }
+        }
+        // audio counts as queued until it is fed, so a slow model holds up new audio
+        self.shared.dequeued(len);
+    }
+
+    fn stream(&mut self) -> crate::Result<&mut OwnedStream> {
+        if self.shut_down {
+            return Err(crate::Error::ShuttingDown);
+        }
+        self.state.stream()
+    }
+
+    fn intermediate_decode(&mut self) -> crate::Result<String> {
+        let auto_flush = self.options.auto_flush;
+        let stream = self.stream()?;
+        if auto_flush {
+            stream.intermediate_decode_with_buffer_flush()
+        } else {
+            stream.intermediate_decode()
+        }
+    }
+
+    fn intermediate_decode_with_metadata(&mut self, num_results: u32) -> crate::Result<Metadata> {
+        let auto_flush = self.options.auto_flush;
+        let stream = self.stream()?;
+        if auto_flush {
+            stream.intermediate_decode_with_metadata_and_buffer_flush(num_results)
+        } else {
+            stream.intermediate_decode_with_metadata(num_results)
+        }
+    }
+
+    fn run_job(&mut self, job: Option<StreamJob>) -> crate::Result<JobOutput> {
+        match job {
+            None => Ok(JobOutput::Dropped),
+            Some(StreamJob::Feed(audio)) => {
+                self.feed(Audio::Owned(audio));
+                Ok(JobOutput::Fed)
+            }
+            Some(StreamJob::FeedShared(audio)) => {
+                let len = audio.len();
+                self.feed(Audio::Shared(audio, 0..len));
+                Ok(JobOutput::Fed)
+            }
+            Some(StreamJob::IntermediateDecode) => self.intermediate_decode().map(JobOutput::Text),
+            Some(StreamJob::IntermediateDecodeWithMetadata(num_results)) => self
+                .intermediate_decode_with_metadata(num_results)
+                .map(JobOutput::Metadata),
+            Some(StreamJob::FlushBuffers) => self
+                .stream()
+                .and_then(OwnedStream::flush_buffers)
+                .map(|()| JobOutput::Flushed),
+        }
+    }
+
+    /// Finish the stream with `finish`, creating it first if it is idle.
+    fn finish<T>(
+        self,
+        finish: impl FnOnce(OwnedStream) -> (crate::Result<T>, Model),
+    ) -> (crate::Result<T>, Model) {
+        if self.shut_down {
+            return (Err(crate::Error::ShuttingDown), self.state.into_model());
+        }
+        self.state.finish(finish)
+    }
+
+    /// Finish the stream and free its state, giving the result to the watchdog callback
+    /// if audio was fed since the stream was created.
+    fn finish_idle(&mut self) {
+        if let Some(stream) = self.state.stream.take() {
+            let (res, model) = stream.finish_stream_with_metadata(self.options.default_num_results);
+            self.state.idle = Some(model);
+            if let (Some(on_idle), Some(_)) = (self.on_idle.as_mut(), self.last_feed) {
+                on_idle(res);
+            }
+        }
+        self.last_feed = None;
+        self.undecoded = 0;
+    }
+
+    fn shutdown(&mut self) {
+        if !self.shut_down {
+            self.finish_idle();
+            self.shut_down = true;
+        }
+        self.shared.release_shutdown();
+    }
+}
+
+fn work(
+    stream: OwnedStream,
+    jobs: &JobQueue<Queued>,
+    options: StreamOptions,
+    shared: &Shared,
+    on_idle: Option<OnIdle>,
+) -> Model {
+    let worker = Worker {
+        state: State {
+            stream: Some(stream),
+            idle: None,
+            observer: None,
+            queue_wait: None,
+        },
+        options,
+        shared,
+        on_idle,
+        undecoded: 0,
+        last_feed: None,
+        shut_down: false,
+    };
+    job_queue::work(jobs, worker)
+}
+
+impl JobExecutor for Worker<'_> {
+    type Job = Queued;
+    type Output = Model;
+
+    fn timeout(&self) -> Option<Duration> {
+        match (self.options.idle_timeout, self.last_feed) {
+            (Some(timeout), Some(last)) => Some(timeout.saturating_sub(last.elapsed())),
+            _ => None,
+        }
+    }
+
+    fn idle(&mut self) {
+        // nobody is feeding this stream anymore: finish it, and free its state
+        self.finish_idle();
+    }
+
+    fn run(mut self, Queued { job, at }: Queued) -> ControlFlow<Model, Self> {
+        self.state.queue_wait = Some(at.elapsed());
+        match job {
+            Job::Feed(audio) => self.feed(audio),
+            Job::IntermediateDecode(tx) => {
+                tx.send(self.intermediate_decode());
+            }
+            Job::IntermediateDecodeWithMetadata(num_results, tx) => {
+                tx.send(self.intermediate_decode_with_metadata(num_results));
+            }
+            Job::Batch(jobs, tx) => {
+                tx.send(jobs.into_iter().map(|job| self.run_job(job)).collect());
+            }
+            Job::Finish(tx) => {
+                let (res, model) = self.finish(OwnedStream::finish_stream);
+                tx.send(res);
+                return ControlFlow::Break(model);
+            }
+            Job::FinishWithMetadata(num_results, tx) => {
+                let (res, model) =
+                    self.finish(|stream| stream.finish_stream_with_metadata(num_results));
+                tx.send(res);
+                return ControlFlow::Break(model);
+            }
+            Job::FinishWithWords(tx) => {
+                let (res, model) = self.finish(|stream| {
+                    let (res, model) = stream.finish_stream_with_metadata(1);
+                    (res.map(|metadata| metadata.best_words()), model)
+                });
+                tx.send(res);
+                return ControlFlow::Break(model);
+            }
+            Job::SetTimingObserver(observer) => self.state.set_timing_observer(observer),
+            Job::Shutdown => self.shutdown(),
+        }
+        ControlFlow::Continue(self)
+    }
+
+    fn closed(self) -> Model {
+        self.shared.stats.cancelled(self.undecoded);
+        self.state.into_model()
+    }
+}
@@ -1,6 +1,10 @@
 use std::borrow::{Borrow, Cow};
 use std::ffi::CStr;
 use std::fmt::{Debug, Display, Formatter};
+use std::time::Duration;
+
+/// The length of a timestep, the unit `libstt` positions tokens in.
+pub const TIMESTEP: Duration = Duration::from_millis(20);
 
 /// Stores text of an individual token, along with its timing information.
 #[repr(transparent)]
@@ -15,13 +19,13 @@ impl TokenMetadata {
     /// The text corresponding to this token
     #[inline]
     #[must_use]
-    pub fn text(&self) -> Cow<str> {
+    pub fn text(&self) -> Cow<'_, str> {
         // SAFETY: self.ptr.text will always point to valid metadata
         let cstr = unsafe { CStr::from_ptr(self.ptr.text) };
         cstr.to_string_lossy()
     }
 
-    /// Position of the token in units of 20ms
+    /// Position of the token in [`TIMESTEP`]s of 20ms
     #[inline]
     #[must_use]
     pub const fn timestep(&self) -> u32 {
@@ -35,6 +39,13 @@ impl TokenMetadata {
         self.ptr.start_time
     }
 
+    /// Position of the token, from its [`timestep`](TokenMetadata::timestep).
+    #[inline]
+    #[must_use]
+    pub const fn timestep_duration(&self) -> Duration {
+        TIMESTEP.saturating_mul(self.timestep())
+    }
+
     /// Convert this into an [`OwnedTokenMetadata`](OwnedTokenMetadata) struct.
     ///
     /// This is relatively cheap compared to its parent `to_owned` functions,
@@ -79,18 +90,63 @@ impl Display for TokenMetadata {
 /// An owned variant of [`TokenMetadata`](TokenMetadata).
 #[non_exhaustive]
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OwnedTokenMetadata {
     /// The text corresponding to this token
     pub text: String,
-    /// Position of the token in units of 20ms
+    /// Position of the token in [`TIMESTEP`]s of 20ms
     pub timestep: u32,
     /// Position of the token in seconds
     pub start_time: f32,
 }
 
+impl OwnedTokenMetadata {
+    /// Position of the token, from its [`timestep`](OwnedTokenMetadata::timestep).
+    #[inline]
+    #[must_use]
+    pub const fn timestep_duration(&self) -> Duration {
+        TIMESTEP.saturating_mul(self.timestep)
+    }
+
+    /// When the token ends, given the token after it in the same transcript, if any.
+    ///
+    /// `libstt` only reports when tokens start, so a token is taken to end where the next
+    /// one starts, or one [`TIMESTEP`] after it starts if it is the last.
+    #[inline]
+    #[must_use]
+    pub fn end_time(&self, next: Option<&Self>) -> Duration {
+        let start = self.timestep_duration();
+        next.map_or(start + TIMESTEP, |next| next.timestep_duration().max(start))
+    }
+}
+
 impl Display for OwnedTokenMetadata {
     #[inline]
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.text)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{OwnedTokenMetadata, TIMESTEP};
+    use std::time::Duration;
+
+    fn token(timestep: u32) -> OwnedTokenMetadata {
+        OwnedTokenMetadata {
+            text: "a".to_string(),
+            timestep,
+            start_time: timestep as f32 * 0.02,
+        }
+    }
+
+    #[test]
+    fn tokens_end_where_the_next_one_starts() {
+        let (first, second) = (token(3), token(10));
+        assert_eq!(first.timestep_duration(), Duration::from_millis(60));
+        assert_eq!(first.end_time(Some(&second)), Duration::from_millis(200));
+        assert_eq!(second.end_time(None), Duration::from_millis(200) + TIMESTEP);
+        // out of order tokens don't end before they start
+        assert_eq!(second.end_time(Some(&first)), second.timestep_duration());
+    }
+}
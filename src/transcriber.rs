@@ -0,0 +1,702 @@
+use crate::audio::{duration_of, samples_in};
+use crate::sink::TranscriptSink;
+use crate::stream::OwnedState;
+use crate::vad::{EnergyDetector, VoiceActivityDetector};
+use crate::{
+    Diarizer, InferenceStats, Model, OwnedTokenMetadata, StatsHandle, TokenMetadata, Word,
+};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How a [`Transcriber`] decides an utterance has finished.
+///
+/// Voice activity detectors often mistake the quiet end of a word for silence,
+/// cutting it off. The decoder knows better whether the speaker is mid-word,
+/// so the decoder-based policies check intermediate decodes every
+/// [`decode_interval`](TranscriberConfig::decode_interval), at the cost of running them.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum EndpointPolicy {
+    /// The utterance ends after [`trailing_silence`](TranscriberConfig::trailing_silence)
+    /// of silence, as judged by the voice activity detector.
+    #[default]
+    VadOnly,
+    /// The utterance ends once the decoder's text stops changing,
+    /// and it has decoded no new tokens for
+    /// [`trailing_silence`](TranscriberConfig::trailing_silence).
+    /// The voice activity detector only starts utterances.
+    DecoderAssisted,
+    /// The utterance ends after [`trailing_silence`](TranscriberConfig::trailing_silence)
+    /// of silence, as judged by the voice activity detector,
+    /// once the decoder's text has also stopped changing.
+    /// If it keeps changing, the utterance ends after twice the trailing silence regardless.
+    Hybrid,
+}
+
+/// Tuning knobs for how a [`Transcriber`] splits audio into utterances.
+#[derive(Copy, Clone, Debug)]
+pub struct TranscriberConfig {
+    /// How much audio from before speech was detected to include in an utterance.
+    ///
+    /// Voice activity detectors take a few frames to trigger,
+    /// so without this the start of the first word is often cut off.
+    pub pre_roll: Duration,
+    /// Utterances with less speech than this are assumed to be noise and discarded.
+    pub min_speech: Duration,
+    /// How long speech must stop for before the utterance is considered finished.
+    pub trailing_silence: Duration,
+    /// How the end of an utterance is detected.
+    pub endpoint_policy: EndpointPolicy,
+    /// How often to check the decoder's intermediate text,
+    /// for the decoder-based [`EndpointPolicy`]s.
+    pub decode_interval: Duration,
+}
+
+impl Default for TranscriberConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            pre_roll: Duration::from_millis(300),
+            min_speech: Duration::from_millis(150),
+            trailing_silence: Duration::from_millis(700),
+            endpoint_policy: EndpointPolicy::VadOnly,
+            decode_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+/// A finished utterance.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Segment {
+    /// The transcribed text.
+    pub text: String,
+    /// Offset of the start of this utterance from the start of the audio.
+    pub start: Duration,
+    /// Offset of the end of the last speech in this utterance from the start of the audio.
+    pub end: Duration,
+    /// The words in this utterance, with their offsets from the start of the audio.
+    pub words: Vec<Word>,
+    /// The tokens in this utterance, with their positions from the start of the audio.
+    pub tokens: Vec<OwnedTokenMetadata>,
+    /// Approximated confidence value for this utterance.
+    /// See [`CandidateTranscript::confidence`](crate::CandidateTranscript::confidence).
+    ///
+    /// Noise mistaken for speech usually has a much lower confidence than real speech,
+    /// so this is useful for filtering out garbage segments.
+    pub confidence: f64,
+    /// The speaker of this utterance, if a [`Diarizer`] is set and could tell.
+    pub speaker: Option<u32>,
+    /// Which model transcribed this utterance:
+    /// the number of times [`Transcriber::swap_model`] had been called before it started.
+    pub model_generation: u32,
+}
+
+/// Events emitted by a [`Transcriber`] as audio is pushed into it.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum TranscriptEvent {
+    /// Speech was detected, and a new utterance has started.
+    SpeechStarted {
+        /// Offset of the start of the utterance from the start of the audio,
+        /// including pre-roll.
+        start: Duration,
+    },
+    /// An utterance finished and was transcribed.
+    Final(Segment),
+}
+
+struct ActiveSegment {
+    state: OwnedState,
+    start: u64,
+    last_speech: u64,
+    speech_samples: u64,
+    silence_samples: u64,
+    /// The audio of this utterance, only kept if there is a diarizer to pass it to.
+    audio: Option<Vec<i16>>,
+    /// Where the decoder is next due to be checked, for the decoder-based endpoint policies.
+    next_check: u64,
+    /// The text of the last intermediate decode.
+    decoded: Option<String>,
+    /// Whether the text was the same in the last two intermediate decodes.
+    text_stable: bool,
+    /// Samples fed since the start of the last token in the last intermediate decode.
+    decoder_silence: u64,
+}
+
+/// Transcribes a live audio feed, using voice activity detection to split it into utterances.
+///
+/// Audio is pushed in with [`push_audio`](Transcriber::push_audio) as it arrives.
+/// When speech is detected, a new [`Stream`](crate::Stream) is started on the model,
+/// and once the speaker stops for long enough, the stream is finished
+/// and a [`TranscriptEvent::Final`] is emitted.
+///
+/// The voice activity detector is pluggable: see the [`vad`](crate::vad) module.
+/// Segments and their words can be labelled with speakers by setting a [`Diarizer`].
+pub struct Transcriber<V = EnergyDetector> {
+    // declared before the model, so the streaming state is always freed first
+    segment: Option<ActiveSegment>,
+    model: Model,
+    vad: V,
+    diarizer: Option<Box<dyn Diarizer + Send + Sync>>,
+    config: TranscriberConfig,
+    sample_rate: u32,
+    frame_len: usize,
+    pending: Vec<i16>,
+    pre_roll: VecDeque<i16>,
+    position: u64,
+    model_generation: u32,
+    stats: StatsHandle,
+}
+
+// SAFETY: the streaming state is only ever accessed through `&mut self`
+unsafe impl<V: Send> Send for Transcriber<V> {}
+unsafe impl<V: Sync> Sync for Transcriber<V> {}
+
+impl<V: VoiceActivityDetector> Transcriber<V> {
+    /// Create a new `Transcriber` using the default [`TranscriberConfig`].
+    #[inline]
+    pub fn new(model: Model, vad: V) -> Self {
+        Self::with_config(model, vad, TranscriberConfig::default())
+    }
+
+    /// Create a new `Transcriber`.
+    #[inline]
+    pub fn with_config(model: Model, vad: V, config: TranscriberConfig) -> Self {
+        let sample_rate = model.get_sample_rate() as u32;
+        let frame_len = vad.frame_len(sample_rate).max(1);
+
+        Self {
+            segment: None,
+            model,
+            vad,
+            diarizer: None,
+            config,
+            sample_rate,
+            frame_len,
+            pending: Vec::with_capacity(frame_len),
+            pre_roll: VecDeque::new(),
+            position: 0,
+            model_generation: 0,
+            stats: StatsHandle::default(),
+        }
+    }
+
+    /// Return a reference to the [`Model`] this `Transcriber` uses.
+    #[inline]
+    #[must_use]
+    pub const fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// Return a reference to the voice activity detector this `Transcriber` uses.
+    #[inline]
+    #[must_use]
+    pub const fn vad(&self) -> &V {
+        &self.vad
+    }
+
+    /// Set the [`Diarizer`] used to label the speakers of segments and their words,
+    /// returning the `Transcriber`.
+    #[inline]
+    #[must_use]
+    pub fn with_diarizer(mut self, diarizer: impl Diarizer + Send + Sync + 'static) -> Self {
+        self.set_diarizer(diarizer);
+        self
+    }
+
+    /// Set the [`Diarizer`] used to label the speakers of segments and their words.
+    ///
+    /// Utterances already in progress are labelled with it only if there was a diarizer
+    /// set when they started, since their audio isn't kept otherwise.
+    #[inline]
+    pub fn set_diarizer(&mut self, diarizer: impl Diarizer + Send + Sync + 'static) {
+        self.diarizer = Some(Box::new(diarizer));
+    }
+
+    /// Remove the [`Diarizer`] from this `Transcriber`, returning it.
+    #[inline]
+    pub fn take_diarizer(&mut self) -> Option<Box<dyn Diarizer + Send + Sync>> {
+        self.diarizer.take()
+    }
+
+    /// Return the [`TranscriberConfig`] this `Transcriber` uses.
+    #[inline]
+    #[must_use]
+    pub const fn config(&self) -> &TranscriberConfig {
+        &self.config
+    }
+
+    /// Return the [`InferenceStats`] of this `Transcriber` so far.
+    ///
+    /// Audio buffered until it fills a frame for the detector is counted as received,
+    /// but not yet as fed or dropped.
+    #[inline]
+    #[must_use]
+    pub fn stats(&self) -> InferenceStats {
+        self.stats.snapshot()
+    }
+
+    /// Return a [`StatsHandle`] for this `Transcriber`,
+    /// which can still be read after it is turned back into its model.
+    #[inline]
+    #[must_use]
+    pub fn stats_handle(&self) -> StatsHandle {
+        self.stats.clone()
+    }
+
+    /// Whether an utterance is currently in progress.
+    #[inline]
+    #[must_use]
+    pub const fn in_speech(&self) -> bool {
+        self.segment.is_some()
+    }
+
+    /// Feed audio into the transcriber, returning any events this caused.
+    ///
+    /// `audio` should be 16-bit, mono audio at the model's sample rate.
+    /// It can be of any length: it is buffered internally into frames for the detector.
+    ///
+    /// # Errors
+    /// Passes through any errors from the voice activity detector or the C library.
+    /// See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn push_audio(&mut self, mut audio: &[i16]) -> crate::Result<Vec<TranscriptEvent>> {
+        let mut events = Vec::new();
+        self.stats.received(audio.len());
+
+        while !audio.is_empty() {
+            let needed = self.frame_len - self.pending.len();
+            let (head, tail) = audio.split_at(needed.min(audio.len()));
+            self.pending.extend_from_slice(head);
+            audio = tail;
+
+            if self.pending.len() == self.frame_len {
+                let frame = std::mem::take(&mut self.pending);
+                let res = self.process_frame(&frame, &mut events);
+                self.pending = frame;
+                self.pending.clear();
+                res?;
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Feed audio into the transcriber, writing every utterance it finishes to `sink`.
+    ///
+    /// See [`push_audio`](Transcriber::push_audio).
+    ///
+    /// # Errors
+    /// Passes through any errors from the voice activity detector, the C library or `sink`.
+    /// See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn push_audio_to(
+        &mut self,
+        audio: &[i16],
+        mut sink: impl TranscriptSink,
+    ) -> crate::Result<()> {
+        for event in self.push_audio(audio)? {
+            if let TranscriptEvent::Final(segment) = event {
+                sink.write_segment(&segment)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finish the utterance in progress, if any, write it to `sink`, and flush `sink`.
+    ///
+    /// See [`finish`](Transcriber::finish).
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library or `sink`. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn finish_to(&mut self, mut sink: impl TranscriptSink) -> crate::Result<()> {
+        if let Some(segment) = self.finish()? {
+            sink.write_segment(&segment)?;
+        }
+        sink.flush()
+    }
+
+    /// Finish the utterance in progress, if any, and return its transcription.
+    ///
+    /// Buffered audio that does not fill a whole frame is fed to the model as-is.
+    ///
+    /// # Errors
+    /// Passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn finish(&mut self) -> crate::Result<Option<Segment>> {
+        let pending = std::mem::take(&mut self.pending);
+        self.position += pending.len() as u64;
+
+        if let Some(segment) = self.segment.as_mut() {
+            segment.silence_samples += pending.len() as u64;
+            Self::feed(&mut self.model, &self.stats, segment, &pending);
+        } else {
+            self.stats.gated(pending.len() as u64);
+        }
+        self.stats.gated(self.pre_roll.len() as u64);
+        self.pre_roll.clear();
+
+        self.end_segment()
+    }
+
+    /// Switch to transcribing with `model`, returning the model used until now.
+    ///
+    /// The utterance in progress, if any, is finished on the old model and returned along with it.
+    /// Every utterance after it is transcribed with `model`, and tagged with the next
+    /// [`model_generation`](Segment::model_generation).
+    /// This is useful for moving between quality and latency tiers in a live service.
+    ///
+    /// # Errors
+    /// Returns [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate)
+    /// if `model` expects a different sample rate to the current model.
+    /// Otherwise, passes through any errors from the C library
+    /// finishing the utterance in progress, which is lost. See enum [`Error`](crate::Error).
+    /// The current model is kept if this fails, and `model` is handed back with the error.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn swap_model(
+        &mut self,
+        model: Model,
+    ) -> Result<(Model, Option<Segment>), (crate::Error, Model)> {
+        let sample_rate = model.get_sample_rate() as u32;
+        if sample_rate != self.sample_rate {
+            return Err((crate::Error::UnsupportedSampleRate(sample_rate), model));
+        }
+
+        let segment = match self.end_segment() {
+            Ok(segment) => segment,
+            Err(e) => return Err((e, model)),
+        };
+        self.model_generation += 1;
+        Ok((std::mem::replace(&mut self.model, model), segment))
+    }
+
+    /// Take this `Transcriber`, and return the [`Model`] it used.
+    ///
+    /// Any utterance in progress is discarded,
+    /// and counted in [`InferenceStats::dropped_cancelled`].
+    #[inline]
+    #[must_use]
+    pub fn into_model(self) -> Model {
+        if let Some(segment) = &self.segment {
+            self.stats.cancelled(self.position - segment.start);
+        }
+        self.model
+    }
+
+    fn process_frame(
+        &mut self,
+        frame: &[i16],
+        events: &mut Vec<TranscriptEvent>,
+    ) -> crate::Result<()> {
+        let is_speech = self.vad.is_speech(frame, self.sample_rate)?;
+        let frame_start = self.position;
+        self.position += frame.len() as u64;
+        let trailing_silence = self.duration_to_samples(self.config.trailing_silence);
+        let decode_interval = self.duration_to_samples(self.config.decode_interval).max(1);
+
+        match self.segment.as_mut() {
+            Some(segment) => {
+                Self::feed(&mut self.model, &self.stats, segment, frame);
+
+                if is_speech {
+                    segment.speech_samples += frame.len() as u64;
+                    segment.silence_samples = 0;
+                    segment.last_speech = self.position;
+                } else {
+                    segment.silence_samples += frame.len() as u64;
+                }
+
+                let policy = self.config.endpoint_policy;
+                if policy != EndpointPolicy::VadOnly && self.position >= segment.next_check {
+                    segment.next_check = self.position + decode_interval;
+                    Self::check_decoder(&mut self.model, segment, self.position, self.sample_rate)?;
+                }
+
+                let vad_done = segment.silence_samples >= trailing_silence;
+                let finished = match policy {
+                    EndpointPolicy::VadOnly => vad_done,
+                    EndpointPolicy::DecoderAssisted => {
+                        segment.text_stable && segment.decoder_silence >= trailing_silence
+                    }
+                    EndpointPolicy::Hybrid => {
+                        vad_done
+                            && (segment.text_stable
+                                || segment.silence_samples >= trailing_silence * 2)
+                    }
+                };
+                if finished {
+                    if let Some(segment) = self.end_segment()? {
+                        events.push(TranscriptEvent::Final(segment));
+                    }
+                }
+            }
+            None if is_speech => {
+                let start = frame_start - self.pre_roll.len() as u64;
+                let mut segment = ActiveSegment {
+                    state: OwnedState::new(&mut self.model)?,
+                    start,
+                    last_speech: self.position,
+                    speech_samples: frame.len() as u64,
+                    silence_samples: 0,
+                    audio: self.diarizer.as_ref().map(|_| Vec::new()),
+                    next_check: 0,
+                    decoded: None,
+                    text_stable: false,
+                    decoder_silence: 0,
+                };
+
+                let (a, b) = self.pre_roll.as_slices();
+                for audio in [a, b, frame] {
+                    Self::feed(&mut self.model, &self.stats, &mut segment, audio);
+                }
+                self.pre_roll.clear();
+                self.segment = Some(segment);
+                events.push(TranscriptEvent::SpeechStarted {
+                    start: self.samples_to_duration(start),
+                });
+            }
+            None => {
+                let max = self.duration_to_samples(self.config.pre_roll) as usize;
+                self.pre_roll.extend(frame);
+                let excess = self.pre_roll.len().saturating_sub(max);
+                self.pre_roll.drain(..excess);
+                self.stats.gated(excess as u64);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end_segment(&mut self) -> crate::Result<Option<Segment>> {
+        let segment = match self.segment.take() {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        self.vad.reset();
+
+        if segment.speech_samples < self.duration_to_samples(self.config.min_speech) {
+            // too short to be speech, dropping the state frees it without decoding
+            self.stats.gated(self.position - segment.start);
+            return Ok(None);
+        }
+
+        // SAFETY: the state was created from `self.model`
+        let stream = unsafe { segment.state.into_stream(&mut self.model) };
+        let metadata = stream.finish_stream_with_metadata(1)?;
+        let (text, mut words, mut tokens, confidence) = match metadata.transcripts().first() {
+            Some(transcript) => (
+                transcript
+                    .tokens()
+                    .iter()
+                    .map(TokenMetadata::text)
+                    .collect::<String>(),
+                transcript.words(),
+                transcript
+                    .tokens()
+                    .iter()
+                    .map(TokenMetadata::to_owned)
+                    .collect::<Vec<_>>(),
+                transcript.confidence(),
+            ),
+            None => (String::new(), Vec::new(), Vec::new(), 0.0),
+        };
+
+        let mut speaker = None;
+        if let (Some(diarizer), Some(audio)) = (self.diarizer.as_mut(), segment.audio) {
+            diarizer.label_words(&audio, self.sample_rate, &mut words)?;
+            speaker = diarizer.speaker(&audio, self.sample_rate)?;
+        }
+
+        // word timings are relative to the start of the stream, which started with this segment
+        let start = self.samples_to_duration(segment.start);
+        let start_timestep = (start.as_millis() / crate::TIMESTEP.as_millis()) as u32;
+        for word in &mut words {
+            word.start += start;
+            word.timestep += start_timestep;
+        }
+        for token in &mut tokens {
+            token.start_time += start.as_secs_f32();
+            token.timestep += start_timestep;
+        }
+
+        Ok(Some(Segment {
+            text,
+            start,
+            end: self.samples_to_duration(segment.last_speech),
+            words,
+            tokens,
+            confidence,
+            speaker,
+            model_generation: self.model_generation,
+        }))
+    }
+
+    /// Run an intermediate decode, and note whether its text changed and when its last token started.
+    fn check_decoder(
+        model: &mut Model,
+        segment: &mut ActiveSegment,
+        position: u64,
+        sample_rate: u32,
+    ) -> crate::Result<()> {
+        // SAFETY: the state is owned by the active segment and references `model`
+        let metadata =
+            unsafe { segment.state.as_stream(model) }.intermediate_decode_with_metadata(1)?;
+        let (text, last_token) = match metadata.transcripts().first() {
+            Some(transcript) => (
+                transcript
+                    .tokens()
+                    .iter()
+                    .map(TokenMetadata::text)
+                    .collect::<String>(),
+                transcript.tokens().last().map(TokenMetadata::start_time),
+            ),
+            None => (String::new(), None),
+        };
+
+        // token timings are relative to the start of the stream, which started with this segment
+        let last_token = last_token.map_or(0, |start_time| {
+            samples_in(Duration::from_secs_f32(start_time.max(0.0)), sample_rate) as u64
+        });
+        segment.decoder_silence = (position - segment.start).saturating_sub(last_token);
+        segment.text_stable = segment.decoded.as_ref() == Some(&text);
+        segment.decoded = Some(text);
+        Ok(())
+    }
+
+    fn feed(model: &mut Model, stats: &StatsHandle, segment: &mut ActiveSegment, audio: &[i16]) {
+        // SAFETY: the state is owned by the active segment and references `model`
+        unsafe { segment.state.as_stream(model) }.feed_audio(audio);
+        stats.fed(audio.len());
+        if let Some(kept) = segment.audio.as_mut() {
+            kept.extend_from_slice(audio);
+        }
+    }
+
+    fn samples_to_duration(&self, samples: u64) -> Duration {
+        duration_of(samples, self.sample_rate)
+    }
+
+    fn duration_to_samples(&self, duration: Duration) -> u64 {
+        samples_in(duration, self.sample_rate) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{concat, silence};
+    use std::sync::{Arc, Mutex};
+
+    const MS: usize = 16;
+
+    fn speech(ms: usize) -> Vec<i16> {
+        (0..ms * MS)
+            .map(|i| if i % 2 == 0 { 8000 } else { -8000 })
+            .collect()
+    }
+
+    fn transcriber() -> Transcriber {
+        Transcriber::new(Model::new("model").unwrap(), EnergyDetector::default())
+    }
+
+    fn finals(events: Vec<TranscriptEvent>) -> Vec<Segment> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                TranscriptEvent::Final(segment) => Some(segment),
+                TranscriptEvent::SpeechStarted { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn utterances_start_with_pre_roll_and_end_after_trailing_silence() {
+        let mut transcriber = transcriber();
+        let audio = concat(&[
+            silence(Duration::from_millis(600), 16000),
+            speech(1020),
+            silence(Duration::from_millis(690), 16000),
+        ]);
+        let events = transcriber.push_audio(&audio).unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [TranscriptEvent::SpeechStarted { start }] if *start == Duration::from_millis(300)
+        ));
+        assert!(transcriber.in_speech());
+
+        let segments = finals(
+            transcriber
+                .push_audio(&silence(Duration::from_millis(30), 16000))
+                .unwrap(),
+        );
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].start, Duration::from_millis(300));
+        assert_eq!(segments[0].end, Duration::from_millis(1620));
+        assert!(!segments[0].text.is_empty());
+        assert!(!transcriber.in_speech());
+    }
+
+    #[test]
+    fn bursts_shorter_than_min_speech_are_dropped() {
+        let mut transcriber = transcriber();
+        let audio = concat(&[speech(90), silence(Duration::from_secs(1), 16000)]);
+        let events = transcriber.push_audio(&audio).unwrap();
+        assert!(matches!(
+            events.as_slice(),
+            [TranscriptEvent::SpeechStarted { .. }]
+        ));
+        assert!(!transcriber.in_speech());
+        assert_eq!(transcriber.finish().unwrap().map(|s| s.text), None);
+    }
+
+    #[test]
+    fn finish_returns_the_utterance_in_progress() {
+        let mut transcriber = transcriber();
+        transcriber.push_audio(&speech(1005)).unwrap();
+        assert!(transcriber.in_speech());
+
+        let segment = transcriber.finish().unwrap().unwrap();
+        assert_eq!(segment.start, Duration::ZERO);
+        assert_eq!(segment.end, Duration::from_millis(990));
+        assert_eq!(segment.model_generation, 0);
+        assert!(!transcriber.in_speech());
+        assert!(transcriber.finish().unwrap().is_none());
+    }
+
+    /// Labels audio by its loudness, and records the length of the audio it was given.
+    struct LoudnessDiarizer(Arc<Mutex<Vec<usize>>>);
+
+    impl Diarizer for LoudnessDiarizer {
+        fn speaker(&mut self, audio: &[i16], _sample_rate: u32) -> crate::Result<Option<u32>> {
+            self.0
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(audio.len());
+            Ok(audio.iter().max().map(|&peak| u32::from(peak > 4000)))
+        }
+    }
+
+    #[test]
+    fn diarizers_label_segments_and_their_words() {
+        let calls = Arc::default();
+        let mut transcriber = transcriber().with_diarizer(LoudnessDiarizer(Arc::clone(&calls)));
+        transcriber.push_audio(&speech(1005)).unwrap();
+        let segment = transcriber.finish().unwrap().unwrap();
+
+        // one call for each word, then one for the whole utterance
+        assert_eq!(*calls.lock().unwrap(), [8000, 8000, 80, 16080]);
+        assert_eq!(segment.speaker, Some(1));
+        let speakers: Vec<_> = segment.words.iter().map(|word| word.speaker).collect();
+        assert_eq!(speakers, [Some(1); 3]);
+
+        assert!(transcriber.take_diarizer().is_some());
+        transcriber.push_audio(&speech(1005)).unwrap();
+        let segment = transcriber.finish().unwrap().unwrap();
+        assert_eq!(segment.speaker, None);
+        assert!(segment.words.iter().all(|word| word.speaker.is_none()));
+        assert_eq!(calls.lock().unwrap().len(), 4);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::segment::WordSegmenter;
+use crate::Metadata;
+use std::ops::Range;
+use std::time::Duration;
+
+/// A word in a transcript, made up of one or more tokens.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Word {
+    /// The text of this word.
+    pub text: String,
+    /// Offset of the start of this word from the start of the audio.
+    pub start: Duration,
+    /// Position of the start of this word in [`TIMESTEP`](crate::TIMESTEP)s of 20ms.
+    pub timestep: u32,
+    /// The speaker of this word, if a [`Diarizer`](crate::Diarizer) labelled it.
+    pub speaker: Option<u32>,
+}
+
+/// Where a token sits in the text of its transcript,
+/// from [`CandidateTranscript::char_offsets`](crate::CandidateTranscript::char_offsets).
+///
+/// The text of a transcript is its tokens joined together,
+/// as returned by [`CandidateTranscript::text`](crate::CandidateTranscript::text).
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenOffset {
+    /// The byte indices of the token, for slicing the text.
+    pub bytes: Range<usize>,
+    /// The character indices of the token, for UIs that count in characters.
+    pub chars: Range<usize>,
+}
+
+/// Information about the model that produced a [`Transcription`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModelInfo {
+    /// The sample rate the model expects, in Hz.
+    pub sample_rate: u32,
+    /// The beam width used by the decoder.
+    pub beam_width: u32,
+}
+
+/// The result of transcribing audio, with everything most applications need in one place.
+///
+/// Unlike [`Metadata`], this is an owned, plain data type,
+/// and can be serialized with `serde` when the `serde` feature is enabled.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transcription {
+    /// The transcribed text.
+    pub text: String,
+    /// The words in the transcribed text, with their timings.
+    pub words: Vec<Word>,
+    /// Approximated confidence value for this transcription.
+    /// See [`CandidateTranscript::confidence`](crate::CandidateTranscript::confidence).
+    pub confidence: f64,
+    /// How much audio was transcribed.
+    pub audio_duration: Duration,
+    /// How long transcribing the audio took.
+    pub processing_time: Duration,
+    /// The model that produced this transcription.
+    pub model_info: ModelInfo,
+}
+
+impl Transcription {
+    /// Build a `Transcription` from the best candidate transcript in `metadata`.
+    pub(crate) fn from_metadata(
+        metadata: &Metadata,
+        audio_duration: Duration,
+        processing_time: Duration,
+        model_info: ModelInfo,
+    ) -> Self {
+        let (text, words, confidence) = match metadata.transcripts().first() {
+            Some(transcript) => (
+                transcript
+                    .tokens()
+                    .iter()
+                    .map(|t| t.text())
+                    .collect::<String>(),
+                transcript.words(),
+                transcript.confidence(),
+            ),
+            None => (String::new(), Vec::new(), 0.0),
+        };
+
+        Self {
+            text,
+            words,
+            confidence,
+            audio_duration,
+            processing_time,
+            model_info,
+        }
+    }
+}
+
+/// Group tokens, given as `(text, timestep, start_time)`, into words found by `segmenter`.
+///
+/// Each word takes its timing from the token it starts in.
+pub(crate) fn group_words<'a>(
+    tokens: impl IntoIterator<Item = (&'a str, u32, f32)>,
+    segmenter: &(impl WordSegmenter + ?Sized),
+) -> Vec<Word> {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    split_words(&tokens, segmenter)
+        .into_iter()
+        .map(|(word, _)| word)
+        .collect()
+}
+
+/// Like [`group_words`], but pairs each word with when it ends:
+/// when the token after its last one starts, or `audio_duration` if there is none.
+pub(crate) fn group_words_with_end_times<'a>(
+    tokens: impl IntoIterator<Item = (&'a str, u32, f32)>,
+    segmenter: &(impl WordSegmenter + ?Sized),
+    audio_duration: Duration,
+) -> Vec<(Word, Duration)> {
+    let tokens: Vec<_> = tokens.into_iter().collect();
+    split_words(&tokens, segmenter)
+        .into_iter()
+        .map(|(word, next)| {
+            let end = tokens
+                .get(next)
+                .map_or(audio_duration, |(_, _, start_time)| {
+                    Duration::from_secs_f32(start_time.max(0.0))
+                });
+            let end = end.max(word.start);
+            (word, end)
+        })
+        .collect()
+}
+
+/// Split `tokens` into words, each with the index of the token after its last one.
+fn split_words(
+    tokens: &[(&str, u32, f32)],
+    segmenter: &(impl WordSegmenter + ?Sized),
+) -> Vec<(Word, usize)> {
+    let offsets = token_offsets(tokens.iter().map(|(text, _, _)| *text));
+    let text: String = tokens.iter().map(|(text, _, _)| *text).collect();
+
+    segmenter
+        .split(&text)
+        .into_iter()
+        .filter_map(|range| {
+            let word = text.get(range.clone()).filter(|word| !word.is_empty())?;
+            let token = offsets.partition_point(|o| o.bytes.end <= range.start);
+            let last = offsets.partition_point(|o| o.bytes.end < range.end);
+            let (_, timestep, start_time) = *tokens.get(token)?;
+            let word = Word {
+                text: word.to_string(),
+                start: Duration::from_secs_f32(start_time.max(0.0)),
+                timestep,
+                speaker: None,
+            };
+            Some((word, last + 1))
+        })
+        .collect()
+}
+
+/// Find where each token, given as its text, sits in the tokens joined together.
+pub(crate) fn token_offsets<'a>(tokens: impl IntoIterator<Item = &'a str>) -> Vec<TokenOffset> {
+    let mut byte = 0;
+    let mut char = 0;
+    tokens
+        .into_iter()
+        .map(|text| {
+            let start = (byte, char);
+            byte += text.len();
+            char += text.chars().count();
+            TokenOffset {
+                bytes: start.0..byte,
+                chars: start.1..char,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,746 @@
+//! Helpers for the setup most applications need before they can transcribe anything:
+//! finding a model in a directory, and converting audio into the form the model expects.
+//!
+//! [`ModelLocator`] makes a model directory a first-class concept: it picks the model
+//! and scorer by explicit rules, and reports anything it had to choose between.
+//! [`ModelConfig`] loads a model set up with a scorer, beam width and hot words,
+//! which it can read from environment variables.
+//!
+//! The examples use these too, so they don't each carry their own copy.
+
+use crate::Model;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// The formats of models `libstt` can load, in order of precedence:
+/// when a directory holds models in several formats, [`ModelLocator`] picks the earliest.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ModelFormat {
+    /// A TensorFlow Lite model, `.tflite`. Current releases of `libstt` only load these.
+    TfLite,
+    /// A memory mapped TensorFlow model, `.pbmm`.
+    Pbmm,
+    /// A TensorFlow model, `.pb`.
+    Pb,
+}
+
+impl ModelFormat {
+    /// The format of the model at `path`, going by its file extension.
+    #[inline]
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "tflite" => Some(Self::TfLite),
+            "pbmm" => Some(Self::Pbmm),
+            "pb" => Some(Self::Pb),
+            _ => None,
+        }
+    }
+
+    /// The file extension of this format, without the leading dot.
+    #[inline]
+    #[must_use]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::TfLite => "tflite",
+            Self::Pbmm => "pbmm",
+            Self::Pb => "pb",
+        }
+    }
+
+    /// The format of the model in `buffer`, going by its contents,
+    /// or `None` if it doesn't look like any of them.
+    ///
+    /// TensorFlow Lite models are recognised by their `TFL3` identifier, and memory mapped
+    /// models by the directory of tensors at their end. Plain TensorFlow models have no
+    /// identifier, so anything starting like a serialized graph is taken to be one.
+    #[allow(clippy::missing_inline_in_public_items)]
+    #[must_use]
+    pub fn detect(buffer: &[u8]) -> Option<Self> {
+        if is_tflite(buffer) {
+            return Some(Self::TfLite);
+        }
+        if let Some(offset) = buffer
+            .len()
+            .checked_sub(8)
+            .and_then(|end| directory_offset(&buffer[end..], end as u64))
+        {
+            if is_memmapped_directory(&buffer[offset as usize..buffer.len() - 8]) {
+                return Some(Self::Pbmm);
+            }
+        }
+        is_graph_def(buffer).then_some(Self::Pb)
+    }
+
+    /// The format of the model in the file at `path`, going by its contents,
+    /// like [`detect`](ModelFormat::detect). Only the start and end of the file are read.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if the file could not be read.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn detect_file(path: impl AsRef<Path>) -> crate::Result<Option<Self>> {
+        let mut file = std::fs::File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut head = Vec::with_capacity(16);
+        file.by_ref().take(16).read_to_end(&mut head)?;
+        if is_tflite(&head) {
+            return Ok(Some(Self::TfLite));
+        }
+        if let Some(end) = len.checked_sub(8) {
+            let mut tail = [0; 8];
+            file.seek(SeekFrom::Start(end))?;
+            file.read_exact(&mut tail)?;
+            if let Some(offset) = directory_offset(&tail, end) {
+                let mut directory = Vec::new();
+                file.seek(SeekFrom::Start(offset))?;
+                file.take(end - offset).read_to_end(&mut directory)?;
+                if is_memmapped_directory(&directory) {
+                    return Ok(Some(Self::Pbmm));
+                }
+            }
+        }
+        Ok(is_graph_def(&head).then_some(Self::Pb))
+    }
+}
+
+/// The largest directory of tensors [`ModelFormat::detect_file`] reads from the end of a file.
+/// Real ones are a few kilobytes.
+const MAX_DIRECTORY_LEN: u64 = 1 << 20;
+
+/// Whether `head` has the FlatBuffers file identifier of TensorFlow Lite models.
+fn is_tflite(head: &[u8]) -> bool {
+    head.get(4..8) == Some(b"TFL3")
+}
+
+/// The offset of the directory of a memory mapped model, from the last 8 bytes of a file
+/// that are at `end`, if it could be one.
+fn directory_offset(tail: &[u8], end: u64) -> Option<u64> {
+    let offset = u64::from_le_bytes(tail.try_into().ok()?);
+    (offset < end && end - offset <= MAX_DIRECTORY_LEN).then_some(offset)
+}
+
+/// Whether `directory` names tensors the way TensorFlow's memory mapped file system does.
+fn is_memmapped_directory(directory: &[u8]) -> bool {
+    const PREFIX: &[u8] = b"memmapped_package://";
+    directory
+        .windows(PREFIX.len())
+        .any(|window| window == PREFIX)
+}
+
+/// Whether `head` starts like a serialized `GraphDef`: a length-delimited `node`,
+/// whose first field is its length-delimited `name`.
+fn is_graph_def(head: &[u8]) -> bool {
+    let Some((&0x0a, rest)) = head.split_first() else {
+        return false;
+    };
+    // skip the varint length of the node
+    let Some(len) = rest.iter().position(|byte| byte & 0x80 == 0) else {
+        return false;
+    };
+    rest.get(len + 1) == Some(&0x0a)
+}
+
+/// More than one file in a directory scanned by [`ModelLocator::scan`] could be used.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ambiguity {
+    /// There are several models. `chosen` was picked over `others`.
+    Models {
+        /// The model that was picked.
+        chosen: PathBuf,
+        /// The models that were passed over.
+        others: Vec<PathBuf>,
+    },
+    /// There are several scorers. `chosen` was picked over `others`.
+    Scorers {
+        /// The scorer that was picked.
+        chosen: PathBuf,
+        /// The scorers that were passed over.
+        others: Vec<PathBuf>,
+    },
+}
+
+/// What [`ModelLocator::scan`] found in a model directory.
+///
+/// Models are picked by [`ModelFormat`] precedence, then by file name,
+/// and scorers (`.scorer`) by file name.
+/// Anything that had to be decided between is listed in
+/// [`ambiguities`](ModelLocator::ambiguities), so applications can warn about it or refuse it.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelLocator {
+    /// The directory that was scanned.
+    pub dir: PathBuf,
+    /// The model that was picked, and its format, if there is one.
+    pub model: Option<(PathBuf, ModelFormat)>,
+    /// The scorer that was picked, if there is one.
+    pub scorer: Option<PathBuf>,
+    /// Choices that had to be made between several files.
+    pub ambiguities: Vec<Ambiguity>,
+}
+
+impl ModelLocator {
+    /// Scan `dir` for a model and scorer. Subdirectories are not searched.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if `dir` could not be read.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn scan(dir: impl AsRef<Path>) -> crate::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut files = dir
+            .read_dir()?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        files.retain(|path| path.is_file());
+        files.sort();
+
+        let mut models: Vec<_> = files
+            .iter()
+            .filter_map(|path| ModelFormat::from_path(path).map(|format| (path.clone(), format)))
+            .collect();
+        // stable, so models of the same format stay sorted by name
+        models.sort_by_key(|(_, format)| *format);
+        let scorers: Vec<_> = files
+            .into_iter()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "scorer"))
+            .collect();
+
+        let mut ambiguities = Vec::new();
+        if models.len() > 1 {
+            ambiguities.push(Ambiguity::Models {
+                chosen: models[0].0.clone(),
+                others: models[1..].iter().map(|(path, _)| path.clone()).collect(),
+            });
+        }
+        if scorers.len() > 1 {
+            ambiguities.push(Ambiguity::Scorers {
+                chosen: scorers[0].clone(),
+                others: scorers[1..].to_vec(),
+            });
+        }
+
+        Ok(Self {
+            dir,
+            model: models.into_iter().next(),
+            scorer: scorers.into_iter().next(),
+            ambiguities,
+        })
+    }
+
+    /// Whether exactly one model and at most one scorer were found.
+    #[inline]
+    #[must_use]
+    pub fn is_unambiguous(&self) -> bool {
+        self.model.is_some() && self.ambiguities.is_empty()
+    }
+
+    /// Return the files that were picked.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if no model was found.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn files(&self) -> crate::Result<ModelFiles> {
+        let (model, _) = self
+            .model
+            .clone()
+            .ok_or_else(|| crate::Error::Io(format!("no model found in {}", self.dir.display())))?;
+        Ok(ModelFiles {
+            model,
+            scorer: self.scorer.clone(),
+        })
+    }
+}
+
+/// The model and scorer found in a directory by [`ModelFiles::find`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModelFiles {
+    /// Path to the model.
+    pub model: PathBuf,
+    /// Path to the scorer, if there is one.
+    pub scorer: Option<PathBuf>,
+}
+
+impl ModelFiles {
+    /// Find the model and scorer in `dir`.
+    ///
+    /// Shorthand for [`ModelLocator::scan`] followed by [`ModelLocator::files`],
+    /// which quietly picks between several models or scorers.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if `dir` could not be read,
+    /// or has no model in it.
+    #[inline]
+    pub fn find(dir: impl AsRef<Path>) -> crate::Result<Self> {
+        ModelLocator::scan(dir)?.files()
+    }
+
+    /// Load the model, and enable the scorer if there is one.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if a path is not valid UTF-8.
+    /// Otherwise, passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn load(&self) -> crate::Result<Model> {
+        let mut model = Model::new(path_to_string(&self.model)?)?;
+        if let Some(scorer) = &self.scorer {
+            model.enable_external_scorer(path_to_string(scorer)?)?;
+        }
+        Ok(model)
+    }
+}
+
+/// Everything needed to load a model ready to transcribe with,
+/// for applications that take their configuration from outside rather than hard-coding it.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelConfig {
+    /// Path to the model.
+    pub model: PathBuf,
+    /// Path to the scorer, if there is one.
+    pub scorer: Option<PathBuf>,
+    /// The beam width to decode with, or `None` to keep the model's default.
+    pub beam_width: Option<u32>,
+    /// Hot words, and their boosts.
+    pub hot_words: Vec<(String, f32)>,
+}
+
+impl ModelConfig {
+    /// Create a new `ModelConfig` that loads the model at `model`, with the defaults for the rest.
+    #[inline]
+    #[must_use]
+    pub fn new(model: impl Into<PathBuf>) -> Self {
+        Self {
+            model: model.into(),
+            scorer: None,
+            beam_width: None,
+            hot_words: Vec::new(),
+        }
+    }
+
+    /// Read the configuration from environment variables,
+    /// so containerized deployments can be configured without changing code:
+    ///
+    /// * `COQUI_MODEL_PATH`: the path to the model. Required.
+    /// * `COQUI_SCORER_PATH`: the path to the scorer.
+    /// * `COQUI_BEAM_WIDTH`: the beam width to decode with.
+    /// * `COQUI_HOT_WORDS`: hot words and their boosts, as `word:boost` separated by commas,
+    ///   such as `coqui:2.5,kubernetes:4`.
+    ///
+    /// Variables that are set but empty are treated as unset.
+    ///
+    /// # Errors
+    /// Returns [`Error::InvalidConfig`](crate::Error::InvalidConfig) if `COQUI_MODEL_PATH`
+    /// is unset, or any variable is invalid.
+    #[inline]
+    pub fn from_env() -> crate::Result<Self> {
+        Self::from_vars(|name| std::env::var_os(name))
+    }
+
+    /// Read the configuration from `var`, which returns the value of a variable by name.
+    fn from_vars(var: impl Fn(&str) -> Option<std::ffi::OsString>) -> crate::Result<Self> {
+        let var = |name: &str| var(name).filter(|value| !value.is_empty());
+        let text = |name: &str| {
+            var(name)
+                .map(|value| {
+                    value.into_string().map_err(|_| {
+                        crate::Error::InvalidConfig(format!("{} is not valid UTF-8", name))
+                    })
+                })
+                .transpose()
+        };
+
+        let model = var("COQUI_MODEL_PATH")
+            .ok_or_else(|| crate::Error::InvalidConfig("COQUI_MODEL_PATH is not set".into()))?;
+        let beam_width = text("COQUI_BEAM_WIDTH")?
+            .map(|width| {
+                width.trim().parse().map_err(|_| {
+                    crate::Error::InvalidConfig(format!(
+                        "COQUI_BEAM_WIDTH of {:?} is not a whole number",
+                        width
+                    ))
+                })
+            })
+            .transpose()?;
+        let hot_words = text("COQUI_HOT_WORDS")?
+            .as_deref()
+            .map(parse_hot_words)
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Self {
+            model: model.into(),
+            scorer: var("COQUI_SCORER_PATH").map(PathBuf::from),
+            beam_width,
+            hot_words,
+        })
+    }
+
+    /// Set [`scorer`](ModelConfig::scorer).
+    #[inline]
+    #[must_use]
+    pub fn with_scorer(mut self, scorer: impl Into<PathBuf>) -> Self {
+        self.scorer = Some(scorer.into());
+        self
+    }
+
+    /// Set [`beam_width`](ModelConfig::beam_width).
+    #[inline]
+    #[must_use]
+    pub const fn with_beam_width(mut self, beam_width: u32) -> Self {
+        self.beam_width = Some(beam_width);
+        self
+    }
+
+    /// Add a hot word to [`hot_words`](ModelConfig::hot_words).
+    #[inline]
+    #[must_use]
+    pub fn with_hot_word(mut self, word: impl Into<String>, boost: f32) -> Self {
+        self.hot_words.push((word.into(), boost));
+        self
+    }
+
+    /// Load the model, and set it up as configured.
+    ///
+    /// # Errors
+    /// Returns [`Error::Io`](crate::Error::Io) if a path is not valid UTF-8.
+    /// Otherwise, passes through any errors from the C library. See enum [`Error`](crate::Error).
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn load(&self) -> crate::Result<Model> {
+        let mut model = Model::new(path_to_string(&self.model)?)?;
+        if let Some(beam_width) = self.beam_width {
+            model.set_model_beam_width(beam_width)?;
+        }
+        if let Some(scorer) = &self.scorer {
+            model.enable_external_scorer(path_to_string(scorer)?)?;
+        }
+        for (word, boost) in &self.hot_words {
+            model.add_hot_word(word.as_str(), *boost)?;
+        }
+        Ok(model)
+    }
+}
+
+/// Parse hot words written as `word:boost`, separated by commas.
+fn parse_hot_words(hot_words: &str) -> crate::Result<Vec<(String, f32)>> {
+    hot_words
+        .split(',')
+        .map(str::trim)
+        .filter(|hot_word| !hot_word.is_empty())
+        .map(|hot_word| {
+            hot_word
+                .rsplit_once(':')
+                .and_then(|(word, boost)| Some((word.trim(), boost.trim().parse().ok()?)))
+                .filter(|(word, _)| !word.is_empty())
+                .map(|(word, boost)| (word.to_string(), boost))
+                .ok_or_else(|| {
+                    crate::Error::InvalidConfig(format!(
+                        "hot word {:?} is not written as word:boost",
+                        hot_word
+                    ))
+                })
+        })
+        .collect()
+}
+
+fn path_to_string(path: &Path) -> crate::Result<String> {
+    path.to_str()
+        .map(ToString::to_string)
+        .ok_or_else(|| crate::Error::Io(format!("{} is not valid UTF-8", path.display())))
+}
+
+/// Mix interleaved audio with `channels` channels down to mono,
+/// by averaging the channels of each frame.
+///
+/// A trailing partial frame is dropped.
+///
+/// # Errors
+/// Returns [`Error::InvalidAudio`](crate::Error::InvalidAudio) if `channels` is 0.
+#[inline]
+pub fn downmix(samples: &[i16], channels: u16) -> crate::Result<Vec<i16>> {
+    let mut output = Vec::new();
+    downmix_into(samples, channels, &mut output)?;
+    Ok(output)
+}
+
+/// Like [`downmix`], but appends the mixed audio to `output`.
+///
+/// Reusing `output` between calls, once it has grown to fit, avoids
+/// allocating for every packet of audio.
+///
+/// # Errors
+/// Returns [`Error::InvalidAudio`](crate::Error::InvalidAudio) if `channels` is 0.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn downmix_into(samples: &[i16], channels: u16, output: &mut Vec<i16>) -> crate::Result<()> {
+    match channels {
+        0 => {
+            return Err(crate::Error::InvalidAudio(
+                "audio must have at least one channel".to_string(),
+            ))
+        }
+        1 => output.extend_from_slice(samples),
+        2 => crate::audio::downmix_stereo(samples, output),
+        channels => output.extend(samples.chunks_exact(usize::from(channels)).map(|frame| {
+            let sum: i32 = frame.iter().map(|&s| i32::from(s)).sum();
+            (sum / i32::from(channels)) as i16
+        })),
+    }
+    Ok(())
+}
+
+/// Resample mono audio from `from_rate` Hz to `to_rate` Hz, by linear interpolation.
+///
+/// # Errors
+/// Returns [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate)
+/// if either sample rate is 0.
+#[inline]
+pub fn resample(samples: &[i16], from_rate: u32, to_rate: u32) -> crate::Result<Vec<i16>> {
+    let mut output = Vec::new();
+    resample_into(samples, from_rate, to_rate, &mut output)?;
+    Ok(output)
+}
+
+/// Like [`resample`], but appends the resampled audio to `output`.
+///
+/// Reusing `output` between calls, once it has grown to fit, avoids
+/// allocating for every packet of audio.
+///
+/// # Errors
+/// Returns [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate)
+/// if either sample rate is 0.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn resample_into(
+    samples: &[i16],
+    from_rate: u32,
+    to_rate: u32,
+    output: &mut Vec<i16>,
+) -> crate::Result<()> {
+    if from_rate == 0 || to_rate == 0 {
+        return Err(crate::Error::UnsupportedSampleRate(from_rate.min(to_rate)));
+    }
+    if from_rate == to_rate || samples.is_empty() {
+        output.extend_from_slice(samples);
+        return Ok(());
+    }
+
+    let step = f64::from(from_rate) / f64::from(to_rate);
+    let len = (samples.len() as f64 / step).ceil() as usize;
+    output.extend((0..len).map(|i| {
+        let position = i as f64 * step;
+        let index = position as usize;
+        let a = f64::from(samples[index]);
+        let b = f64::from(samples[(index + 1).min(samples.len() - 1)]);
+        (a + (b - a) * position.fract()).round() as i16
+    }));
+    Ok(())
+}
+
+/// Convert interleaved audio with `channels` channels at `sample_rate` Hz
+/// into mono audio at the sample rate `model` expects.
+///
+/// See [`downmix`] and [`resample`].
+///
+/// # Errors
+/// Returns [`Error::InvalidAudio`](crate::Error::InvalidAudio) if `channels` is 0,
+/// and [`Error::UnsupportedSampleRate`](crate::Error::UnsupportedSampleRate)
+/// if `sample_rate` is 0.
+#[inline]
+pub fn prepare_audio(
+    samples: &[i16],
+    channels: u16,
+    sample_rate: u32,
+    model: &Model,
+) -> crate::Result<Vec<i16>> {
+    let mono = downmix(samples, channels)?;
+    resample(&mono, sample_rate, model.get_sample_rate() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        downmix, downmix_into, resample, resample_into, Ambiguity, ModelConfig, ModelFiles,
+        ModelFormat, ModelLocator,
+    };
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+    use std::fs;
+
+    #[test]
+    fn downmix_averages_without_overflowing() {
+        let stereo = [i16::MAX, i16::MAX, -4, 2, 7];
+        assert_eq!(downmix(&stereo, 2).unwrap(), [i16::MAX, -1]);
+        assert_eq!(downmix(&stereo, 1).unwrap(), stereo);
+        assert!(downmix(&stereo, 0).is_err());
+    }
+
+    #[test]
+    fn resample_interpolates() {
+        assert_eq!(
+            resample(&[0, 100, 200], 1, 2).unwrap(),
+            [0, 50, 100, 150, 200, 200]
+        );
+        assert_eq!(resample(&[0, 50, 100, 150], 2, 1).unwrap(), [0, 100]);
+        assert_eq!(resample(&[1, 2, 3], 16000, 16000).unwrap(), [1, 2, 3]);
+        assert!(resample(&[1], 0, 16000).is_err());
+    }
+
+    #[test]
+    fn into_variants_append_to_the_buffer() {
+        let mut buffer = Vec::with_capacity(8);
+        downmix_into(&[2, 4, 6, 8], 2, &mut buffer).unwrap();
+        resample_into(&[0, 100], 1, 2, &mut buffer).unwrap();
+        assert_eq!(buffer, [3, 7, 0, 50, 100, 100]);
+        assert_eq!(buffer.capacity(), 8);
+    }
+
+    #[test]
+    fn find_picks_model_and_scorer() {
+        let dir = std::env::temp_dir().join(format!("coqui-stt-util-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(ModelFiles::find(&dir).is_err());
+
+        for name in ["b.tflite", "a.pbmm", "notes.txt", "kenlm.scorer"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        let files = ModelFiles::find(&dir).unwrap();
+        assert_eq!(files.model, dir.join("b.tflite"));
+        assert_eq!(files.scorer, Some(dir.join("kenlm.scorer")));
+
+        fs::write(dir.join("c.tflite"), b"").unwrap();
+        fs::write(dir.join("a.pb"), b"").unwrap();
+        let located = ModelLocator::scan(&dir).unwrap();
+        assert_eq!(
+            located.model,
+            Some((dir.join("b.tflite"), ModelFormat::TfLite))
+        );
+        assert_eq!(
+            located.ambiguities,
+            [Ambiguity::Models {
+                chosen: dir.join("b.tflite"),
+                others: vec![dir.join("c.tflite"), dir.join("a.pbmm"), dir.join("a.pb")],
+            }]
+        );
+        assert!(!located.is_unambiguous());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn formats_are_detected_from_their_contents() {
+        let mut tflite = vec![0x1c, 0, 0, 0];
+        tflite.extend_from_slice(b"TFL3");
+        tflite.resize(64, 0);
+        let graph_def = [0x0a, 0x96, 0x01, 0x0a, 0x05, b'i', b'n', b'p', b'u', b't'];
+        let mut pbmm = vec![0; 64];
+        pbmm.extend_from_slice(b"\x0a\x28\x12\x24memmapped_package://weights");
+        pbmm.extend_from_slice(&64u64.to_le_bytes());
+        assert_eq!(ModelFormat::detect(&tflite), Some(ModelFormat::TfLite));
+        assert_eq!(ModelFormat::detect(&graph_def), Some(ModelFormat::Pb));
+        assert_eq!(ModelFormat::detect(&pbmm), Some(ModelFormat::Pbmm));
+        for unknown in [&b""[..], b"RIFF....WAVE", &[0x0a, 0x80]] {
+            assert_eq!(ModelFormat::detect(unknown), None);
+        }
+
+        let dir = std::env::temp_dir().join(format!("coqui-stt-detect-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (contents, format) in [
+            (&tflite[..], Some(ModelFormat::TfLite)),
+            (&graph_def[..], Some(ModelFormat::Pb)),
+            (&pbmm[..], Some(ModelFormat::Pbmm)),
+            (b"", None),
+        ] {
+            // the extension is ignored
+            let path = dir.join("model.bin");
+            fs::write(&path, contents).unwrap();
+            assert_eq!(ModelFormat::detect_file(&path).unwrap(), format);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_is_read_from_variables() {
+        let read = |vars: &[(&str, &str)]| {
+            let vars: HashMap<_, _> = vars.iter().copied().collect();
+            ModelConfig::from_vars(|name| vars.get(name).map(OsString::from))
+        };
+
+        let config = read(&[
+            ("COQUI_MODEL_PATH", "/models/en.tflite"),
+            ("COQUI_SCORER_PATH", "/models/en.scorer"),
+            ("COQUI_BEAM_WIDTH", "500"),
+            ("COQUI_HOT_WORDS", "coqui:2.5, kubernetes:-1,"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config,
+            ModelConfig::new("/models/en.tflite")
+                .with_scorer("/models/en.scorer")
+                .with_beam_width(500)
+                .with_hot_word("coqui", 2.5)
+                .with_hot_word("kubernetes", -1.0)
+        );
+        let config = read(&[("COQUI_MODEL_PATH", "en.tflite"), ("COQUI_SCORER_PATH", "")]);
+        assert_eq!(config.unwrap(), ModelConfig::new("en.tflite"));
+
+        for invalid in [
+            &[][..],
+            &[
+                ("COQUI_MODEL_PATH", "en.tflite"),
+                ("COQUI_BEAM_WIDTH", "wide"),
+            ],
+            &[
+                ("COQUI_MODEL_PATH", "en.tflite"),
+                ("COQUI_HOT_WORDS", "coqui"),
+            ],
+            &[("COQUI_MODEL_PATH", "en.tflite"), ("COQUI_HOT_WORDS", ":2")],
+        ] {
+            assert!(matches!(read(invalid), Err(crate::Error::InvalidConfig(_))));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn downmix_stays_between_the_channels(samples: Vec<i16>, channels in 1u16..=8) {
+            let mono = downmix(&samples, channels).unwrap();
+            prop_assert_eq!(mono.len(), samples.len() / usize::from(channels));
+            for (&sample, frame) in mono.iter().zip(samples.chunks_exact(usize::from(channels))) {
+                prop_assert!(sample >= *frame.iter().min().unwrap());
+                prop_assert!(sample <= *frame.iter().max().unwrap());
+            }
+        }
+
+        #[test]
+        fn downmix_of_identical_channels_is_the_original(mono: Vec<i16>, channels in 1u16..=8) {
+            let interleaved: Vec<_> = mono
+                .iter()
+                .flat_map(|&s| std::iter::repeat_n(s, usize::from(channels)))
+                .collect();
+            prop_assert_eq!(downmix(&interleaved, channels).unwrap(), mono);
+        }
+
+        #[test]
+        fn resample_length_matches_the_ratio(
+            samples in prop::collection::vec(any::<i16>(), 0..2000),
+            from_rate in 1u32..=96_000,
+            to_rate in 1u32..=96_000,
+        ) {
+            let resampled = resample(&samples, from_rate, to_rate).unwrap();
+            let expected = (samples.len() as u64 * u64::from(to_rate)).div_ceil(u64::from(from_rate));
+            prop_assert_eq!(resampled.len() as u64, expected);
+        }
+
+        #[test]
+        fn resample_stays_within_the_input(
+            samples in prop::collection::vec(any::<i16>(), 1..500),
+            from_rate in 8_000u32..=48_000,
+            to_rate in 8_000u32..=48_000,
+        ) {
+            let resampled = resample(&samples, from_rate, to_rate).unwrap();
+            let min = *samples.iter().min().unwrap();
+            let max = *samples.iter().max().unwrap();
+            prop_assert!(resampled.iter().all(|&s| (min..=max).contains(&s)));
+            prop_assert_eq!(resampled.first(), samples.first());
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use super::VoiceActivityDetector;
+
+/// A detector that classifies frames by their RMS energy.
+///
+/// This has no dependencies and costs next to nothing,
+/// but any sufficiently loud noise will be treated as speech.
+#[derive(Copy, Clone, Debug)]
+pub struct EnergyDetector {
+    threshold: f32,
+}
+
+impl EnergyDetector {
+    /// Create a new detector that treats any frame louder than
+    /// `threshold` (in dBFS, so usually negative) as speech.
+    #[inline]
+    #[must_use]
+    pub const fn new(threshold: f32) -> Self {
+        Self { threshold }
+    }
+
+    /// The threshold in dBFS above which a frame is considered speech.
+    #[inline]
+    #[must_use]
+    pub const fn threshold(&self) -> f32 {
+        self.threshold
+    }
+}
+
+impl Default for EnergyDetector {
+    /// A threshold of -45 dBFS, which works for a close microphone in a quiet room.
+    #[inline]
+    fn default() -> Self {
+        Self::new(-45.0)
+    }
+}
+
+impl VoiceActivityDetector for EnergyDetector {
+    /// Frames are 30ms long.
+    #[inline]
+    fn frame_len(&self, sample_rate: u32) -> usize {
+        sample_rate as usize * 30 / 1000
+    }
+
+    #[inline]
+    fn is_speech(&mut self, frame: &[i16], _: u32) -> crate::Result<bool> {
+        if frame.is_empty() {
+            return Ok(false);
+        }
+
+        let sum_squares: f64 = frame
+            .iter()
+            .map(|&s| {
+                let s = f64::from(s) / 32768.0;
+                s * s
+            })
+            .sum();
+        let rms = (sum_squares / frame.len() as f64).sqrt();
+        let dbfs = 20.0 * rms.log10();
+
+        Ok(dbfs > f64::from(self.threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(amplitude: i16) -> Vec<i16> {
+        (0..480)
+            .map(|i| if i % 2 == 0 { amplitude } else { -amplitude })
+            .collect()
+    }
+
+    #[test]
+    fn frames_are_30ms() {
+        assert_eq!(EnergyDetector::default().frame_len(16000), 480);
+        assert_eq!(EnergyDetector::default().frame_len(8000), 240);
+    }
+
+    #[test]
+    fn silence_and_empty_frames_are_not_speech() {
+        let mut vad = EnergyDetector::default();
+        assert!(!vad.is_speech(&[], 16000).unwrap());
+        assert!(!vad.is_speech(&[0; 480], 16000).unwrap());
+        assert!(!vad.is_speech(&square(100), 16000).unwrap());
+        assert!(vad.is_speech(&square(8000), 16000).unwrap());
+    }
+
+    #[test]
+    fn frames_are_compared_against_the_threshold() {
+        // a square wave at half of full scale is about -6 dBFS
+        let frame = square(16384);
+        assert!(EnergyDetector::new(-7.0).is_speech(&frame, 16000).unwrap());
+        assert!(!EnergyDetector::new(-5.0).is_speech(&frame, 16000).unwrap());
+    }
+}
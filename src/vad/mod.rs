@@ -0,0 +1,63 @@
+//! Voice activity detection, used to split live audio into utterances.
+//!
+//! The [`Transcriber`](crate::Transcriber) asks a [`VoiceActivityDetector`]
+//! whether each frame of incoming audio contains speech,
+//! and uses that to decide when an utterance begins and ends.
+//!
+//! [`EnergyDetector`] is always available and is the default.
+//! It is cheap, but misfires in noisy environments;
+//! the `webrtc-vad` and `silero` features enable more robust detectors.
+
+mod energy;
+#[cfg(feature = "silero")]
+mod silero;
+#[cfg(feature = "webrtc-vad")]
+mod webrtc;
+
+pub use energy::EnergyDetector;
+#[cfg(feature = "silero")]
+pub use silero::SileroVad;
+#[cfg(feature = "webrtc-vad")]
+pub use webrtc::{Aggressiveness, WebRtcVad};
+
+/// A backend that decides whether a frame of audio contains speech.
+///
+/// Implement this to plug your own detector into the [`Transcriber`](crate::Transcriber).
+pub trait VoiceActivityDetector {
+    /// The number of samples this detector expects per frame at `sample_rate` Hz.
+    ///
+    /// Every frame passed to [`is_speech`](VoiceActivityDetector::is_speech)
+    /// will be exactly this long.
+    fn frame_len(&self, sample_rate: u32) -> usize;
+
+    /// Return `true` if `frame` contains speech.
+    ///
+    /// `frame` is 16-bit, mono audio at `sample_rate` Hz.
+    ///
+    /// # Errors
+    /// Returns an error if the backend fails to process the frame.
+    fn is_speech(&mut self, frame: &[i16], sample_rate: u32) -> crate::Result<bool>;
+
+    /// Clear any state carried over between frames.
+    ///
+    /// This is called whenever an utterance ends. The default implementation does nothing.
+    #[inline]
+    fn reset(&mut self) {}
+}
+
+impl<T: VoiceActivityDetector + ?Sized> VoiceActivityDetector for Box<T> {
+    #[inline]
+    fn frame_len(&self, sample_rate: u32) -> usize {
+        (**self).frame_len(sample_rate)
+    }
+
+    #[inline]
+    fn is_speech(&mut self, frame: &[i16], sample_rate: u32) -> crate::Result<bool> {
+        (**self).is_speech(frame, sample_rate)
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        (**self).reset();
+    }
+}
@@ -0,0 +1,155 @@
+use super::VoiceActivityDetector;
+use ort::session::{Session, SessionOutputs};
+use ort::value::Tensor;
+use std::path::Path;
+
+const STATE_LEN: usize = 2 * 128;
+
+/// A detector running the neural [Silero VAD](https://github.com/snakers4/silero-vad)
+/// model (v5, ONNX export) through ONNX Runtime.
+///
+/// This is the most accurate detector available, and copes well with music and
+/// background chatter, but costs noticeably more CPU than the others.
+///
+/// Only sample rates of 8 and 16 kHz are supported.
+///
+/// ONNX Runtime is loaded dynamically: point the `ORT_DYLIB_PATH` environment variable
+/// at `libonnxruntime` if it is not in the default library search path.
+pub struct SileroVad {
+    session: Session,
+    threshold: f32,
+    state: Vec<f32>,
+    context: Vec<i16>,
+}
+
+impl SileroVad {
+    /// Load the Silero VAD model from `model_path`.
+    ///
+    /// `threshold` is the speech probability (between 0 and 1) above which a frame
+    /// is considered speech. 0.5 is a good default.
+    ///
+    /// # Errors
+    /// Returns an error if ONNX Runtime could not be loaded, or the model is invalid.
+    #[inline]
+    pub fn new(model_path: impl AsRef<Path>, threshold: f32) -> crate::Result<Self> {
+        Self::_new(model_path.as_ref(), threshold)
+    }
+
+    fn _new(model_path: &Path, threshold: f32) -> crate::Result<Self> {
+        let session = Session::builder()
+            .and_then(|mut b| b.commit_from_file(model_path))
+            .map_err(|e| crate::Error::Vad(e.to_string()))?;
+
+        Ok(Self {
+            session,
+            threshold,
+            state: vec![0.0; STATE_LEN],
+            context: Vec::new(),
+        })
+    }
+
+    /// The speech probability above which a frame is considered speech.
+    #[inline]
+    #[must_use]
+    pub const fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    /// Run the model over `frame`, returning the probability that it contains speech.
+    ///
+    /// # Errors
+    /// Returns an error if the sample rate is unsupported, the model failed to run,
+    /// or it returned no probability or a state of the wrong size.
+    #[allow(clippy::missing_inline_in_public_items)]
+    pub fn speech_probability(&mut self, frame: &[i16], sample_rate: u32) -> crate::Result<f32> {
+        let context_len = match sample_rate {
+            8000 => 32,
+            16000 => 64,
+            _ => {
+                return Err(crate::Error::Vad(format!(
+                    "unsupported sample rate: {} Hz",
+                    sample_rate
+                )))
+            }
+        };
+        if self.context.len() != context_len {
+            self.context = vec![0; context_len];
+        }
+
+        // the model expects the tail of the previous frame to be prepended to this one
+        let input = crate::audio::i16_to_f32(&[self.context.as_slice(), frame].concat());
+        let input_len = input.len();
+
+        let inputs = (|| {
+            Ok::<_, ort::Error>(ort::inputs![
+                "input" => Tensor::from_array(([1, input_len], input))?,
+                "state" => Tensor::from_array(([2, 1, 128], self.state.clone()))?,
+                "sr" => Tensor::from_array(([0_usize; 0], vec![i64::from(sample_rate)]))?,
+            ])
+        })()
+        .map_err(|e| crate::Error::Vad(e.to_string()))?;
+
+        let outputs = self
+            .session
+            .run(inputs)
+            .map_err(|e| crate::Error::Vad(e.to_string()))?;
+        let probability = output(&outputs, "output")?;
+        let state = output(&outputs, "stateN")?;
+        if state.len() != STATE_LEN {
+            return Err(crate::Error::Vad(format!(
+                "expected a state of {} values from the model, got {}",
+                STATE_LEN,
+                state.len()
+            )));
+        }
+
+        let probability = match probability.first() {
+            Some(&probability) => probability,
+            None => {
+                return Err(crate::Error::Vad(
+                    "expected a speech probability from the model, got none".to_owned(),
+                ))
+            }
+        };
+        self.state.copy_from_slice(state);
+        let tail = frame.len().saturating_sub(context_len);
+        self.context.clear();
+        self.context.extend_from_slice(&frame[tail..]);
+        self.context.resize(context_len, 0);
+
+        Ok(probability)
+    }
+}
+
+/// The values of the output `name` of the model, or an error if it has no such output.
+fn output<'a>(outputs: &'a SessionOutputs<'_>, name: &str) -> crate::Result<&'a [f32]> {
+    let (_, values) = outputs
+        .get(name)
+        .ok_or_else(|| crate::Error::Vad(format!("the model has no `{}` output", name)))?
+        .try_extract_tensor::<f32>()
+        .map_err(|e| crate::Error::Vad(e.to_string()))?;
+    Ok(values)
+}
+
+impl VoiceActivityDetector for SileroVad {
+    /// Frames are 512 samples at 16 kHz, and 256 samples at 8 kHz.
+    #[inline]
+    fn frame_len(&self, sample_rate: u32) -> usize {
+        if sample_rate == 8000 {
+            256
+        } else {
+            512
+        }
+    }
+
+    #[inline]
+    fn is_speech(&mut self, frame: &[i16], sample_rate: u32) -> crate::Result<bool> {
+        Ok(self.speech_probability(frame, sample_rate)? > self.threshold)
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        self.state.iter_mut().for_each(|s| *s = 0.0);
+        self.context.clear();
+    }
+}
@@ -0,0 +1,109 @@
+use super::VoiceActivityDetector;
+use webrtc_vad::{SampleRate, Vad, VadMode};
+
+/// How aggressively [`WebRtcVad`] filters out non-speech.
+///
+/// More aggressive modes report speech less often,
+/// at the cost of missing more quiet speech.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aggressiveness {
+    /// Least aggressive, reports speech most often.
+    Quality,
+    /// Suited for low bitrate audio.
+    LowBitrate,
+    /// Aggressive.
+    Aggressive,
+    /// Most aggressive, reports speech least often.
+    VeryAggressive,
+}
+
+impl Aggressiveness {
+    const fn to_mode(self) -> VadMode {
+        match self {
+            Self::Quality => VadMode::Quality,
+            Self::LowBitrate => VadMode::LowBitrate,
+            Self::Aggressive => VadMode::Aggressive,
+            Self::VeryAggressive => VadMode::VeryAggressive,
+        }
+    }
+}
+
+/// A detector backed by the WebRTC project's GMM-based voice activity detector.
+///
+/// This is much more robust to background noise than [`EnergyDetector`](super::EnergyDetector),
+/// while still being cheap enough to run on every frame.
+///
+/// Only sample rates of 8, 16, 32 and 48 kHz are supported.
+pub struct WebRtcVad {
+    inner: Vad,
+    aggressiveness: Aggressiveness,
+    sample_rate: Option<u32>,
+}
+
+// SAFETY: the inner `Vad` owns its C state exclusively and has no thread affinity
+unsafe impl Send for WebRtcVad {}
+
+impl WebRtcVad {
+    /// Create a new WebRTC voice activity detector.
+    #[inline]
+    #[must_use]
+    pub fn new(aggressiveness: Aggressiveness) -> Self {
+        Self {
+            inner: Vad::new_with_mode(aggressiveness.to_mode()),
+            aggressiveness,
+            sample_rate: None,
+        }
+    }
+
+    /// The aggressiveness this detector was created with.
+    #[inline]
+    #[must_use]
+    pub const fn aggressiveness(&self) -> Aggressiveness {
+        self.aggressiveness
+    }
+}
+
+impl Default for WebRtcVad {
+    #[inline]
+    fn default() -> Self {
+        Self::new(Aggressiveness::Quality)
+    }
+}
+
+impl VoiceActivityDetector for WebRtcVad {
+    /// Frames are 30ms long.
+    #[inline]
+    fn frame_len(&self, sample_rate: u32) -> usize {
+        sample_rate as usize * 30 / 1000
+    }
+
+    #[inline]
+    fn is_speech(&mut self, frame: &[i16], sample_rate: u32) -> crate::Result<bool> {
+        if self.sample_rate != Some(sample_rate) {
+            let rate = i32::try_from(sample_rate)
+                .ok()
+                .and_then(|r| SampleRate::try_from(r).ok())
+                .ok_or_else(|| {
+                    crate::Error::Vad(format!("unsupported sample rate: {} Hz", sample_rate))
+                })?;
+            self.inner.set_sample_rate(rate);
+            self.sample_rate = Some(sample_rate);
+        }
+
+        if frame.is_empty() {
+            return Ok(false);
+        }
+
+        self.inner
+            .is_voice_segment(frame)
+            .map_err(|()| crate::Error::Vad(format!("invalid frame length: {}", frame.len())))
+    }
+
+    #[inline]
+    fn reset(&mut self) {
+        // this also resets the mode and sample rate to their defaults
+        self.inner.reset();
+        self.inner.set_mode(self.aggressiveness.to_mode());
+        self.sample_rate = None;
+    }
+}
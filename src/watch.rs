@@ -0,0 +1,425 @@
+use crate::{Model, Shutdown, Transcription};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// Configuration for [`watch_dir`].
+pub struct WatchConfig {
+    models: Vec<Model>,
+    extensions: Vec<String>,
+    poll_interval: Duration,
+    text_sidecar: bool,
+    json_sidecar: bool,
+    shutdown: Option<Shutdown>,
+    #[cfg(feature = "core-affinity")]
+    cores: Vec<usize>,
+}
+
+impl WatchConfig {
+    /// Create a new `WatchConfig` that transcribes files with `model`.
+    ///
+    /// By default, `.wav` files are picked up, the directory is checked every second,
+    /// and a `.txt` sidecar file is written next to each file transcribed.
+    #[inline]
+    #[must_use]
+    pub fn new(model: Model) -> Self {
+        Self {
+            models: vec![model],
+            extensions: vec!["wav".to_string()],
+            poll_interval: Duration::from_secs(1),
+            text_sidecar: true,
+            json_sidecar: false,
+            shutdown: None,
+            #[cfg(feature = "core-affinity")]
+            cores: Vec::new(),
+        }
+    }
+
+    /// Add another model, to transcribe one more file at a time.
+    ///
+    /// Each model gets its own worker thread.
+    #[inline]
+    #[must_use]
+    pub fn with_model(mut self, model: Model) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    /// Set the file extensions (without the leading `.`) to pick up.
+    ///
+    /// Files are read as WAV regardless of their extension.
+    #[inline]
+    #[must_use]
+    pub fn with_extensions(
+        mut self,
+        extensions: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.extensions = extensions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set how often the directory is checked for new files.
+    ///
+    /// A file is only picked up once its size and modification time
+    /// have stayed the same between two checks, so it is not read while still being written.
+    #[inline]
+    #[must_use]
+    pub const fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Set whether to write the transcribed text to a `.txt` file next to each file.
+    #[inline]
+    #[must_use]
+    pub const fn with_text_sidecar(mut self, enabled: bool) -> Self {
+        self.text_sidecar = enabled;
+        self
+    }
+
+    /// Set whether to write the full [`Transcription`] as JSON to a `.json` file next to each file.
+    #[inline]
+    #[must_use]
+    pub const fn with_json_sidecar(mut self, enabled: bool) -> Self {
+        self.json_sidecar = enabled;
+        self
+    }
+
+    /// Stop watching the directory when `shutdown` is triggered.
+    ///
+    /// The workers transcribe the files already picked up, then exit,
+    /// at which point the directory counts as finished shutting down.
+    /// [`WatchHandle::stop`] still has to be called to get the models back.
+    #[inline]
+    #[must_use]
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Pin the worker threads to the CPU cores with these IDs,
+    /// one each, going back to the first core if there are more workers than cores.
+    ///
+    /// See [`pin_current_thread`](crate::pin_current_thread). By default, workers aren't pinned.
+    #[cfg(feature = "core-affinity")]
+    #[inline]
+    #[must_use]
+    pub fn with_cores(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.cores = cores.into_iter().collect();
+        self
+    }
+
+    /// Whether every enabled sidecar of `path` exists. With none enabled, there's no way to tell.
+    fn has_sidecar(&self, path: &Path) -> bool {
+        (self.text_sidecar || self.json_sidecar)
+            && (!self.text_sidecar || path.with_extension("txt").exists())
+            && (!self.json_sidecar || path.with_extension("json").exists())
+    }
+
+    fn is_audio_file(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+    }
+}
+
+/// A handle to a directory being watched by [`watch_dir`].
+///
+/// Dropping this stops watching the directory,
+/// and waits for files already picked up to finish transcribing.
+pub struct WatchHandle {
+    stop: Option<Sender<()>>,
+    scanner: Option<JoinHandle<()>>,
+    workers: Vec<JoinHandle<Model>>,
+}
+
+impl WatchHandle {
+    /// Stop watching the directory, wait for files already picked up to finish transcribing,
+    /// and return the models that were used.
+    #[inline]
+    #[must_use]
+    pub fn stop(mut self) -> Vec<Model> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Vec<Model> {
+        // this wakes up and stops the scanner,
+        // which in turn closes the job queue and stops the workers once it is empty
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(scanner) = self.scanner.take() {
+            let _ = scanner.join();
+        }
+        self.workers
+            .drain(..)
+            .filter_map(|worker| worker.join().ok())
+            .collect()
+    }
+}
+
+impl Drop for WatchHandle {
+    #[inline]
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Watch the directory at `path` for new audio files, and transcribe each one.
+///
+/// `callback` is called with the path and [`Transcription`] of every file transcribed,
+/// or the error that stopped it from being transcribed,
+/// from one of the worker threads. Errors reading the directory itself are reported
+/// with the path of the directory.
+///
+/// Files must be WAV files at the model's sample rate. Multichannel audio is downmixed.
+/// Files that already have the enabled sidecar files next to them are skipped,
+/// so a directory can be watched again after a restart without transcribing everything twice.
+///
+/// Watching stops when the returned [`WatchHandle`] is dropped.
+///
+/// # Errors
+/// Returns an error if `path` is not a readable directory.
+#[allow(clippy::missing_inline_in_public_items)]
+pub fn watch_dir<F>(
+    path: impl Into<PathBuf>,
+    mut config: WatchConfig,
+    callback: F,
+) -> crate::Result<WatchHandle>
+where
+    F: Fn(&Path, crate::Result<Transcription>) + Send + Sync + 'static,
+{
+    let path = path.into();
+    std::fs::read_dir(&path)?;
+
+    let callback = Arc::new(callback);
+    let (job_tx, job_rx) = mpsc::channel::<PathBuf>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    let (stop_tx, stop_rx) = mpsc::channel();
+    // the directory has finished shutting down once the last worker exits
+    let shutdown = config.shutdown.as_ref().map(|shutdown| {
+        let stop_tx = stop_tx.clone();
+        Arc::new(shutdown.register(move || {
+            let _ = stop_tx.send(());
+        }))
+    });
+
+    let workers = std::mem::take(&mut config.models)
+        .into_iter()
+        .enumerate()
+        .map(|(i, model)| {
+            #[cfg(feature = "core-affinity")]
+            let core = config.cores.get(i % config.cores.len().max(1)).copied();
+            #[cfg(not(feature = "core-affinity"))]
+            let _ = i;
+            let job_rx = Arc::clone(&job_rx);
+            let callback = Arc::clone(&callback);
+            let shutdown = shutdown.clone();
+            let text_sidecar = config.text_sidecar;
+            let json_sidecar = config.json_sidecar;
+            std::thread::spawn(move || {
+                #[cfg(feature = "core-affinity")]
+                if let Some(core) = core {
+                    crate::pin_current_thread(core);
+                }
+                let mut model = model;
+                while let Some(file) = next_job(&job_rx) {
+                    let result = transcribe_file(&mut model, &file).and_then(|transcription| {
+                        write_sidecars(&file, &transcription, text_sidecar, json_sidecar)?;
+                        Ok(transcription)
+                    });
+                    callback(&file, result);
+                }
+                drop(shutdown);
+                model
+            })
+        })
+        .collect();
+    drop(shutdown);
+
+    let scanner = std::thread::spawn(move || scan(&path, &config, &stop_rx, &job_tx, &*callback));
+
+    Ok(WatchHandle {
+        stop: Some(stop_tx),
+        scanner: Some(scanner),
+        workers,
+    })
+}
+
+fn next_job(job_rx: &Mutex<Receiver<PathBuf>>) -> Option<PathBuf> {
+    job_rx.lock().ok()?.recv().ok()
+}
+
+fn scan(
+    dir: &Path,
+    config: &WatchConfig,
+    stop_rx: &Receiver<()>,
+    job_tx: &Sender<PathBuf>,
+    callback: &dyn Fn(&Path, crate::Result<Transcription>),
+) {
+    let mut seen = HashSet::new();
+    let mut settling: HashMap<PathBuf, (u64, Option<SystemTime>)> = HashMap::new();
+    let mut failing = false;
+
+    loop {
+        match std::fs::read_dir(dir) {
+            Ok(entries) => {
+                failing = false;
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    if seen.contains(&path) || !config.is_audio_file(&path) {
+                        continue;
+                    }
+                    if config.has_sidecar(&path) {
+                        seen.insert(path);
+                        continue;
+                    }
+                    let metadata = match entry.metadata() {
+                        Ok(metadata) if metadata.is_file() => metadata,
+                        _ => continue,
+                    };
+
+                    let signature = (metadata.len(), metadata.modified().ok());
+                    if settling.get(&path) == Some(&signature) {
+                        settling.remove(&path);
+                        seen.insert(path.clone());
+                        if job_tx.send(path).is_err() {
+                            return;
+                        }
+                    } else {
+                        settling.insert(path, signature);
+                    }
+                }
+            }
+            Err(e) if !failing => {
+                failing = true;
+                callback(dir, Err(e.into()));
+            }
+            Err(_) => {}
+        }
+
+        match stop_rx.recv_timeout(config.poll_interval) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => return,
+        }
+    }
+}
+
+fn transcribe_file(model: &mut Model, path: &Path) -> crate::Result<Transcription> {
+    let audio = crate::audio::read_wav(path, model.get_sample_rate() as u32)?;
+    model.speech_to_text_best(&audio)
+}
+
+fn write_sidecars(
+    path: &Path,
+    transcription: &Transcription,
+    text: bool,
+    json: bool,
+) -> crate::Result<()> {
+    if text {
+        std::fs::write(path.with_extension("txt"), &transcription.text)?;
+    }
+    if json {
+        let json = serde_json::to_string_pretty(transcription)?;
+        std::fs::write(path.with_extension("json"), json)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::tone;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("coqui-stt-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_wav(path: &Path) {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for sample in tone(440.0, Duration::from_secs(1), 16000) {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn files_are_skipped_only_with_every_enabled_sidecar() {
+        let dir = temp_dir("sidecars");
+        let audio = dir.join("a.wav");
+        let config = |text, json| {
+            WatchConfig::new(Model::new("model").unwrap())
+                .with_text_sidecar(text)
+                .with_json_sidecar(json)
+        };
+
+        std::fs::write(audio.with_extension("txt"), "").unwrap();
+        assert!(config(true, false).has_sidecar(&audio));
+        assert!(!config(true, true).has_sidecar(&audio));
+        assert!(!config(false, true).has_sidecar(&audio));
+        // with no sidecars, nothing records a file was transcribed
+        assert!(!config(false, false).has_sidecar(&audio));
+
+        std::fs::write(audio.with_extension("json"), "").unwrap();
+        assert!(config(true, true).has_sidecar(&audio));
+        assert!(config(false, true).has_sidecar(&audio));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn new_files_are_transcribed_into_sidecars() {
+        let dir = temp_dir("watch");
+        write_wav(&dir.join("done.wav"));
+        std::fs::write(dir.join("done.txt"), "").unwrap();
+        std::fs::write(dir.join("done.json"), "").unwrap();
+        write_wav(&dir.join("new.wav"));
+        std::fs::write(dir.join("ignored.mp3"), "").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let config = WatchConfig::new(Model::new("model").unwrap())
+            .with_json_sidecar(true)
+            .with_poll_interval(Duration::from_millis(10));
+        let handle = watch_dir(&dir, config, move |path, result| {
+            let _ = tx
+                .lock()
+                .unwrap()
+                .send((path.to_path_buf(), result.map(|t| t.text)));
+        })
+        .unwrap();
+
+        let (path, text) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(path, dir.join("new.wav"));
+        let text = text.unwrap();
+        assert_eq!(std::fs::read_to_string(dir.join("new.txt")).unwrap(), text);
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("new.json")).unwrap()).unwrap();
+        assert_eq!(json["text"], text.as_str());
+
+        assert_eq!(handle.stop().len(), 1);
+        assert!(rx.try_recv().is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn missing_directories_are_an_error() {
+        let config = WatchConfig::new(Model::new("model").unwrap());
+        let dir = std::env::temp_dir().join("coqui-stt-watch-missing");
+        assert!(matches!(
+            watch_dir(dir, config, |_, _| {}),
+            Err(crate::Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+// Word error rates and word alignments, for comparing transcripts.
+// Words are separated by whitespace and compared exactly.
+
+/// The edit distance between the words of `reference` and `hypothesis`,
+/// and the number of words in `reference`.
+pub(crate) fn word_errors(reference: &str, hypothesis: &str) -> (usize, usize) {
+    let reference: Vec<&str> = reference.split_whitespace().collect();
+    // one row of the edit distance table at a time
+    let mut row: Vec<usize> = (0..=reference.len()).collect();
+    for (i, word) in hypothesis.split_whitespace().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, expected) in reference.iter().enumerate() {
+            let substitution = diagonal + usize::from(word != *expected);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    (row[reference.len()], reference.len())
+}
+
+/// `errors` relative to `words`, counting no words as one, so an empty reference
+/// gives 1 for each inserted word rather than infinity.
+pub(crate) fn rate(errors: usize, words: usize) -> f64 {
+    errors as f64 / words.max(1) as f64
+}
+
+/// Line the words of `a` and `b` up with as few edits as possible.
+///
+/// Each pair is a word from both, which may differ, or a word only one of them has.
+pub(crate) fn align<'a>(a: &'a str, b: &'a str) -> Vec<(Option<&'a str>, Option<&'a str>)> {
+    let a: Vec<&str> = a.split_whitespace().collect();
+    let b: Vec<&str> = b.split_whitespace().collect();
+    // the whole table this time, to walk back through
+    let width = b.len() + 1;
+    let mut table = vec![0; (a.len() + 1) * width];
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            table[i * width + j] = match (i, j) {
+                (0, j) => j,
+                (i, 0) => i,
+                (i, j) => (table[(i - 1) * width + j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                    .min(table[(i - 1) * width + j] + 1)
+                    .min(table[i * width + j - 1] + 1),
+            };
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 || j > 0 {
+        let here = table[i * width + j];
+        let diagonal = (i > 0 && j > 0).then(|| table[(i - 1) * width + j - 1]);
+        // when there's a choice, keep matching words together,
+        // then line up changed words from the start rather than the end
+        if diagonal == Some(here) && a[i - 1] == b[j - 1] {
+            pairs.push((Some(a[i - 1]), Some(b[j - 1])));
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && here == table[(i - 1) * width + j] + 1 {
+            pairs.push((Some(a[i - 1]), None));
+            i -= 1;
+        } else if j > 0 && here == table[i * width + j - 1] + 1 {
+            pairs.push((None, Some(b[j - 1])));
+            j -= 1;
+        } else {
+            pairs.push((Some(a[i - 1]), Some(b[j - 1])));
+            i -= 1;
+            j -= 1;
+        }
+    }
+    pairs.reverse();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{align, rate, word_errors};
+
+    fn wer(reference: &str, hypothesis: &str) -> f64 {
+        let (errors, words) = word_errors(reference, hypothesis);
+        rate(errors, words)
+    }
+
+    #[test]
+    fn word_error_rate_counts_edits() {
+        assert_eq!(wer("", ""), 0.0);
+        assert_eq!(wer("", "hello there"), 2.0);
+        assert_eq!(wer("the cat sat", "the cat sat"), 0.0);
+        assert_eq!(wer("the cat sat", "the bat sat"), 1.0 / 3.0);
+        assert_eq!(wer("the cat sat", "cat sat down"), 2.0 / 3.0);
+        assert_eq!(wer("the cat sat", ""), 1.0);
+        assert_eq!(wer(" the  cat ", "the\tcat"), 0.0);
+    }
+
+    #[test]
+    fn alignments_take_the_fewest_edits() {
+        assert_eq!(
+            align("the cat sat", "cat sat down"),
+            [
+                (Some("the"), None),
+                (Some("cat"), Some("cat")),
+                (Some("sat"), Some("sat")),
+                (None, Some("down")),
+            ]
+        );
+        assert_eq!(
+            align("the cat", "the bat"),
+            [(Some("the"), Some("the")), (Some("cat"), Some("bat"))]
+        );
+        assert_eq!(
+            align("hello world", "hi"),
+            [(Some("hello"), Some("hi")), (Some("world"), None)]
+        );
+        assert_eq!(align("", ""), []);
+    }
+}
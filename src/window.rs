@@ -0,0 +1,248 @@
+use crate::audio::{duration_of, samples_in};
+use crate::{Word, TIMESTEP};
+use std::ops::Range;
+use std::time::Duration;
+
+/// How [`Model::speech_to_text_windowed`](crate::Model::speech_to_text_windowed)
+/// splits long audio into windows.
+///
+/// Each window is decoded on its own, so memory use stays the same however long the audio is,
+/// and the model's state can't drift over hours of audio. Neighbouring windows overlap,
+/// so words cut off at the edge of one window are heard whole in the other.
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WindowOptions {
+    /// How much audio each window holds.
+    pub window: Duration,
+    /// How much of each window is also in the next one.
+    /// Overlaps of more than half a window are cut down to half a window.
+    pub overlap: Duration,
+    /// How far apart in time the same word may be heard in two overlapping windows
+    /// and still be taken as the same word when stitching them together.
+    pub tolerance: Duration,
+}
+
+impl Default for WindowOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(30),
+            overlap: Duration::from_secs(5),
+            tolerance: Duration::from_millis(200),
+        }
+    }
+}
+
+impl WindowOptions {
+    /// Set [`window`](WindowOptions::window).
+    #[inline]
+    #[must_use]
+    pub const fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set [`overlap`](WindowOptions::overlap).
+    #[inline]
+    #[must_use]
+    pub const fn with_overlap(mut self, overlap: Duration) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Set [`tolerance`](WindowOptions::tolerance).
+    #[inline]
+    #[must_use]
+    pub const fn with_tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl WindowOptions {
+    /// The samples of each window that `len` samples of audio at `sample_rate` Hz is split into.
+    /// Audio no longer than one window is one window.
+    pub(crate) fn ranges(&self, len: usize, sample_rate: u32) -> Vec<Range<usize>> {
+        let window_len = samples_in(self.window, sample_rate).max(1);
+        let overlap_len = samples_in(self.overlap, sample_rate).min(window_len / 2);
+        let step = window_len - overlap_len;
+        let mut ranges = Vec::new();
+        let mut offset = 0;
+        loop {
+            let end = (offset + window_len).min(len);
+            ranges.push(offset..end);
+            if end == len {
+                return ranges;
+            }
+            offset += step;
+        }
+    }
+}
+
+/// The words of one decoded window, with their times already from the start of the audio.
+pub(crate) struct Window {
+    pub(crate) start: Duration,
+    pub(crate) end: Duration,
+    pub(crate) words: Vec<Word>,
+}
+
+impl Window {
+    /// The window of the samples in `range`, with `words` timed from the start of the window,
+    /// as they are decoded.
+    pub(crate) fn new(range: Range<usize>, sample_rate: u32, mut words: Vec<Word>) -> Self {
+        let start = duration_of(range.start as u64, sample_rate);
+        let steps = (start.as_millis() / TIMESTEP.as_millis()) as u32;
+        for word in &mut words {
+            word.start += start;
+            word.timestep = word.timestep.saturating_add(steps);
+        }
+        Self {
+            start,
+            end: duration_of(range.end as u64, sample_rate),
+            words,
+        }
+    }
+}
+
+/// The text of stitched `words`, separated by spaces.
+pub(crate) fn join_words(words: &[Word]) -> String {
+    words
+        .iter()
+        .map(|word| word.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Join the words of consecutive, overlapping windows into one transcript.
+///
+/// In each overlap, both windows heard the same stretch of audio, so this looks for a word
+/// both heard at about the same time, nearest the middle of the overlap,
+/// and switches from the earlier window to the later one there.
+/// If they don't agree on any word, it switches at the middle of the overlap,
+/// where both windows had the most context either side.
+pub(crate) fn stitch(windows: Vec<Window>, tolerance: Duration) -> Vec<Word> {
+    let mut stitched: Vec<Word> = Vec::new();
+    // where the window before ended, if there was one
+    let mut previous_end: Option<Duration> = None;
+    for window in windows {
+        let Some(overlap_end) = previous_end.filter(|&end| end > window.start) else {
+            stitched.extend(window.words);
+            previous_end = Some(window.end);
+            continue;
+        };
+        let middle = window.start + (overlap_end - window.start) / 2;
+        let distance = |start: Duration| start.abs_diff(middle);
+
+        // the earlier window's words in the overlap start at `first_overlapping`
+        let first_overlapping = stitched.partition_point(|word| word.start < window.start);
+        let anchor = stitched[first_overlapping..]
+            .iter()
+            .enumerate()
+            .filter_map(|(i, earlier)| {
+                let j = window.words.iter().position(|later| {
+                    later.start < overlap_end
+                        && later.text == earlier.text
+                        && later.start.abs_diff(earlier.start) <= tolerance
+                })?;
+                Some((first_overlapping + i, j))
+            })
+            .min_by_key(|&(i, _)| distance(stitched[i].start));
+
+        let (keep, from) = anchor.unwrap_or_else(|| {
+            (
+                stitched.partition_point(|word| word.start < middle),
+                window.words.partition_point(|word| word.start < middle),
+            )
+        });
+        stitched.truncate(keep);
+        stitched.extend(window.words.into_iter().skip(from));
+        previous_end = Some(window.end);
+    }
+    stitched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stitch, Window, WindowOptions};
+    use crate::Word;
+    use std::time::Duration;
+
+    fn words(words: &[(&str, u64)]) -> Vec<Word> {
+        words
+            .iter()
+            .map(|&(text, ms)| Word {
+                text: text.to_string(),
+                start: Duration::from_millis(ms),
+                timestep: (ms / 20) as u32,
+                speaker: None,
+            })
+            .collect()
+    }
+
+    fn texts(words: &[Word]) -> Vec<&str> {
+        words.iter().map(|word| word.text.as_str()).collect()
+    }
+
+    fn window(start: u64, end: u64, heard: &[(&str, u64)]) -> Window {
+        Window {
+            start: Duration::from_millis(start),
+            end: Duration::from_millis(end),
+            words: words(heard),
+        }
+    }
+
+    #[test]
+    fn switches_windows_at_a_word_both_heard() {
+        let stitched = stitch(
+            vec![
+                // "quick" is cut off at the end of the first window
+                window(
+                    0,
+                    3000,
+                    &[("the", 0), ("fox", 1000), ("and", 2000), ("quack", 2900)],
+                ),
+                // and "fox" at the start of the second
+                window(
+                    2000,
+                    5000,
+                    &[("ox", 2000), ("and", 2010), ("quick", 2900), ("dog", 4000)],
+                ),
+            ],
+            Duration::from_millis(200),
+        );
+        assert_eq!(texts(&stitched), ["the", "fox", "and", "quick", "dog"]);
+        // the word switched at takes its timing from the later window
+        assert_eq!(stitched[2].start, Duration::from_millis(2010));
+    }
+
+    #[test]
+    fn switches_windows_in_the_middle_without_a_word_both_heard() {
+        let stitched = stitch(
+            vec![
+                window(0, 3000, &[("one", 500), ("two", 2200), ("thee", 2800)]),
+                window(2000, 5000, &[("wo", 2100), ("three", 2700), ("four", 4000)]),
+                // windows that don't overlap are just joined
+                window(5000, 6000, &[("five", 5500)]),
+            ],
+            Duration::from_millis(200),
+        );
+        assert_eq!(texts(&stitched), ["one", "two", "three", "four", "five"]);
+    }
+
+    #[test]
+    fn windows_overlap_and_cover_everything() {
+        let options = WindowOptions::default()
+            .with_window(Duration::from_secs(1))
+            .with_overlap(Duration::from_secs(2));
+        // the overlap is cut down to half a window
+        assert_eq!(options.ranges(25, 10), [0..10, 5..15, 10..20, 15..25]);
+        assert_eq!(
+            options.ranges(27, 10),
+            [0..10, 5..15, 10..20, 15..25, 20..27]
+        );
+        for len in [0, 10] {
+            let ranges = options.ranges(len, 10);
+            assert_eq!((ranges.len(), ranges.first()), (1, Some(&(0..len))));
+        }
+    }
+}
@@ -0,0 +1,604 @@
+//! A fake `libstt`, implementing the Coqui STT C API in Rust.
+//!
+//! This lets `coqui-stt`'s pointer handling be exercised without a real model,
+//! including under Miri and the sanitizers, which can't see inside the real `libstt`.
+//! It's a dev-dependency of `coqui-stt`, linked into its unit tests and `tests/fake_stt.rs`:
+//!
+//! ```text
+//! cargo test --test fake_stt
+//! cargo +nightly miri test --test fake_stt
+//! RUSTFLAGS=-Zsanitizer=address cargo +nightly test --test fake_stt --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! `coqui-stt-sys` still asks the linker for `libstt`, so one has to be installed,
+//! but every symbol is taken from this crate instead, which is linked statically.
+//! Only binaries that use this crate are affected; examples and doc tests still run against
+//! the real `libstt`.
+//!
+//! Every pointer handed out is tracked. Freeing or using a pointer that isn't live,
+//! freeing a model while a stream created from it is still alive, or freeing something
+//! with the wrong function all panic, which aborts the process as the panic can't unwind
+//! out of an `extern "C"` function. [`live`] can be used to check nothing was leaked.
+//!
+//! Models transcribe audio as one word for every half a second of it,
+//! cycling through [`WORDS`], unless told otherwise with [`set_output`].
+//! The best candidate transcript has a confidence of minus the model's beam width
+//! over [`BEAM_WIDTH`], so -1 by default, and each candidate after it is one less confident.
+
+#![allow(non_snake_case, clippy::missing_safety_doc)]
+
+use coqui_stt_sys::{CandidateTranscript, Metadata, ModelState, StreamingState, TokenMetadata};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_short, c_uint};
+use std::ptr::NonNull;
+use std::sync::{Mutex, RwLock, RwLockWriteGuard};
+use std::thread::ThreadId;
+
+/// The words transcribed by fake models, in order.
+pub const WORDS: [&str; 4] = ["hello", "world", "fake", "speech"];
+
+/// The sample rate fake models expect.
+pub const SAMPLE_RATE: c_int = 16000;
+
+/// The default beam width of fake models.
+pub const BEAM_WIDTH: c_uint = 500;
+
+/// Creating a model or enabling a scorer from this path fails.
+pub const FAILING_PATH: &str = "fail";
+
+const CREATE_MODEL_FAILED: c_int = 0x3007;
+const INVALID_SCORER: c_int = 0x2002;
+const SCORER_NOT_ENABLED: c_int = 0x2004;
+
+/// What fake models transcribe, instead of a word for every half a second of audio.
+///
+/// See [`set_output`].
+#[derive(Clone, Debug, Default)]
+pub struct Output {
+    /// The tokens of each candidate transcript, best first.
+    ///
+    /// Tokens can contain any bytes, including invalid UTF-8.
+    /// A NUL byte ends a token early, as it would in C.
+    pub transcripts: Vec<Vec<Vec<u8>>>,
+}
+
+thread_local! {
+    static OUTPUT: RefCell<Option<Output>> = const { RefCell::new(None) };
+}
+
+/// Set what fake models transcribe on the current thread, or reset it with `None`.
+///
+/// No more candidate transcripts than requested are returned,
+/// and strings returned are the tokens of the first candidate joined together.
+pub fn set_output(output: Option<Output>) {
+    OUTPUT.with(|o| *o.borrow_mut() = output);
+}
+
+static FEEDS: RwLock<()> = RwLock::new(());
+
+/// Block every call to `STT_FeedAudioContent`, on any thread, until the returned guard is dropped.
+///
+/// Useful for keeping a worker thread busy while testing what happens to the audio queued behind it.
+pub fn hold_feeds() -> RwLockWriteGuard<'static, ()> {
+    FEEDS.write().unwrap_or_else(|e| e.into_inner())
+}
+
+static DECODES: RwLock<()> = RwLock::new(());
+
+/// Block every call to `STT_SpeechToText` and `STT_SpeechToTextWithMetadata`, on any thread,
+/// until the returned guard is dropped.
+///
+/// Useful for simulating a model that wedged.
+pub fn hold_decodes() -> RwLockWriteGuard<'static, ()> {
+    DECODES.write().unwrap_or_else(|e| e.into_inner())
+}
+
+thread_local! {
+    static FAILING_DECODES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Make the next `count` calls to `STT_SpeechToText` and `STT_SpeechToTextWithMetadata`
+/// on the current thread fail, returning null.
+///
+/// Useful for simulating transient inference failures.
+pub fn fail_decodes(count: usize) {
+    FAILING_DECODES.with(|c| c.set(count));
+}
+
+/// Whether this decode should fail, counting it towards [`fail_decodes`].
+fn decode_fails() -> bool {
+    FAILING_DECODES.with(|c| {
+        let failing = c.get();
+        c.set(failing.saturating_sub(1));
+        failing > 0
+    })
+}
+
+/// Kinds of pointers handed out by the fake library.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A `ModelState`.
+    Model,
+    /// A `StreamingState`.
+    Stream,
+    /// A string, to be freed with `STT_FreeString`.
+    String,
+    /// A `Metadata`, to be freed with `STT_FreeMetadata`.
+    Metadata,
+}
+
+struct Allocation {
+    kind: Kind,
+    thread: ThreadId,
+    /// The model a stream was created from.
+    model: Option<usize>,
+}
+
+static LIVE: Mutex<BTreeMap<usize, Allocation>> = Mutex::new(BTreeMap::new());
+
+/// The number of pointers of `kind` that were handed out on the current thread
+/// and haven't been freed yet.
+pub fn live(kind: Kind) -> usize {
+    let thread = std::thread::current().id();
+    LIVE.lock()
+        .unwrap()
+        .values()
+        .filter(|a| a.kind == kind && a.thread == thread)
+        .count()
+}
+
+fn track<T>(ptr: *mut T, kind: Kind, model: Option<usize>) -> *mut T {
+    let allocation = Allocation {
+        kind,
+        thread: std::thread::current().id(),
+        model,
+    };
+    LIVE.lock().unwrap().insert(ptr as usize, allocation);
+    ptr
+}
+
+fn check<T>(ptr: *const T, kind: Kind) {
+    match LIVE.lock().unwrap().get(&(ptr as usize)) {
+        Some(a) if a.kind == kind => {}
+        Some(a) => panic!("{:?} {:p} used as a {:?}", a.kind, ptr, kind),
+        None => panic!(
+            "{:?} {:p} used after being freed, or never allocated",
+            kind, ptr
+        ),
+    }
+}
+
+fn untrack<T>(ptr: *const T, kind: Kind) {
+    let mut live = LIVE.lock().unwrap();
+    match live.get(&(ptr as usize)) {
+        Some(a) if a.kind == kind => {}
+        Some(a) => panic!("{:?} {:p} freed as a {:?}", a.kind, ptr, kind),
+        None => panic!("{:?} {:p} double freed, or never allocated", kind, ptr),
+    }
+    if kind == Kind::Model {
+        let streams = live
+            .values()
+            .filter(|a| a.model == Some(ptr as usize))
+            .count();
+        assert!(
+            streams == 0,
+            "model {:p} freed while {} stream(s) created from it are still alive",
+            ptr,
+            streams
+        );
+    }
+    live.remove(&(ptr as usize));
+}
+
+struct FakeModel {
+    beam_width: c_uint,
+    scorer: bool,
+}
+
+struct FakeStream {
+    model: *mut FakeModel,
+    fed: usize,
+}
+
+fn model<'a>(ptr: *const ModelState) -> &'a mut FakeModel {
+    check(ptr, Kind::Model);
+    unsafe { &mut *(ptr as *mut FakeModel) }
+}
+
+fn stream<'a>(ptr: *const StreamingState) -> &'a mut FakeStream {
+    check(ptr, Kind::Stream);
+    let stream = unsafe { &mut *(ptr as *mut FakeStream) };
+    check(stream.model as *const ModelState, Kind::Model);
+    stream
+}
+
+fn new_string(s: impl Into<Vec<u8>>) -> *mut c_char {
+    let s = truncate_at_nul(s.into());
+    track(CString::new(s).unwrap().into_raw(), Kind::String, None)
+}
+
+fn str_arg<'a>(ptr: *const c_char) -> &'a str {
+    assert!(!ptr.is_null(), "null string passed in");
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .expect("string passed in is not valid UTF-8")
+}
+
+/// Read every sample in an audio buffer passed in, so out of bounds buffers are caught,
+/// and return the number of samples.
+unsafe fn read_audio(buffer: *const c_short, buffer_size: c_uint) -> usize {
+    assert!(!buffer.is_null(), "null audio buffer passed in");
+    let samples = std::slice::from_raw_parts(buffer, buffer_size as usize);
+    std::hint::black_box(samples.iter().map(|&s| i64::from(s)).sum::<i64>());
+    samples.len()
+}
+
+/// The words transcribed from `samples` samples of audio.
+fn words(samples: usize) -> impl Iterator<Item = &'static str> {
+    let n = samples.div_ceil(SAMPLE_RATE as usize / 2);
+    WORDS.iter().copied().cycle().take(n)
+}
+
+/// Leak a boxed slice, returning a dangling (but non-null and aligned) pointer if it's empty.
+fn leak_slice<T>(items: Vec<T>) -> (*const T, c_uint) {
+    let len = items.len() as c_uint;
+    if items.is_empty() {
+        return (NonNull::dangling().as_ptr(), 0);
+    }
+    (Box::into_raw(items.into_boxed_slice()).cast::<T>(), len)
+}
+
+unsafe fn free_slice<T>(ptr: *const T, len: c_uint) -> Box<[T]> {
+    if len == 0 {
+        return Box::new([]);
+    }
+    Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+        ptr as *mut T,
+        len as usize,
+    ))
+}
+
+/// The tokens of each candidate transcript of `samples` samples of audio,
+/// as their text and timestep.
+fn candidates(samples: usize, num_results: c_uint) -> Vec<Vec<(Vec<u8>, c_uint)>> {
+    if let Some(output) = OUTPUT.with(|o| o.borrow().clone()) {
+        return output
+            .transcripts
+            .into_iter()
+            .take(num_results as usize)
+            .map(|tokens| {
+                tokens
+                    .into_iter()
+                    .enumerate()
+                    .map(|(n, text)| (text, n as c_uint))
+                    .collect()
+            })
+            .collect();
+    }
+
+    let mut tokens = Vec::new();
+    for (n, word) in words(samples).enumerate() {
+        // a word every half a second, in 20ms timesteps
+        let start = n as c_uint * 25;
+        if n > 0 {
+            tokens.push((b" ".to_vec(), start - 1));
+        }
+        tokens.extend(
+            word.bytes()
+                .enumerate()
+                .map(|(j, c)| (vec![c], start + j as c_uint)),
+        );
+    }
+    vec![tokens; num_results.min(3) as usize]
+}
+
+/// The text of the best candidate transcript of `samples` samples of audio.
+fn transcribe(samples: usize) -> Vec<u8> {
+    candidates(samples, 1)
+        .into_iter()
+        .flatten()
+        .flat_map(|(text, _)| truncate_at_nul(text))
+        .collect()
+}
+
+fn truncate_at_nul(mut bytes: Vec<u8>) -> Vec<u8> {
+    if let Some(nul) = bytes.iter().position(|&b| b == 0) {
+        bytes.truncate(nul);
+    }
+    bytes
+}
+
+fn new_metadata(model: &FakeModel, samples: usize, num_results: c_uint) -> *mut Metadata {
+    // a narrower beam is more confident, so models can be told apart by their confidence
+    let confidence = -f64::from(model.beam_width) / f64::from(BEAM_WIDTH);
+    let transcripts = candidates(samples, num_results)
+        .into_iter()
+        .enumerate()
+        .map(|(i, tokens)| {
+            let tokens = tokens
+                .into_iter()
+                .map(|(text, timestep)| TokenMetadata {
+                    text: CString::new(truncate_at_nul(text)).unwrap().into_raw(),
+                    timestep,
+                    start_time: timestep as f32 * 0.02,
+                })
+                .collect();
+            let (tokens, num_tokens) = leak_slice(tokens);
+            CandidateTranscript {
+                tokens,
+                num_tokens,
+                confidence: confidence - i as f64,
+            }
+        })
+        .collect();
+    let (transcripts, num_transcripts) = leak_slice(transcripts);
+
+    let metadata = Box::into_raw(Box::new(Metadata {
+        transcripts,
+        num_transcripts,
+    }));
+    track(metadata, Kind::Metadata, None)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_CreateModel(
+    model_path: *const c_char,
+    retval: *mut *mut ModelState,
+) -> c_int {
+    if str_arg(model_path) == FAILING_PATH {
+        *retval = std::ptr::null_mut();
+        return CREATE_MODEL_FAILED;
+    }
+
+    let model = Box::into_raw(Box::new(FakeModel {
+        beam_width: BEAM_WIDTH,
+        scorer: false,
+    }));
+    *retval = track(model, Kind::Model, None).cast();
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_CreateModelFromBuffer(
+    buffer: *const c_char,
+    buffer_size: c_uint,
+    retval: *mut *mut ModelState,
+) -> c_int {
+    let buffer = std::slice::from_raw_parts(buffer.cast::<u8>(), buffer_size as usize);
+    if buffer == FAILING_PATH.as_bytes() {
+        *retval = std::ptr::null_mut();
+        return CREATE_MODEL_FAILED;
+    }
+    STT_CreateModel(c"buffer".as_ptr(), retval)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_GetModelBeamWidth(ctx: *const ModelState) -> c_uint {
+    model(ctx).beam_width
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_SetModelBeamWidth(ctx: *mut ModelState, beam_width: c_uint) -> c_int {
+    model(ctx).beam_width = beam_width;
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_GetModelSampleRate(ctx: *const ModelState) -> c_int {
+    model(ctx);
+    SAMPLE_RATE
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_FreeModel(ctx: *mut ModelState) {
+    untrack(ctx, Kind::Model);
+    drop(Box::from_raw(ctx.cast::<FakeModel>()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_EnableExternalScorer(
+    ctx: *mut ModelState,
+    scorer_path: *const c_char,
+) -> c_int {
+    let model = model(ctx);
+    if str_arg(scorer_path) == FAILING_PATH {
+        return INVALID_SCORER;
+    }
+    model.scorer = true;
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_EnableExternalScorerFromBuffer(
+    ctx: *mut ModelState,
+    buffer: *const c_char,
+    buffer_size: c_uint,
+) -> c_int {
+    let model = model(ctx);
+    let buffer = std::slice::from_raw_parts(buffer.cast::<u8>(), buffer_size as usize);
+    if buffer == FAILING_PATH.as_bytes() {
+        return INVALID_SCORER;
+    }
+    model.scorer = true;
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_AddHotWord(
+    ctx: *mut ModelState,
+    word: *const c_char,
+    _boost: f32,
+) -> c_int {
+    model(ctx);
+    str_arg(word);
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_EraseHotWord(ctx: *mut ModelState, word: *const c_char) -> c_int {
+    model(ctx);
+    str_arg(word);
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_ClearHotWords(ctx: *mut ModelState) -> c_int {
+    model(ctx);
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_DisableExternalScorer(ctx: *mut ModelState) -> c_int {
+    let model = model(ctx);
+    if !model.scorer {
+        return SCORER_NOT_ENABLED;
+    }
+    model.scorer = false;
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_SetScorerAlphaBeta(
+    ctx: *mut ModelState,
+    _alpha: f32,
+    _beta: f32,
+) -> c_int {
+    if !model(ctx).scorer {
+        return SCORER_NOT_ENABLED;
+    }
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_SpeechToText(
+    ctx: *mut ModelState,
+    buffer: *const c_short,
+    buffer_size: c_uint,
+) -> *mut c_char {
+    let _decoding = DECODES.read().unwrap_or_else(|e| e.into_inner());
+    model(ctx);
+    if decode_fails() {
+        return std::ptr::null_mut();
+    }
+    new_string(transcribe(read_audio(buffer, buffer_size)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_SpeechToTextWithMetadata(
+    ctx: *mut ModelState,
+    buffer: *const c_short,
+    buffer_size: c_uint,
+    num_results: c_uint,
+) -> *mut Metadata {
+    let _decoding = DECODES.read().unwrap_or_else(|e| e.into_inner());
+    let model = model(ctx);
+    if decode_fails() {
+        return std::ptr::null_mut();
+    }
+    new_metadata(model, read_audio(buffer, buffer_size), num_results)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_CreateStream(
+    ctx: *mut ModelState,
+    retval: *mut *mut StreamingState,
+) -> c_int {
+    model(ctx);
+    let stream = Box::into_raw(Box::new(FakeStream {
+        model: ctx.cast(),
+        fed: 0,
+    }));
+    *retval = track(stream, Kind::Stream, Some(ctx as usize)).cast();
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_FeedAudioContent(
+    sctx: *mut StreamingState,
+    buffer: *const c_short,
+    buffer_size: c_uint,
+) {
+    let _feeding = FEEDS.read().unwrap_or_else(|e| e.into_inner());
+    stream(sctx).fed += read_audio(buffer, buffer_size);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_IntermediateDecode(sctx: *const StreamingState) -> *mut c_char {
+    new_string(transcribe(stream(sctx).fed))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_IntermediateDecodeWithMetadata(
+    sctx: *const StreamingState,
+    num_results: c_uint,
+) -> *mut Metadata {
+    let stream = stream(sctx);
+    new_metadata(model(stream.model.cast()), stream.fed, num_results)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_IntermediateDecodeFlushBuffers(
+    sctx: *mut StreamingState,
+) -> *mut c_char {
+    STT_IntermediateDecode(sctx)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_IntermediateDecodeWithMetadataFlushBuffers(
+    sctx: *mut StreamingState,
+    num_results: c_uint,
+) -> *mut Metadata {
+    STT_IntermediateDecodeWithMetadata(sctx, num_results)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_FinishStream(sctx: *mut StreamingState) -> *mut c_char {
+    let text = STT_IntermediateDecode(sctx);
+    STT_FreeStream(sctx);
+    text
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_FinishStreamWithMetadata(
+    sctx: *mut StreamingState,
+    num_results: c_uint,
+) -> *mut Metadata {
+    let metadata = STT_IntermediateDecodeWithMetadata(sctx, num_results);
+    STT_FreeStream(sctx);
+    metadata
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_FreeStream(sctx: *mut StreamingState) {
+    untrack(sctx, Kind::Stream);
+    drop(Box::from_raw(sctx.cast::<FakeStream>()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_FreeMetadata(m: *mut Metadata) {
+    untrack(m, Kind::Metadata);
+    let metadata = Box::from_raw(m);
+    for transcript in free_slice(metadata.transcripts, metadata.num_transcripts).iter() {
+        for token in free_slice(transcript.tokens, transcript.num_tokens).iter() {
+            drop(CString::from_raw(token.text as *mut c_char));
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_FreeString(s: *mut c_char) {
+    untrack(s, Kind::String);
+    drop(CString::from_raw(s));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_Version() -> *mut c_char {
+    new_string("0.0.0-fake")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn STT_ErrorCodeToErrorMessage(error_code: c_int) -> *mut c_char {
+    new_string(format!("fake error {:#x}", error_code))
+}
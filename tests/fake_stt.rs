@@ -0,0 +1,2101 @@
+//! Exercises the pointer lifecycle of the wrapper against the fake `libstt`.
+//!
+//! Run with `cargo test --test fake_stt`, or under Miri with
+//! `cargo +nightly miri test --test fake_stt`.
+//! Any double free, use after free, or model freed before its streams aborts the test run.
+
+use coqui_stt::vad::EnergyDetector;
+use coqui_stt::{
+    Demux, EndpointPolicy, Error, EventBus, EventFilter, HealthCheck, JobOutput, MiniExecutor,
+    Model, ModelPool, MultiModel, OwnedStream, PoolOptions, Priority, Progress, PushToTalk,
+    ServiceOptions, Shutdown, Stream, StreamJob, StreamOperation, StreamOptions, StreamTiming,
+    SttService, SubmitOptions, ThreadSafeStream, Transcriber, TranscriberConfig, TranscriptEvent,
+};
+use coqui_stt_fake::{live, Kind, FAILING_PATH, SAMPLE_RATE};
+
+const HALF_SECOND: usize = SAMPLE_RATE as usize / 2;
+
+fn assert_nothing_live() {
+    for kind in [Kind::Model, Kind::Stream, Kind::String, Kind::Metadata] {
+        assert_eq!(live(kind), 0, "{:?} leaked", kind);
+    }
+}
+
+fn speech(len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| if i % 2 == 0 { 8000 } else { -8000 })
+        .collect()
+}
+
+#[test]
+fn model_is_freed_once() {
+    let model = Model::new("model").unwrap();
+    assert_eq!(live(Kind::Model), 1);
+    assert_eq!(model.get_sample_rate(), SAMPLE_RATE);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn failed_model_creation_leaks_nothing() {
+    let Err(e) = Model::new(FAILING_PATH) else {
+        panic!("the model loaded");
+    };
+    assert_eq!(e.op(), Some("STT_CreateModel"));
+    assert!(matches!(e.root(), Error::CreateModelFailed));
+    assert!(matches!(Model::new("nul\0byte"), Err(Error::NulBytesFound)));
+    assert_nothing_live();
+}
+
+#[test]
+fn scorer_errors_are_passed_through() {
+    let mut model = Model::new("model").unwrap();
+    let e = model.enable_external_scorer(FAILING_PATH).unwrap_err();
+    assert!(matches!(e.root(), Error::InvalidScorer));
+    assert_eq!(e.to_string(), "STT_EnableExternalScorer failed");
+    assert!(std::error::Error::source(&e).is_some());
+    let e = model.disable_external_scorer().unwrap_err();
+    assert!(matches!(e.root(), Error::ScorerNotEnabled));
+    assert_eq!(e.op(), Some("STT_DisableExternalScorer"));
+    model.enable_external_scorer("scorer").unwrap();
+    model.set_scorer_alpha_beta(0.9, 1.2).unwrap();
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn speech_to_text_frees_string() {
+    let mut model = Model::new("model").unwrap();
+    let text = model.speech_to_text(&[0; HALF_SECOND * 2]).unwrap();
+    assert_eq!(text, "hello world");
+    assert_eq!(model.speech_to_text(&[]).unwrap(), "");
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn metadata_slices_are_valid() {
+    let mut model = Model::new("model").unwrap();
+    let metadata = model
+        .speech_to_text_with_metadata(&[0; HALF_SECOND * 2], 3)
+        .unwrap();
+    assert_eq!(metadata.num_transcripts(), 3);
+
+    let best = &metadata.transcripts()[0];
+    assert_eq!(best.text(), "hello world");
+    let words: Vec<_> = best.words().into_iter().map(|w| w.text).collect();
+    assert_eq!(words, ["hello", "world"]);
+
+    let owned = metadata.to_owned();
+    drop(metadata);
+    assert_eq!(live(Kind::Metadata), 0);
+    let text: String = owned.transcripts()[0]
+        .tokens()
+        .iter()
+        .map(|t| t.text.as_str())
+        .collect();
+    assert_eq!(text, "hello world");
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn empty_metadata_is_valid() {
+    let mut model = Model::new("model").unwrap();
+    let metadata = model.speech_to_text_with_metadata(&[], 0).unwrap();
+    assert!(metadata.transcripts().is_empty());
+    drop(metadata);
+    let metadata = model.speech_to_text_with_metadata(&[], 1).unwrap();
+    assert!(metadata.transcripts()[0].tokens().is_empty());
+    drop(metadata);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn finished_stream_is_not_freed_again() {
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    stream.feed_audio(&[0; HALF_SECOND]);
+    assert_eq!(stream.intermediate_decode().unwrap(), "hello");
+    stream.feed_audio(&[0; HALF_SECOND]);
+    assert_eq!(stream.finish_stream().unwrap(), "hello world");
+    assert_eq!(live(Kind::Stream), 0);
+
+    let stream = Stream::from_model(&mut model).unwrap();
+    let metadata = stream.finish_stream_with_metadata(2).unwrap();
+    assert_eq!(metadata.num_transcripts(), 2);
+    drop(metadata);
+
+    let stream = Stream::from_model(&mut model).unwrap();
+    let transcription = stream.finish_stream_transcription().unwrap();
+    assert!(transcription.text.is_empty());
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn dropped_stream_is_freed() {
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    stream.feed_audio(&[0; 100]);
+    drop(stream);
+    assert_eq!(live(Kind::Stream), 0);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn stream_survives_round_trip_through_raw_pointer() {
+    let mut model = Model::new("model").unwrap();
+    let stream = Stream::from_model(&mut model).unwrap();
+    let state = unsafe { stream.into_state() };
+    assert_eq!(live(Kind::Stream), 1);
+
+    let mut stream = unsafe { Stream::from_ptr(&mut model, state) };
+    stream.feed_audio(&[0; HALF_SECOND]);
+    assert_eq!(stream.finish_stream().unwrap(), "hello");
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn raw_states_round_trip() {
+    use coqui_stt::raw;
+
+    let model = Model::new("model").unwrap();
+    let state = unsafe { raw::model_into_state(model) };
+    assert_eq!(live(Kind::Model), 1);
+    let mut model = unsafe { raw::model_from_state(state) };
+    assert_eq!(unsafe { raw::model_as_state_ptr(&mut model) }, state);
+
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    let borrowed = unsafe { raw::stream_as_state_ptr(&mut stream) };
+    let state = unsafe { raw::stream_into_state(stream) };
+    assert_eq!((borrowed, live(Kind::Stream)), (state, 1));
+    let mut stream = unsafe { raw::stream_from_state(&mut model, state) };
+    stream.feed_audio(&[0; HALF_SECOND]);
+    assert_eq!(stream.finish_stream().unwrap(), "hello");
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn model_config_sets_the_model_up() {
+    let config = coqui_stt::util::ModelConfig::new("model")
+        .with_scorer("scorer")
+        .with_beam_width(7)
+        .with_hot_word("hello", 2.0);
+    let model = config.load().unwrap();
+    assert_eq!(model.get_beam_width(), 7);
+    drop(model);
+
+    let config = coqui_stt::util::ModelConfig::new(coqui_stt_fake::FAILING_PATH);
+    assert!(config.load().is_err());
+    assert_nothing_live();
+}
+
+#[test]
+fn owned_stream_frees_state_before_model() {
+    let model = Model::new("model").unwrap();
+    let mut stream = OwnedStream::new(model).unwrap();
+    stream.feed_audio(&[0; HALF_SECOND]);
+    // the fake library aborts if the model is freed first
+    drop(stream);
+    assert_nothing_live();
+
+    let stream = OwnedStream::new(Model::new("model").unwrap()).unwrap();
+    let (text, model) = stream.finish_stream();
+    assert_eq!(text.unwrap(), "");
+    assert_eq!(live(Kind::Stream), 0);
+
+    let stream = OwnedStream::new(model).unwrap();
+    let model = stream.into_model();
+    assert_eq!(live(Kind::Stream), 0);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn threadsafe_stream_frees_state_before_model() {
+    let options = StreamOptions::default()
+        .with_default_num_results(3)
+        .with_chunk_size(Some(100));
+    let stream = ThreadSafeStream::new_with_options(Model::new("model").unwrap(), options).unwrap();
+    let shared: std::sync::Arc<[i16]> = speech(HALF_SECOND * 2).into();
+    stream
+        .feed_audio_range(shared.clone(), 0..HALF_SECOND)
+        .unwrap();
+    assert!(stream
+        .feed_audio_range(shared.clone(), 1..usize::MAX)
+        .is_err());
+    assert_eq!(stream.intermediate_decode().unwrap(), "hello");
+    let metadata = stream.intermediate_decode_with_metadata().unwrap();
+    assert_eq!(metadata.num_transcripts(), 3);
+    drop(metadata);
+    let (metadata, model) = stream.finish_stream_with_metadata();
+    assert_eq!(metadata.unwrap().num_transcripts(), 3);
+    assert_eq!(live(Kind::Stream), 0);
+
+    // dropping the stream without finishing it still frees it on the worker thread
+    let stream = ThreadSafeStream::new(model).unwrap();
+    stream.feed_audio_shared(shared);
+    drop(stream);
+    assert_nothing_live();
+}
+
+#[test]
+fn transcriber_frees_segments() {
+    let model = Model::new("model").unwrap();
+    let mut transcriber = Transcriber::new(model, EnergyDetector::default());
+
+    let mut events = transcriber.push_audio(&speech(HALF_SECOND * 2)).unwrap();
+    assert!(transcriber.in_speech());
+    events.extend(transcriber.push_audio(&[0; HALF_SECOND * 4]).unwrap());
+    assert!(!transcriber.in_speech());
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, TranscriptEvent::Final(segment)
+            if !segment.text.is_empty() && !segment.tokens.is_empty())));
+    assert_eq!(live(Kind::Stream), 0);
+
+    // an utterance still in progress is freed along with the transcriber
+    transcriber.push_audio(&speech(HALF_SECOND)).unwrap();
+    assert_eq!(live(Kind::Stream), 1);
+    drop(transcriber);
+    assert_nothing_live();
+}
+
+#[test]
+fn demux_keeps_a_transcriber_per_key() {
+    let mut created = Vec::new();
+    let mut demux = Demux::new(|key: &&str| {
+        created.push(key.to_string());
+        Ok(Transcriber::new(
+            Model::new("model")?,
+            EnergyDetector::default(),
+        ))
+    });
+
+    let mut events = demux
+        .push_audio("radio 1", &speech(HALF_SECOND * 2))
+        .unwrap();
+    events.extend(demux.push_audio("radio 2", &speech(HALF_SECOND)).unwrap());
+    events.extend(demux.push_audio("radio 1", &[0; HALF_SECOND * 4]).unwrap());
+    assert_eq!(demux.len(), 2);
+    assert!(demux.get(&"radio 2").unwrap().in_speech());
+    let finals: Vec<_> = events
+        .into_iter()
+        .filter_map(|(key, event)| match event {
+            TranscriptEvent::Final(segment) => Some((key, segment.text)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(finals, [("radio 1", "hello world fake speech".to_string())]);
+
+    // only the key still mid-utterance has a final left
+    let mut demux = demux.with_idle_timeout(Some(std::time::Duration::ZERO));
+    let evicted: Vec<_> = demux
+        .evict_idle()
+        .into_iter()
+        .map(|(key, event)| match event.unwrap() {
+            TranscriptEvent::Final(segment) => (key, segment.text),
+            event => panic!("unexpected {event:?}"),
+        })
+        .collect();
+    assert!(demux.is_empty());
+    assert_eq!(evicted, [("radio 2", "hello".to_string())]);
+    drop(demux);
+    assert_eq!(created, ["radio 1", "radio 2"]);
+    assert_nothing_live();
+}
+
+#[test]
+fn event_bus_filters_what_subscribers_receive() {
+    let bus = EventBus::new();
+    let everything = bus.subscribe(EventFilter::new());
+    let finals = bus.subscribe(EventFilter::new().finals_only());
+    let keyword = bus.subscribe(EventFilter::new().with_keywords(["FAKE speech", "hello"]));
+    let radio_2 = bus.subscribe(EventFilter::new().with_keys(["radio 2"]));
+    let missed = bus.subscribe(EventFilter::new().with_keywords(["hell", "speech hello"]));
+    drop(bus.subscribe(EventFilter::new()));
+
+    let mut demux = Demux::new(|_: &&str| {
+        Ok(Transcriber::new(
+            Model::new("model")?,
+            EnergyDetector::default(),
+        ))
+    });
+    for (key, audio) in [
+        ("radio 1", speech(HALF_SECOND * 2)),
+        ("radio 1", vec![0; HALF_SECOND * 4]),
+        ("radio 2", speech(HALF_SECOND)),
+    ] {
+        bus.publish_all(demux.push_audio(key, &audio).unwrap());
+    }
+    bus.publish_all(
+        demux
+            .finish_all()
+            .into_iter()
+            .map(|(key, event)| (key, event.unwrap())),
+    );
+    // the dropped subscriber was noticed and removed
+    assert_eq!(bus.subscriber_count(), 5);
+    drop(bus);
+
+    let received = |rx: std::sync::mpsc::Receiver<(&'static str, TranscriptEvent)>| -> Vec<_> {
+        rx.iter()
+            .map(|(key, event)| match event {
+                TranscriptEvent::SpeechStarted { .. } => (key, None),
+                TranscriptEvent::Final(segment) => (key, Some(segment.text)),
+                _ => unreachable!(),
+            })
+            .collect()
+    };
+    let final_1 = ("radio 1", Some("hello world fake speech".to_string()));
+    let final_2 = ("radio 2", Some("hello".to_string()));
+    assert_eq!(
+        received(everything),
+        [
+            ("radio 1", None),
+            final_1.clone(),
+            ("radio 2", None),
+            final_2.clone()
+        ]
+    );
+    assert_eq!(received(finals), [final_1.clone(), final_2.clone()]);
+    assert_eq!(received(keyword), [final_1, final_2.clone()]);
+    assert_eq!(received(radio_2), [("radio 2", None), final_2]);
+    assert_eq!(received(missed), []);
+}
+
+#[test]
+#[cfg(feature = "debug-tap")]
+fn tap_writer_records_what_the_model_heard() {
+    use coqui_stt::audio::{Agc, Pipeline};
+    use coqui_stt::debug::TapWriter;
+
+    let dir = std::env::temp_dir().join("coqui-stt-fake-tap");
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    stream
+        .set_preprocessor(
+            Pipeline::new()
+                .with(Agc::default())
+                .with(TapWriter::new(&dir)),
+        )
+        .unwrap();
+    stream.feed_audio(&speech(HALF_SECOND));
+    stream.feed_audio(&speech(HALF_SECOND));
+    stream.finish_stream().unwrap();
+
+    let files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(files.len(), 1);
+    let mut wav = audrey::open(&files[0]).unwrap();
+    let description = wav.description();
+    assert_eq!(description.sample_rate(), SAMPLE_RATE as u32);
+    assert_eq!(description.channel_count(), 1);
+    let tapped: Vec<i16> = wav.samples().map(Result::unwrap).collect();
+    assert_eq!(tapped.len(), HALF_SECOND * 2);
+    // what was written is after the gain control, not what was fed in
+    assert_ne!(tapped[HALF_SECOND..], speech(HALF_SECOND)[..]);
+
+    // a disabled tap writes nothing
+    std::fs::remove_dir_all(&dir).unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    stream
+        .set_preprocessor(TapWriter::new(&dir).with_enabled(false))
+        .unwrap();
+    stream.feed_audio(&speech(HALF_SECOND));
+    stream.finish_stream().unwrap();
+    assert!(!dir.exists());
+}
+
+#[test]
+fn replay_realtime_paces_audio_like_a_microphone() {
+    use coqui_stt::testing::{replay_realtime, replay_realtime_with, ReplayOptions};
+
+    let mut audio = speech(HALF_SECOND * 2);
+    audio.extend_from_slice(&[0; HALF_SECOND * 4]);
+    let finals = |events: Vec<TranscriptEvent>| -> Vec<_> {
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                TranscriptEvent::Final(segment) => Some(segment.text),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let mut transcriber = Transcriber::new(Model::new("model").unwrap(), EnergyDetector::default());
+    let direct = finals(transcriber.push_audio(&audio).unwrap());
+
+    // 3 seconds of audio at 30 times real time
+    let mut transcriber = Transcriber::new(Model::new("model").unwrap(), EnergyDetector::default());
+    let started = std::time::Instant::now();
+    let replayed = finals(replay_realtime(&mut transcriber, &audio, 30.0).unwrap());
+    assert!(started.elapsed() >= std::time::Duration::from_millis(100));
+    assert_eq!(replayed, direct);
+
+    // jitter changes when chunks arrive, but not what they contain
+    let options = ReplayOptions::default()
+        .with_speed(30.0)
+        .with_chunk(std::time::Duration::from_millis(30))
+        .with_jitter(std::time::Duration::from_millis(50), 7);
+    let mut transcriber = Transcriber::new(Model::new("model").unwrap(), EnergyDetector::default());
+    let jittered = finals(replay_realtime_with(&mut transcriber, &audio, &options).unwrap());
+    assert_eq!(jittered, direct);
+
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    let fed = replay_realtime_with(&mut stream, &audio, &options).unwrap();
+    assert_eq!(fed.len(), 0);
+    assert_eq!(
+        stream.finish_stream().unwrap(),
+        "hello world fake speech hello world"
+    );
+}
+
+#[test]
+fn push_to_talk_frees_sessions() {
+    let mut ptt = PushToTalk::new(Model::new("model").unwrap());
+    ptt.push_audio(&[0; HALF_SECOND]);
+    ptt.start().unwrap();
+    ptt.push_audio(&[0; HALF_SECOND]);
+    let transcription = ptt.stop().unwrap().unwrap();
+    assert!(!transcription.text.is_empty());
+    assert_eq!(live(Kind::Stream), 0);
+
+    ptt.start().unwrap();
+    ptt.abort();
+    assert_eq!(live(Kind::Stream), 0);
+    assert!(ptt.stop().unwrap().is_none());
+
+    ptt.start().unwrap();
+    drop(ptt);
+    assert_nothing_live();
+}
+
+#[test]
+fn capabilities_frees_version() {
+    let capabilities = coqui_stt::capabilities().unwrap();
+    assert_eq!(capabilities.version, "0.0.0-fake");
+    assert_eq!(capabilities.runtime, coqui_stt::Runtime::Unknown);
+    assert_eq!(capabilities.cuda, None);
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "crypto")]
+#[cfg_attr(miri, ignore = "Miri can't lock memory")]
+fn encrypted_model_round_trips() {
+    use coqui_stt::Aead;
+
+    let key = [7; 32];
+    let ciphertext = Aead::ChaCha20Poly1305.encrypt(b"model", &key, &[1; 12]);
+    let mut model =
+        Model::new_from_encrypted_buffer(&ciphertext, &key, Aead::ChaCha20Poly1305).unwrap();
+    assert_eq!(model.speech_to_text(&[0; HALF_SECOND]).unwrap(), "hello");
+    drop(model);
+
+    let mut tampered = ciphertext;
+    tampered[12] ^= 1;
+    assert!(matches!(
+        Model::new_from_encrypted_buffer(&tampered, &key, Aead::ChaCha20Poly1305),
+        Err(Error::DecryptionFailed)
+    ));
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "crypto")]
+#[cfg_attr(miri, ignore = "Miri can't lock memory")]
+fn encrypted_models_load_beyond_the_memlock_limit() {
+    use coqui_stt::Aead;
+
+    // twice the default RLIMIT_MEMLOCK on most systems, so loaded from unlocked memory
+    let plaintext = vec![0x5a; 16 << 20];
+    let key = [7; 32];
+    let ciphertext = Aead::ChaCha20Poly1305.encrypt(&plaintext, &key, &[2; 12]);
+    let mut model =
+        Model::new_from_encrypted_buffer(&ciphertext, &key, Aead::ChaCha20Poly1305).unwrap();
+    assert_eq!(model.speech_to_text(&[0; HALF_SECOND]).unwrap(), "hello");
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "zstd")]
+#[cfg_attr(miri, ignore = "zstd is written in C")]
+fn compressed_model_loads_from_buffer() {
+    let dir = std::env::temp_dir().join("coqui-stt-fake-zstd");
+    std::fs::create_dir_all(&dir).unwrap();
+    let good = dir.join("model.tflite.zst");
+    let bad = dir.join("failing.tflite.zst");
+    std::fs::write(&good, zstd::encode_all(&b"model"[..], 0).unwrap()).unwrap();
+    std::fs::write(&bad, zstd::encode_all(FAILING_PATH.as_bytes(), 0).unwrap()).unwrap();
+
+    let mut model = Model::new(good.to_str().unwrap()).unwrap();
+    model
+        .enable_external_scorer(good.to_str().unwrap())
+        .unwrap();
+    // compressed files are loaded from memory, and the errors say so
+    let e = model
+        .enable_external_scorer(bad.to_str().unwrap())
+        .unwrap_err();
+    assert!(matches!(e.root(), Error::InvalidScorer));
+    assert_eq!(e.op(), Some("STT_EnableExternalScorerFromBuffer"));
+    drop(model);
+    let Err(e) = Model::new(bad.to_str().unwrap()) else {
+        panic!("the model loaded");
+    };
+    assert!(matches!(e.root(), Error::CreateModelFailed));
+    assert_eq!(e.op(), Some("STT_CreateModelFromBuffer"));
+    assert_nothing_live();
+}
+
+#[test]
+fn dropped_audio_is_counted() {
+    let options = StreamOptions::default().with_max_queued_samples(Some(HALF_SECOND));
+    let stream = ThreadSafeStream::new_with_options(Model::new("model").unwrap(), options).unwrap();
+    let stats = stream.stats_handle();
+    stream.feed_audio(speech(HALF_SECOND * 2));
+    stream.feed_audio(speech(HALF_SECOND));
+    let (text, model) = stream.finish_stream();
+    assert_eq!(text.unwrap(), "hello");
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.samples_received, HALF_SECOND as u64 * 3);
+    assert_eq!(snapshot.samples_fed, HALF_SECOND as u64);
+    assert_eq!(snapshot.dropped_backpressure, HALF_SECOND as u64 * 2);
+    assert_eq!(snapshot.dropped_cancelled, 0);
+
+    let mut transcriber = Transcriber::new(model, EnergyDetector::default());
+    let stats = transcriber.stats_handle();
+    transcriber.push_audio(&[0; HALF_SECOND * 2]).unwrap();
+    transcriber.push_audio(&speech(HALF_SECOND)).unwrap();
+    drop(transcriber.into_model());
+    let snapshot = stats.snapshot();
+    assert!(snapshot.dropped_gate > 0);
+    assert!(snapshot.dropped_cancelled >= HALF_SECOND as u64);
+    assert_nothing_live();
+}
+
+#[test]
+fn async_feed_is_all_or_nothing() {
+    use std::future::Future;
+    use std::task::{Context, Poll, Waker};
+
+    let options = StreamOptions::default()
+        .with_max_queued_samples(Some(HALF_SECOND))
+        .with_chunk_size(Some(100));
+    let stream = ThreadSafeStream::new_with_options(Model::new("model").unwrap(), options).unwrap();
+    let mut cx = Context::from_waker(Waker::noop());
+
+    let hold = coqui_stt_fake::hold_feeds();
+    let mut first = std::pin::pin!(stream.feed_audio_async(speech(HALF_SECOND)));
+    assert_eq!(first.as_mut().poll(&mut cx), Poll::Ready(()));
+    // the worker is stuck feeding the first buffer, so there's no room for the second
+    let mut second = Box::pin(stream.feed_audio_async(speech(HALF_SECOND)));
+    assert_eq!(second.as_mut().poll(&mut cx), Poll::Pending);
+    drop(second);
+    drop(hold);
+
+    let stats = stream.stats_handle();
+    let (text, model) = stream.finish_stream();
+    assert_eq!(text.unwrap(), "hello");
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.samples_received, HALF_SECOND as u64);
+    assert_eq!(snapshot.samples_fed, HALF_SECOND as u64);
+    assert_eq!(snapshot.dropped(), 0);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn threadsafe_stream_reports_worker_panics() {
+    let options =
+        StreamOptions::default().with_idle_timeout(Some(std::time::Duration::from_millis(10)));
+    let stream = ThreadSafeStream::new_with_watchdog(Model::new("model").unwrap(), options, |_| {
+        panic!("callback failed")
+    })
+    .unwrap();
+
+    stream.feed_audio(speech(HALF_SECOND));
+    let mut res = Ok(String::new());
+    for _ in 0..500 {
+        res = stream.intermediate_decode();
+        if res.is_err() {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert!(matches!(res, Err(Error::WorkerPanicked)));
+    stream.feed_audio(speech(HALF_SECOND));
+    drop(stream);
+}
+
+#[test]
+fn watchdog_finishes_idle_streams() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let options =
+        StreamOptions::default().with_idle_timeout(Some(std::time::Duration::from_millis(50)));
+    let stream =
+        ThreadSafeStream::new_with_watchdog(Model::new("model").unwrap(), options, move |res| {
+            let _ = tx.send(res.map(|metadata| {
+                metadata.transcripts()[0]
+                    .tokens()
+                    .iter()
+                    .map(|token| token.text())
+                    .collect::<String>()
+            }));
+        })
+        .unwrap();
+
+    stream.feed_audio(speech(HALF_SECOND * 2));
+    let idle = rx.recv_timeout(std::time::Duration::from_secs(60)).unwrap();
+    assert_eq!(idle.unwrap(), "hello world");
+
+    // audio fed afterwards goes to a new stream
+    stream.feed_audio(speech(HALF_SECOND));
+    let (text, model) = stream.finish_stream();
+    assert_eq!(text.unwrap(), "hello");
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn timing_observers_see_every_operation() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let timings = Arc::new(Mutex::new(Vec::<StreamTiming>::new()));
+    let observe = |timings: &Arc<Mutex<Vec<StreamTiming>>>| {
+        let timings = Arc::clone(timings);
+        move |timing| timings.lock().unwrap().push(timing)
+    };
+
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    stream.set_timing_observer(observe(&timings));
+    stream.feed_audio(&speech(HALF_SECOND));
+    assert_eq!(stream.intermediate_decode().unwrap(), "hello");
+    assert_eq!(stream.finish_stream().unwrap(), "hello");
+    let seen = std::mem::take(&mut *timings.lock().unwrap());
+    let operations: Vec<_> = seen.iter().map(|t| (t.operation, t.samples)).collect();
+    assert_eq!(
+        operations,
+        [
+            (StreamOperation::Feed, HALF_SECOND),
+            (StreamOperation::IntermediateDecode, 0),
+            (StreamOperation::Finish, 0),
+        ]
+    );
+    assert!(seen.iter().all(|t| t.queue_wait.is_none()));
+
+    let options = StreamOptions::default().with_chunk_size(Some(HALF_SECOND / 2));
+    let stream = ThreadSafeStream::new_with_options(model, options).unwrap();
+    stream.set_timing_observer(observe(&timings));
+    let hold = coqui_stt_fake::hold_feeds();
+    stream.feed_audio(speech(HALF_SECOND));
+    // queued up behind the first buffer, which the worker can't feed yet
+    stream.feed_audio(speech(HALF_SECOND));
+    std::thread::sleep(Duration::from_millis(50));
+    drop(hold);
+    let (text, model) = stream.finish_stream();
+    assert_eq!(text.unwrap(), "hello world");
+
+    let seen = std::mem::take(&mut *timings.lock().unwrap());
+    let operations: Vec<_> = seen.iter().map(|t| (t.operation, t.samples)).collect();
+    assert_eq!(
+        operations,
+        [
+            (StreamOperation::Feed, HALF_SECOND / 2),
+            (StreamOperation::Feed, HALF_SECOND / 2),
+            (StreamOperation::Feed, HALF_SECOND / 2),
+            (StreamOperation::Feed, HALF_SECOND / 2),
+            (StreamOperation::Finish, 0),
+        ]
+    );
+    // only the first chunk of each buffer reports how long it was queued
+    let waits: Vec<_> = seen.iter().map(|t| t.queue_wait.is_some()).collect();
+    assert_eq!(waits, [true, false, true, false, true]);
+    assert!(seen[2].queue_wait.unwrap() >= Duration::from_millis(50));
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn silence_is_fed_as_whole_samples() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let fed = Arc::new(Mutex::new(Vec::new()));
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    {
+        let fed = Arc::clone(&fed);
+        stream.set_timing_observer(move |timing| fed.lock().unwrap().push(timing.samples));
+    }
+    stream.feed_silence(Duration::from_millis(250));
+    // 1.6 samples
+    stream.feed_silence(Duration::from_micros(100));
+    stream.feed_silence(Duration::ZERO);
+    stream.feed_audio(&speech(HALF_SECOND));
+    let transcription = stream.finish_stream_transcription().unwrap();
+    assert_eq!(*fed.lock().unwrap(), [4000, 1, 0, HALF_SECOND, 0]);
+    assert_eq!(
+        transcription.audio_duration,
+        Duration::from_millis(750) + Duration::from_nanos(62500)
+    );
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn pool_replaces_wedged_models() {
+    let check = HealthCheck::default().with_deadline(std::time::Duration::from_secs(1));
+    let options = PoolOptions::default().with_health_check(Some(check));
+    let pool = ModelPool::new_with_options(1, options, || Model::new("model")).unwrap();
+
+    // healthy models are handed out again
+    let model = pool.get().unwrap();
+    assert!(pool.try_get().unwrap().is_none());
+    drop(model);
+    drop(pool.get().unwrap());
+    assert_eq!(live(Kind::Model), 1);
+
+    // a wedged model is left behind, and a new one is loaded in its place
+    let hold = coqui_stt_fake::hold_decodes();
+    let mut model = pool.get().unwrap();
+    assert_eq!(live(Kind::Model), 2);
+    drop(hold);
+    assert_eq!(model.speech_to_text(speech(HALF_SECOND)).unwrap(), "hello");
+    assert_eq!(pool.stats().in_use, 1);
+    drop(model);
+
+    let stats = pool.stats();
+    assert_eq!((stats.size, stats.available, stats.in_use), (1, 1, 0));
+    assert_eq!((stats.served, stats.errors), (3, 1));
+    drop(pool);
+
+    // the wedged model is freed once its check finally finishes
+    for _ in 0..500 {
+        if live(Kind::Model) == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_nothing_live();
+}
+
+#[test]
+fn pool_resizes_within_memory_limit() {
+    let options = PoolOptions::default()
+        .with_model_footprint(100)
+        .with_max_memory(Some(300));
+    let pool = ModelPool::new_with_options(1, options, || Model::new("model")).unwrap();
+
+    let held = pool.get().unwrap();
+    pool.resize(3).unwrap();
+    for _ in 0..500 {
+        if pool.stats().available == 2 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(pool.stats().available, 2);
+
+    assert!(matches!(
+        pool.resize(4),
+        Err(Error::MemoryLimitExceeded {
+            needed: 400,
+            limit: 300
+        })
+    ));
+
+    // idle models are retired straight away, models in use when they come back
+    pool.resize(0).unwrap();
+    assert_eq!(pool.stats().available, 0);
+    drop(held);
+    let stats = pool.stats();
+    assert_eq!((stats.available, stats.in_use), (0, 0));
+    assert!(pool.try_get().unwrap().is_none());
+    drop(pool);
+    assert_nothing_live();
+}
+
+#[test]
+fn service_queues_requests_for_the_pool() {
+    let pool = ModelPool::new(1, || Model::new("model")).unwrap();
+    let options = ServiceOptions::default().with_max_queued(4);
+    let service = SttService::new_with_options(pool.clone(), options).unwrap();
+
+    let submission = service.submit(speech(HALF_SECOND * 2), SubmitOptions::default());
+    assert_eq!(submission.wait().unwrap().text, "hello world");
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let submission = service.submit(
+        speech(HALF_SECOND),
+        SubmitOptions::default().with_client("alice"),
+    );
+    // so it can be spawned on multithreaded runtimes
+    fn assert_send(_: &impl Send) {}
+    assert_send(&submission);
+    assert_eq!(runtime.block_on(submission).unwrap().text, "hello");
+
+    let expired = service.submit(
+        speech(HALF_SECOND),
+        SubmitOptions::default().with_deadline(Some(std::time::Duration::ZERO)),
+    );
+    assert!(matches!(expired.wait(), Err(Error::DeadlineExceeded)));
+    let too_late = service.submit(
+        speech(HALF_SECOND),
+        SubmitOptions::default().with_deadline_at(Some(std::time::Instant::now())),
+    );
+    assert!(matches!(too_late.wait(), Err(Error::DeadlineExceeded)));
+    let batch = service.submit(
+        speech(HALF_SECOND),
+        SubmitOptions::default().with_priority(Priority::Batch),
+    );
+    assert_eq!(batch.wait().unwrap().text, "hello");
+
+    // with the only model held, requests wait until the queue is full;
+    // the worker may have taken the first one off the queue to wait for the model
+    let held = pool.get().unwrap();
+    let submissions: Vec<_> = (0..6)
+        .map(|_| service.submit(speech(HALF_SECOND), SubmitOptions::default()))
+        .collect();
+    drop(held);
+    let results: Vec<_> = submissions.into_iter().map(|s| s.wait()).collect();
+    assert!(matches!(results.last(), Some(Err(Error::QueueFull))));
+    let transcribed = results.iter().filter(|res| res.is_ok()).count();
+    assert!((4..=5).contains(&transcribed));
+    assert_eq!(service.queued(), 0);
+
+    drop((service, pool));
+    for _ in 0..500 {
+        if live(Kind::Model) == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_nothing_live();
+}
+
+#[test]
+fn service_refuses_requests_it_cannot_serve_in_time() {
+    let pool = ModelPool::new(1, || Model::new("model")).unwrap();
+    let options =
+        ServiceOptions::default().with_max_latency(Some(std::time::Duration::from_nanos(1)));
+    let service = SttService::new_with_options(pool, options).unwrap();
+
+    // nothing to go by until a request has been transcribed
+    assert_eq!(service.real_time_factor(), None);
+    let first = service.submit(speech(HALF_SECOND), SubmitOptions::default());
+    assert_eq!(first.wait().unwrap().text, "hello");
+    assert!(service.real_time_factor().is_some_and(|rtf| rtf > 0.0));
+
+    let second = service.submit(speech(HALF_SECOND), SubmitOptions::default());
+    let Err(Error::Overloaded { projected, limit }) = second.wait() else {
+        panic!("the request was accepted");
+    };
+    assert!(projected > limit);
+    assert_eq!(
+        service.projected_latency(Priority::Interactive, std::time::Duration::ZERO),
+        Some(std::time::Duration::ZERO)
+    );
+
+    drop(service);
+    for _ in 0..500 {
+        if live(Kind::Model) == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_nothing_live();
+}
+
+#[test]
+fn service_fails_over_to_its_standby() {
+    let pool = ModelPool::new(1, || Model::new("model")).unwrap();
+    // every transcription takes longer than no time at all, so the first one fails over
+    let policy = coqui_stt::FailoverPolicy::default()
+        .with_after_strikes(1)
+        .with_latency_budget(Some(std::time::Duration::ZERO))
+        .with_retry_primary_after(std::time::Duration::from_secs(3600));
+    let options = ServiceOptions::default().with_failover(policy);
+    let service = SttService::new_with_options(pool, options).unwrap();
+    let events = service.failover_events();
+
+    // without a standby, there is nothing to fail over to
+    let first = service.submit(speech(HALF_SECOND), SubmitOptions::default());
+    assert_eq!(first.wait().unwrap().text, "hello");
+    assert!(!service.is_failed_over());
+    assert!(events.try_recv().is_err());
+
+    assert!(matches!(
+        service.set_standby(Model::new("model").unwrap()),
+        Ok(())
+    ));
+    let second = service.submit(speech(HALF_SECOND), SubmitOptions::default());
+    assert_eq!(second.wait().unwrap().text, "hello");
+    assert!(service.is_failed_over());
+    assert!(matches!(
+        events.try_recv(),
+        Ok(coqui_stt::FailoverEvent::FailedOver(
+            coqui_stt::FailoverCause::TooSlow(_)
+        ))
+    ));
+
+    // the standby carries on transcribing while the primary waits to be tried again
+    let third = service.submit(speech(HALF_SECOND * 2), SubmitOptions::default());
+    assert_eq!(third.wait().unwrap().text, "hello world");
+    assert!(service.is_failed_over());
+    assert!(events.try_recv().is_err());
+
+    drop(service);
+    for _ in 0..500 {
+        if live(Kind::Model) == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "record")]
+fn service_requests_can_be_recorded_and_replayed() {
+    use coqui_stt::recording::{replay, Recorder};
+
+    let dir = std::env::temp_dir().join("coqui-stt-fake-recording");
+    let _ = std::fs::remove_dir_all(&dir);
+    let pool = ModelPool::new(1, || Model::new("model")).unwrap();
+    let service = SttService::new(pool).unwrap();
+    service.set_recorder(Some(Recorder::create(&dir).unwrap()));
+
+    let options = SubmitOptions::default()
+        .with_client("alice")
+        .with_priority(Priority::Batch);
+    let first = service.submit(speech(HALF_SECOND), options);
+    assert_eq!(first.wait().unwrap().text, "hello");
+    let second = service.submit(speech(HALF_SECOND * 2), SubmitOptions::default());
+    assert_eq!(second.wait().unwrap().text, "hello world");
+    // the same audio again is only stored once
+    let third = service.submit(speech(HALF_SECOND), SubmitOptions::default());
+    assert_eq!(third.wait().unwrap().text, "hello");
+
+    // the workers finish recording before they exit
+    drop(service);
+    for _ in 0..500 {
+        if live(Kind::Model) == 0 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let log = std::fs::read_to_string(dir.join("requests.jsonl")).unwrap();
+    assert_eq!(log.lines().count(), 3);
+    assert!(log.lines().next().unwrap().contains(r#""client":"alice""#));
+    let wavs = std::fs::read_dir(&dir)
+        .unwrap()
+        .filter(|entry| {
+            entry.as_ref().unwrap().path().extension() == Some(std::ffi::OsStr::new("wav"))
+        })
+        .count();
+    assert_eq!(wavs, 2);
+
+    // the same model transcribes everything the same way
+    let mut model = Model::new("model").unwrap();
+    let report = replay(&dir, &mut model).unwrap();
+    assert_eq!(report.results.len(), 3);
+    assert_eq!(report.results[0].recording.priority, Priority::Batch);
+    assert_eq!(report.changed().count(), 0);
+    assert_eq!(report.divergence(), 0.0);
+    assert_eq!(report.wer_delta(), None);
+
+    // a "new model" that hears something else, checked against what was really said
+    let log = log.replacen(r#""reference":null"#, r#""reference":"hello""#, 1);
+    std::fs::write(dir.join("requests.jsonl"), log).unwrap();
+    coqui_stt_fake::set_output(Some(coqui_stt_fake::Output {
+        transcripts: vec![vec![b"h".to_vec(), b"i".to_vec()]],
+    }));
+    let report = replay(&dir, &mut model).unwrap();
+    coqui_stt_fake::set_output(None);
+    assert_eq!(report.changed().count(), 3);
+    assert_eq!(report.failures(), 0);
+    assert_eq!(report.results[1].divergence(), Some(1.0));
+    assert_eq!(report.divergence(), 1.0);
+    assert_eq!(report.results[0].wer_delta(), Some(1.0));
+    assert_eq!(report.wer_delta(), Some(1.0));
+
+    drop(model);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_nothing_live();
+}
+
+#[test]
+fn ab_comparison_diffs_two_models() {
+    use coqui_stt::ab::{compare, Utterance, WordDiff};
+    use coqui_stt::audio::AudioBuffer;
+
+    let buffer = |len| AudioBuffer::new(speech(len), 1, SAMPLE_RATE as u32);
+    let dataset = [
+        Utterance::new("short", buffer(HALF_SECOND)).with_reference("hello"),
+        Utterance::new("long", buffer(HALF_SECOND * 2)),
+        Utterance::new("broken", AudioBuffer::new(speech(HALF_SECOND), 0, 16000)),
+    ];
+    let mut a = Model::new("model").unwrap();
+    let mut b = Model::new("model").unwrap();
+    // model A runs on another thread, so only model B hears this
+    coqui_stt_fake::set_output(Some(coqui_stt_fake::Output {
+        transcripts: vec![vec![b"h".to_vec(), b"i".to_vec()]],
+    }));
+    let report = compare(&mut a, &mut b, &dataset);
+    coqui_stt_fake::set_output(None);
+
+    assert_eq!(report.utterances.len(), 3);
+    assert_eq!(report.a.wer, Some(0.0));
+    assert_eq!(report.b.wer, Some(1.0));
+    assert_eq!(report.wer_delta(), Some(1.0));
+    assert_eq!((report.a.failures, report.b.failures), (1, 1));
+    assert_eq!(
+        report.utterances[0].diff,
+        [WordDiff::Changed {
+            a: "hello".to_string(),
+            b: "hi".to_string()
+        }]
+    );
+    assert_eq!(
+        report.utterances[1].diff,
+        [
+            WordDiff::Changed {
+                a: "hello".to_string(),
+                b: "hi".to_string()
+            },
+            WordDiff::OnlyA("world".to_string())
+        ]
+    );
+    assert!(report.utterances[2].b.error.is_some());
+    assert_eq!(report.differences().count(), 2);
+
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("| WER | 0.0% | 100.0% | +100.0% |"));
+    assert!(markdown.contains("2 of 3 utterances"));
+    assert!(markdown.contains("- Diff: ~~hello~~ **hi** ~~world~~"));
+
+    drop((a, b));
+    assert_nothing_live();
+}
+
+#[test]
+fn stream_lease_keeps_its_model() {
+    let pool = ModelPool::new(1, || Model::new("model")).unwrap();
+
+    let mut lease = pool.stream().unwrap();
+    assert!(pool.try_get().unwrap().is_none());
+    lease.feed_audio(&speech(HALF_SECOND * 2));
+    assert_eq!(lease.finish_stream().unwrap(), "hello world");
+    assert_eq!(pool.stats().available, 1);
+
+    // dropping a stream part way through returns its model too
+    let mut lease = pool.stream().unwrap();
+    lease.feed_audio(&speech(HALF_SECOND));
+    drop(lease);
+    assert_eq!(pool.stats().available, 1);
+    assert_eq!(live(Kind::Stream), 0);
+    drop(pool);
+    assert_nothing_live();
+}
+
+#[test]
+fn char_offsets_count_multibyte_tokens() {
+    coqui_stt_fake::set_output(Some(coqui_stt_fake::Output {
+        transcripts: vec![vec![
+            b"n".to_vec(),
+            "\u{e4}".as_bytes().to_vec(),
+            b"h".to_vec(),
+        ]],
+    }));
+    let mut model = Model::new("model").unwrap();
+    let metadata = model
+        .speech_to_text_with_metadata(&[0; HALF_SECOND], 1)
+        .unwrap();
+    coqui_stt_fake::set_output(None);
+
+    let best = &metadata.transcripts()[0];
+    let offsets = best.char_offsets();
+    assert_eq!(
+        offsets.iter().map(|o| o.bytes.clone()).collect::<Vec<_>>(),
+        [0..1, 1..3, 3..4]
+    );
+    assert_eq!(
+        offsets.iter().map(|o| o.chars.clone()).collect::<Vec<_>>(),
+        [0..1, 1..2, 2..3]
+    );
+    assert_eq!(&best.text()[offsets[1].bytes.clone()], "\u{e4}");
+    assert_eq!(metadata.to_owned().transcripts()[0].char_offsets(), offsets);
+
+    drop(metadata);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn intermediate_decode_holds_back_partial_characters() {
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    let output = |tokens: &[&[u8]]| {
+        coqui_stt_fake::set_output(Some(coqui_stt_fake::Output {
+            transcripts: vec![tokens.iter().map(|t| t.to_vec()).collect()],
+        }));
+    };
+
+    output(&[b"n", &[0xc3]]);
+    assert_eq!(stream.intermediate_decode().unwrap(), "n");
+    assert_eq!(stream.pending_utf8(), [0xc3]);
+
+    output(&[b"n", &[0xc3, 0xa4]]);
+    assert_eq!(stream.intermediate_decode().unwrap(), "n\u{e4}");
+
+    // invalid bytes that aren't cut off are still an error
+    output(&[&[0xff], b"n"]);
+    assert!(matches!(
+        stream.intermediate_decode(),
+        Err(Error::Utf8Error(_))
+    ));
+    coqui_stt_fake::set_output(None);
+
+    drop(stream);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn decoder_assisted_endpointing_ends_settled_utterances() {
+    // the decoder settles on the same text, however much more audio it hears
+    coqui_stt_fake::set_output(Some(coqui_stt_fake::Output {
+        transcripts: vec![vec![b"h".to_vec(), b"i".to_vec()]],
+    }));
+    let config = TranscriberConfig {
+        endpoint_policy: EndpointPolicy::DecoderAssisted,
+        ..TranscriberConfig::default()
+    };
+    let model = Model::new("model").unwrap();
+    let mut transcriber = Transcriber::with_config(model, EnergyDetector::default(), config);
+
+    // the detector hears speech throughout, so it would never end the utterance by itself
+    let events = transcriber.push_audio(&speech(HALF_SECOND * 4)).unwrap();
+    coqui_stt_fake::set_output(None);
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, TranscriptEvent::Final(segment) if segment.text == "hi")));
+    assert_eq!(live(Kind::Metadata), 0);
+
+    drop(transcriber);
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "parking_lot")]
+fn mutex_model_is_shared_by_reference() {
+    let model = coqui_stt::MutexModel::new(Model::new("model").unwrap());
+    let audio = speech(HALF_SECOND);
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                assert_eq!(model.speech_to_text(&audio).unwrap(), "hello");
+                assert_eq!(model.get_sample_rate(), SAMPLE_RATE);
+            });
+        }
+    });
+    model.add_hot_word("hello", 2.0).unwrap();
+    let stream = model.with_model(|m| {
+        let mut stream = Stream::from_model(m).unwrap();
+        stream.feed_audio(&audio);
+        stream.finish_stream().unwrap()
+    });
+    assert_eq!(stream, "hello");
+
+    drop(model.into_inner());
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "parking_lot")]
+fn mutex_model_announces_scorer_changes() {
+    use coqui_stt::ScorerEvent;
+
+    let model = coqui_stt::MutexModel::new(Model::new("model").unwrap());
+    let events = model.scorer_events();
+    model.enable_external_scorer("scorer").unwrap();
+    model.set_scorer_alpha_beta(0.5, 1.5).unwrap();
+    // changes that fail aren't announced
+    assert!(model
+        .enable_external_scorer(coqui_stt_fake::FAILING_PATH)
+        .is_err());
+    model.disable_external_scorer().unwrap();
+    assert!(model.set_scorer_alpha_beta(1.0, 1.0).is_err());
+    assert_eq!(
+        events.try_iter().collect::<Vec<_>>(),
+        [
+            ScorerEvent::Enabled {
+                path: "scorer".to_string()
+            },
+            ScorerEvent::HyperparametersChanged {
+                alpha: 0.5,
+                beta: 1.5
+            },
+            ScorerEvent::Disabled,
+        ]
+    );
+
+    drop(events);
+    model.enable_external_scorer("scorer").unwrap();
+    drop(model.into_inner());
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn async_read_is_transcribed_as_it_arrives() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let bytes: Vec<u8> = speech(HALF_SECOND * 2)
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect();
+
+    let (res, model) = runtime.block_on(coqui_stt::transcribe_async_read(
+        Model::new("model").unwrap(),
+        &bytes[..],
+        coqui_stt::audio::PcmFormat::S16Le,
+    ));
+    assert_eq!(res.unwrap().text, "hello world");
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "http")]
+#[cfg_attr(miri, ignore = "Miri can't open sockets")]
+fn http_source_streams_audio() {
+    use std::io::{BufRead, BufReader, Write};
+
+    let body: Vec<u8> = speech(HALF_SECOND * 2)
+        .iter()
+        .flat_map(|sample| sample.to_be_bytes())
+        .collect();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        for content_type in [
+            format!("audio/L16; rate={}; channels=1", SAMPLE_RATE),
+            "text/html".to_string(),
+            format!("audio/L16; rate={}", SAMPLE_RATE),
+        ] {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(
+                socket,
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                content_type,
+                body.len()
+            )
+            .unwrap();
+            let _ = socket.write_all(&body);
+        }
+    });
+
+    let mut model = Model::new("model").unwrap();
+    let source = coqui_stt::HttpSource::new(&url);
+    assert_eq!(source.transcribe(&mut model).unwrap().text, "hello world");
+    assert!(matches!(source.open(), Err(Error::InvalidAudio(_))));
+    assert!(matches!(
+        source.with_max_bytes(100).open(),
+        Err(Error::Io(_))
+    ));
+    server.join().unwrap();
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn retrying_model_retries_and_reloads() {
+    let policy = coqui_stt::RetryPolicy::default()
+        .with_backoff(std::time::Duration::ZERO)
+        .with_reload_after(Some(3));
+    let loads = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = std::sync::Arc::clone(&loads);
+    let mut model = coqui_stt::RetryingModel::new_with_policy(
+        move || {
+            counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Model::new("model")
+        },
+        policy,
+    )
+    .unwrap();
+    let audio = speech(HALF_SECOND);
+
+    coqui_stt_fake::fail_decodes(2);
+    assert_eq!(model.speech_to_text(&audio).unwrap(), "hello");
+    assert_eq!(loads.load(std::sync::atomic::Ordering::Relaxed), 1);
+
+    // out of retries, then reloaded on the third failure in a row
+    coqui_stt_fake::fail_decodes(3);
+    assert!(matches!(
+        model.speech_to_text(&audio).map_err(|e| e.root().clone()),
+        Err(Error::Unknown)
+    ));
+    let metadata = model.speech_to_text_with_metadata(&audio, 1).unwrap();
+    assert_eq!(metadata.num_transcripts(), 1);
+    drop(metadata);
+    assert_eq!(loads.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn flush_buffers_frees_the_decode() {
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    stream.feed_audio(&speech(HALF_SECOND));
+    stream.flush_buffers().unwrap();
+    assert_eq!(live(Kind::String), 0);
+    assert_eq!(stream.finish_stream().unwrap(), "hello");
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn one_shot_calls_convert_audio_to_what_the_model_expects() {
+    let mut model = Model::new("model").unwrap();
+
+    // a second of stereo audio at half the model's sample rate
+    let stereo =
+        coqui_stt::audio::AudioBuffer::new(speech(HALF_SECOND * 2), 2, SAMPLE_RATE as u32 / 2);
+    assert_eq!(stereo.duration(), std::time::Duration::from_secs(1));
+    assert_eq!(model.speech_to_text(&stereo).unwrap(), "hello world");
+    assert_eq!(model.speech_to_text(stereo).unwrap(), "hello world");
+
+    let floats = vec![0.5_f32; HALF_SECOND * 2];
+    assert_eq!(model.speech_to_text(&floats).unwrap(), "hello world");
+
+    let empty = coqui_stt::audio::AudioBuffer::new(Vec::new(), 0, SAMPLE_RATE as u32);
+    let Err(e) = model.speech_to_text(empty) else {
+        panic!("audio without channels was transcribed");
+    };
+    assert!(matches!(e, coqui_stt::Error::InvalidAudio(_)));
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn windowed_decoding_stitches_windows_together() {
+    let mut model = Model::new("model").unwrap();
+    let options = coqui_stt::WindowOptions::default()
+        .with_window(std::time::Duration::from_secs(1))
+        .with_overlap(std::time::Duration::from_millis(500));
+
+    // audio that fits in one window is decoded as usual
+    let transcription = model
+        .speech_to_text_windowed(speech(HALF_SECOND * 2), &options)
+        .unwrap();
+    assert_eq!(transcription.text, "hello world");
+
+    // every window starts again from "hello", so each one after the first
+    // only contributes the "world" after the middle of its overlap
+    let transcription = model
+        .speech_to_text_windowed(speech(HALF_SECOND * 6), &options)
+        .unwrap();
+    assert_eq!(transcription.text, "hello world world world world world");
+    let starts: Vec<_> = transcription
+        .words
+        .iter()
+        .map(|word| word.start.as_millis())
+        .collect();
+    assert_eq!(starts, [0, 500, 1000, 1500, 2000, 2500]);
+    assert_eq!(transcription.words[5].timestep, 125);
+    assert_eq!(
+        transcription.audio_duration,
+        std::time::Duration::from_secs(3)
+    );
+
+    drop(model);
+    assert_nothing_live();
+}
+
+/// Write `samples` as a mono WAV file at the fake model's sample rate.
+#[cfg(feature = "batch")]
+fn write_wav(path: &std::path::Path, samples: &[i16]) {
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE as u32).to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE as u32 * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    wav.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+    std::fs::write(path, wav).unwrap();
+}
+
+#[test]
+#[cfg(feature = "batch")]
+fn batch_jobs_carry_on_where_they_left_off() {
+    use coqui_stt::{BatchJob, WindowOptions};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    let dir = std::env::temp_dir().join("coqui-stt-fake-batch");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = [
+        dir.join("long.wav"),
+        dir.join("short.wav"),
+        dir.join("missing.wav"),
+    ];
+    write_wav(&files[0], &speech(HALF_SECOND * 6));
+    write_wav(&files[1], &speech(HALF_SECOND));
+    let manifest = dir.join("manifest.jsonl");
+    let job = BatchJob::new(&manifest).with_windows(
+        WindowOptions::default()
+            .with_window(std::time::Duration::from_secs(1))
+            .with_overlap(std::time::Duration::from_millis(500)),
+    );
+    let mut model = Model::new("model").unwrap();
+    let run = |model: &mut Model| {
+        let mut results = HashMap::new();
+        let report = job
+            .run(model, &files, |file, res| {
+                results.insert(file.to_path_buf(), res.map(|t| t.text));
+            })
+            .unwrap();
+        (report, results)
+    };
+    let text = |results: &HashMap<PathBuf, coqui_stt::Result<String>>, file: &PathBuf| {
+        results[file].as_ref().ok().cloned()
+    };
+
+    let (report, results) = run(&mut model);
+    assert_eq!((report.completed, report.skipped, report.failed), (2, 0, 1));
+    let long = "hello world world world world world".to_string();
+    assert_eq!(text(&results, &files[0]), Some(long.clone()));
+    assert_eq!(text(&results, &files[1]), Some("hello".to_string()));
+    assert!(results[&files[2]].is_err());
+    // a line for each of the long file's five windows, and one for the short file
+    let log = std::fs::read_to_string(&manifest).unwrap();
+    assert_eq!(log.lines().count(), 6);
+
+    // crash two windows into the long file, part way through writing the third
+    let lines: Vec<&str> = log.lines().collect();
+    let crashed = format!("{}\n{}\n{}", lines[0], lines[1], &lines[2][..10]);
+    std::fs::write(&manifest, crashed).unwrap();
+    let (report, results) = run(&mut model);
+    assert_eq!((report.completed, report.skipped, report.failed), (2, 0, 1));
+    assert_eq!(text(&results, &files[0]), Some(long));
+    let log = std::fs::read_to_string(&manifest).unwrap();
+    // only the other three windows and the short file were transcribed again
+    assert_eq!(log.lines().count(), 2 + 1 + 4);
+
+    let (report, results) = run(&mut model);
+    assert_eq!((report.completed, report.skipped, report.failed), (0, 2, 1));
+    assert_eq!(results.len(), 1);
+
+    // a shutdown stops the job before anything else is started
+    let shutdown = coqui_stt::Shutdown::new();
+    shutdown.trigger();
+    let report = BatchJob::new(dir.join("other.jsonl"))
+        .with_shutdown(shutdown)
+        .run(&mut model, &files, |_, _| {
+            panic!("nothing should be transcribed")
+        })
+        .unwrap();
+    assert_eq!(report.remaining, 3);
+
+    drop(model);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "batch")]
+fn batch_jobs_skip_windows_that_fail() {
+    use coqui_stt::{BatchJob, ErrorPolicy, WindowOptions};
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir().join("coqui-stt-fake-batch-errors");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = [dir.join("long.wav"), dir.join("missing.wav")];
+    write_wav(&files[0], &speech(HALF_SECOND * 6));
+    let windows = WindowOptions::default()
+        .with_window(Duration::from_secs(1))
+        .with_overlap(Duration::from_millis(500));
+    let job = BatchJob::new(dir.join("manifest.jsonl")).with_windows(windows);
+    let mut model = Model::new("model").unwrap();
+
+    // the first window fails, and the file is transcribed from the rest
+    coqui_stt_fake::fail_decodes(1);
+    let mut texts = Vec::new();
+    let report = job
+        .run(&mut model, &files, |_, res| texts.push(res.map(|t| t.text)))
+        .unwrap();
+    assert_eq!(
+        (report.completed, report.incomplete, report.failed),
+        (1, 1, 1)
+    );
+    assert_eq!(
+        texts[0].as_deref().ok(),
+        Some("hello world world world world")
+    );
+    assert!(texts[1].is_err());
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(report.errors[0].file, files[0]);
+    assert_eq!(
+        report.errors[0].window,
+        Some(Duration::ZERO..Duration::from_secs(1))
+    );
+    assert_eq!(
+        (&report.errors[1].file, &report.errors[1].window),
+        (&files[1], &None)
+    );
+
+    // the window that failed is tried again on the next run
+    let mut texts = Vec::new();
+    let report = job
+        .run(&mut model, &files[..1], |_, res| {
+            texts.push(res.map(|t| t.text))
+        })
+        .unwrap();
+    assert_eq!((report.completed, report.incomplete), (1, 0));
+    assert!(report.errors.is_empty());
+    assert_eq!(
+        texts[0].as_deref().ok(),
+        Some("hello world world world world world")
+    );
+
+    // failing fast stops the run at the first error
+    coqui_stt_fake::fail_decodes(1);
+    let res = BatchJob::new(dir.join("other.jsonl"))
+        .with_windows(windows)
+        .with_error_policy(ErrorPolicy::FailFast)
+        .run(&mut model, &files, |_, _| {
+            panic!("nothing should be transcribed")
+        });
+    assert!(res.is_err());
+
+    drop(model);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_nothing_live();
+}
+
+#[test]
+fn speech_to_text_best_has_words() {
+    let mut model = Model::new("model").unwrap();
+    let transcription = model.speech_to_text_best(speech(HALF_SECOND * 2)).unwrap();
+    assert_eq!(transcription.text, "hello world");
+    assert_eq!(transcription.words.len(), 2);
+    assert_eq!(transcription.words[1].text, "world");
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn words_end_where_the_next_token_starts() {
+    let mut model = Model::new("model").unwrap();
+    let audio = speech(HALF_SECOND * 3);
+    let audio_duration = std::time::Duration::from_secs_f64(1.5);
+    let metadata = model.speech_to_text_with_metadata(&audio, 1).unwrap();
+    let transcript = &metadata.transcripts()[0];
+    let timed = transcript.words_with_end_times(audio_duration);
+    assert_eq!(
+        timed
+            .iter()
+            .map(|(word, _)| word.clone())
+            .collect::<Vec<_>>(),
+        transcript.words()
+    );
+    assert_eq!(timed.len(), 3);
+    for pair in timed.windows(2) {
+        let ((word, end), (next, _)) = (&pair[0], &pair[1]);
+        assert!(word.start < *end && *end <= next.start);
+    }
+    assert_eq!(timed[2].1, audio_duration);
+    assert_eq!(
+        transcript.to_owned().words_with_end_times(audio_duration),
+        timed
+    );
+}
+
+#[test]
+fn packed_metadata_matches_owned() {
+    let mut model = Model::new("model").unwrap();
+    let metadata = model
+        .speech_to_text_with_metadata(speech(HALF_SECOND * 3), 3)
+        .unwrap();
+    let packed = metadata.to_packed();
+    let owned = metadata.to_owned();
+    drop(metadata);
+
+    assert_eq!(packed.num_transcripts(), owned.num_transcripts());
+    for (packed, owned) in packed.transcripts().zip(owned.transcripts()) {
+        assert_eq!(packed.text(), owned.text());
+        assert_eq!(packed.words(), owned.words());
+        assert_eq!(packed.confidence(), owned.confidence());
+        for (token, owned) in packed.tokens().iter().zip(owned.tokens()) {
+            assert_eq!(packed.token_text(token), owned.text);
+            assert_eq!(token.timestep, owned.timestep);
+        }
+        assert_eq!(packed.to_owned().tokens().len(), owned.tokens().len());
+    }
+    assert!(packed.transcript(3).is_none());
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn packets_of_bytes_are_fed_whole() {
+    let mut model = Model::new("model").unwrap();
+    let mut stream = Stream::from_model(&mut model).unwrap();
+    // 20ms packets, split so samples straddle them
+    stream.reserve_audio_hint(SAMPLE_RATE as usize / 50);
+    let bytes: Vec<u8> = speech(HALF_SECOND * 2)
+        .iter()
+        .flat_map(|s| s.to_le_bytes())
+        .collect();
+    for packet in bytes.chunks(641) {
+        stream.feed_audio_bytes_le(packet);
+    }
+    assert_eq!(stream.finish_stream().unwrap(), "hello world");
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+#[cfg(feature = "core-affinity")]
+fn pinned_worker_transcribes() {
+    let core = coqui_stt::available_cores()[0];
+    let options = StreamOptions::default().with_core(Some(core));
+    let stream = ThreadSafeStream::new_with_options(Model::new("model").unwrap(), options).unwrap();
+    stream.feed_audio(speech(HALF_SECOND));
+    let (res, model) = stream.finish_stream();
+    assert_eq!(res.unwrap(), "hello");
+
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn shutdown_finishes_streams_and_drains_pools() {
+    let shutdown = Shutdown::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let stream = ThreadSafeStream::new_with_watchdog(
+        Model::new("model").unwrap(),
+        StreamOptions::default(),
+        move |res| {
+            let _ = tx.send(res.map(|metadata| metadata.transcripts().len()));
+        },
+    )
+    .unwrap();
+    stream.watch_shutdown(&shutdown);
+    let pool = ModelPool::new(1, || Model::new("model")).unwrap();
+    pool.watch_shutdown(&shutdown);
+    let mut lease = pool.stream().unwrap();
+    lease.feed_audio(&speech(HALF_SECOND));
+
+    stream.feed_audio(speech(HALF_SECOND));
+    // the pool is still waiting for its model to come back
+    assert!(!shutdown.shutdown(std::time::Duration::from_millis(50)));
+    assert_eq!(rx.recv().unwrap().unwrap(), 1);
+    assert!(matches!(pool.get(), Err(Error::ShuttingDown)));
+    assert_eq!(shutdown.active(), 1);
+    assert_eq!(lease.finish_stream().unwrap(), "hello");
+    assert!(shutdown.wait(std::time::Duration::from_secs(10)));
+
+    // nothing more is accepted
+    stream.feed_audio(speech(HALF_SECOND));
+    assert!(matches!(
+        stream.intermediate_decode(),
+        Err(Error::ShuttingDown)
+    ));
+    assert_eq!(stream.stats().dropped_cancelled, HALF_SECOND as u64);
+    let (res, model) = stream.finish_stream();
+    assert!(matches!(res, Err(Error::ShuttingDown)));
+
+    drop(model);
+    drop(pool);
+    assert_nothing_live();
+}
+
+#[test]
+fn batched_jobs_run_in_one_hop() {
+    let stream = ThreadSafeStream::new(Model::new("model").unwrap()).unwrap();
+    let outputs = stream.run([
+        StreamJob::Feed(speech(HALF_SECOND)),
+        StreamJob::IntermediateDecode,
+        StreamJob::FeedShared(speech(HALF_SECOND).into()),
+        StreamJob::IntermediateDecodeWithMetadata(2),
+    ]);
+    let mut outputs = outputs.into_iter().map(Result::unwrap);
+    assert!(matches!(outputs.next(), Some(JobOutput::Fed)));
+    assert!(matches!(outputs.next(), Some(JobOutput::Text(text)) if text == "hello"));
+    assert!(matches!(outputs.next(), Some(JobOutput::Fed)));
+    match outputs.next() {
+        Some(JobOutput::Metadata(metadata)) => assert_eq!(metadata.num_transcripts(), 2),
+        _ => panic!("expected metadata"),
+    }
+    assert_eq!(stream.stats().samples_fed, HALF_SECOND as u64 * 2);
+
+    let (res, model) = stream.finish_stream();
+    assert_eq!(res.unwrap(), "hello world");
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn many_streams_share_an_executor() {
+    let executor = MiniExecutor::new(2);
+    let streams: Vec<_> = (0..50)
+        .map(|_| executor.stream(Model::new("model").unwrap()).unwrap())
+        .collect();
+    for stream in &streams {
+        stream.feed_audio(speech(HALF_SECOND));
+    }
+    assert_eq!(streams[0].intermediate_decode().unwrap(), "hello");
+    for stream in &streams {
+        stream.feed_audio(speech(HALF_SECOND));
+    }
+    let metadata = streams[1].intermediate_decode_with_metadata(2).unwrap();
+    assert_eq!(metadata.num_transcripts(), 2);
+    for stream in streams {
+        let (res, model) = stream.finish_stream();
+        assert_eq!(res.unwrap(), "hello world");
+        drop(model);
+    }
+
+    // streams outliving their executor run on the caller's thread instead
+    let stream = executor.stream(Model::new("model").unwrap()).unwrap();
+    drop(executor);
+    stream.feed_audio(speech(HALF_SECOND));
+    let (res, model) = stream.finish_stream_with_metadata(1);
+    assert_eq!(res.unwrap().num_transcripts(), 1);
+    drop(model);
+    assert_nothing_live();
+}
+
+#[test]
+fn every_blocking_call_has_an_async_variant() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let stream = ThreadSafeStream::new(Model::new("model").unwrap()).unwrap();
+        stream.feed_audio_async(speech(HALF_SECOND)).await;
+        let decode = stream.intermediate_decode_async();
+        // so they can be spawned on multithreaded runtimes
+        fn assert_send(_: &impl Send) {}
+        assert_send(&decode);
+        assert_eq!(decode.await.unwrap(), "hello");
+        let metadata = stream.intermediate_decode_with_metadata_async().await;
+        assert_eq!(metadata.unwrap().num_transcripts(), 1);
+        let metadata = stream.intermediate_decode_with_num_results_async(2).await;
+        assert_eq!(metadata.unwrap().num_transcripts(), 2);
+        let outputs = stream
+            .run_async([
+                StreamJob::Feed(speech(HALF_SECOND)),
+                StreamJob::IntermediateDecode,
+            ])
+            .await;
+        assert!(matches!(&outputs[1], Ok(JobOutput::Text(text)) if text == "hello world"));
+        let (res, model) = stream.finish_stream_with_num_results_async(2).await;
+        assert_eq!(res.unwrap().num_transcripts(), 2);
+
+        let stream = ThreadSafeStream::new(model).unwrap();
+        stream.feed_audio(speech(HALF_SECOND));
+        let (res, model) = stream.finish_stream_async().await;
+        assert_eq!(res.unwrap(), "hello");
+
+        let stream = ThreadSafeStream::new(model).unwrap();
+        stream.feed_audio(speech(HALF_SECOND));
+        let model = stream.into_model_async().await;
+        let (res, model) = ThreadSafeStream::new(model)
+            .unwrap()
+            .finish_stream_with_metadata_async()
+            .await;
+        assert_eq!(res.unwrap().num_transcripts(), 1);
+
+        let stream = ThreadSafeStream::new(model).unwrap();
+        stream.feed_audio(speech(HALF_SECOND));
+        let (res, model) = stream.finish_stream_words_async().await;
+        assert_eq!(res.unwrap()[0].text, "hello");
+        drop(model);
+    });
+    assert_nothing_live();
+}
+
+#[test]
+fn threadsafe_stream_finishes_with_words() {
+    use std::time::Duration;
+
+    let stream = ThreadSafeStream::new(Model::new("model").unwrap()).unwrap();
+    stream.feed_audio(speech(HALF_SECOND * 2));
+    let (res, model) = stream.finish_stream_words();
+    let words = res.unwrap();
+    let words: Vec<_> = words
+        .iter()
+        .map(|word| (word.text.as_str(), word.start))
+        .collect();
+    assert_eq!(
+        words,
+        [
+            ("hello", Duration::ZERO),
+            ("world", Duration::from_millis(500))
+        ]
+    );
+    assert_eq!(live(Kind::Metadata), 0);
+
+    // a stream with nothing fed has no words
+    let (res, model) = ThreadSafeStream::new(model).unwrap().finish_stream_words();
+    assert!(res.unwrap().is_empty());
+    drop(model);
+    assert_nothing_live();
+}
+
+fn model_with_beam_width(beam_width: u32) -> Model {
+    let mut model = Model::new("model").unwrap();
+    model.set_model_beam_width(beam_width).unwrap();
+    model
+}
+
+#[test]
+fn multi_model_picks_the_most_confident_language() {
+    // a narrower beam makes the fake model more confident
+    let mut models = MultiModel::new()
+        .with_model("en", model_with_beam_width(500))
+        .with_model("de", model_with_beam_width(250))
+        .with_model("fr", model_with_beam_width(1000));
+    let detection = models.auto_detect(&speech(HALF_SECOND * 4)).unwrap();
+
+    assert_eq!(detection.language, "de");
+    assert_eq!(detection.transcription.text, "hello world fake speech");
+    // "hello world fake speech" is 23 tokens
+    let expected = [
+        ("en", -1.0 / 23.0),
+        ("de", -0.5 / 23.0),
+        ("fr", -2.0 / 23.0),
+    ];
+    assert_eq!(detection.scores.len(), expected.len());
+    for ((language, score), (expected_language, expected_score)) in
+        detection.scores.iter().zip(expected)
+    {
+        assert_eq!(language, expected_language);
+        assert!(
+            (score - expected_score).abs() < 1e-9,
+            "{}: {}",
+            language,
+            score
+        );
+    }
+
+    drop(models);
+    assert_nothing_live();
+}
+
+#[test]
+fn multi_model_ties_go_to_the_first_registered() {
+    let mut models = MultiModel::new()
+        .with_model("en", Model::new("model").unwrap())
+        .with_model("de", Model::new("model").unwrap());
+    let detection = models.auto_detect(&speech(HALF_SECOND * 2)).unwrap();
+    assert_eq!(detection.language, "en");
+    assert_eq!(detection.scores[0].1, detection.scores[1].1);
+
+    // an empty prefix has no tokens, so scores negative infinity rather than NaN
+    let mut models = models
+        .with_model("de", model_with_beam_width(250))
+        .with_detection_prefix(std::time::Duration::ZERO);
+    let detection = models.auto_detect(&speech(HALF_SECOND * 2)).unwrap();
+    assert_eq!(detection.language, "en");
+    assert!(detection
+        .scores
+        .iter()
+        .all(|(_, score)| *score == f64::NEG_INFINITY));
+    assert_eq!(detection.transcription.text, "hello world");
+
+    assert!(matches!(
+        MultiModel::new().auto_detect(&speech(HALF_SECOND)),
+        Err(Error::NoModel)
+    ));
+    drop(models);
+    assert_nothing_live();
+}
+
+#[test]
+fn swapped_models_finish_the_utterance_in_progress() {
+    let mut transcriber = Transcriber::new(Model::new("model").unwrap(), EnergyDetector::default());
+    let (model, segment) = transcriber
+        .swap_model(model_with_beam_width(1000))
+        .map_err(|(e, _)| e)
+        .unwrap();
+    assert!(segment.is_none());
+    drop(model);
+
+    let events = transcriber.push_audio(&speech(HALF_SECOND * 3)).unwrap();
+    assert!(events
+        .iter()
+        .all(|event| matches!(event, TranscriptEvent::SpeechStarted { .. })));
+    let (old, segment) = transcriber
+        .swap_model(model_with_beam_width(250))
+        .map_err(|(e, _)| e)
+        .unwrap();
+    assert_eq!(old.get_beam_width(), 1000);
+    drop(old);
+
+    // everything pushed before the swap is transcribed by the old model
+    let segment = segment.unwrap();
+    assert_eq!(segment.text, "hello world fake");
+    assert_eq!(segment.start, std::time::Duration::ZERO);
+    assert_eq!(segment.confidence, -2.0);
+    assert_eq!(segment.model_generation, 1);
+
+    // and everything after it by the new one, carrying on from where it left off
+    transcriber.push_audio(&speech(HALF_SECOND * 2)).unwrap();
+    let segment = transcriber.finish().unwrap().unwrap();
+    assert_eq!(segment.text, "hello world");
+    assert!(segment.start >= std::time::Duration::from_millis(1500));
+    assert_eq!(segment.confidence, -0.5);
+    assert_eq!(segment.model_generation, 2);
+
+    drop(transcriber);
+    assert_nothing_live();
+}
+
+#[test]
+fn progress_is_reported_after_every_chunk() {
+    use std::time::Duration;
+
+    let mut model = Model::new("model").unwrap();
+    let mut reports = Vec::new();
+    let transcription = model
+        .speech_to_text_with_progress(
+            speech(HALF_SECOND * 4),
+            Duration::from_millis(500),
+            |progress: &Progress| reports.push(progress.clone()),
+        )
+        .unwrap();
+    assert_eq!(transcription.text, "hello world fake speech");
+
+    let texts: Vec<_> = reports.iter().map(|p| p.partial_text.as_str()).collect();
+    assert_eq!(
+        texts,
+        [
+            "hello",
+            "hello world",
+            "hello world fake",
+            "hello world fake speech"
+        ]
+    );
+    let processed: Vec<_> = reports.iter().map(|p| p.processed.as_millis()).collect();
+    assert_eq!(processed, [500, 1000, 1500, 2000]);
+    assert!(reports.iter().all(|p| p.total == Duration::from_secs(2)));
+    let last = reports.last().unwrap();
+    assert_eq!(last.eta, Duration::ZERO);
+    assert_eq!(last.fraction(), 1.0);
+
+    // a chunk size that doesn't divide the audio evenly still ends on all of it
+    let mut processed = Vec::new();
+    model
+        .speech_to_text_with_progress(
+            speech(HALF_SECOND * 2),
+            Duration::from_millis(300),
+            |progress: &Progress| processed.push(progress.processed.as_millis()),
+        )
+        .unwrap();
+    assert_eq!(processed, [300, 600, 900, 1000]);
+
+    let mut calls = 0;
+    let transcription = model
+        .speech_to_text_with_progress(&[] as &[i16], Duration::from_millis(500), |_: &Progress| {
+            calls += 1
+        })
+        .unwrap();
+    assert_eq!(transcription.text, "");
+    assert_eq!(calls, 0);
+    drop(model);
+    assert_nothing_live();
+}
+#[test]
+#[cfg(feature = "songbird")]
+fn voice_receiver_transcribes_each_user() {
+    use coqui_stt::vad::EnergyDetector;
+    use coqui_stt::VoiceReceiver;
+
+    // 48 kHz stereo, as songbird decodes it
+    let discord = |len: usize| -> Vec<i16> {
+        speech(len * 3)
+            .into_iter()
+            .flat_map(|sample| [sample, sample])
+            .collect()
+    };
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let receiver = VoiceReceiver::new(
+        || {
+            Ok(Transcriber::new(
+                Model::new("model")?,
+                EnergyDetector::default(),
+            ))
+        },
+        move |event| tx.send(event).unwrap(),
+    );
+    receiver.set_user(1, 100);
+    for _ in 0..2 {
+        receiver.push_voice(1, discord(HALF_SECOND));
+        receiver.push_voice(2, discord(HALF_SECOND));
+    }
+    receiver.remove_user(100);
+    drop(receiver);
+
+    let finals: Vec<_> = rx
+        .iter()
+        .filter_map(|event| match event.event.unwrap() {
+            TranscriptEvent::Final(segment) => Some((event.ssrc, event.user_id, segment.text)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        finals,
+        [
+            (1, Some(100), "hello world".to_string()),
+            (2, None, "hello world".to_string())
+        ]
+    );
+    assert_nothing_live();
+}
@@ -0,0 +1,259 @@
+//! Golden transcription tests against the real `libstt` and the official English model.
+//!
+//! Run with `cargo test --features model-tests --test golden`.
+//! The model and sample audio are downloaded on first run into Cargo's temporary directory
+//! for integration tests (or `COQUI_STT_TEST_DATA`, if set), and checked against the SHA-256
+//! checksums in `tests/golden/checksums.txt`. Set `COQUI_STT_TEST_MODEL_URL` and
+//! `COQUI_STT_TEST_AUDIO_URL` to download them from somewhere else.
+//!
+//! Every result is compared with the matching file in `tests/golden/`.
+//! A checksum or result that isn't recorded there fails the test.
+//! Run with `COQUI_STT_BLESS=1` to record them, or to accept a change in behaviour,
+//! and commit the recorded files.
+
+#![cfg(feature = "model-tests")]
+
+use audrey::Reader;
+use coqui_stt::{Metadata, Model, Stream, ThreadSafeStream};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+const MODEL_URL: &str =
+    "https://coqui.gateway.scarf.sh/english/coqui/v1.0.0-huge-vocab/model.tflite";
+const AUDIO_URL: &str =
+    "https://github.com/coqui-ai/STT/releases/download/v1.3.0/audio-1.3.0.tar.gz";
+
+/// Chunk size used to feed streams, 20ms at 16kHz.
+const CHUNK: usize = 320;
+
+struct Assets {
+    model: PathBuf,
+    audio: Vec<(String, Vec<i16>)>,
+}
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn data_dir() -> PathBuf {
+    std::env::var_os("COQUI_STT_TEST_DATA").map_or_else(
+        || Path::new(env!("CARGO_TARGET_TMPDIR")).join("model-tests"),
+        PathBuf::from,
+    )
+}
+
+fn blessing() -> bool {
+    std::env::var_os("COQUI_STT_BLESS").is_some_and(|bless| bless == "1")
+}
+
+/// Download `url` into the data directory as `name` if it isn't there already,
+/// and check it against its pinned checksum.
+fn fetch(url: &str, name: &str) -> PathBuf {
+    static CHECKSUMS: Mutex<()> = Mutex::new(());
+
+    let path = data_dir().join(name);
+    if !path.exists() {
+        fs::create_dir_all(data_dir()).unwrap();
+        let mut body = Vec::new();
+        ureq::get(url)
+            .call()
+            .unwrap_or_else(|e| panic!("failed to download {}: {}", url, e))
+            .into_reader()
+            .read_to_end(&mut body)
+            .unwrap();
+        let partial = path.with_extension("part");
+        fs::write(&partial, body).unwrap();
+        fs::rename(partial, &path).unwrap();
+    }
+
+    let digest = format!("{:x}", Sha256::digest(fs::read(&path).unwrap()));
+    let _lock = CHECKSUMS.lock().unwrap();
+    let checksums_path = golden_dir().join("checksums.txt");
+    let mut checksums: BTreeMap<String, String> = fs::read_to_string(&checksums_path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(digest, name)| (name.to_string(), digest.to_string()))
+        .collect();
+    if blessing() {
+        eprintln!("recording checksum of {}", name);
+        checksums.insert(name.to_string(), digest);
+        let contents: String = checksums
+            .iter()
+            .map(|(name, digest)| format!("{}  {}\n", digest, name))
+            .collect();
+        fs::write(checksums_path, contents).unwrap();
+        return path;
+    }
+    let pinned = checksums.get(name).unwrap_or_else(|| {
+        panic!(
+            "no checksum for {} in {}; set COQUI_STT_BLESS=1 to record it",
+            name,
+            checksums_path.display()
+        )
+    });
+    assert_eq!(
+        *pinned,
+        digest,
+        "checksum mismatch for {}; delete {} to download it again",
+        name,
+        path.display()
+    );
+
+    path
+}
+
+fn assets() -> &'static Assets {
+    static ASSETS: OnceLock<Assets> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        fs::create_dir_all(golden_dir()).unwrap();
+        let model_url = std::env::var("COQUI_STT_TEST_MODEL_URL").unwrap_or(MODEL_URL.into());
+        let audio_url = std::env::var("COQUI_STT_TEST_AUDIO_URL").unwrap_or(AUDIO_URL.into());
+        let model = fetch(&model_url, "model.tflite");
+        let archive = fetch(&audio_url, "audio.tar.gz");
+
+        let audio_dir = data_dir().join("audio");
+        if !audio_dir.exists() {
+            let decoder = flate2::read::GzDecoder::new(File::open(archive).unwrap());
+            tar::Archive::new(decoder).unpack(&audio_dir).unwrap();
+        }
+
+        let mut wavs = Vec::new();
+        let mut dirs = vec![audio_dir];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension().is_some_and(|ext| ext == "wav") {
+                    wavs.push(path);
+                }
+            }
+        }
+        wavs.sort();
+        assert!(!wavs.is_empty(), "the audio archive contained no WAV files");
+
+        let audio = wavs
+            .into_iter()
+            .map(|path| {
+                let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+                let mut reader = Reader::new(File::open(&path).unwrap()).unwrap();
+                let desc = reader.description();
+                assert_eq!(desc.channel_count(), 1, "{} is not mono", name);
+                assert_eq!(desc.sample_rate(), 16000, "{} is not 16kHz", name);
+                let samples = reader.samples().map(Result::unwrap).collect();
+                (name, samples)
+            })
+            .collect();
+
+        Assets { model, audio }
+    })
+}
+
+fn model() -> Model {
+    Model::new(assets().model.to_str().unwrap()).unwrap()
+}
+
+/// Compare `actual` with the golden file `name`, or record it when blessing.
+fn check_golden(name: &str, actual: &str) {
+    let path = golden_dir().join(format!("{}.txt", name));
+    if blessing() {
+        eprintln!("recording {}", path.display());
+        fs::write(path, format!("{}\n", actual)).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "no golden output at {} ({}); set COQUI_STT_BLESS=1 to record it",
+            path.display(),
+            e
+        )
+    });
+    assert_eq!(
+        actual,
+        expected.trim_end_matches('\n'),
+        "output differs from {}; set COQUI_STT_BLESS=1 to accept the new output",
+        path.display()
+    );
+}
+
+/// Describe the candidate transcripts in `metadata`, one per line,
+/// with every token and the timestep it starts at.
+fn describe(metadata: &Metadata) -> String {
+    metadata
+        .transcripts()
+        .iter()
+        .map(|transcript| {
+            transcript
+                .tokens()
+                .iter()
+                .map(|token| format!("{}@{}", token.text(), token.timestep()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn model_matches_golden() {
+    let mut model = model();
+    assert_eq!(model.get_sample_rate(), 16000);
+    for (name, audio) in &assets().audio {
+        let text = model.speech_to_text(audio).unwrap();
+        check_golden(&format!("model-{}", name), &text);
+    }
+}
+
+#[test]
+fn metadata_matches_golden() {
+    let mut model = model();
+    for (name, audio) in &assets().audio {
+        let metadata = model.speech_to_text_with_metadata(audio, 3).unwrap();
+        check_golden(&format!("metadata-{}", name), &describe(&metadata));
+
+        // the owned copy must say exactly the same thing
+        let owned = metadata.to_owned();
+        assert_eq!(owned.num_transcripts(), metadata.num_transcripts());
+        for (owned, borrowed) in owned.transcripts().iter().zip(metadata.transcripts()) {
+            assert_eq!(owned.num_tokens(), borrowed.num_tokens() as usize);
+            assert!((owned.confidence() - borrowed.confidence()).abs() < f64::EPSILON);
+        }
+    }
+}
+
+#[test]
+fn stream_matches_model() {
+    let mut model = model();
+    for (name, audio) in &assets().audio {
+        let expected = model.speech_to_text(audio).unwrap();
+
+        let mut stream = Stream::from_model(&mut model).unwrap();
+        for chunk in audio.chunks(CHUNK) {
+            stream.feed_audio(chunk);
+        }
+        let text = stream.finish_stream().unwrap();
+        assert_eq!(text, expected, "{}", name);
+        check_golden(&format!("stream-{}", name), &text);
+    }
+}
+
+#[test]
+fn threadsafe_stream_matches_model() {
+    let mut model = model();
+    for (name, audio) in &assets().audio {
+        let expected = model.speech_to_text(audio).unwrap();
+
+        let stream = ThreadSafeStream::new(model).unwrap();
+        for chunk in audio.chunks(CHUNK) {
+            stream.feed_audio(chunk.to_vec());
+        }
+        let (text, returned) = stream.finish_stream();
+        model = returned;
+        assert_eq!(text.unwrap(), expected, "{}", name);
+    }
+}
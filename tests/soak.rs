@@ -0,0 +1,115 @@
+//! Soak tests for leaks in the paths that free `libstt`'s pointers.
+//!
+//! Run with `cargo test --release --test soak -- --ignored`.
+//! Each test runs thousands of create, feed and finish cycles against the fake `libstt`,
+//! and checks the resident set size of the process stays flat once it has warmed up.
+//! Set `COQUI_STT_SOAK_CYCLES` to run more or fewer cycles than the default 5000.
+//!
+//! Resident set size is only measured on Linux; elsewhere the cycles still run,
+//! which is enough for the fake `libstt` to catch double frees, but leaks go unnoticed.
+
+use coqui_stt::testing::tone;
+use coqui_stt::{Model, Stream, ThreadSafeStream};
+use coqui_stt_fake::SAMPLE_RATE;
+use std::time::Duration;
+
+/// How much the resident set size may grow after warming up, in bytes,
+/// to allow for the allocator holding on to memory.
+const ALLOWED_GROWTH: u64 = 1 << 20;
+
+fn cycles() -> usize {
+    std::env::var("COQUI_STT_SOAK_CYCLES")
+        .ok()
+        .and_then(|cycles| cycles.parse().ok())
+        .unwrap_or(5000)
+}
+
+/// The resident set size of this process in bytes, where the platform reports it.
+fn rss() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Run `cycle` [`cycles`] times, and check the resident set size stays flat
+/// after the first tenth of them.
+fn soak(mut cycle: impl FnMut()) {
+    let cycles = cycles();
+    let warmup = cycles / 10;
+    for _ in 0..warmup {
+        cycle();
+    }
+    let before = rss();
+    for _ in warmup..cycles {
+        cycle();
+    }
+
+    match (before, rss()) {
+        (Some(before), Some(after)) => assert!(
+            after <= before + ALLOWED_GROWTH,
+            "resident set size grew from {} to {} bytes over {} cycles",
+            before,
+            after,
+            cycles - warmup
+        ),
+        _ => eprintln!("resident set size isn't available, only checked for crashes"),
+    }
+}
+
+fn speech() -> Vec<i16> {
+    tone(440.0, Duration::from_secs(1), SAMPLE_RATE as u32)
+}
+
+#[test]
+#[ignore = "soak test, run explicitly"]
+fn model_create_and_decode() {
+    let audio = speech();
+    soak(|| {
+        let mut model = Model::new("model").unwrap();
+        assert_eq!(model.speech_to_text(&audio).unwrap(), "hello world");
+        drop(model.speech_to_text_with_metadata(&audio, 3).unwrap());
+    });
+}
+
+#[test]
+#[ignore = "soak test, run explicitly"]
+fn stream_feed_and_finish() {
+    let audio = speech();
+    let mut model = Model::new("model").unwrap();
+    soak(|| {
+        let mut stream = Stream::from_model(&mut model).unwrap();
+        stream.feed_audio(&audio);
+        assert!(!stream.intermediate_decode().unwrap().is_empty());
+        drop(stream.intermediate_decode_with_metadata(2).unwrap());
+        drop(stream.finish_stream_with_metadata(2).unwrap());
+    });
+}
+
+#[test]
+#[ignore = "soak test, run explicitly"]
+fn threadsafe_stream_feed_and_finish() {
+    let audio: std::sync::Arc<[i16]> = speech().into();
+    let mut model = Some(Model::new("model").unwrap());
+    soak(|| {
+        let stream = ThreadSafeStream::new(model.take().unwrap()).unwrap();
+        stream.feed_audio_shared(audio.clone());
+        assert!(!stream.intermediate_decode().unwrap().is_empty());
+        let (res, returned) = stream.finish_stream();
+        assert_eq!(res.unwrap(), "hello world");
+        model = Some(returned);
+    });
+}
+
+#[test]
+#[ignore = "soak test, run explicitly"]
+fn metadata_to_owned() {
+    let audio = speech();
+    let mut model = Model::new("model").unwrap();
+    soak(|| {
+        let metadata = model.speech_to_text_with_metadata(&audio, 5).unwrap();
+        let owned = metadata.to_owned();
+        drop(metadata);
+        assert!(!owned.transcripts().is_empty());
+    });
+}